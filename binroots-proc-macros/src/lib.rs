@@ -1,6 +1,8 @@
+#![allow(clippy::needless_doctest_main)]
+
 use convert_case::{Case, Casing};
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, parse_quote, DeriveInput};
 
 /// # binroots_enum
 /// A procedural macro attribute that enables serialization, default and debug operations for an enum.
@@ -37,10 +39,64 @@ use syn::{parse_macro_input, DeriveInput};
 ///     VariantB
 /// }
 /// ```
+/// `#[binroots_enum(rename_all = "...")]` converts the case of the variant names written to disk, taking the same rules as
+/// `#[serde(rename_all = "...")]`, such as `"kebab-case"` or `"snake_case"`:
+/// ```
+/// use binroots::binroots_enum;
+/// use binroots::save::{root_location, RootType, Save};
+///
+/// #[binroots_enum(rename_all = "kebab-case")]
+/// pub enum Status {
+///     Nothing,
+///     WatchingStream(String),
+/// }
+///
+/// fn main() {
+///     Status::WatchingStream("speedruns".into()).save("stream-status", RootType::InMemory).unwrap();
+///
+///     let root = root_location(RootType::InMemory).unwrap();
+///     assert_eq!(std::fs::read_to_string(root.join("stream-status")).unwrap(), "watching-stream");
+///     assert_eq!(std::fs::read_to_string(root.join("stream-status.value")).unwrap(), "speedruns");
+///     assert_eq!(Status::VARIANT_NAMES, ["nothing", "watching-stream"]);
+/// }
+/// ```
+/// Enums without a sensible default can opt out of [`Default`] with `no_default`, skipping both the derive and picking a
+/// default variant:
+/// ```
+/// use binroots::binroots_enum;
+///
+/// #[binroots_enum(no_default)]
+/// pub enum Direction {
+///     North,
+///     South,
+/// }
+///
+/// fn main() {
+///     assert_eq!(Direction::South.variant_file_value(), "South");
+/// }
+/// ```
+/// The default variant can carry data, which is then filled with the [`Default`] of each field:
+/// ```
+/// use binroots::binroots_enum;
+///
+/// #[binroots_enum]
+/// pub enum Mode {
+///     #[default]
+///     Normal { volume: u8, muted: bool },
+///     Off,
+/// }
+///
+/// fn main() {
+///     assert!(matches!(Mode::default(), Mode::Normal { volume: 0, muted: false }));
+/// }
+/// ```
 /// The generated code includes a new implementation of the input struct with the following changes:
-///     - `derive`s [`Debug`], [`Default`], [`binroots::Serialize`][brserialize]
+///     - `derive`s [`Debug`], [`Default`], [`binroots::Serialize`][brserialize], [`binroots::Deserialize`][brdeserialize], along with your own derives. Other attributes, such as `#[serde(rename_all = "...")]`, are kept.
 ///     - Adds a `new` method to the struct, which constructs a new instance of the struct from its fields.
-///     - A `#[default]` marker inserted wherever possible, overrided by the `manual` annotation
+///     - A `#[default]` marker inserted wherever possible, overrided by the `manual` annotation and skipped with `no_default`. [`Default`] is implemented by hand if the default variant carries data.
+///     - Generates `Self::VARIANT_NAMES`, the name of each variant as written to the variant file, following serde's `rename` and `rename_all`
+///     - Adds a `variant_file_value` method returning the contents of the variant file saved for a value, and a `from_variant_file` constructor turning those contents back into a unit variant
+///     - Adds `variant_names`, `variant_file_value_with` and `from_variant_file_with`, which do the same for saves using a [`binroots::naming::NamingPolicy`][brnaming], such as fields of a `#[binroots_struct(naming = "kebab")]`
 // Example
 /// ```rust
 /// use binroots::binroots_enum;
@@ -57,9 +113,76 @@ use syn::{parse_macro_input, DeriveInput};
 ///     let activity = Activity::Playing("bideo games".into());
 ///
 ///     activity.save("activity", RootType::InMemory).unwrap(); // Saves the enum to the disk
+///
+///     assert_eq!(Activity::VARIANT_NAMES, ["Nothing", "Playing", "Watching"]);
+///     assert_eq!(activity.variant_file_value(), "Playing");
+///     assert!(matches!(Activity::from_variant_file("Nothing\n"), Some(Activity::Nothing)));
+///     assert!(Activity::from_variant_file("Playing").is_none()); // Carries data
+/// }
+/// ```
+/// `VARIANT_NAMES` and `variant_file_value` don't know the naming policy of the save, which renames the variants written
+/// to disk like the fields:
+/// ```rust
+/// use binroots::naming::NamingPolicy;
+/// use binroots::save::root_location;
+/// use binroots::{binroots_enum, binroots_struct};
+///
+/// #[binroots_enum]
+/// pub enum Activity {
+///     Nothing,
+///     WatchingStream(String),
+/// }
+///
+/// #[binroots_struct(naming = "kebab")]
+/// pub struct Streamer {
+///     activity: Activity,
+/// }
+///
+/// fn main() {
+///     let activity = Activity::WatchingStream("speedruns".into());
+///     Streamer::new(activity).save().unwrap();
+///
+///     let root = root_location(Streamer::ROOT_TYPE).unwrap().join("streamer");
+///     let saved = std::fs::read_to_string(root.join("activity")).unwrap();
+///     assert_eq!(saved, "watching-stream");
+///     assert_eq!(Activity::variant_names(NamingPolicy::Kebab), ["nothing", "watching-stream"]);
+///     assert_eq!(Activity::WatchingStream(String::new()).variant_file_value_with(NamingPolicy::Kebab), saved);
+///     assert!(matches!(
+///         Activity::from_variant_file_with("nothing\n", NamingPolicy::Kebab),
+///         Some(Activity::Nothing)
+///     ));
+/// }
+/// ```
+/// Your derives and serde attributes are kept, and the variant names follow serde's renaming:
+/// ```rust
+/// use binroots::binroots_enum;
+/// use binroots::save::{root_location, RootType, Save};
+///
+/// #[binroots_enum]
+/// #[derive(Clone, Copy, PartialEq, Debug)]
+/// #[serde(rename_all = "kebab-case")]
+/// pub enum Presence {
+///     #[default]
+///     Offline,
+///     DoNotDisturb,
+///     #[serde(rename = "afk")]
+///     Away,
+/// }
+///
+/// fn main() {
+///     let presence = Presence::DoNotDisturb;
+///     presence.save("chat-presence", RootType::InMemory).unwrap();
+///
+///     let root = root_location(RootType::InMemory).unwrap().join("chat-presence");
+///     assert_eq!(std::fs::read_to_string(root).unwrap(), "do-not-disturb");
+///     assert_eq!(Presence::VARIANT_NAMES, ["offline", "do-not-disturb", "afk"]);
+///     assert_eq!(Presence::from_variant_file("afk"), Some(Presence::Away));
+///     assert_ne!(presence.clone(), Presence::default());
 /// }
 /// ```
 /// [brserialize]: https://docs.rs/binroots/latest/binroots/trait.Serialize.html
+/// [brdeserialize]: https://docs.rs/binroots/latest/binroots/trait.Deserialize.html
+/// [brnaming]: https://docs.rs/binroots/latest/binroots/naming/enum.NamingPolicy.html
 #[proc_macro_attribute]
 pub fn binroots_enum(
     attr: proc_macro::TokenStream,
@@ -68,54 +191,133 @@ pub fn binroots_enum(
     let input = parse_macro_input!(item as DeriveInput);
     let vis = input.vis;
     let ident = input.ident;
-    let attrs = input.attrs;
+    let mut attrs = input.attrs;
     let generics = input.generics;
 
-    let mut found = None;
     let mut manual = false;
+    let mut no_default = false;
 
-    for a in attr {
-        manual = a.to_string() == "manual"
-    }
-
-    let variants = if let syn::Data::Enum(syn::DataEnum { variants, .. }) = input.data {
-        if manual {
-            variants.into_iter().map(|v| quote!(#v)).collect::<Vec<_>>()
-        } else {
-            variants
-                .into_iter()
-                .enumerate()
-                .map(|(i, v)| {
-                    if (v.ident == "None"
-                        || v.ident == "Nothing"
-                        || v.ident == "Empty"
-                        || v.ident == "Default")
-                        && found.is_none()
-                    {
-                        found = Some(i)
-                    }
-
-                    if let Some(c) = found {
-                        if c == i {
-                            quote! {
-                                #[default]
-                                #v
-                            }
-                        } else {
-                            quote!(#v)
+    let mut attr = attr.into_iter();
+    while let Some(a) = attr.next() {
+        match a.to_string().as_str() {
+            "manual" => manual = true,
+            "no_default" => no_default = true,
+            "rename_all" => {
+                let rule = attr
+                    .nth(1)
+                    .and_then(|rule| syn::parse_str::<syn::LitStr>(&rule.to_string()).ok());
+                match rule {
+                    Some(rule) if RENAME_RULES.contains(&rule.value().as_str()) => {
+                        if serde_option(&attrs, "rename_all").is_some() {
+                            panic!("#[binroots_enum(rename_all = ...)] replaces #[serde(rename_all = ...)], use only one of them");
                         }
-                    } else {
-                        quote!(#v)
+                        attrs.push(parse_quote!(#[serde(rename_all = #rule)]));
                     }
-                })
-                .collect::<Vec<_>>()
+                    _ => panic!("Unknown #[binroots_enum(rename_all = ...)], expected one of {RENAME_RULES:?}"),
+                }
+            }
+            _ => {}
         }
+    }
+
+    let variants = if let syn::Data::Enum(syn::DataEnum { variants, .. }) = input.data {
+        variants
     } else {
         panic!("#[binroots_enum] only supports enums.")
     };
 
+    // Variant files hold the names serde gives the variants
+    let rename_all = serde_option(&attrs, "rename_all");
+    let serde_name = |v: &syn::Variant| {
+        serde_option(&v.attrs, "rename")
+            .unwrap_or_else(|| rename_variant(&v.ident.to_string(), rename_all.as_deref()))
+    };
+    let names = variants.iter().map(serde_name).collect::<Vec<_>>();
+    let patterns = variants
+        .iter()
+        .map(|v| {
+            let variant = &v.ident;
+            match v.fields {
+                syn::Fields::Named(_) => quote!(Self::#variant { .. }),
+                syn::Fields::Unnamed(_) => quote!(Self::#variant(..)),
+                syn::Fields::Unit => quote!(Self::#variant),
+            }
+        })
+        .collect::<Vec<_>>();
+    let unit_names = variants
+        .iter()
+        .filter(|v| matches!(v.fields, syn::Fields::Unit))
+        .map(serde_name)
+        .collect::<Vec<_>>();
+    let unit_variants = variants
+        .iter()
+        .filter(|v| matches!(v.fields, syn::Fields::Unit))
+        .map(|v| v.ident.clone())
+        .collect::<Vec<_>>();
+
+    let mut variants = variants.into_iter().collect::<Vec<_>>();
+    let is_default = |v: &syn::Variant| v.attrs.iter().any(|a| a.path.is_ident("default"));
+    if !manual && !no_default && !variants.iter().any(is_default) {
+        let found = variants.iter_mut().find(|v| {
+            v.ident == "None" || v.ident == "Nothing" || v.ident == "Empty" || v.ident == "Default"
+        });
+        if let Some(v) = found {
+            v.attrs.push(parse_quote!(#[default]));
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // `#[derive(Default)]` only supports unit variants, so variants carrying data are defaulted by hand
+    let default_impl = variants
+        .iter_mut()
+        .filter(|_| !no_default)
+        .find(|v| is_default(v) && !matches!(v.fields, syn::Fields::Unit))
+        .map(|v| {
+            v.attrs.retain(|a| !a.path.is_ident("default"));
+
+            let variant = &v.ident;
+            let value = match &v.fields {
+                syn::Fields::Named(fields) => {
+                    let names = fields.named.iter().map(|f| &f.ident);
+                    quote!(Self::#variant { #( #names: Default::default() ),* })
+                }
+                _ => {
+                    let values = v.fields.iter().map(|_| quote!(Default::default()));
+                    quote!(Self::#variant( #( #values ),* ))
+                }
+            };
+            let predicates = where_clause.into_iter().flat_map(|w| w.predicates.iter());
+            let bounds = v.fields.iter().map(|f| &f.ty);
+
+            quote! {
+                impl #impl_generics Default for #ident #ty_generics
+                where
+                    #( #predicates, )*
+                    #( #bounds: Default, )*
+                {
+                    fn default() -> Self {
+                        #value
+                    }
+                }
+            }
+        });
+
+    let mut derives = vec![
+        parse_quote!(Debug),
+        parse_quote!(binroots::Serialize),
+        parse_quote!(binroots::Deserialize),
+    ];
+    if default_impl.is_none() && !no_default {
+        derives.insert(1, parse_quote!(Default));
+    }
+    // Without a default, the user may still derive `Default` themselves
+    let implemented: &[&str] = if no_default { &[] } else { &["Default"] };
+    let derives = merge_derives(&attrs, derives, implemented);
+    let attrs = attrs.iter().filter(|a| !a.path.is_ident("derive"));
+
     let output = quote! {
-        #[derive(Debug, Default, binroots::Serialize)]
+        #derives
         #( #attrs )*
         #vis enum #ident #generics {
             #(
@@ -123,6 +325,51 @@ pub fn binroots_enum(
                 #variants
             ),*
         }
+
+        #default_impl
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// The name of each variant, as written to the variant file by saves without a naming policy
+            pub const VARIANT_NAMES: &'static [&'static str] = &[#( #names ),*];
+
+            /// The contents of the variant file saved for `self` by saves without a naming policy
+            pub fn variant_file_value(&self) -> &'static str {
+                match self {
+                    #( #patterns => #names ),*
+                }
+            }
+
+            /// Constructs the unit variant named by the contents of a variant file, ignoring a trailing newline. Returns
+            /// `None` for unknown names and variants carrying data, whose payload is saved separately.
+            pub fn from_variant_file(contents: &str) -> Option<Self> {
+                match contents.strip_suffix('\n').unwrap_or(contents) {
+                    #( #unit_names => Some(Self::#unit_variants), )*
+                    _ => None,
+                }
+            }
+
+            /// The name of each variant, as written to the variant file by saves using `naming`
+            pub fn variant_names(naming: binroots::naming::NamingPolicy) -> Vec<String> {
+                Self::VARIANT_NAMES.iter().map(|name| naming.apply(name)).collect()
+            }
+
+            /// The contents of the variant file saved for `self` by saves using `naming`
+            pub fn variant_file_value_with(&self, naming: binroots::naming::NamingPolicy) -> String {
+                naming.apply(self.variant_file_value())
+            }
+
+            /// Like `from_variant_file`, for variant files saved using `naming`
+            pub fn from_variant_file_with(
+                contents: &str,
+                naming: binroots::naming::NamingPolicy,
+            ) -> Option<Self> {
+                let contents = contents.strip_suffix('\n').unwrap_or(contents);
+                #( if naming.apply(#unit_names) == contents {
+                    return Some(Self::#unit_variants);
+                } )*
+                None
+            }
+        }
     };
 
     output.into()
@@ -150,14 +397,33 @@ pub fn binroots_enum(
 ///     field2: String,
 ///     field3: bool,
 /// }
+///
+/// // --- OR ---
+///
+/// #[binroots_struct(config)] // Saves to `$XDG_CONFIG_HOME/<CARGO_PKG_NAME>/my-settings/` on Unix
+/// pub struct MySettings {
+///     theme: String,
+/// }
 /// ```
+/// `data` and `runtime` pick [`binroots::save::RootType::Data`][brrt] and [`binroots::save::RootType::Runtime`][brrt]
+/// the same way.
 /// The generated code includes a new implementation of the input struct with the following changes:
 ///     - Wraps each field in a [`binroots::field::BinrootsField`][brfield]
-///     - `derive`s [`Debug`] and [`binroots::Serialize`][brserialize]
-///     - Generates `Self::ROOT_FOLDER`, the kebab-case name of the struct
+///     - `derive`s [`Debug`], [`binroots::Serialize`][brserialize] and [`binroots::Deserialize`][brdeserialize]
+///     - Generates `Self::ROOT_FOLDER`, the kebab-case name of the struct unless pinned with `name = "..."`
+///     - Generates `Self::SCHEMA`, a [`binroots::consume::Schema`][brschema] describing each field for consumers outside of Rust
 ///     - Adds a `new` method to the struct, which constructs a new instance of the struct from its fields.
+///     - Adds a `builder` method returning a generated `<Struct>Builder`, which starts from [`Default`] and has a setter for each field.
 ///     - Adds a `save` method to the struct, which serializes the struct and saves it to disk using the [`binroots::save::Save`][brsave] trait, saving to `Self::ROOT_FOLDER`.
-///     - A [`Default`] implementation is added to the struct, which constructs a default instance of the struct with default values for all fields.
+///     - Adds a `reserve_root` method claiming `Self::ROOT_FOLDER` for the struct with [`binroots::namespace::reserve`][brreserve], failing if another struct in the program already saves there. `save`, `save_fields` and `ensure_root` call it first, and programs can call it at startup to detect collisions early.
+///     - Adds a `save_fields` method saving only the named fields in one pass with [`binroots::save::save_fields_with`][brsavefields]. The `json_mirror` isn't updated by partial saves.
+///     - Adds a `save_projected` method saving only the part of the struct at a path such as `"settings/theme"` with [`binroots::save::save_projected_with`][brsaveprojected], without cloning anything.
+///     - Adds `load` and `load_or_default` methods reading the struct back from `Self::ROOT_FOLDER` with [`binroots::load`][brload], the latter falling back to [`Default`] if the root is missing or can't be read. Not generated for structs with lifetimes, whose borrowed fields can't be loaded.
+///     - Adds an `ensure_root` method creating the files and folders of the [`Default`] value that don't exist yet with [`binroots::save::ensure_root`][brensureroot], leaving existing ones untouched.
+///     - Adds a `save_options` method returning the [`binroots::save::SaveOptions`][brsaveoptions] used by `save`, for saving single fields the same way
+///     - Records when each field was saved, see [`binroots::field::BinrootsField::last_saved`][brlastsaved]
+///     - Adds a `paths` method returning a generated `<Struct>Paths` holding the absolute on-disk path of each field, and a `path_of` method looking one up by name.
+///     - A [`Default`] implementation is added to the struct, which constructs a default instance of the struct with default values for all fields. For structs with lifetimes, it only applies when every field type implements [`Default`].
 ///
 // Example
 /// ```rust
@@ -190,99 +456,1543 @@ pub fn binroots_enum(
 ///     person.email.save(Person::ROOT_FOLDER, RootType::InMemory).unwrap(); // Saves only person.email to the disk in its appropriate location
 /// }
 /// ```
-/// [brserialize]: https://docs.rs/binroots/latest/binroots/trait.Serialize.html
-/// [brfield]: https://docs.rs/binroots/latest/binroots/field/struct.BinrootsField.html
-/// [brsave]: https://docs.rs/binroots/latest/binroots/save/trait.Save.html
-/// [brrt]: https://docs.rs/binroots/latest/binroots/save/struct.RootType.html
-#[proc_macro_attribute]
-pub fn binroots_struct(
-    attr: proc_macro::TokenStream,
-    item: proc_macro::TokenStream,
-) -> proc_macro::TokenStream {
-    let input = parse_macro_input!(item as DeriveInput);
-    let struct_name = &input.ident;
-    let vis = &input.vis;
-
-    let mut root_type =
-        quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::InMemory);
-
-    for a in attr {
-        if a.to_string() == "persistent" {
-            root_type = quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::Persistent);
-        }
-    }
-
-    let fields = if let syn::Data::Struct(syn::DataStruct {
-        fields: syn::Fields::Named(ref fields),
-        ..
-    }) = input.data
-    {
-        &fields.named
-    } else {
-        panic!("#[binroots_struct] only supports named struct fields")
-    };
-
-    let field_names = fields.iter().map(|field| {
-        let field_name = &field.ident.as_ref().unwrap();
-        let field_name_str = &field.ident.as_ref().unwrap().to_string();
-        let field_type = &field.ty;
-
-        quote! {
-            #field_name: binroots::field::BinrootsField<#field_name_str, #field_type>,
-        }
-    });
-
-    let field_initializers_new = fields.iter().map(|field| {
-        let field_name = &field.ident.as_ref().unwrap();
-        let field_name_str = &field.ident.as_ref().unwrap().to_string();
-        let field_type = &field.ty;
-
-        quote! {
-            #field_name: binroots::field::BinrootsField::<#field_name_str, #field_type>::new(#field_name),
-        }
-    });
-
-    let field_initializers_default = fields.iter().map(|field| {
-        let field_name = &field.ident.as_ref().unwrap();
-        let field_name_str = &field.ident.as_ref().unwrap().to_string();
-        let field_type = &field.ty;
-
-        quote! {
-            #field_name: binroots::field::BinrootsField::<#field_name_str, #field_type>::default(),
-        }
-    });
-
-    let struct_name_str = struct_name.to_string().to_case(Case::Kebab);
-
-    let output = quote! {
-        #[derive(Debug, binroots::Serialize)]
-        #vis struct #struct_name {
-            #( #field_names )*
-        }
-
-        impl #struct_name {
-            const ROOT_FOLDER: &'static str = #struct_name_str;
-            #root_type;
-            pub fn new(#fields) -> Self {
-                Self {
-                    #( #field_initializers_new )*
-                }
-            }
-
-            pub fn save(&self) -> Result<(), binroots::save::SaveError> {
-                binroots::save::Save::save(self, Self::ROOT_FOLDER, Self::ROOT_TYPE)
-            }
-        }
-
-        impl Default for #struct_name {
-            fn default() -> Self {
-                Self {
-                    #( #field_initializers_default )*
-                }
-            }
-        }
-
-    };
-
-    output.into()
+/// ## Derives and attributes
+/// Doc comments and other attributes on the struct and its fields are kept. Your derives are added to the generated
+/// `Debug`, `binroots::Serialize` and `binroots::Deserialize`, leaving out the ones that are already generated, including
+/// `Default`:
+/// ```rust
+/// use binroots::binroots_struct;
+///
+/// /// Whether the service is reachable
+/// #[binroots_struct]
+/// #[derive(Debug, Default, Clone, PartialEq)]
+/// pub struct Status {
+///     /// Set once the first peer connects
+///     #[serde(alias = "online")]
+///     is_online: bool,
+/// }
+///
+/// fn main() {
+///     let status = Status::default();
+///
+///     assert!(!*status.is_online);
+///     assert!(format!("{status:?}").starts_with("Status"));
+///     assert_eq!(status.clone(), status);
+/// }
+/// ```
+/// ## Builders
+/// `new` takes every field in order, which gets hard to read for large structs. `builder` starts from the [`Default`]
+/// value instead, only setting the fields that differ:
+/// ```rust
+/// use binroots::binroots_struct;
+///
+/// #[binroots_struct]
+/// pub struct Status {
+///     is_online: bool,
+///     peers: usize,
+///     motd: String,
+/// }
+///
+/// fn main() {
+///     let status = Status::builder().is_online(true).peers(3).build();
+///
+///     assert!(*status.is_online);
+///     assert_eq!(*status.peers, 3);
+///     assert_eq!(*status.motd, "");
+/// }
+/// ```
+/// ## Root collisions
+/// Two structs saving to the same folder would overwrite each other's files, so the second one to save fails instead:
+/// ```rust
+/// use binroots::save::SaveError;
+///
+/// mod network {
+///     #[binroots::binroots_struct]
+///     pub struct Status {
+///         pub online: bool,
+///     }
+/// }
+///
+/// mod audio {
+///     #[binroots::binroots_struct]
+///     pub struct Status {
+///         pub volume: u8,
+///     }
+/// }
+///
+/// fn main() {
+///     network::Status::reserve_root().unwrap(); // Detects collisions at startup
+///     network::Status::default().save().unwrap();
+///
+///     match audio::Status::default().save() {
+///         Err(SaveError::RootCollision { first, second, .. }) => {
+///             assert!(first.ends_with("network::Status"));
+///             assert!(second.ends_with("audio::Status"));
+///         }
+///         other => panic!("{other:?}"),
+///     }
+/// }
+/// ```
+/// ## Borrowed fields
+/// Structs with lifetime parameters are supported, so status structs can borrow from the program's state instead of
+/// cloning it before each save:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Greeting<'a> {
+///     user: &'a str,
+///     recent: Vec<&'a str>,
+///     unread: usize,
+/// }
+///
+/// fn main() {
+///     let user = String::from("alex");
+///     let recent = vec![String::from("sam"), String::from("kai")];
+///     Greeting::new(&user, recent.iter().map(String::as_str).collect(), 2).save().unwrap();
+///
+///     let root = root_location(Greeting::ROOT_TYPE).unwrap().join(Greeting::ROOT_FOLDER);
+///     assert_eq!(std::fs::read_to_string(root.join("user")).unwrap(), "alex");
+///     assert_eq!(std::fs::read_to_string(root.join("recent/1")).unwrap(), "kai");
+///     assert_eq!(*Greeting::default().user, "");
+/// }
+/// ```
+/// ## Generic fields
+/// Type parameters are supported too. `load` is available where the fields can be deserialized, and `Default` and the
+/// builder where every field has a default:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Cache<T: binroots::Serialize> {
+///     latest: T,
+///     hits: usize,
+/// }
+///
+/// fn main() {
+///     Cache::new(String::from("index.html"), 3).save().unwrap();
+///
+///     let root = root_location(Cache::<String>::ROOT_TYPE).unwrap().join("cache");
+///     assert_eq!(std::fs::read_to_string(root.join("latest")).unwrap(), "index.html");
+///     assert_eq!(*Cache::<String>::load().unwrap().hits, 3);
+/// }
+/// ```
+/// ## Tuple structs
+/// The fields of tuple structs are saved to numbered files, like tuples. `paths` and the builder name them `_0`, `_1`,
+/// and so on. Skipped fields aren't numbered, and the other field options aren't supported, since they find fields by
+/// name. A single field would be saved as the root itself, so at least two fields must be saved.
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Point(i32, #[binroots(skip)] bool, i32);
+///
+/// fn main() {
+///     Point::new(3, true, -4).save().unwrap();
+///
+///     let root = root_location(Point::ROOT_TYPE).unwrap().join("point");
+///     assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "3");
+///     assert_eq!(std::fs::read_to_string(root.join("1")).unwrap(), "-4");
+///     assert_eq!(Point::paths().unwrap()._2, root.join("1"));
+///     assert_eq!(*Point::load().unwrap().2, -4);
+/// }
+/// ```
+/// ## Field options
+/// Fields can be annotated with `#[binroots(...)]`:
+/// - `template = "..."` saves the field's contents formatted for humans, replacing `{value}` with the value, while the raw
+///   value is saved in a `.raw` sibling. Useful for files read in status bars rather than parsed by scripts.
+/// - `encoding = "cbor"` saves the field's leaves as compact CBOR in `.cbor` files, for fields read by other programs
+///   rather than people. Requires binroots' `cbor` feature.
+/// - `format = "json"` saves the whole field as a single pretty-printed `<field>.json` file instead of a tree of files and
+///   folders, for deeply nested values such as configs that are read and edited as a whole. `load` reads it back.
+/// - `elide_default` deletes the field's leaves while they hold their type's default value, such as `0` or an empty
+///   string, instead of writing them. `load` reads missing leaves back as the default.
+/// - `redact` saves the field's leaves as `***`, keeping private details in memory without publishing them. `load` reads
+///   redacted leaves back as the default. Maps can only be redacted together with `format = "json"`, since their keys
+///   would otherwise be saved as file names.
+/// - `rename = "..."` saves the field under another name, such as `is-online`, keeping its file in place when the Rust
+///   field is renamed. The name is still subject to `naming`, and is the name expected by `save_fields`.
+/// - `skip` leaves the field out of saving entirely, for secrets or runtime handles such as sockets. It isn't wrapped in a
+///   `BinrootsField` and isn't part of `SCHEMA` or `paths`, but is still set by `new`, `Default` and the builder. `load`
+///   sets it to its [`Default`].
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Network {
+///     #[binroots(template = "{value} connections")]
+///     connections: usize,
+///     #[binroots(elide_default)]
+///     errors: usize,
+///     #[binroots(redact)]
+///     token: String,
+///     #[binroots(format = "json")]
+///     peers: Vec<(String, u16)>,
+///     #[binroots(skip)]
+///     socket: Option<std::net::UdpSocket>,
+/// }
+///
+/// fn main() {
+///     let mut network = Network::default();
+///     *network.connections = 3;
+///     *network.token = "secret".into();
+///     network.save().unwrap();
+///
+///     let root = root_location(Network::ROOT_TYPE).unwrap().join(Network::ROOT_FOLDER);
+///     assert_eq!(std::fs::read_to_string(root.join("connections")).unwrap(), "3 connections");
+///     assert_eq!(std::fs::read_to_string(root.join("connections.raw")).unwrap(), "3");
+///     assert!(!root.join("errors").exists()); // Still 0
+///     assert_eq!(std::fs::read_to_string(root.join("token")).unwrap(), "***");
+///     assert_eq!(std::fs::read_to_string(root.join("peers.json")).unwrap(), "[]");
+///     assert!(!root.join("socket").exists());
+///
+///     // Single fields need the same options to be saved with the template
+///     network.connections.save_with(Network::ROOT_FOLDER, Network::ROOT_TYPE, &Network::save_options()).unwrap();
+/// }
+/// ```
+/// ## Saving some fields
+/// `self.save_fields(&[...])` saves a subset of the fields together, sharing the directory creation and locking that
+/// separate `BinrootsField::save` calls would each repeat:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Presence {
+///     is_online: bool,
+///     activity: String,
+///     bio: String,
+/// }
+///
+/// fn main() {
+///     let mut presence = Presence::new(false, "idle".into(), "hi".into());
+///     presence.save().unwrap();
+///
+///     *presence.is_online = true;
+///     *presence.activity = "coding".into();
+///     *presence.bio = "unsaved".into();
+///     presence.save_fields(&["is_online", "activity"]).unwrap();
+///
+///     let root = root_location(Presence::ROOT_TYPE).unwrap().join(Presence::ROOT_FOLDER);
+///     assert_eq!(std::fs::read_to_string(root.join("activity")).unwrap(), "coding");
+///     assert_eq!(std::fs::read_to_string(root.join("bio")).unwrap(), "hi");
+///     assert!(presence.save_fields(&["mood"]).is_err());
+/// }
+/// ```
+/// `self.autosave()` returns a [`binroots::guard::SaveGuard`][brguard], mutably borrowing the struct and calling `save`
+/// when it's dropped:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Player {
+///     track: String,
+///     position: u32,
+/// }
+///
+/// fn main() {
+///     let mut player = Player::new("intro".into(), 0);
+///
+///     {
+///         let mut player = player.autosave();
+///         *player.track = "outro".into();
+///         *player.position = 12;
+///     } // Saved here
+///
+///     let root = root_location(Player::ROOT_TYPE).unwrap().join(Player::ROOT_FOLDER);
+///     assert_eq!(std::fs::read_to_string(root.join("track")).unwrap(), "outro");
+///     assert!(player.track.last_saved().is_some());
+/// }
+/// ```
+/// `self.save_changed()` saves the fields that changed since they were last saved, found with
+/// `BinrootsField::is_dirty`. Fields are dirty until they're first saved, and after mutable access such as
+/// `*presence.bio = ...`:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Heartbeat {
+///     beats: u64,
+///     note: String,
+/// }
+///
+/// fn main() {
+///     let mut heartbeat = Heartbeat::new(1, "fine".into());
+///     heartbeat.save_changed().unwrap(); // Saves both fields
+///
+///     let root = root_location(Heartbeat::ROOT_TYPE).unwrap().join(Heartbeat::ROOT_FOLDER);
+///     std::fs::write(root.join("note"), "edited elsewhere").unwrap();
+///
+///     *heartbeat.beats += 1;
+///     assert!(heartbeat.beats.is_dirty() && !heartbeat.note.is_dirty());
+///     heartbeat.save_changed().unwrap(); // Only saves `beats`
+///
+///     assert_eq!(std::fs::read_to_string(root.join("beats")).unwrap(), "2");
+///     assert_eq!(std::fs::read_to_string(root.join("note")).unwrap(), "edited elsewhere");
+/// }
+/// ```
+/// `self.save_projected("...")` reaches further into a field, saving a single map entry, sequence element or nested field:
+/// ```rust
+/// use std::collections::BTreeMap;
+///
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Inventory {
+///     counts: BTreeMap<String, u32>,
+/// }
+///
+/// fn main() {
+///     let mut inventory = Inventory::new(BTreeMap::from([("apples".into(), 1), ("pears".into(), 2)]));
+///     inventory.save().unwrap();
+///
+///     inventory.counts.insert("apples".into(), 5);
+///     inventory.counts.insert("pears".into(), 7);
+///     inventory.save_projected("counts/apples").unwrap();
+///
+///     let root = root_location(Inventory::ROOT_TYPE).unwrap().join(Inventory::ROOT_FOLDER);
+///     assert_eq!(std::fs::read_to_string(root.join("counts/apples")).unwrap(), "5");
+///     assert_eq!(std::fs::read_to_string(root.join("counts/pears")).unwrap(), "2");
+/// }
+/// ```
+/// ## Loading
+/// `Self::load()` reads a saved struct back, using the same options as `save`:
+/// ```rust
+/// use binroots::binroots_struct;
+///
+/// #[binroots_struct]
+/// pub struct Session {
+///     user: String,
+///     #[binroots(template = "{value} tabs open")]
+///     tabs: usize,
+///     history: Vec<String>,
+/// }
+///
+/// fn main() {
+///     Session::new("alex".into(), 3, vec!["/".into(), "/about".into()]).save().unwrap();
+///
+///     let session = Session::load().unwrap();
+///     assert_eq!(*session.tabs, 3);
+///     assert_eq!(session.history.len(), 2);
+/// }
+/// ```
+/// ## Clearing the root
+/// `Self::clear()` removes the root folder with everything saved in it, such as on shutdown or for a "reset state"
+/// command. See [`binroots::save::delete_with`][brdelete].
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Session {
+///     user: String,
+/// }
+///
+/// fn main() {
+///     Session::new("alex".into()).save().unwrap();
+///     Session::clear().unwrap();
+///
+///     assert!(!root_location(Session::ROOT_TYPE).unwrap().join(Session::ROOT_FOLDER).exists());
+///     assert!(Session::clear().is_ok()); // Nothing left to remove
+/// }
+/// ```
+/// ## Preparing the root
+/// `Self::ensure_root()` creates the file interface before the first save, e.g. from an installer that sets its
+/// permissions. Fields that already exist keep their values:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Daemon {
+///     pid: u32,
+///     peers: Vec<String>,
+/// }
+///
+/// fn main() {
+///     let root = root_location(Daemon::ROOT_TYPE).unwrap().join(Daemon::ROOT_FOLDER);
+/// #   let _ = std::fs::remove_dir_all(&root);
+///     std::fs::create_dir_all(&root).unwrap();
+///     std::fs::write(root.join("pid"), "42").unwrap();
+///
+///     Daemon::ensure_root().unwrap();
+///
+///     assert_eq!(std::fs::read_to_string(root.join("pid")).unwrap(), "42");
+///     assert!(root.join("peers").is_dir());
+/// }
+/// ```
+/// ## Field paths
+/// `Self::paths()` resolves where each field lives on this platform, so paths can't silently break when a field is renamed:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct]
+/// pub struct Status {
+///     is_online: bool,
+/// }
+///
+/// fn main() {
+///     let root = root_location(Status::ROOT_TYPE).unwrap().join("status");
+///
+///     assert_eq!(Status::paths().unwrap().is_online, root.join("is_online"));
+///     assert_eq!(Status::path_of("is_online").unwrap(), Some(root.join("is_online")));
+///     assert_eq!(Status::path_of("is_offline").unwrap(), None);
+/// }
+/// ```
+/// A field's file can keep its name across Rust renames with `#[binroots(rename = "...")]`:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::{root_location, Save};
+///
+/// #[binroots_struct]
+/// pub struct Presence {
+///     #[binroots(rename = "is-online")]
+///     is_online: bool,
+/// }
+///
+/// fn main() {
+///     let root = root_location(Presence::ROOT_TYPE).unwrap().join("presence");
+///     Presence::new(true).save().unwrap();
+///
+///     assert!(root.join("is-online").exists());
+///     assert_eq!(Presence::paths().unwrap().is_online, root.join("is-online"));
+///     assert_eq!(Presence::path_of("is_online").unwrap(), Some(root.join("is-online")));
+/// }
+/// ```
+/// Each field also gets a `<FIELD>_PATH` constant with its path relative to the root location, known at compile time so
+/// it can be printed or documented for external scripts. `Self::field_paths()` lists all of them. Unlike `paths`, they
+/// don't follow a `.config` changing the naming at runtime, see `root_config`. Like `paths`, they point at the file
+/// holding the field's value: `<field>.json` for `format = "json"`, and the `<field>.raw` sibling for `template`.
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(naming = "kebab")]
+/// pub struct Status {
+///     is_online: bool,
+///     activity: String,
+/// }
+///
+/// fn main() {
+///     assert_eq!(Status::IS_ONLINE_PATH, "status/is-online");
+///     assert_eq!(Status::field_paths(), ["status/is-online", "status/activity"]);
+///
+///     let root = root_location(Status::ROOT_TYPE).unwrap();
+///     assert_eq!(root.join(Status::IS_ONLINE_PATH), Status::paths().unwrap().is_online);
+/// }
+/// ```
+/// Every constant matches the file `paths` and `save` use:
+/// ```rust
+/// use std::collections::BTreeMap;
+///
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(naming = "kebab")]
+/// pub struct Dashboard {
+///     #[binroots(rename = "parseHTTP2Response")]
+///     parse_http2_response: bool,
+///     #[binroots(format = "json")]
+///     key_binds: BTreeMap<String, String>,
+///     #[binroots(template = "{value}%")]
+///     battery_level: u8,
+/// }
+///
+/// fn main() {
+///     let root = root_location(Dashboard::ROOT_TYPE).unwrap();
+///     let paths = Dashboard::paths().unwrap();
+///     assert_eq!(
+///         Dashboard::field_paths().iter().map(|path| root.join(path)).collect::<Vec<_>>(),
+///         [paths.parse_http2_response, paths.key_binds, paths.battery_level]
+///     );
+///     assert_eq!(Dashboard::KEY_BINDS_PATH, "dashboard/key-binds.json");
+///     assert_eq!(Dashboard::BATTERY_LEVEL_PATH, "dashboard/battery-level.raw");
+///     assert_eq!(
+///         Dashboard::path_of("key_binds").unwrap(),
+///         Some(root.join(Dashboard::KEY_BINDS_PATH))
+///     );
+///
+///     Dashboard::new(true, BTreeMap::new(), 80).save().unwrap();
+///     for path in Dashboard::field_paths() {
+///         assert!(root.join(path).is_file(), "{path}");
+///     }
+/// }
+/// ```
+/// ## Naming
+/// `#[binroots_struct(naming = "...")]` applies a [`binroots::naming::NamingPolicy`][brnaming] to the root folder, the
+/// fields and the names of enum variants, so every path follows the same case. One of `"kebab"`, `"snake"` or `"as_is"`.
+/// Without it, the root folder is kebab-case while fields and variants keep their Rust names.
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(naming = "kebab")]
+/// pub struct NowPlaying {
+///     track_title: String,
+/// }
+///
+/// fn main() {
+///     NowPlaying::new("Intro".into()).save().unwrap();
+///
+///     let root = root_location(NowPlaying::ROOT_TYPE).unwrap().join("now-playing");
+///     assert_eq!(std::fs::read_to_string(root.join("track-title")).unwrap(), "Intro");
+///     assert_eq!(NowPlaying::paths().unwrap().track_title, root.join("track-title"));
+/// }
+/// ```
+/// Trees saved before choosing a policy can be moved over with [`binroots::naming::migrate`][brmigrate], which renames
+/// the fields but not the root folder itself.
+///
+/// `#[binroots_struct(name = "...")]` pins `ROOT_FOLDER` to the given name, used as-is, so renaming the struct doesn't move
+/// its root and break the scripts reading it:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(name = "status", naming = "kebab")]
+/// pub struct UserPresenceV2 {
+///     last_seen: u64,
+/// }
+///
+/// fn main() {
+///     assert_eq!(UserPresenceV2::ROOT_FOLDER, "status");
+///     UserPresenceV2::new(1700000000).save().unwrap();
+///
+///     let root = root_location(UserPresenceV2::ROOT_TYPE).unwrap().join("status");
+///     assert_eq!(std::fs::read_to_string(root.join("last-seen")).unwrap(), "1700000000");
+/// }
+/// ```
+///
+/// The name must be a single folder, so it can't be empty, `.` or `..`, or contain a path separator:
+/// ```compile_fail
+/// use binroots::binroots_struct;
+///
+/// #[binroots_struct(name = "..")]
+/// pub struct Escapes {
+///     last_seen: u64,
+/// }
+/// ```
+/// ## Consuming from other languages
+/// Annotating with `#[binroots_struct(json_mirror)]` makes `save` also write a canonical `<ROOT_FOLDER>.json` next to the folder,
+/// and `Self::SCHEMA` can generate reader modules that stay in sync with the Rust type:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::{root_location, RootType};
+///
+/// #[binroots_struct(json_mirror)]
+/// pub struct Weather {
+///     temperature: f32,
+///     alerts: Vec<String>,
+/// }
+///
+/// fn main() {
+///     Weather::new(21.5, vec!["Wind".into()]).save().unwrap(); // Also writes `/tmp/<CARGO_PKG_NAME>/weather.json` on Unix
+///
+///     let root = root_location(Weather::ROOT_TYPE).unwrap().join(Weather::ROOT_FOLDER);
+///     let python = Weather::SCHEMA.python_reader(&root);
+///     let typescript = Weather::SCHEMA.typescript_reader(&root);
+///
+///     assert!(python.contains("def alerts(self) -> List[str]:"));
+///     assert!(typescript.contains("get temperature(): number {"));
+/// }
+/// ```
+/// The readers keep the Rust field names, and read the files under the names the `naming` policy saves them as:
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(naming = "kebab")]
+/// pub struct NowListening {
+///     track_title: String,
+/// }
+///
+/// fn main() {
+///     NowListening::new("Intro".into()).save().unwrap();
+///
+///     let root = root_location(NowListening::ROOT_TYPE).unwrap().join(NowListening::ROOT_FOLDER);
+///     let python = NowListening::SCHEMA.python_reader(&root);
+///     assert!(python.contains("def track_title(self) -> str:\n        return _text(self.root, \"track-title\")"));
+///
+///     // Read the tree back through the stub where Python is available
+///     if let Ok(output) = std::process::Command::new("python3")
+///         .args(["-c", &format!("{python}\nprint(NowListening().track_title, end='')")])
+///         .output()
+///     {
+///         assert_eq!(String::from_utf8_lossy(&output.stdout), "Intro");
+///     }
+/// }
+/// ```
+/// ## End-user configuration
+/// Annotating with `#[binroots_struct(root_config)]` sets [`binroots::save::SaveOptions::root_config`][brrootconfig], so `save`
+/// and `load` follow a `.config` file that users write inside the root folder to tune the published format. Its
+/// `json_mirror` key also turns the JSON mirror on or off. See [`binroots::config`][brconfig] for the supported keys.
+/// ```rust
+/// use binroots::binroots_struct;
+/// use binroots::save::root_location;
+///
+/// #[binroots_struct(root_config)]
+/// pub struct Greeting {
+///     text: String,
+/// }
+///
+/// fn main() {
+///     let root = root_location(Greeting::ROOT_TYPE).unwrap().join(Greeting::ROOT_FOLDER);
+///     std::fs::create_dir_all(&root).unwrap();
+///     std::fs::write(root.join(".config"), "trailing_newline = true\njson_mirror = true\n").unwrap();
+///
+///     Greeting::new("hi".into()).save().unwrap();
+///
+///     assert_eq!(std::fs::read_to_string(root.join("text")).unwrap(), "hi\n");
+///     assert!(root.with_extension("json").exists());
+///     assert_eq!(*Greeting::load().unwrap().text, "hi");
+/// }
+/// ```
+/// [brserialize]: https://docs.rs/binroots/latest/binroots/trait.Serialize.html
+/// [brfield]: https://docs.rs/binroots/latest/binroots/field/struct.BinrootsField.html
+/// [brsave]: https://docs.rs/binroots/latest/binroots/save/trait.Save.html
+/// [brrt]: https://docs.rs/binroots/latest/binroots/save/struct.RootType.html
+/// [brdeserialize]: https://docs.rs/binroots/latest/binroots/trait.Deserialize.html
+/// [brload]: https://docs.rs/binroots/latest/binroots/load/index.html
+/// [brsavefields]: https://docs.rs/binroots/latest/binroots/save/fn.save_fields_with.html
+/// [brsaveprojected]: https://docs.rs/binroots/latest/binroots/save/fn.save_projected_with.html
+/// [brensureroot]: https://docs.rs/binroots/latest/binroots/save/fn.ensure_root.html
+/// [brnaming]: https://docs.rs/binroots/latest/binroots/naming/enum.NamingPolicy.html
+/// [brmigrate]: https://docs.rs/binroots/latest/binroots/naming/fn.migrate.html
+/// [brschema]: https://docs.rs/binroots/latest/binroots/consume/struct.Schema.html
+/// [brsaveoptions]: https://docs.rs/binroots/latest/binroots/save/struct.SaveOptions.html
+/// [brreserve]: https://docs.rs/binroots/latest/binroots/namespace/fn.reserve.html
+/// [brlastsaved]: https://docs.rs/binroots/latest/binroots/field/struct.BinrootsField.html#method.last_saved
+/// [brrootconfig]: https://docs.rs/binroots/latest/binroots/save/struct.SaveOptions.html#structfield.root_config
+/// [brconfig]: https://docs.rs/binroots/latest/binroots/config/index.html
+/// [brguard]: https://docs.rs/binroots/latest/binroots/guard/struct.SaveGuard.html
+/// [brdelete]: https://docs.rs/binroots/latest/binroots/save/fn.delete_with.html
+#[proc_macro_attribute]
+pub fn binroots_struct(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let struct_name = &input.ident;
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // Borrowed fields can't be loaded back from disk, which only yields owned data
+    let borrows = generics.lifetimes().next().is_some();
+
+    let mut root_type =
+        quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::InMemory);
+
+    let mut json_mirror = false;
+    let mut root_config = false;
+    let mut naming = None;
+    let mut root_folder = None;
+
+    let mut attr = attr.into_iter();
+    while let Some(a) = attr.next() {
+        match a.to_string().as_str() {
+            "persistent" => {
+                root_type = quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::Persistent);
+            }
+            "config" => {
+                root_type = quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::Config);
+            }
+            "data" => {
+                root_type = quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::Data);
+            }
+            "runtime" => {
+                root_type = quote!(const ROOT_TYPE: binroots::save::RootType = binroots::save::RootType::Runtime);
+            }
+            "json_mirror" => json_mirror = true,
+            "root_config" => root_config = true,
+            "naming" => {
+                let policy = attr.nth(1).map(|policy| policy.to_string());
+                naming = Some(match policy.as_deref().map(|policy| policy.trim_matches('"')) {
+                    Some("kebab") => (quote!(Kebab), Case::Kebab),
+                    Some("snake") => (quote!(Snake), Case::Snake),
+                    Some("as_is") => (quote!(AsIs), Case::Pascal),
+                    _ => panic!("Unknown #[binroots_struct(naming = ...)], expected \"kebab\", \"snake\" or \"as_is\""),
+                });
+            }
+            "name" => {
+                let name = attr
+                    .nth(1)
+                    .and_then(|name| syn::parse_str::<syn::LitStr>(&name.to_string()).ok())
+                    .map(|name| name.value());
+                root_folder = Some(match name {
+                    Some(name)
+                        if !name.is_empty()
+                            && name != "."
+                            && name != ".."
+                            && !name.contains(['/', '\\']) =>
+                    {
+                        name
+                    }
+                    _ => panic!("#[binroots_struct(name = ...)] expects a folder name as a string, e.g. name = \"status\""),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let (fields, tuple) = match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(ref fields),
+            ..
+        }) => (fields.named.iter().cloned().collect::<Vec<_>>(), false),
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Unnamed(ref fields),
+            ..
+        }) => (positional_fields(&fields.unnamed), true),
+        _ => panic!("#[binroots_struct] only supports structs with fields"),
+    };
+    let member = |field: &syn::Field| field_member(field, tuple);
+
+    let names_module = syn::Ident::new(
+        &format!("__binroots_{struct_name}_names"),
+        struct_name.span(),
+    );
+    // Skipped fields stay plain struct members, so everything about saving only concerns the other fields
+    let skipped = |field: &syn::Field| FieldOptions::parse(field).skip;
+    let saved_fields = fields
+        .iter()
+        .filter(|field| !skipped(field))
+        .collect::<Vec<_>>();
+    // Serde saves a tuple struct with a single field as the field itself, so there'd be no `0` to find it in
+    if tuple && saved_fields.len() < 2 {
+        panic!("#[binroots_struct] tuple structs need at least two saved fields, use named fields instead");
+    }
+
+    let names = field_name_markers(vis, &names_module, saved_fields.iter().copied());
+
+    let field_names = fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let name_param = field_name_param(&names_module, field);
+        let field_type = &field.ty;
+        let attrs = field.attrs.iter().filter(|a| !a.path.is_ident("binroots"));
+        let field_name = (!tuple).then(|| quote!(#field_name:));
+
+        if skipped(field) {
+            quote! {
+                #( #attrs )*
+                #[serde(skip)]
+                #field_name #field_type,
+            }
+        } else {
+            let rename = FieldOptions::parse(field)
+                .rename
+                .filter(|_| !tuple)
+                .map(|rename| quote!(#[serde(rename = #rename)]));
+
+            quote! {
+                #( #attrs )*
+                #rename
+                #field_name binroots::field::BinrootsField<#name_param, #field_type>,
+            }
+        }
+    });
+    let definition = if tuple {
+        quote!(#vis struct #struct_name #generics ( #( #field_names )* ) #where_clause;)
+    } else {
+        quote!(#vis struct #struct_name #generics #where_clause { #( #field_names )* })
+    };
+
+    let field_initializers_new = fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let member = member(field);
+        let name_param = field_name_param(&names_module, field);
+        let field_type = &field.ty;
+
+        if skipped(field) {
+            quote!(#member: #field_name,)
+        } else {
+            quote! {
+                #member: binroots::field::BinrootsField::<#name_param, #field_type>::new(#field_name),
+            }
+        }
+    });
+
+    let field_initializers_default = fields.iter().map(|field| {
+        let member = member(field);
+        let name_param = field_name_param(&names_module, field);
+        let field_type = &field.ty;
+
+        if skipped(field) {
+            quote!(#member: Default::default(),)
+        } else {
+            quote! {
+                #member: binroots::field::BinrootsField::<#name_param, #field_type>::default(),
+            }
+        }
+    });
+
+    let schema_fields = saved_fields.iter().map(|field| {
+        let field_name_str = &field_key(field);
+        let field_type = &field.ty;
+        let field_type_str = type_to_string(quote!(#field_type).to_string());
+
+        quote! {
+            binroots::consume::SchemaField { name: #field_name_str, ty: #field_type_str },
+        }
+    });
+
+    let new_params = fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let field_type = &field.ty;
+
+        quote!(#field_name: #field_type)
+    });
+
+    let templates = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+        let template = FieldOptions::parse(field).template?;
+
+        Some(quote!(.template(#field_name_str, #template)))
+    });
+
+    let encodings = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+        let encoding = match FieldOptions::parse(field).encoding?.as_str() {
+            "text" => quote!(Text),
+            "cbor" => quote!(Cbor),
+            other => panic!("Unknown #[binroots(encoding = \"{other}\")], expected \"text\" or \"cbor\""),
+        };
+
+        Some(quote!(.field_encoding(#field_name_str, binroots::fileserializer::LeafEncoding::#encoding)))
+    });
+
+    let formats = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+        let format = match FieldOptions::parse(field).format?.as_str() {
+            "json" => quote!(Json),
+            other => panic!("Unknown #[binroots(format = \"{other}\")], expected \"json\""),
+        };
+
+        Some(quote!(.field_format(#field_name_str, binroots::fileserializer::FieldFormat::#format)))
+    });
+
+    let elided = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+
+        FieldOptions::parse(field)
+            .elide_default
+            .then(|| quote!(.elide_field(#field_name_str)))
+    });
+
+    let redacted = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+
+        FieldOptions::parse(field)
+            .redact
+            .then(|| quote!(.redact_field(#field_name_str)))
+    });
+
+    // Saves clear the dirty flags of the fields in `fields` before serializing, so changes made meanwhile aren't lost
+    let saved_names = saved_fields.iter().map(|field| field_key(field));
+    let begin_save = saved_fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = member(field);
+            let field_name_str = &field_key(field);
+            let dirty = quote::format_ident!("__binroots_dirty_{i}");
+
+            quote! {
+                let #dirty = fields.contains(&#field_name_str).then(|| self.#member.begin_save());
+            }
+        })
+        .collect::<Vec<_>>();
+    let end_save = saved_fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = member(field);
+            let dirty = quote::format_ident!("__binroots_dirty_{i}");
+
+            quote! {
+                if let Some(was_dirty) = #dirty {
+                    self.#member.end_save(was_dirty, result.is_ok());
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let dirty_fields = saved_fields.iter().map(|field| {
+        let member = member(field);
+        let field_name_str = &field_key(field);
+
+        quote! {
+            if self.#member.is_dirty() {
+                fields.push(#field_name_str);
+            }
+        }
+    });
+
+    let path_fields = saved_fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let member = member(field);
+        let doc = format!("The absolute path of `{}`", quote!(#member));
+
+        quote! {
+            #[doc = #doc]
+            pub #field_name: std::path::PathBuf,
+        }
+    });
+
+    let path_initializers = saved_fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let field_name_str = &field_key(field);
+        let suffix = file_suffix(field);
+
+        quote! {
+            #field_name: root.join(naming.apply(#field_name_str) + #suffix),
+        }
+    });
+
+    let suffixes = saved_fields.iter().filter_map(|field| {
+        let field_name_str = &field_key(field);
+        let suffix = file_suffix(field);
+
+        (!suffix.is_empty()).then(|| quote!(#field_name_str => #suffix,))
+    });
+
+    let renamed = saved_fields.iter().filter_map(|field| {
+        let field_name_str = field.ident.as_ref().unwrap().to_string();
+        let rename = FieldOptions::parse(field).rename?;
+
+        Some(quote!(#field_name_str => #rename,))
+    });
+
+    let builder_setters = fields.iter().map(|field| {
+        let field_name = &field.ident.as_ref().unwrap();
+        let member = member(field);
+        let name_param = field_name_param(&names_module, field);
+        let field_type = &field.ty;
+        let doc = format!("Sets `{}`", quote!(#member));
+        let value = if skipped(field) {
+            quote!(#field_name)
+        } else {
+            quote!(binroots::field::BinrootsField::<#name_param, #field_type>::new(#field_name))
+        };
+
+        quote! {
+            #[doc = #doc]
+            pub fn #field_name(mut self, #field_name: #field_type) -> Self {
+                self.0.#member = #value;
+                self
+            }
+        }
+    });
+
+    let builder_name = syn::Ident::new(&format!("{struct_name}Builder"), struct_name.span());
+    let builder_doc = format!("Builds a [`{struct_name}`] starting from its [`Default`] value");
+
+    let paths_name = syn::Ident::new(&format!("{struct_name}Paths"), struct_name.span());
+    let paths_doc = format!("The absolute on-disk path of each field of [`{struct_name}`]");
+
+    let struct_name_str = match (root_folder, &naming) {
+        (Some(root_folder), _) => root_folder,
+        (None, Some((_, Case::Pascal))) => struct_name.to_string(),
+        (None, Some((_, case))) => struct_name.to_string().to_case(*case),
+        (None, None) => struct_name.to_string().to_case(Case::Kebab),
+    };
+    let path_consts = saved_fields
+        .iter()
+        .map(|field| {
+            let field_name = field.ident.as_ref().unwrap();
+            let const_name = syn::Ident::new(
+                &format!("{}_PATH", field_name.to_string().to_uppercase()),
+                field_name.span(),
+            );
+            let key = field_key(field);
+            let suffix = file_suffix(field);
+            let path = match &naming {
+                Some((_, Case::Kebab)) => {
+                    format!("{struct_name_str}/{}{suffix}", words(&key).join("-"))
+                }
+                Some((_, Case::Snake)) => {
+                    format!("{struct_name_str}/{}{suffix}", words(&key).join("_"))
+                }
+                _ => format!("{struct_name_str}/{key}{suffix}"),
+            };
+            let doc = format!(
+                "The path `{}` is saved to, relative to the root location",
+                quote!(#field_name)
+            );
+
+            (const_name, path, doc)
+        })
+        .collect::<Vec<_>>();
+    let path_const_names = path_consts.iter().map(|(name, _, _)| name);
+    let path_consts = path_consts.iter().map(|(name, path, doc)| {
+        quote! {
+            #[doc = #doc]
+            pub const #name: &'static str = #path;
+        }
+    });
+
+    let schema_naming = naming
+        .as_ref()
+        .map(|(policy, _)| quote!(binroots::naming::NamingPolicy::#policy))
+        .unwrap_or_else(|| quote!(binroots::naming::NamingPolicy::AsIs));
+    let naming = naming
+        .map(|(policy, _)| quote!(.naming(binroots::naming::NamingPolicy::#policy)))
+        .unwrap_or_default();
+    let struct_ident_str = struct_name.to_string();
+
+    let root_config = root_config.then(|| quote!(.root_config(true)));
+
+    let derives = if borrows {
+        vec![parse_quote!(Debug), parse_quote!(binroots::Serialize)]
+    } else {
+        vec![
+            parse_quote!(Debug),
+            parse_quote!(binroots::Serialize),
+            parse_quote!(binroots::Deserialize),
+        ]
+    };
+    // `Default` is implemented below, so a derived one would conflict
+    let derives = merge_derives(&input.attrs, derives, &["Default"]);
+    let attrs = input.attrs.iter().filter(|a| !a.path.is_ident("derive"));
+
+    let load = if borrows {
+        quote!()
+    } else {
+        quote! {
+            pub fn load() -> Result<Self, binroots::load::LoadError>
+            where
+                Self: for<'de> binroots::Deserialize<'de>,
+            {
+                binroots::load::load_with(Self::ROOT_FOLDER, Self::ROOT_TYPE, &Self::save_options())
+            }
+
+            pub fn load_or_default() -> Self
+            where
+                Self: Default + for<'de> binroots::Deserialize<'de>,
+            {
+                Self::load().unwrap_or_default()
+            }
+        }
+    };
+
+    // Borrowed fields such as `&'a Config` and generic ones such as `T` may not have a default, so only provide `Default`
+    // where every field does
+    let bounded = borrows || generics.type_params().next().is_some();
+    let default_bounds = fields.iter().filter(|_| bounded).map(|field| {
+        let field_type = &field.ty;
+
+        quote!(#field_type: Default,)
+    });
+    let default_where = if bounded {
+        let predicates = where_clause.into_iter().flat_map(|w| w.predicates.iter());
+        quote!(where #( #predicates, )* #( #default_bounds )*)
+    } else {
+        quote!(#where_clause)
+    };
+
+    let output = quote! {
+        #names
+
+        #derives
+        #( #attrs )*
+        #definition
+
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            const ROOT_FOLDER: &'static str = #struct_name_str;
+            #root_type;
+            const SCHEMA: binroots::consume::Schema = binroots::consume::Schema {
+                name: #struct_ident_str,
+                root_folder: Self::ROOT_FOLDER,
+                naming: #schema_naming,
+                fields: &[ #( #schema_fields )* ],
+            };
+            #( #path_consts )*
+
+            pub fn field_paths() -> &'static [&'static str] {
+                &[#( Self::#path_const_names ),*]
+            }
+
+            pub fn new(#( #new_params ),*) -> Self {
+                Self {
+                    #( #field_initializers_new )*
+                }
+            }
+
+            pub fn builder() -> #builder_name #ty_generics
+            where
+                Self: Default,
+            {
+                #builder_name(Self::default())
+            }
+
+            pub fn save_options() -> binroots::save::SaveOptions {
+                binroots::save::SaveOptions::default() #naming #root_config #( #templates )* #( #encodings )* #( #formats )* #( #elided )* #( #redacted )*
+            }
+
+            pub fn reserve_root() -> Result<(), binroots::save::SaveError> {
+                binroots::namespace::reserve(
+                    Self::ROOT_FOLDER,
+                    Self::ROOT_TYPE,
+                    concat!(module_path!(), "::", #struct_ident_str),
+                )
+            }
+
+            pub fn save(&self) -> Result<(), binroots::save::SaveError> {
+                Self::reserve_root()?;
+                let options = Self::save_options();
+                #[allow(unused_variables)]
+                let fields: &[&str] = &[ #( #saved_names ),* ];
+                #( #begin_save )*
+                let result = (|| -> Result<(), binroots::save::SaveError> {
+                    binroots::save::Save::save_with(self, Self::ROOT_FOLDER, Self::ROOT_TYPE, &options)?;
+                    if binroots::config::RootConfig::of(Self::ROOT_FOLDER, Self::ROOT_TYPE, &options)?
+                        .json_mirror
+                        .unwrap_or(#json_mirror)
+                    {
+                        binroots::consume::save_json_mirror(self, Self::ROOT_FOLDER, Self::ROOT_TYPE)?;
+                    }
+                    Ok(())
+                })();
+                #( #end_save )*
+                result
+            }
+
+            pub fn save_fields(&self, fields: &[&str]) -> Result<(), binroots::save::SaveError> {
+                Self::reserve_root()?;
+                #( #begin_save )*
+                let result = binroots::save::save_fields_with(self, fields, Self::ROOT_FOLDER, Self::ROOT_TYPE, &Self::save_options());
+                #( #end_save )*
+                result
+            }
+
+            pub fn clear() -> Result<(), binroots::save::SaveError> {
+                binroots::save::delete_with(Self::ROOT_FOLDER, Self::ROOT_TYPE, &Self::save_options())
+            }
+
+            pub fn autosave(&mut self) -> binroots::guard::SaveGuard<'_, Self> {
+                binroots::guard::SaveGuard::with_saver(self, Self::save)
+            }
+
+            pub fn save_changed(&self) -> Result<(), binroots::save::SaveError> {
+                let mut fields: Vec<&str> = Vec::new();
+                #( #dirty_fields )*
+                if fields.is_empty() {
+                    return Ok(());
+                }
+                self.save_fields(&fields)
+            }
+
+            pub fn save_projected(&self, field_path: &str) -> Result<(), binroots::save::SaveError> {
+                Self::reserve_root()?;
+                let fields = [field_path.trim_matches('/')];
+                #( #begin_save )*
+                let result = binroots::save::save_projected_with(self, field_path, Self::ROOT_FOLDER, Self::ROOT_TYPE, &Self::save_options());
+                #( #end_save )*
+                result
+            }
+
+            #load
+
+            pub fn ensure_root() -> Result<Vec<std::path::PathBuf>, binroots::save::SaveError>
+            where
+                Self: Default,
+            {
+                Self::reserve_root()?;
+                binroots::save::ensure_root::<Self, _>(Self::ROOT_FOLDER, Self::ROOT_TYPE, &Self::save_options())
+            }
+
+            pub fn paths() -> Result<#paths_name, binroots::save::RootLocationError> {
+                let root = binroots::save::root_location(Self::ROOT_TYPE)?.join(Self::ROOT_FOLDER);
+                let naming = Self::save_options().serializer.naming;
+
+                Ok(#paths_name {
+                    #( #path_initializers )*
+                })
+            }
+
+            pub fn path_of(field: &str) -> Result<Option<std::path::PathBuf>, binroots::save::RootLocationError> {
+                let root = binroots::save::root_location(Self::ROOT_TYPE)?.join(Self::ROOT_FOLDER);
+                let naming = Self::save_options().serializer.naming;
+                let field = match field {
+                    #( #renamed )*
+                    field => field,
+                };
+
+                Ok(Self::SCHEMA
+                    .fields
+                    .iter()
+                    .find(|f| f.name == field)
+                    .map(|f| {
+                        let suffix = match f.name {
+                            #( #suffixes )*
+                            _ => "",
+                        };
+                        root.join(naming.apply(f.name) + suffix)
+                    }))
+            }
+        }
+
+        #[doc = #builder_doc]
+        #vis struct #builder_name #generics (#struct_name #ty_generics) #where_clause;
+
+        impl #impl_generics #builder_name #ty_generics #where_clause {
+            #( #builder_setters )*
+
+            /// Finishes building
+            pub fn build(self) -> #struct_name #ty_generics {
+                self.0
+            }
+        }
+
+        #[doc = #paths_doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis struct #paths_name {
+            #( #path_fields )*
+        }
+
+        impl #impl_generics Default for #struct_name #ty_generics #default_where {
+            fn default() -> Self {
+                Self {
+                    #( #field_initializers_default )*
+                }
+            }
+        }
+
+    };
+
+    output.into()
+}
+
+/// Options set on a field of a [`binroots_struct`] with `#[binroots(...)]`
+#[derive(Default)]
+struct FieldOptions {
+    template: Option<String>,
+    encoding: Option<String>,
+    format: Option<String>,
+    elide_default: bool,
+    redact: bool,
+    skip: bool,
+    rename: Option<String>,
+}
+
+impl FieldOptions {
+    fn parse(field: &syn::Field) -> Self {
+        let mut options = Self::default();
+
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("binroots")) {
+            let Ok(syn::Meta::List(list)) = attr.parse_meta() else {
+                panic!("#[binroots] expects a list of options, e.g. #[binroots(template = \"{{value}}\")]")
+            };
+
+            for nested in list.nested {
+                match nested {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("template") => options.template = Some(lit.value()),
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("encoding") => options.encoding = Some(lit.value()),
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("format") => options.format = Some(lit.value()),
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("rename") => options.rename = Some(lit.value()),
+                    syn::NestedMeta::Meta(syn::Meta::Path(path))
+                        if path.is_ident("elide_default") =>
+                    {
+                        options.elide_default = true
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("redact") => {
+                        options.redact = true
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip") => {
+                        options.skip = true
+                    }
+                    _ => panic!("Unknown #[binroots] option on a field"),
+                }
+            }
+        }
+
+        options
+    }
+}
+
+/// Builds the `#[derive(...)]` of a generated type from the macro's own `derives` and the ones in the user's `attrs`.
+/// User derives of the same trait as one of `derives` or `implemented` are dropped, so they don't conflict.
+fn merge_derives(
+    attrs: &[syn::Attribute],
+    mut derives: Vec<syn::Path>,
+    implemented: &[&str],
+) -> proc_macro2::TokenStream {
+    let trait_name = |path: &syn::Path| path.segments.last().map(|s| s.ident.to_string());
+    let user_derives = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("derive"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten();
+
+    for derive in user_derives {
+        if let syn::NestedMeta::Meta(syn::Meta::Path(path)) = derive {
+            let name = trait_name(&path);
+            let provided = derives.iter().any(|d| trait_name(d) == name)
+                || implemented.iter().any(|i| name.as_deref() == Some(*i));
+
+            if !provided {
+                derives.push(path);
+            }
+        }
+    }
+
+    quote!(#[derive(#( #derives ),*)])
+}
+
+/// Names the fields of a tuple struct `_0`, `_1`, ... and saves them under their position among the saved fields, the way
+/// serde numbers them
+fn positional_fields(
+    fields: &syn::punctuated::Punctuated<syn::Field, syn::Token![,]>,
+) -> Vec<syn::Field> {
+    let mut position = 0;
+
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let mut field = field.clone();
+            let options = FieldOptions::parse(&field);
+            // These options find their field by name, which tuple elements don't have
+            if options.rename.is_some()
+                || options.template.is_some()
+                || options.encoding.is_some()
+                || options.format.is_some()
+                || options.elide_default
+                || options.redact
+            {
+                panic!("#[binroots_struct] tuple structs only support #[binroots(skip)] on their fields");
+            }
+
+            field.ident = Some(quote::format_ident!("_{i}"));
+            if !options.skip {
+                let key = position.to_string();
+                field.attrs.push(parse_quote!(#[binroots(rename = #key)]));
+                position += 1;
+            }
+
+            field
+        })
+        .collect()
+}
+
+/// How a field is accessed: by its name, or by its index in tuple structs, where [`positional_fields`] named it `_<index>`
+fn field_member(field: &syn::Field, tuple: bool) -> syn::Member {
+    let ident = field.ident.clone().unwrap();
+
+    if tuple {
+        syn::Member::Unnamed(syn::Index::from(
+            ident.to_string()[1..].parse::<usize>().unwrap(),
+        ))
+    } else {
+        syn::Member::Named(ident)
+    }
+}
+
+/// The value of `#[serde(<key> = "...")]` in `attrs`, if any
+fn serde_option(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("serde"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            })) if path.is_ident(key) => Some(lit.value()),
+            _ => None,
+        })
+}
+
+/// The rules of serde's `#[serde(rename_all = "...")]`
+const RENAME_RULES: &[&str] = &[
+    "lowercase",
+    "UPPERCASE",
+    "PascalCase",
+    "camelCase",
+    "snake_case",
+    "SCREAMING_SNAKE_CASE",
+    "kebab-case",
+    "SCREAMING-KEBAB-CASE",
+];
+
+/// Renames a PascalCase variant the way serde's `#[serde(rename_all = "...")]` does
+fn rename_variant(variant: &str, rename_all: Option<&str>) -> String {
+    let snake = || {
+        let mut snake = String::new();
+        for (i, ch) in variant.char_indices() {
+            if i > 0 && ch.is_uppercase() {
+                snake.push('_');
+            }
+            snake.push(ch.to_ascii_lowercase());
+        }
+        snake
+    };
+
+    match rename_all {
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => {
+            let mut chars = variant.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().to_ascii_uppercase().replace('_', "-"),
+        _ => variant.to_string(),
+    }
+}
+
+/// Splits `name` into lowercase words on `_`, `-`, spaces and case changes, like `binroots::naming::NamingPolicy` does.
+/// binroots depends on this crate, so the policy can't be called while expanding; the `<FIELD>_PATH` doc test checks
+/// that both agree
+fn words(name: &str) -> Vec<String> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if c.is_uppercase() && !word.is_empty() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map_or(false, |next| next.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        word.extend(c.to_lowercase());
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// The name a field is saved under: its `#[binroots(rename = "...")]`, or its Rust name. Only ever used as a string, since
+/// renames needn't be identifiers
+fn field_key(field: &syn::Field) -> String {
+    FieldOptions::parse(field)
+        .rename
+        .unwrap_or_else(|| field.ident.as_ref().unwrap().to_string())
+}
+
+/// The extension of the file holding a field's value: `.json` for `format = "json"`, since the field is saved as a
+/// single `<field>.json` file, and `.raw` for `template`, since `<field>` only holds the formatted value
+fn file_suffix(field: &syn::Field) -> &'static str {
+    let options = FieldOptions::parse(field);
+
+    if options.format.is_some() {
+        ".json"
+    } else if options.template.is_some() {
+        ".raw"
+    } else {
+        ""
+    }
+}
+
+/// The generic naming a field's `BinrootsField`: the field's name, or its marker type from [`field_name_markers`] with
+/// the `stable` feature
+fn field_name_param(names_module: &syn::Ident, field: &syn::Field) -> proc_macro2::TokenStream {
+    let field_name = field.ident.as_ref().unwrap();
+
+    if cfg!(feature = "stable") {
+        quote!(#names_module::#field_name)
+    } else {
+        let field_name_str = field_key(field);
+        quote!(#field_name_str)
+    }
+}
+
+/// With the `stable` feature, declares a zero-sized `binroots::field::FieldName` marker type per field in a hidden
+/// module, since stable Rust can't name a `BinrootsField` with a `&'static str` const generic
+fn field_name_markers<'a>(
+    vis: &syn::Visibility,
+    names_module: &syn::Ident,
+    fields: impl IntoIterator<Item = &'a syn::Field>,
+) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "stable") {
+        return quote!();
+    }
+
+    let markers = fields.into_iter().map(|field| {
+        let field_name = field.ident.as_ref().unwrap();
+        let field_name_str = field_key(field);
+
+        quote! {
+            pub struct #field_name;
+
+            impl binroots::field::FieldName for #field_name {
+                const NAME: &'static ::core::primitive::str = #field_name_str;
+            }
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_snake_case, non_camel_case_types)]
+        #vis mod #names_module {
+            #( #markers )*
+        }
+    }
+}
+
+/// Renders a type's tokens the way it would be written by hand, e.g. `Option<String>` instead of `Option < String >`
+fn type_to_string(raw: String) -> String {
+    let chars = raw.chars().collect::<Vec<_>>();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| {
+            **c != ' '
+                || (*i > 0
+                    && *i + 1 < chars.len()
+                    && is_word(chars[i - 1])
+                    && is_word(chars[i + 1]))
+        })
+        .map(|(_, c)| c)
+        .collect()
 }
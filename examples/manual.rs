@@ -27,6 +27,8 @@ struct Hello {
     uhoh: UhOh,
 }
 
+// The value only shows how floats are saved
+#[allow(clippy::approx_constant)]
 fn main() {
     println!("{:?}", binroots::save::root_location(RootType::InMemory));
     let h = Hello {
@@ -0,0 +1,245 @@
+//! ## `binroots::autosave`
+//! Contains [`Autosave`][`crate::autosave::Autosave`], which owns a value and saves it from a background thread whenever
+//! it changes, at most once per interval. Changes made in between are coalesced into the next save, so daemons updating
+//! their state hundreds of times a second only write it a few times.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use binroots::autosave::Autosave;
+//! use binroots::save::{root_location, RootType};
+//!
+//! let status = Autosave::new((0u32, "idle"), "autosave-status", RootType::InMemory, Duration::from_secs(1));
+//!
+//! for _ in 0..1000 {
+//!     status.update(|status| status.0 += 1);
+//! }
+//! status.update(|status| status.1 = "busy");
+//! status.flush().unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("autosave-status");
+//! assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "1000");
+//! assert!(status.saves() <= 2);
+//! ```
+
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::config;
+use crate::fileserializer::FileSerializer;
+use crate::save::{save_root_with, RootType, SaveError, SaveOptions};
+
+struct State<T> {
+    value: T,
+    dirty: bool,
+    saving: bool,
+    flushing: bool,
+    stopped: bool,
+    saves: u64,
+    error: Option<SaveError>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    changed: Condvar,
+    root: PathBuf,
+    root_type: RootType,
+    options: SaveOptions,
+    interval: Duration,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State<T>>) -> MutexGuard<'a, State<T>> {
+        self.changed.wait(state).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A value saved in the background after it changes, at most once per interval. Pending changes are saved when it's
+/// dropped.
+pub struct Autosave<T: Serialize + Send + 'static> {
+    shared: Arc<Shared<T>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T: Serialize + Send + 'static> std::fmt::Debug for Autosave<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Autosave")
+            .field("root", &self.shared.root)
+            .field("interval", &self.shared.interval)
+            .field("saves", &self.saves())
+            .finish()
+    }
+}
+
+impl<T: Serialize + Send + 'static> Autosave<T> {
+    /// Takes ownership of `value`, saving it to "[`root_location`][`crate::save::root_location`]/\<root\>" at most once
+    /// every `interval` while it changes
+    pub fn new<P: Into<PathBuf>>(
+        value: T,
+        root: P,
+        root_type: RootType,
+        interval: Duration,
+    ) -> Self {
+        Self::with_options(value, root, root_type, interval, SaveOptions::default())
+    }
+
+    /// Like [`Autosave::new`], saving with `options`
+    pub fn with_options<P: Into<PathBuf>>(
+        value: T,
+        root: P,
+        root_type: RootType,
+        interval: Duration,
+        options: SaveOptions,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                value,
+                dirty: false,
+                saving: false,
+                flushing: false,
+                stopped: false,
+                saves: 0,
+                error: None,
+            }),
+            changed: Condvar::new(),
+            root: root.into(),
+            root_type,
+            options,
+            interval,
+        });
+        let worker_shared = shared.clone();
+
+        Self {
+            shared,
+            worker: Some(std::thread::spawn(move || work(&worker_shared))),
+        }
+    }
+
+    /// Mutates the value, scheduling a save
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut state = self.shared.lock();
+        let result = f(&mut state.value);
+        state.dirty = true;
+        drop(state);
+
+        self.shared.changed.notify_all();
+        result
+    }
+
+    /// Reads the value without scheduling a save
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.shared.lock().value)
+    }
+
+    /// Saves pending changes right away and blocks until they're written. Returns the error of the last failed save
+    /// that wasn't returned yet, including failed background saves.
+    pub fn flush(&self) -> Result<(), SaveError> {
+        let mut state = self.shared.lock();
+        state.flushing = true;
+        self.shared.changed.notify_all();
+
+        while state.dirty || state.saving {
+            state = self.shared.wait(state);
+        }
+        state.flushing = false;
+
+        state.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Takes the error of the last failed background save, if any
+    pub fn take_error(&self) -> Option<SaveError> {
+        self.shared.lock().error.take()
+    }
+
+    /// The number of saves written so far
+    pub fn saves(&self) -> u64 {
+        self.shared.lock().saves
+    }
+}
+
+impl<T: Serialize + Send + 'static> Drop for Autosave<T> {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work<T: Serialize>(shared: &Shared<T>) {
+    let mut last_save: Option<Instant> = None;
+    let mut state = shared.lock();
+
+    loop {
+        while !state.dirty && !state.stopped {
+            state = shared.wait(state);
+        }
+        if !state.dirty {
+            return;
+        }
+
+        // Coalesce the changes made until the interval has passed
+        if let Some(due) = last_save.map(|last_save| last_save + shared.interval) {
+            while !state.stopped && !state.flushing {
+                let now = Instant::now();
+                if now >= due {
+                    break;
+                }
+                state = shared
+                    .changed
+                    .wait_timeout(state, due - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+
+        let serialized = serialize(shared, &state.value);
+        state.dirty = false;
+        state.saving = true;
+        drop(state);
+
+        let result = serialized.and_then(|(serializer, options)| {
+            save_root_with(
+                serializer,
+                shared.root.clone(),
+                shared.root_type.clone(),
+                &options,
+            )
+        });
+        last_save = Some(Instant::now());
+
+        state = shared.lock();
+        state.saving = false;
+        state.saves += 1;
+        if let Err(e) = result {
+            state.error = Some(e);
+        }
+        shared.changed.notify_all();
+    }
+}
+
+fn serialize<T: Serialize>(
+    shared: &Shared<T>,
+    value: &T,
+) -> Result<(FileSerializer, SaveOptions), SaveError> {
+    let options = config::configure(&shared.options, &shared.root, &shared.root_type)?.into_owned();
+
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer
+        .serialize_value(value)
+        .map_err(SaveError::SerializeError)?;
+
+    Ok((serializer, options))
+}
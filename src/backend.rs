@@ -0,0 +1,879 @@
+//! ## `binroots::backend`
+//! Contains the [`Backend`][`crate::backend::Backend`] trait performing the filesystem operations of a save, selected with
+//! [`SaveOptions::backend`][`crate::save::SaveOptions::backend`], and its default implementation
+//! [`Filesystem`][`crate::backend::Filesystem`]. [`Memory`][`crate::backend::Memory`] keeps the saved tree in memory
+//! instead, for unit tests, and with the `archive` feature, [`Archive`][`crate::backend::Archive`] exports it as a tar
+//! archive.
+//!
+//! With the `fault-injection` feature, [`FaultInjector`][`crate::backend::FaultInjector`] wraps another backend and fails
+//! chosen operations, so applications can test their handling of [`SaveError`][`crate::save::SaveError`] deterministically.
+//!
+//! ## Example
+//!
+//! A test double recording what a save writes, without touching the disk:
+//!
+//! ```
+//! use std::path::{Path, PathBuf};
+//! use std::sync::{Arc, Mutex};
+//!
+//! use binroots::save::{Backend, RootType, Save, SaveOptions};
+//!
+//! #[derive(Debug, Default)]
+//! struct Recorder(Mutex<Vec<(PathBuf, Vec<u8>)>>);
+//!
+//! impl Backend for Recorder {
+//!     fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+//!         self.0.lock().unwrap().push((path.to_path_buf(), contents.to_vec()));
+//!         Ok(())
+//!     }
+//!
+//!     fn mkdir(&self, _path: &Path) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn remove(&self, _path: &Path) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn remove_dir(&self, _path: &Path) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn rename(&self, _from: &Path, _to: &Path) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let recorder = Arc::new(Recorder::default());
+//! let options = SaveOptions::default().backend(recorder.clone());
+//! (7, "seven").save_with("backend-recorder", RootType::InMemory, &options).unwrap();
+//!
+//! let writes = recorder.0.lock().unwrap();
+//! assert!(writes.iter().any(|(path, contents)| path.ends_with("backend-recorder/1") && contents == b"seven"));
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::clock::IdGen;
+
+/// Performs the filesystem operations of a save. Paths are absolute.
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    /// Creates or truncates the file at `path` and writes `contents` to it
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()>;
+
+    /// Creates the folder at `path` along with its missing parents
+    fn mkdir(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Removes the file at `path`
+    fn remove(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Removes the folder at `path` along with its contents
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Moves the file or folder at `from` to `to`, replacing `to`
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+
+    /// Reads the contents of the file at `path`, such as a leaf compared before rewriting it. Defaults to
+    /// [`std::fs::read`]
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    /// Lists the entries of the folder at `path` in no particular order, such as the elements removed when a sequence
+    /// shrinks. Defaults to [`std::fs::read_dir`]
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+        std::fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                Ok(DirEntry {
+                    path: entry.path(),
+                    is_dir: file_type.is_dir(),
+                    is_symlink: file_type.is_symlink(),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether a file or folder exists at `path`, without following symlinks. Defaults to
+    /// [`std::fs::symlink_metadata`]
+    fn exists(&self, path: &Path) -> bool {
+        std::fs::symlink_metadata(path).is_ok()
+    }
+
+    /// Whether a folder exists at `path`, without following symlinks. Defaults to [`std::fs::symlink_metadata`]
+    fn is_dir(&self, path: &Path) -> bool {
+        std::fs::symlink_metadata(path).map_or(false, |metadata| metadata.is_dir())
+    }
+
+    /// Like [`Backend::write`], but hints the OS not to keep `contents` in its page cache afterwards, so writing a large
+    /// file doesn't evict data other programs are using. Used for leaves selected by
+    /// [`SaveOptions::drop_cache_above`][`crate::save::SaveOptions::drop_cache_above`]. Defaults to [`Backend::write`]
+    fn write_uncached(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.write(path, contents)
+    }
+
+    /// Like [`Backend::write`], but holds an exclusive lock on the file while replacing its contents, so readers using
+    /// [`read::locked_read`][`crate::read::locked_read`] never see a partial value. Used with
+    /// [`SaveOptions::lock_leaves`][`crate::save::SaveOptions::lock_leaves`]. Defaults to [`Backend::write`]
+    fn write_locked(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.write(path, contents)
+    }
+
+    /// Sets the Unix permission bits of the file at `path` to `mode`. Used by
+    /// [`SaveOptions::permissions`][`crate::save::SaveOptions::permissions`]. Defaults to doing nothing
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        let _ = (path, mode);
+        Ok(())
+    }
+
+    /// Creates the file at `path` with the Unix permission bits `mode` before anything is written to it, or gives an
+    /// existing file `mode` without changing its contents, so the contents are never readable with other permissions.
+    /// Used by [`SaveOptions::permissions`][`crate::save::SaveOptions::permissions`]. Defaults to doing nothing,
+    /// leaving the mode to [`Backend::set_mode`] once the file is written
+    fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        let _ = (path, mode);
+        Ok(())
+    }
+
+    /// Flushes the file or folder at `path` to durable storage, so a write or rename inside it survives power loss.
+    /// Used by [`SaveOptions::fsync`][`crate::save::SaveOptions::fsync`]. Defaults to doing nothing
+    fn sync(&self, path: &Path) -> std::io::Result<()> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Whether the backend operates on the real filesystem exactly like [`Filesystem`], letting async saves
+    /// perform its operations with `tokio::fs` instead. Defaults to `false`
+    fn is_filesystem(&self) -> bool {
+        false
+    }
+
+    /// Writes `contents` to a temporary file, then renames it over `path` so readers never see partial contents.
+    ///
+    /// The temporary file is created inside `temp_dir` if given, such as
+    /// [`SaveOptions::temp_dir`][`crate::save::SaveOptions::temp_dir`], or else next to `path`, named by [`temp_path`]
+    /// with an id from `ids`. If `mode` is given, the temporary file gets it before its contents are written and
+    /// before it's renamed over `path`, using [`Backend::create_with_mode`] and [`Backend::set_mode`]. If `temp_dir` is on another filesystem and the rename fails with
+    /// [`std::io::ErrorKind::CrossesDevices`], the contents are copied to a temporary file next to `path` and renamed from
+    /// there instead.
+    fn write_atomic(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        temp_dir: Option<&Path>,
+        ids: &dyn IdGen,
+        mode: Option<u32>,
+    ) -> std::io::Result<()> {
+        // Temporary files that weren't written whole or couldn't be renamed over `path` are removed again
+        let cleaned_up = |temp_file: &Path, result: std::io::Result<()>| {
+            if result.is_err() {
+                let _ = self.remove(temp_file);
+            }
+            result
+        };
+        let write = |temp_file: &Path| {
+            let written = match mode {
+                Some(mode) => self
+                    .create_with_mode(temp_file, mode)
+                    .and_then(|_| self.write(temp_file, contents))
+                    .and_then(|_| self.set_mode(temp_file, mode)),
+                None => self.write(temp_file, contents),
+            };
+            cleaned_up(temp_file, written)
+        };
+        let rename = |temp_file: &Path| cleaned_up(temp_file, self.rename(temp_file, path));
+        let local = || temp_path(path, None, ids);
+        let temp_file = temp_path(path, temp_dir, ids);
+
+        write(&temp_file)?;
+        match rename(&temp_file) {
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices && temp_dir.is_some() => {
+                let local = local();
+                write(&local)?;
+                rename(&local)
+            }
+            result => result,
+        }
+    }
+}
+
+/// An entry of a folder listed by [`Backend::read_dir`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DirEntry {
+    /// The absolute path of the entry
+    pub path: PathBuf,
+    /// Whether the entry is a folder. Symlinks aren't, even if they point to one
+    pub is_dir: bool,
+    /// Whether the entry is a symlink
+    pub is_symlink: bool,
+}
+
+/// The extension of the temporary files written by [`Backend::write_atomic`]. Names ending in it are reserved inside roots.
+pub const TEMP_EXTENSION: &str = "binroots-tmp";
+
+/// The temporary file [`Backend::write_atomic`] writes before renaming it over `path`: the hidden
+/// `.<file>.<id>.binroots-tmp` next to `path`, or inside `temp_dir` if given, with an id from `ids`. Unique ids keep
+/// concurrent writers, and values named like a temporary file, from clobbering each other.
+pub fn temp_path(path: &Path, temp_dir: Option<&Path>, ids: &dyn IdGen) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = format!(".{name}.{}.{TEMP_EXTENSION}", ids.next_id());
+    match temp_dir {
+        Some(temp_dir) => temp_dir.join(temp),
+        None => path.with_file_name(temp),
+    }
+}
+
+/// The default [`Backend`], operating on the real filesystem with [`std::fs`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Filesystem;
+
+impl Backend for Filesystem {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    /// On Linux, Android and FreeBSD, flushes the file and drops it from the page cache with
+    /// `posix_fadvise(POSIX_FADV_DONTNEED)`, since dirty pages can't be dropped. On Windows, opens the file with
+    /// `FILE_FLAG_SEQUENTIAL_SCAN` so the cache manager recycles its pages first. Elsewhere, writes normally.
+    fn write_uncached(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let mut open = std::fs::OpenOptions::new();
+        open.write(true).create(true).truncate(true);
+        #[cfg(target_family = "windows")]
+        {
+            use std::os::windows::fs::OpenOptionsExt;
+
+            const FILE_FLAG_SEQUENTIAL_SCAN: u32 = 0x0800_0000;
+            open.custom_flags(FILE_FLAG_SEQUENTIAL_SCAN);
+        }
+
+        let mut file = open.open(path)?;
+        file.write_all(contents)?;
+
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        {
+            use std::os::fd::AsRawFd;
+
+            file.sync_data()?;
+            // Only a hint, so failures are ignored
+            // SAFETY: `file` is open for the duration of the call, and `posix_fadvise` doesn't access any memory
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+        }
+
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    /// Takes an `flock` on Unix, truncating the file only once the lock is held. Elsewhere, writes normally.
+    fn write_locked(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            // SAFETY: `file` is open for the duration of the call. The lock is released when `file` is dropped.
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        file.set_len(0)?;
+        file.write_all(contents)
+    }
+
+    /// Does nothing outside Unix, where permissions aren't expressed as mode bits
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        {
+            let _ = (path, mode);
+            Ok(())
+        }
+    }
+
+    /// The mode given to `open` only applies to new files and is masked by the umask, so it's set again on the open
+    /// file before anything is written
+    fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(mode)
+                .open(path)?
+                .set_permissions(std::fs::Permissions::from_mode(mode))
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        {
+            let _ = (path, mode);
+            Ok(())
+        }
+    }
+
+    /// Folders can't be opened as files on Windows, where syncing them is skipped
+    fn sync(&self, path: &Path) -> std::io::Result<()> {
+        if cfg!(target_family = "windows") && path.is_dir() {
+            return Ok(());
+        }
+
+        std::fs::File::open(path)?.sync_all()
+    }
+
+    fn is_filesystem(&self) -> bool {
+        true
+    }
+}
+
+/// A [`Backend`] keeping the saved tree in memory, for unit-testing save logic without writing to the disk. Each
+/// instance holds its own tree, so tests running in parallel can't see each other's files. Saves also read the tree back
+/// through it, so shrinking sequences, [`SaveOptions::mirror`][`crate::save::SaveOptions::mirror`] and
+/// [`SaveOptions::etag`][`crate::save::SaveOptions::etag`] behave like on the disk.
+///
+/// Paths are the absolute paths a save would have written, below [`root_location`][`crate::save::root_location`]. Use
+/// [`Memory::tree`] to list the files below a root by their relative paths.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use binroots::backend::Memory;
+/// use binroots::save::{root_location, RootType, Save, SaveOptions};
+///
+/// let memory = Arc::new(Memory::new());
+/// let options = SaveOptions::default().backend(memory.clone());
+/// (true, "idle").save_with("memory-status", RootType::InMemory, &options).unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("memory-status");
+/// memory.assert_file(root.join("1"), "idle");
+/// assert_eq!(memory.read_to_string(root.join("0")).as_deref(), Some("true"));
+/// assert!(!root.join("1").exists()); // Never written to the disk
+/// ```
+#[derive(Debug, Default)]
+pub struct Memory {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    dirs: Mutex<HashSet<PathBuf>>,
+}
+
+impl Memory {
+    /// Constructs an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The contents of the file at `path`, or [`None`] if it wasn't written
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        self.lock_files().get(path.as_ref()).cloned()
+    }
+
+    /// The contents of the file at `path` as a string, or [`None`] if it wasn't written or isn't valid UTF-8
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        String::from_utf8(self.read(path)?).ok()
+    }
+
+    /// Whether a file or folder exists at `path`
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        self.lock_files().contains_key(path) || self.lock_dirs().contains(path)
+    }
+
+    /// The files below `root` and their contents, keyed by their path relative to `root`
+    pub fn tree<P: AsRef<Path>>(&self, root: P) -> BTreeMap<PathBuf, Vec<u8>> {
+        let root = root.as_ref();
+        self.lock_files()
+            .iter()
+            .filter_map(|(path, contents)| {
+                Some((
+                    path.strip_prefix(root).ok()?.to_path_buf(),
+                    contents.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Panics unless the file at `path` was written with `contents`
+    #[track_caller]
+    pub fn assert_file<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) {
+        let path = path.as_ref();
+        match self.read(path) {
+            Some(actual) if actual == contents.as_ref() => {}
+            Some(actual) => panic!(
+                "{path:?} contains {:?}, expected {:?}",
+                String::from_utf8_lossy(&actual),
+                String::from_utf8_lossy(contents.as_ref())
+            ),
+            None => panic!("{path:?} wasn't written"),
+        }
+    }
+
+    /// Panics if a file or folder exists at `path`
+    #[track_caller]
+    pub fn assert_missing<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        assert!(!self.exists(path), "{path:?} exists");
+    }
+
+    fn lock_files(&self) -> MutexGuard<'_, HashMap<PathBuf, Vec<u8>>> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_dirs(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+        self.dirs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend for Memory {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            self.mkdir(parent)?;
+        }
+        self.lock_files()
+            .insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        self.lock_dirs()
+            .extend(path.ancestors().map(Path::to_path_buf));
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        match self.lock_files().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(std::io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        if !self.lock_dirs().remove(path) {
+            return Err(std::io::ErrorKind::NotFound.into());
+        }
+        self.lock_dirs().retain(|dir| !dir.starts_with(path));
+        self.lock_files().retain(|file, _| !file.starts_with(path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut files = self.lock_files();
+        if let Some(contents) = files.remove(from) {
+            files.insert(to.to_path_buf(), contents);
+            return Ok(());
+        }
+
+        let moved = files
+            .keys()
+            .filter(|file| file.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for file in moved {
+            let contents = files.remove(&file).unwrap_or_default();
+            files.insert(to.join(file.strip_prefix(from).unwrap_or(&file)), contents);
+        }
+
+        let mut dirs = self.lock_dirs();
+        if !dirs.contains(from) {
+            return Err(std::io::ErrorKind::NotFound.into());
+        }
+        let moved = dirs
+            .iter()
+            .filter(|dir| dir.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for dir in moved {
+            dirs.remove(&dir);
+            dirs.insert(to.join(dir.strip_prefix(from).unwrap_or(&dir)));
+        }
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        Memory::read(self, path).ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+        if !self.is_dir(path) {
+            return Err(std::io::ErrorKind::NotFound.into());
+        }
+
+        let children = |is_dir| {
+            move |child: &PathBuf| {
+                (child.parent() == Some(path)).then(|| DirEntry {
+                    path: child.clone(),
+                    is_dir,
+                    is_symlink: false,
+                })
+            }
+        };
+        let mut entries = self
+            .lock_files()
+            .keys()
+            .filter_map(children(false))
+            .collect::<Vec<_>>();
+        entries.extend(self.lock_dirs().iter().filter_map(children(true)));
+        Ok(entries)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        Memory::exists(self, path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.lock_dirs().contains(path)
+    }
+}
+
+/// A [`Backend`] collecting a save in memory to export it as a tar archive with one entry per leaf, such as a snapshot
+/// of the program's state that users attach to bug reports. Requires the `archive` feature.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "archive")] {
+/// use std::sync::Arc;
+///
+/// use binroots::backend::Archive;
+/// use binroots::save::{root_location, RootType, Save, SaveOptions};
+///
+/// let archive = Arc::new(Archive::new());
+/// let options = SaveOptions::default().backend(archive.clone());
+/// (true, "idle").save_with("archive-status", RootType::InMemory, &options).unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("archive-status");
+/// let tar = archive.write_tar(&root, Vec::new()).unwrap();
+///
+/// let mut entries = tar::Archive::new(tar.as_slice());
+/// let paths = entries
+///     .entries()
+///     .unwrap()
+///     .map(|entry| entry.unwrap().path().unwrap().into_owned())
+///     .collect::<Vec<_>>();
+/// assert!(paths.contains(&"archive-status/1".into()));
+/// # }
+/// ```
+#[cfg(feature = "archive")]
+#[derive(Debug)]
+pub struct Archive {
+    tree: Memory,
+    clock: std::sync::Arc<dyn crate::clock::Clock>,
+}
+
+#[cfg(feature = "archive")]
+impl Default for Archive {
+    fn default() -> Self {
+        Self {
+            tree: Memory::default(),
+            clock: std::sync::Arc::new(crate::clock::SystemClock),
+        }
+    }
+}
+
+#[cfg(feature = "archive")]
+impl Archive {
+    /// Constructs an empty archive
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an empty archive whose entries are timestamped by `clock`, such as a
+    /// [`FixedClock`][`crate::clock::FixedClock`] for reproducible archives
+    pub fn with_clock(clock: std::sync::Arc<dyn crate::clock::Clock>) -> Self {
+        Self {
+            clock,
+            ..Self::default()
+        }
+    }
+
+    /// Writes the folders and files saved below `root` to `out` as a tar archive, returning `out`. Entries are named by
+    /// their path relative to the parent of `root`, so the archive unpacks into a folder named like the root.
+    pub fn write_tar<W: std::io::Write>(&self, root: &Path, out: W) -> std::io::Result<W> {
+        let base = root.parent().unwrap_or(root);
+        let mtime = self
+            .clock
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut builder = tar::Builder::new(out);
+
+        let mut dirs = self
+            .tree
+            .lock_dirs()
+            .iter()
+            .filter(|dir| dir.starts_with(root))
+            .cloned()
+            .collect::<Vec<_>>();
+        dirs.sort();
+        for dir in dirs {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_mtime(mtime);
+            header.set_size(0);
+            builder.append_data(
+                &mut header,
+                dir.strip_prefix(base).unwrap_or(&dir),
+                std::io::empty(),
+            )?;
+        }
+
+        for (path, contents) in self.tree.tree(root) {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_size(contents.len() as u64);
+            let name = root.join(path);
+            builder.append_data(
+                &mut header,
+                name.strip_prefix(base).unwrap_or(&name),
+                contents.as_slice(),
+            )?;
+        }
+
+        builder.into_inner()
+    }
+
+    /// Writes the archive of `root` to a `.tar` file at `path`, see [`Archive::write_tar`]
+    pub fn save_tar<P: AsRef<Path>>(&self, root: &Path, path: P) -> std::io::Result<()> {
+        self.write_tar(root, std::fs::File::create(path)?)?
+            .sync_all()
+    }
+}
+
+#[cfg(feature = "archive")]
+impl Backend for Archive {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.tree.write(path, contents)
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        self.tree.mkdir(path)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.tree.remove(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        self.tree.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.tree.rename(from, to)
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        Backend::read(&self.tree, path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+        self.tree.read_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.tree.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.tree.is_dir(path)
+    }
+}
+
+/// A [`Backend`] failing chosen operations of another backend. Requires the `fault-injection` feature.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "fault-injection")]
+/// # {
+/// use std::sync::Arc;
+///
+/// use binroots::backend::{FaultInjector, Filesystem};
+/// use binroots::save::{RootType, Save, SaveError, SaveOptions};
+///
+/// let faults = Arc::new(FaultInjector::new(Filesystem).fail_nth_write(2));
+/// let options = SaveOptions::default().backend(faults.clone());
+///
+/// assert!(matches!(
+///     (1, 2, 3).save_with("fault-injection", RootType::InMemory, &options),
+///     Err(SaveError::WriteFileError { .. })
+/// ));
+/// assert_eq!(faults.writes(), 2);
+/// # }
+/// ```
+#[cfg(feature = "fault-injection")]
+#[derive(Debug)]
+pub struct FaultInjector<B> {
+    inner: B,
+    fail_nth_write: Option<usize>,
+    fail_paths: Vec<std::path::PathBuf>,
+    kind: std::io::ErrorKind,
+    writes: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "fault-injection")]
+impl<B: Backend> FaultInjector<B> {
+    /// Wraps `inner`, initially failing nothing
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            fail_nth_write: None,
+            fail_paths: Vec::new(),
+            kind: std::io::ErrorKind::Other,
+            writes: Default::default(),
+        }
+    }
+
+    /// Fails the `n`th write performed through this backend, counting from `1`
+    pub fn fail_nth_write(mut self, n: usize) -> Self {
+        self.fail_nth_write = Some(n);
+        self
+    }
+
+    /// Fails every operation on a path ending with `path`, e.g. `status/connections`
+    pub fn fail_path<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.fail_paths.push(path.into());
+        self
+    }
+
+    /// Sets the [`std::io::ErrorKind`] of injected failures. Defaults to [`std::io::ErrorKind::Other`]
+    pub fn error_kind(mut self, kind: std::io::ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// The number of writes attempted through this backend, including failed ones
+    pub fn writes(&self) -> usize {
+        self.writes.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn count_write(&self) -> std::io::Result<()> {
+        let n = self
+            .writes
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+            + 1;
+        if self.fail_nth_write == Some(n) {
+            Err(std::io::Error::new(self.kind, "injected fault"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check(&self, path: &Path) -> std::io::Result<()> {
+        if self.fail_paths.iter().any(|p| path.ends_with(p)) {
+            Err(std::io::Error::new(self.kind, "injected fault"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+impl<B: Backend> Backend for FaultInjector<B> {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.count_write()?;
+        self.check(path)?;
+        self.inner.write(path, contents)
+    }
+
+    fn write_uncached(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.count_write()?;
+        self.check(path)?;
+        self.inner.write_uncached(path, contents)
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.mkdir(path)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.remove(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.check(from)?;
+        self.check(to)?;
+        self.inner.rename(from, to)
+    }
+
+    fn write_locked(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.count_write()?;
+        self.check(path)?;
+        self.inner.write_locked(path, contents)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.set_mode(path, mode)
+    }
+
+    fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.create_with_mode(path, mode)
+    }
+
+    fn sync(&self, path: &Path) -> std::io::Result<()> {
+        self.check(path)?;
+        self.inner.sync(path)
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.check(path)?;
+        self.inner.read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+        self.check(path)?;
+        self.inner.read_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner.is_dir(path)
+    }
+}
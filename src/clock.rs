@@ -0,0 +1,141 @@
+//! ## `binroots::clock`
+//! Contains the [`Clock`][`crate::clock::Clock`] and [`IdGen`][`crate::clock::IdGen`] traits that saves take their
+//! timestamps and unique names from, selected with [`SaveOptions::clock`][`crate::save::SaveOptions::clock`] and
+//! [`SaveOptions::ids`][`crate::save::SaveOptions::ids`]. They default to [`SystemClock`][`crate::clock::SystemClock`]
+//! and [`ProcessIds`][`crate::clock::ProcessIds`].
+//!
+//! [`FixedClock`][`crate::clock::FixedClock`] and [`SequentialIds`][`crate::clock::SequentialIds`] make saves
+//! deterministic, so tests and reproducible builds produce byte-identical roots across runs: `.mtime` sidecars,
+//! metadata, stats, trash folders, temporary files, snapshots and archives no longer depend on when or by which process
+//! they were written.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! use binroots::clock::FixedClock;
+//! use binroots::save::{root_location, RootType, Save, SaveOptions};
+//!
+//! let options = SaveOptions::default()
+//!     .clock(Arc::new(FixedClock(UNIX_EPOCH + Duration::from_secs(1_700_000_000))))
+//!     .mtime_sidecars(true);
+//! ("idle",).save_with("clock-status", RootType::InMemory, &options).unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("clock-status");
+//! assert_eq!(std::fs::read_to_string(root.join("0.mtime")).unwrap(), "1700000000");
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The source of the current time for timestamps written by a save
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current time truncated to seconds, as written to files such as `.binroots` and `.stats`
+    fn now_secs(&self) -> SystemTime {
+        let secs = self
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+}
+
+/// The default [`Clock`], reading [`SystemTime::now`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] that's always at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub SystemTime);
+
+impl Default for FixedClock {
+    /// The Unix epoch
+    fn default() -> Self {
+        Self(UNIX_EPOCH)
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// The source of unique names, such as the suffixes of temporary files in
+/// [`SaveOptions::temp_dir`][`crate::save::SaveOptions::temp_dir`]
+pub trait IdGen: std::fmt::Debug + Send + Sync {
+    /// Returns an id that this generator hasn't returned before. Must be usable in a file name.
+    fn next_id(&self) -> String;
+}
+
+/// The default [`IdGen`], returning `<process id>.<counter>` so ids are unique across processes sharing a folder
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessIds;
+
+impl IdGen for ProcessIds {
+    fn next_id(&self) -> String {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        format!(
+            "{}.{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+/// An [`IdGen`] counting up from `0`, for saves that must be reproducible. Only unique within one generator.
+#[derive(Debug, Default)]
+pub struct SequentialIds(AtomicU64);
+
+impl SequentialIds {
+    /// Constructs a generator starting at `0`
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGen for SequentialIds {
+    fn next_id(&self) -> String {
+        self.0.fetch_add(1, Ordering::Relaxed).to_string()
+    }
+}
+
+/// The [`SystemClock`] of every default [`SaveOptions`][`crate::save::SaveOptions`], shared so saves can tell whether
+/// it was replaced with [`is_default_clock`]
+pub(crate) fn default_clock() -> Arc<dyn Clock> {
+    static CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+    CLOCK.get_or_init(|| Arc::new(SystemClock)).clone()
+}
+
+/// The [`ProcessIds`] of every default [`SaveOptions`][`crate::save::SaveOptions`], see [`default_clock`]
+pub(crate) fn default_ids() -> Arc<dyn IdGen> {
+    static IDS: OnceLock<Arc<dyn IdGen>> = OnceLock::new();
+    IDS.get_or_init(|| Arc::new(ProcessIds)).clone()
+}
+
+/// Whether `clock` is the one returned by [`default_clock`]
+#[cfg(feature = "async_save")]
+pub(crate) fn is_default_clock(clock: &Arc<dyn Clock>) -> bool {
+    Arc::as_ptr(clock).cast::<()>() == Arc::as_ptr(&default_clock()).cast::<()>()
+}
+
+/// Whether `ids` is the generator returned by [`default_ids`]
+#[cfg(feature = "async_save")]
+pub(crate) fn is_default_ids(ids: &Arc<dyn IdGen>) -> bool {
+    Arc::as_ptr(ids).cast::<()>() == Arc::as_ptr(&default_ids()).cast::<()>()
+}
@@ -0,0 +1,133 @@
+//! ## `binroots::config`
+//! Contains [`RootConfig`][`crate::config::RootConfig`], read from an optional `<root>/.config` file that end users edit
+//! to tune how a root is published without a new build of the program. Only read when
+//! [`SaveOptions::root_config`][`crate::save::SaveOptions::root_config`] is set.
+//!
+//! The file holds one `key = value` pair per line. Blank lines and lines starting with `#` are ignored, and so are
+//! unknown keys and invalid values, so a typo never fails a save:
+//! - `trailing_newline = true` sets [`SerializeOptions::trailing_newline`]
+//! - `value_extension = payload` sets [`Markers::value`], the extension of enum payloads such as `activity.payload`
+//! - `json_mirror = false` turns the JSON mirror of a `#[binroots_struct(json_mirror)]` off, or on for any other struct
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::config::CONFIG_FILE;
+//! use binroots::save::{root_location, RootType, Save, SaveOptions};
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("config-greeting");
+//! std::fs::create_dir_all(&root).unwrap();
+//! std::fs::write(root.join(CONFIG_FILE), "# Friendlier for `cat`\ntrailing_newline = true\n").unwrap();
+//!
+//! let options = SaveOptions::default().root_config(true);
+//! ("hello",).save_with("config-greeting", RootType::InMemory, &options).unwrap();
+//!
+//! assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "hello\n");
+//! ```
+//!
+//! [`SerializeOptions::trailing_newline`]: crate::fileserializer::SerializeOptions::trailing_newline
+//! [`Markers::value`]: crate::fileserializer::Markers::value
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::save::{root_location, RootType, SaveError, SaveOptions};
+
+/// The name of the file inside a root that [`RootConfig`] is read from
+pub const CONFIG_FILE: &str = ".config";
+
+/// The settings of a root's [`CONFIG_FILE`]. Settings missing from the file are `None`, keeping the program's options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RootConfig {
+    /// Overrides [`SerializeOptions::trailing_newline`][`crate::fileserializer::SerializeOptions::trailing_newline`]
+    pub trailing_newline: Option<bool>,
+    /// Overrides [`Markers::value`][`crate::fileserializer::Markers::value`]
+    pub value_extension: Option<String>,
+    /// Whether the `save` method generated by [`binroots_struct`][`crate::binroots_struct`] writes the JSON mirror
+    pub json_mirror: Option<bool>,
+}
+
+impl RootConfig {
+    /// Parses the contents of a [`CONFIG_FILE`], skipping comments, unknown keys and invalid values
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "trailing_newline" => config.trailing_newline = value.parse().ok(),
+                "json_mirror" => config.json_mirror = value.parse().ok(),
+                "value_extension" if is_extension(value) => {
+                    config.value_extension = Some(value.to_string())
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    /// Reads the [`CONFIG_FILE`] inside the root folder at `path`, or returns the default config if there isn't one
+    pub fn read(path: &Path) -> Self {
+        std::fs::read_to_string(path.join(CONFIG_FILE))
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    /// Reads the config of "[`root_location`]/\<root\>" if [`SaveOptions::root_config`] is set, otherwise returns the
+    /// default config
+    pub fn of<P: Into<PathBuf>>(
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<Self, SaveError> {
+        if !options.root_config {
+            return Ok(Self::default());
+        }
+
+        let path = root_location(root_type)
+            .map_err(SaveError::RootLocationError)?
+            .join(root.into());
+
+        Ok(Self::read(&path))
+    }
+
+    /// Overrides the options covered by the config
+    pub fn apply(&self, options: &mut SaveOptions) {
+        if let Some(trailing_newline) = self.trailing_newline {
+            options.serializer.trailing_newline = trailing_newline;
+        }
+        if let Some(extension) = &self.value_extension {
+            options.serializer.markers.value.clone_from(extension);
+        }
+    }
+}
+
+/// `options` with the config of "[`root_location`]/\<root\>" applied, see [`RootConfig::of`]
+pub(crate) fn configure<'a>(
+    options: &'a SaveOptions,
+    root: &Path,
+    root_type: &RootType,
+) -> Result<Cow<'a, SaveOptions>, SaveError> {
+    let config = RootConfig::of(root, root_type.clone(), options)?;
+    if config == RootConfig::default() {
+        return Ok(Cow::Borrowed(options));
+    }
+
+    let mut options = options.clone();
+    config.apply(&mut options);
+
+    Ok(Cow::Owned(options))
+}
+
+/// Whether `value` can be used as a file extension
+fn is_extension(value: &str) -> bool {
+    !value.is_empty() && !value.contains(['.', '/', '\\']) && !value.chars().any(char::is_control)
+}
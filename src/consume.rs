@@ -0,0 +1,601 @@
+//! ## `binroots::consume`
+//! Contains tools for programs that consume a binroots tree from outside of Rust: the [`Schema`][`crate::consume::Schema`]
+//! generated by [`binroots_struct`][`crate::binroots_struct`], reader stub generators for Python and TypeScript, and the
+//! canonical JSON mirror written by [`save_json_mirror`][`crate::consume::save_json_mirror`].
+//!
+//! Also contains [`spawn_watcher`][`crate::consume::spawn_watcher`], for binaries that let end users run commands when a field changes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::fileserializer::SerializerError;
+use crate::naming::NamingPolicy;
+use crate::save::{root_location, save_to, RootType, SaveError};
+
+/// A description of a struct's on-disk layout, generated as `Self::SCHEMA` by [`binroots_struct`][`crate::binroots_struct`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schema {
+    /// The name of the Rust type
+    pub name: &'static str,
+    /// The folder the type saves to, usually `Self::ROOT_FOLDER`
+    pub root_folder: &'static str,
+    /// The policy the field names are saved under, see [`binroots::naming`][`crate::naming`]
+    pub naming: NamingPolicy,
+    /// Every saved field, in declaration order
+    pub fields: &'static [SchemaField],
+}
+
+/// A single field of a [`Schema`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaField {
+    /// The name of the field, saved as a file named by [`Schema::naming`]
+    pub name: &'static str,
+    /// The Rust type of the field as written in the struct definition, e.g. `Option<String>`
+    pub ty: &'static str,
+}
+
+/// How a consumer should interpret a field's file(s), derived from [`SchemaField::ty`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeafType {
+    /// `"true"` or `"false"`
+    Bool,
+    /// Any integer type
+    Int,
+    /// `f32` or `f64`
+    Float,
+    /// Strings, chars, and anything binroots doesn't know how to describe (such as an enum's variant name)
+    Text,
+    /// An `Option`, represented by the presence of the file
+    Optional(Box<LeafType>),
+    /// A sequence, represented by a folder of numbered files
+    List(Box<LeafType>),
+}
+
+impl LeafType {
+    /// Classifies a Rust type as written in source code
+    pub fn parse(ty: &str) -> Self {
+        let mut ty = ty.trim().trim_start_matches('&').trim();
+        if ty.starts_with('\'') {
+            ty = ty.split_once(' ').map_or("", |(_, rest)| rest).trim();
+        }
+        ty = ty.trim_start_matches("mut ").trim();
+
+        if let Some(inner) = ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            let inner = inner.split(';').next().unwrap_or_default();
+            return Self::List(Box::new(Self::parse(inner)));
+        }
+
+        let (base, args) = match ty.find('<') {
+            Some(i) => (&ty[..i], ty[i + 1..].strip_suffix('>').unwrap_or_default()),
+            None => (ty, ""),
+        };
+        let base = base.rsplit("::").next().unwrap_or(base).trim();
+        let first_arg = || Box::new(Self::parse(top_level_args(args).first().unwrap_or(&"")));
+
+        match base {
+            "bool" => Self::Bool,
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+            | "u128" | "usize" => Self::Int,
+            "f32" | "f64" => Self::Float,
+            "Option" => Self::Optional(first_arg()),
+            "Vec" | "VecDeque" => Self::List(first_arg()),
+            "Box" | "Rc" | "Arc" | "Cow" | "BinrootsField" => {
+                Self::parse(top_level_args(args).last().unwrap_or(&""))
+            }
+            _ => Self::Text,
+        }
+    }
+
+    fn python_reader(&self) -> String {
+        match self {
+            Self::Bool => "_bool".into(),
+            Self::Int => "_int".into(),
+            Self::Float => "_float".into(),
+            Self::Text => "_text".into(),
+            Self::Optional(inner) => format!("_optional({})", inner.python_reader()),
+            Self::List(inner) => format!("_list({})", inner.python_reader()),
+        }
+    }
+
+    fn python_type(&self) -> String {
+        match self {
+            Self::Bool => "bool".into(),
+            Self::Int => "int".into(),
+            Self::Float => "float".into(),
+            Self::Text => "str".into(),
+            Self::Optional(inner) => format!("Optional[{}]", inner.python_type()),
+            Self::List(inner) => format!("List[{}]", inner.python_type()),
+        }
+    }
+
+    fn typescript_reader(&self) -> String {
+        match self {
+            Self::Bool => "bool".into(),
+            Self::Int => "int".into(),
+            Self::Float => "float".into(),
+            Self::Text => "text".into(),
+            Self::Optional(inner) => format!("optional({})", inner.typescript_reader()),
+            Self::List(inner) => format!("list({})", inner.typescript_reader()),
+        }
+    }
+
+    fn typescript_type(&self) -> String {
+        match self {
+            Self::Bool => "boolean".into(),
+            Self::Int | Self::Float => "number".into(),
+            Self::Text => "string".into(),
+            Self::Optional(inner) => format!("{} | null", inner.typescript_type()),
+            Self::List(inner) => format!("Array<{}>", inner.typescript_type()),
+        }
+    }
+}
+
+fn top_level_args(args: &str) -> Vec<&str> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut out = Vec::new();
+
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if !args[start..].trim().is_empty() {
+        out.push(args[start..].trim());
+    }
+
+    out
+}
+
+/// Python's keywords, which can't name a property
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Turns a field name, which can be anything through `#[binroots(rename = "...")]`, into a Python and TypeScript
+/// identifier: characters other than ASCII letters, digits and `_` become `_`, and names starting with a digit or
+/// clashing with a Python keyword get a `_` prefix or suffix. `is-up` becomes `is_up`.
+fn identifier(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    if PYTHON_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    }
+    out
+}
+
+/// Quotes `path` as a JSON string, which is also a valid Python and TypeScript string literal
+fn string_literal(path: &Path) -> String {
+    serde_json::to_string(&path.to_string_lossy()).expect("strings always serialize")
+}
+
+impl Schema {
+    /// Returns the [`LeafType`] of every field, in declaration order
+    pub fn leaf_types(&self) -> Vec<(&'static str, LeafType)> {
+        self.fields
+            .iter()
+            .map(|f| (f.name, LeafType::parse(f.ty)))
+            .collect()
+    }
+
+    /// Generates a dependency-free Python 3 module with a class named after the struct that reads each field from `root`
+    /// (typically [`root_location`] joined with [`Schema::root_folder`]). Fields whose name isn't a Python identifier,
+    /// like a field renamed to `is-up`, become properties like `is_up`.
+    pub fn python_reader<P: AsRef<Path>>(&self, root: P) -> String {
+        let mut out = format!(
+            r#"# Generated by binroots from `{name}`. Do not edit by hand.
+import json
+import os
+from typing import List, Optional
+
+ROOT = {root}
+
+
+def _text(d, n):
+    with open(os.path.join(d, n), encoding="utf-8") as f:
+        return f.read()
+
+
+def _int(d, n):
+    return int(_text(d, n))
+
+
+def _float(d, n):
+    return float(_text(d, n))
+
+
+def _bool(d, n):
+    return _text(d, n) == "true"
+
+
+def _optional(read):
+    return lambda d, n: read(d, n) if os.path.exists(os.path.join(d, n)) else None
+
+
+def _list(read):
+    def inner(d, n):
+        path = os.path.join(d, n)
+        entries = sorted((e for e in os.listdir(path) if e.isdigit()), key=int)
+        return [read(path, e) for e in entries]
+
+    return inner
+
+
+class {name}:
+    def __init__(self, root=ROOT):
+        self.root = root
+
+    def mirror(self):
+        with open(self.root.rstrip("/") + ".json", encoding="utf-8") as f:
+            return json.load(f)
+"#,
+            name = self.name,
+            root = string_literal(root.as_ref()),
+        );
+
+        for (field, ty) in self.leaf_types() {
+            out += &format!(
+                r#"
+    @property
+    def {}(self) -> {}:
+        return {}(self.root, {:?})
+"#,
+                identifier(field),
+                ty.python_type(),
+                ty.python_reader(),
+                self.naming.apply(field),
+            );
+        }
+
+        out
+    }
+
+    /// Generates a TypeScript module for Node.js exporting a class named after the struct that reads each field from `root`
+    /// (typically [`root_location`] joined with [`Schema::root_folder`]). Fields are named as in
+    /// [`Schema::python_reader`].
+    pub fn typescript_reader<P: AsRef<Path>>(&self, root: P) -> String {
+        let mut out = format!(
+            r#"// Generated by binroots from `{name}`. Do not edit by hand.
+import * as fs from "fs";
+import * as path from "path";
+
+export const ROOT = {root};
+
+type Reader<T> = (dir: string, name: string) => T;
+
+const text: Reader<string> = (d, n) => fs.readFileSync(path.join(d, n), "utf8");
+const int: Reader<number> = (d, n) => parseInt(text(d, n), 10);
+const float: Reader<number> = (d, n) => parseFloat(text(d, n));
+const bool: Reader<boolean> = (d, n) => text(d, n) === "true";
+const optional =
+    <T>(read: Reader<T>): Reader<T | null> =>
+    (d, n) =>
+        fs.existsSync(path.join(d, n)) ? read(d, n) : null;
+const list =
+    <T>(read: Reader<T>): Reader<Array<T>> =>
+    (d, n) => {{
+        const dir = path.join(d, n);
+        return fs
+            .readdirSync(dir)
+            .filter((e) => /^\d+$/.test(e))
+            .sort((a, b) => Number(a) - Number(b))
+            .map((e) => read(dir, e));
+    }};
+
+export class {name} {{
+    constructor(public root: string = ROOT) {{}}
+
+    mirror(): unknown {{
+        return JSON.parse(fs.readFileSync(this.root.replace(/\/+$/, "") + ".json", "utf8"));
+    }}
+"#,
+            name = self.name,
+            root = string_literal(root.as_ref()),
+        );
+
+        for (field, ty) in self.leaf_types() {
+            out += &format!(
+                r#"
+    get {}(): {} {{
+        return {}(this.root, {:?});
+    }}
+"#,
+                identifier(field),
+                ty.typescript_type(),
+                ty.typescript_reader(),
+                self.naming.apply(field),
+            );
+        }
+
+        out + "}\n"
+    }
+}
+
+/// Returns the path of the JSON mirror for `root`: a `<root>.json` file next to the root folder
+pub fn json_mirror_path<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+) -> Result<PathBuf, SaveError> {
+    let root = root.into();
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root);
+
+    Ok(PathBuf::from(format!(
+        "{}.json",
+        path.to_string_lossy().trim_end_matches('/')
+    )))
+}
+
+/// Serializes `value` as canonical JSON into `<root>.json`, next to the root folder saved by [`Save::save`][`crate::save::Save::save`].
+///
+/// Called by the `save` method generated by [`binroots_struct`][`crate::binroots_struct`] when annotated with `#[binroots_struct(json_mirror)]`.
+pub fn save_json_mirror<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    root: P,
+    root_type: RootType,
+) -> Result<(), SaveError> {
+    let contents = serde_json::to_vec_pretty(value)
+        .map_err(|e| SaveError::SerializeError(SerializerError::Message(e.to_string())))?;
+
+    save_to(json_mirror_path(root, root_type)?, contents)
+}
+
+/// A running watcher spawned by [`spawn_watcher`] or [`spawn_polling_watcher`]. The watcher stops when this is dropped.
+#[derive(Debug)]
+pub struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    child: Option<Child>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatcherHandle {
+    /// Whether the watcher uses the operating system's notification mechanism rather than polling
+    pub fn is_native(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// Stops the watcher. Equivalent to dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Resolves the field a changed path belongs to, if it's one of `fields` (or any field when `fields` is empty)
+fn changed_field(root: &Path, changed: &Path, fields: &[String]) -> Option<String> {
+    let first = changed.strip_prefix(root).ok()?.components().next()?;
+    let name = first.as_os_str().to_string_lossy();
+    let name = name.strip_suffix(".value").unwrap_or(&name);
+
+    (fields.is_empty() || fields.iter().any(|f| f == name)).then(|| name.to_string())
+}
+
+fn native_watch_command(root: &Path) -> Option<Command> {
+    if cfg!(target_os = "linux") {
+        let mut command = Command::new("inotifywait");
+        command.args(["-m", "-r", "-q", "--format", "%w%f"]);
+        for event in ["close_write", "moved_to", "create", "delete"] {
+            command.args(["-e", event]);
+        }
+        command.arg(root);
+        Some(command)
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("fswatch");
+        command.arg("-r").arg(root);
+        Some(command)
+    } else {
+        None
+    }
+}
+
+/// Watches the `fields` of the folder saved to `root` (every field when `fields` is empty), calling `callback` with the
+/// name of the field whenever its file (or its `.value` sibling) changes.
+///
+/// Uses the operating system's notification mechanism when its command is available: `inotifywait` on Linux and
+/// `fswatch` on macOS. Otherwise, falls back to [`spawn_polling_watcher`] with a 250ms interval.
+///
+/// ## Example
+///
+/// ```no_run
+/// use binroots::consume::spawn_watcher;
+/// use binroots::save::RootType;
+///
+/// let watcher = spawn_watcher("status", RootType::InMemory, &["activity"], |field| {
+///     std::process::Command::new("notify-send").arg(format!("{field} changed")).status().ok();
+/// })
+/// .unwrap();
+///
+/// // ... the watcher stops when `watcher` is dropped
+/// # drop(watcher);
+/// ```
+pub fn spawn_watcher<P, F>(
+    root: P,
+    root_type: RootType,
+    fields: &[&str],
+    mut callback: F,
+) -> Result<WatcherHandle, SaveError>
+where
+    P: Into<PathBuf>,
+    F: FnMut(&str) + Send + 'static,
+{
+    let path = watched_root(root.into(), root_type)?;
+    let fields = fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+
+    let child = native_watch_command(&path).and_then(|mut command| {
+        command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()
+    });
+
+    let Some(mut child) = child else {
+        return spawn_polling_watcher_at(path, fields, Duration::from_millis(250), callback);
+    };
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let thread = std::thread::spawn(move || {
+        for line in std::io::BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if thread_stop.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if let Some(field) = changed_field(&path, Path::new(line.trim()), &fields) {
+                callback(&field);
+            }
+        }
+    });
+
+    Ok(WatcherHandle {
+        stop,
+        child: Some(child),
+        thread: Some(thread),
+    })
+}
+
+/// Like [`spawn_watcher`], but always polls the modification times of the watched files every `interval`. A change is
+/// reported once the file looks the same in two polls in a row, so a file that's truncated before being written is only
+/// reported once.
+pub fn spawn_polling_watcher<P, F>(
+    root: P,
+    root_type: RootType,
+    fields: &[&str],
+    interval: Duration,
+    callback: F,
+) -> Result<WatcherHandle, SaveError>
+where
+    P: Into<PathBuf>,
+    F: FnMut(&str) + Send + 'static,
+{
+    let path = watched_root(root.into(), root_type)?;
+    let fields = fields.iter().map(|f| f.to_string()).collect();
+
+    spawn_polling_watcher_at(path, fields, interval, callback)
+}
+
+fn watched_root(root: PathBuf, root_type: RootType) -> Result<PathBuf, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root);
+
+    std::fs::create_dir_all(&path).map_err(|e| SaveError::CreateDirectoryError {
+        path: path.clone(),
+        source: e,
+        field: None,
+    })?;
+
+    Ok(path)
+}
+
+fn spawn_polling_watcher_at<F>(
+    path: PathBuf,
+    fields: Vec<String>,
+    interval: Duration,
+    mut callback: F,
+) -> Result<WatcherHandle, SaveError>
+where
+    F: FnMut(&str) + Send + 'static,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let thread = std::thread::spawn(move || {
+        let mut reported = poll_snapshot(&path);
+        let mut previous = reported.clone();
+
+        while !thread_stop.load(Ordering::Relaxed) {
+            std::thread::sleep(interval);
+
+            let current = poll_snapshot(&path);
+            let settled = reported
+                .keys()
+                .chain(current.keys())
+                .filter(|p| {
+                    reported.get(*p) != current.get(*p) && previous.get(*p) == current.get(*p)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let mut changed = BTreeSet::new();
+            for p in settled {
+                match current.get(&p) {
+                    Some(state) => reported.insert(p.clone(), *state),
+                    None => reported.remove(&p),
+                };
+                changed.extend(changed_field(&path, &p, &fields));
+            }
+
+            for field in changed {
+                callback(&field);
+            }
+
+            previous = current;
+        }
+    });
+
+    Ok(WatcherHandle {
+        stop,
+        child: None,
+        thread: Some(thread),
+    })
+}
+
+fn poll_snapshot(path: &Path) -> BTreeMap<PathBuf, (Option<SystemTime>, u64)> {
+    let mut out = BTreeMap::new();
+    let mut pending = vec![path.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            }
+            out.insert(entry.path(), (metadata.modified().ok(), metadata.len()));
+        }
+    }
+
+    out
+}
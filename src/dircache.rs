@@ -0,0 +1,197 @@
+//! ## `binroots::dircache`
+//! Contains [`DirCache`][`crate::dircache::DirCache`], the folders a save already created. Every save skips creating a
+//! folder twice, so values with hundreds of files sharing a parent cost one `create_dir_all` per folder instead of one per
+//! file. Sharing a cache across saves with [`SaveOptions::dir_cache`][`crate::save::SaveOptions::dir_cache`] skips the
+//! folders of previous saves too.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use binroots::dircache::DirCache;
+//! use binroots::save::{RootType, Save, SaveOptions};
+//!
+//! let cache = Arc::new(DirCache::new());
+//! let options = SaveOptions::default().dir_cache(cache.clone());
+//!
+//! for tick in 0..3 {
+//!     vec![vec![tick; 4]; 4].save_with("dircache-grid", RootType::InMemory, &options).unwrap();
+//! }
+//! assert!(!cache.is_empty());
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::backend::{Backend, DirEntry};
+use crate::clock::IdGen;
+
+/// The folders created by saves, see the [module documentation][`crate::dircache`]. Folders removed by a save are
+/// forgotten. If a folder is removed by something else, such as another program, the next write into it recreates the
+/// folder and retries once.
+#[derive(Debug, Default)]
+pub struct DirCache {
+    created: Mutex<HashSet<PathBuf>>,
+}
+
+impl DirCache {
+    /// Constructs an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets every folder, so the next save creates them again
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The number of folders known to exist
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no folder is known to exist
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+        self.created.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.lock().contains(path)
+    }
+
+    /// Records `path` and its parents, which were created along with it
+    fn insert(&self, path: &Path) {
+        let mut created = self.lock();
+        for dir in path.ancestors() {
+            if !created.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    /// Forgets `path` and the folders below it
+    fn forget(&self, path: &Path) {
+        self.lock().retain(|dir| !dir.starts_with(path));
+    }
+}
+
+/// A [`Backend`] skipping the folders in a [`DirCache`] when forwarding to `inner`
+#[derive(Debug)]
+pub(crate) struct CachedDirs {
+    inner: Arc<dyn Backend>,
+    cache: Arc<DirCache>,
+}
+
+impl CachedDirs {
+    pub(crate) fn new(inner: Arc<dyn Backend>, cache: Arc<DirCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    /// Performs the write `write` into `path`, recreating its folder and retrying if the cached folder was removed
+    fn write_with(
+        &self,
+        path: &Path,
+        write: impl Fn() -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        match (write(), path.parent()) {
+            (Err(e), Some(parent))
+                if e.kind() == std::io::ErrorKind::NotFound && self.cache.contains(parent) =>
+            {
+                self.cache.forget(parent);
+                self.mkdir(parent)?;
+                write()
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+impl Backend for CachedDirs {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.write_with(path, || self.inner.write(path, contents))
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        if self.cache.contains(path) {
+            return Ok(());
+        }
+
+        self.inner.mkdir(path)?;
+        self.cache.insert(path);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.remove(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        self.cache.forget(path);
+        self.inner.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.cache.forget(from);
+        self.cache.forget(to);
+        self.inner.rename(from, to)
+    }
+
+    fn write_uncached(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.write_with(path, || self.inner.write_uncached(path, contents))
+    }
+
+    fn write_locked(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.write_with(path, || self.inner.write_locked(path, contents))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.inner.set_mode(path, mode)
+    }
+
+    fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.write_with(path, || self.inner.create_with_mode(path, mode))
+    }
+
+    fn sync(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.sync(path)
+    }
+
+    fn is_filesystem(&self) -> bool {
+        self.inner.is_filesystem()
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+        self.inner.read_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn write_atomic(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        temp_dir: Option<&Path>,
+        ids: &dyn IdGen,
+        mode: Option<u32>,
+    ) -> std::io::Result<()> {
+        self.write_with(path, || {
+            self.inner.write_atomic(path, contents, temp_dir, ids, mode)
+        })
+    }
+}
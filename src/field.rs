@@ -1,5 +1,14 @@
 //! ## `binroots::field`
 //! Contains the [`BinrootsField`][`crate::field::BinrootsField`] struct
+//!
+//! By default a field's name is a `&'static str` const generic, which requires nightly Rust. With the `stable` feature
+//! enabled, the name is a zero-sized marker type implementing `FieldName` instead, and
+//! [`binroots::binroots_struct`][`crate::binroots_struct`] generates those markers for each field.
+
+#[cfg(feature = "stable")]
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// # BinrootsField
 /// A wrapper type for fields generated by [`binroots::binroots_struct`][`crate::binroots_struct`]
@@ -9,62 +18,350 @@
 /// [`BinrootsField::save`][struct.BinrootsField.html#method.save] contains a couple differences than other implementations:
 /// - Unlike [`binroots::binroots_struct`][`crate::binroots_struct`], it requires a root folder to save to (typically `Struct::ROOT_FOLDER`)
 /// - Modifies the root save path by appending `BinrootsField::N` (generated as the field name by [`binroots::binroots_struct`][`crate::binroots_struct`])
-#[derive(Default)]
+///
+/// ## Shared and interior-mutable values
+///
+/// Values such as `Cow<str>`, `Rc<T>`, `Arc<str>`, `Cell<T>`, `RefCell<T>` and `Mutex<T>` save exactly like the value they
+/// contain. [`BinrootsField::replace`] swaps in a new value (such as a fresh `Arc`), and [`BinrootsField::update`] mutates a
+/// `RefCell` or `Mutex` through a shared reference without unwrapping it first.
+///
+/// ## Change tracking
+///
+/// Each field remembers when it was last saved, either on its own or as part of its struct. See [`BinrootsField::last_saved`].
+/// Mutable access marks it as changed until the next save, see [`BinrootsField::is_dirty`].
+///
+/// ## Standard traits
+///
+/// `Clone`, `PartialEq`, `Eq`, `PartialOrd`, `Ord` and `Hash` are implemented whenever the value implements them, and
+/// only look at the value, so structs wrapping their fields can derive them like plain structs.
+#[cfg(not(feature = "stable"))]
 pub struct BinrootsField<const N: &'static str, T> {
     pub(crate) value: T,
+    /// Nanoseconds since the Unix epoch, `0` if never saved
+    last_saved: AtomicU64,
+    /// Whether the value may have changed since it was last saved
+    dirty: AtomicBool,
+}
+
+/// # BinrootsField
+/// A wrapper type for fields generated by [`binroots::binroots_struct`][`crate::binroots_struct`], named by the marker
+/// type `N`. See the `stable` feature in [`binroots::field`][`crate::field`].
+#[cfg(feature = "stable")]
+pub struct BinrootsField<N: FieldName, T> {
+    pub(crate) value: T,
+    /// Nanoseconds since the Unix epoch, `0` if never saved
+    last_saved: AtomicU64,
+    /// Whether the value may have changed since it was last saved
+    dirty: AtomicBool,
+    name: PhantomData<fn() -> N>,
+}
+
+/// Names a [`BinrootsField`] on stable Rust, in place of a `&'static str` const generic. Requires the `stable` feature.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "stable")] {
+/// use binroots::field::{BinrootsField, FieldName};
+///
+/// struct Status;
+///
+/// impl FieldName for Status {
+///     const NAME: &'static str = "status";
+/// }
+///
+/// assert_eq!(BinrootsField::<Status, String>::name(), "status");
+/// # }
+/// ```
+#[cfg(feature = "stable")]
+pub trait FieldName {
+    /// The field's name, used as its file name
+    const NAME: &'static str;
 }
 
-impl<const N: &'static str, T: serde::Serialize> serde::Serialize for BinrootsField<N, T> {
-    /// Serializes the field's inner value if the value implements [`binroots::Serialize`][`crate::Serialize`]
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        self.value.serialize(serializer)
+/// Declares an impl block for [`BinrootsField`], taking the field name generic `N` as a `&'static str` const generic, or
+/// as a [`FieldName`] with the `stable` feature
+macro_rules! field_impl {
+    (impl<$($lt:lifetime,)? N $(, $p:ident $(: $b:path)?)*> $($rest:tt)*) => {
+        #[cfg(not(feature = "stable"))]
+        impl<$($lt,)? const N: &'static str $(, $p $(: $b)?)*> $($rest)*
+
+        #[cfg(feature = "stable")]
+        impl<$($lt,)? N: $crate::field::FieldName $(, $p $(: $b)?)*> $($rest)*
+    };
+}
+
+pub(crate) use field_impl;
+
+field_impl! {
+    impl<N, T: Default> Default for BinrootsField<N, T> {
+        fn default() -> Self {
+            Self::new(T::default())
+        }
     }
 }
 
-impl<const N: &'static str, T> std::ops::Deref for BinrootsField<N, T> {
-    type Target = T;
+field_impl! {
+    impl<N, T: serde::Serialize> serde::Serialize for BinrootsField<N, T> {
+        /// Serializes the field's inner value if the value implements [`binroots::Serialize`][`crate::Serialize`]
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.value.serialize(serializer)
+        }
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.value
+field_impl! {
+    impl<'de, N, T: serde::Deserialize<'de>> serde::Deserialize<'de> for BinrootsField<N, T> {
+        /// Deserializes the field's inner value if the value implements [`binroots::Deserialize`][`crate::Deserialize`]
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            T::deserialize(deserializer).map(Self::new)
+        }
     }
 }
 
-impl<const N: &'static str, T> std::ops::DerefMut for BinrootsField<N, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.value
+field_impl! {
+    impl<N, T: Clone> Clone for BinrootsField<N, T> {
+        /// Clones the value, keeping when the field was last saved
+        fn clone(&self) -> Self {
+            Self {
+                value: self.value.clone(),
+                last_saved: AtomicU64::new(self.last_saved.load(Ordering::Relaxed)),
+                dirty: AtomicBool::new(self.is_dirty()),
+                #[cfg(feature = "stable")]
+                name: PhantomData,
+            }
+        }
     }
 }
 
-impl<const N: &'static str, T> AsRef<T> for BinrootsField<N, T> {
-    fn as_ref(&self) -> &T {
-        &self.value
+field_impl! {
+    impl<N, T: PartialEq> PartialEq for BinrootsField<N, T> {
+        /// Compares the values, regardless of when either field was last saved
+        fn eq(&self, other: &Self) -> bool {
+            self.value == other.value
+        }
     }
 }
 
-impl<const N: &'static str, T> AsMut<T> for BinrootsField<N, T> {
-    fn as_mut(&mut self) -> &mut T {
-        &mut self.value
+field_impl! {
+    impl<N, T: Eq> Eq for BinrootsField<N, T> {}
+}
+
+field_impl! {
+    impl<N, T: PartialOrd> PartialOrd for BinrootsField<N, T> {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            self.value.partial_cmp(&other.value)
+        }
     }
 }
 
-impl<const N: &'static str, T: std::fmt::Debug> std::fmt::Debug for BinrootsField<N, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct(&format!("BinrootsField<\"{}\">", N))
-            .field("value", &self.value)
-            .finish()
+field_impl! {
+    impl<N, T: Ord> Ord for BinrootsField<N, T> {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.value.cmp(&other.value)
+        }
     }
 }
 
-impl<const N: &'static str, T> BinrootsField<N, T> {
-    /// Constructs BinrootsField using `value` as the interior value. `BinrootsField::N` must be declared ahead-of-time.
-    ///
-    /// See [`![feature(adt_const_params)]`][<https://github.com/rust-lang/rust/issues/95174>] for using constant `&'static str` generics.
-    pub const fn new(value: T) -> Self {
-        Self { value }
+field_impl! {
+    impl<N, T: std::hash::Hash> std::hash::Hash for BinrootsField<N, T> {
+        /// Hashes the value only, consistent with [`PartialEq`]
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.value.hash(state)
+        }
     }
+}
+
+field_impl! {
+    impl<N, T> From<T> for BinrootsField<N, T> {
+        fn from(value: T) -> Self {
+            Self::new(value)
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> std::ops::Deref for BinrootsField<N, T> {
+        type Target = T;
+
+        fn deref(&self) -> &Self::Target {
+            &self.value
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> std::ops::DerefMut for BinrootsField<N, T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            self.mark_dirty();
+            &mut self.value
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> AsRef<T> for BinrootsField<N, T> {
+        fn as_ref(&self) -> &T {
+            &self.value
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> AsMut<T> for BinrootsField<N, T> {
+        fn as_mut(&mut self) -> &mut T {
+            self.mark_dirty();
+            &mut self.value
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T: std::fmt::Debug> std::fmt::Debug for BinrootsField<N, T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct(&format!("BinrootsField<\"{}\">", Self::name()))
+                .field("value", &self.value)
+                .finish()
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> BinrootsField<N, T> {
+        /// Constructs BinrootsField using `value` as the interior value. `BinrootsField::N` must be declared ahead-of-time.
+        ///
+        /// See [`![feature(adt_const_params)]`][<https://github.com/rust-lang/rust/issues/95174>] for using constant `&'static str` generics.
+        pub const fn new(value: T) -> Self {
+            Self {
+                value,
+                last_saved: AtomicU64::new(0),
+                dirty: AtomicBool::new(true),
+                #[cfg(feature = "stable")]
+                name: PhantomData,
+            }
+        }
+
+        /// Returns `BinrootsField::N`, often the name of the field if generated by [`binroots::binroots_struct`][`crate::binroots_struct`]
+        #[cfg(not(feature = "stable"))]
+        pub const fn name() -> &'static str {
+            N
+        }
+
+        /// Returns [`FieldName::NAME`], often the name of the field if generated by
+        /// [`binroots::binroots_struct`][`crate::binroots_struct`]
+        #[cfg(feature = "stable")]
+        pub const fn name() -> &'static str {
+            N::NAME
+        }
+
+        /// Replaces the field's value, returning the previous one. Useful for swapping in a new `Arc` without cloning the
+        /// data behind it.
+        pub fn replace(&mut self, value: T) -> T {
+            self.mark_dirty();
+            std::mem::replace(&mut self.value, value)
+        }
+
+        /// Returns when the field was last saved by this process, or [`None`] if it hasn't been saved yet
+        pub fn last_saved(&self) -> Option<SystemTime> {
+            match self.last_saved.load(Ordering::Relaxed) {
+                0 => None,
+                nanos => Some(UNIX_EPOCH + Duration::from_nanos(nanos)),
+            }
+        }
+
+        /// Records that the field was just saved, clearing [`BinrootsField::is_dirty`]. Saves made outside of binroots that
+        /// may race with changes to the field should use [`BinrootsField::begin_save`] instead.
+        pub fn mark_saved(&self) {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos().max(1) as u64);
+            self.last_saved.store(nanos, Ordering::Relaxed);
+            self.dirty.store(false, Ordering::Relaxed);
+        }
+
+        /// Clears [`BinrootsField::is_dirty`] before the field is serialized for a save, returning whether it was set, so
+        /// changes made while the save runs keep the field dirty. Pass the result to [`BinrootsField::end_save`] once the
+        /// save finished. Called by [`BinrootsField::save`] and the saves generated by
+        /// [`binroots::binroots_struct`][`crate::binroots_struct`].
+        pub fn begin_save(&self) -> bool {
+            self.dirty.swap(false, Ordering::Relaxed)
+        }
+
+        /// Finishes a save started with [`BinrootsField::begin_save`], recording when the field was saved if `saved`, or
+        /// else marking the field as dirty again if it `was_dirty`
+        pub fn end_save(&self, was_dirty: bool, saved: bool) {
+            if saved {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(1, |d| d.as_nanos().max(1) as u64);
+                self.last_saved.store(nanos, Ordering::Relaxed);
+            } else if was_dirty {
+                self.mark_dirty();
+            }
+        }
+
+        /// Whether the field may have changed since it was last saved. New fields are dirty, as are fields that were
+        /// mutably accessed through [`DerefMut`][`std::ops::DerefMut`], [`AsMut`], [`BinrootsField::replace`] or
+        /// [`BinrootsField::update`]. Saving clears it.
+        pub fn is_dirty(&self) -> bool {
+            self.dirty.load(Ordering::Relaxed)
+        }
+
+        /// Marks the field as changed, for mutations [`BinrootsField::is_dirty`] can't see, such as through a `Cell`
+        pub fn mark_dirty(&self) {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Interior-mutable containers that [`BinrootsField::update`] can mutate through a shared reference
+pub trait InteriorMut {
+    /// The contained value
+    type Inner;
+
+    /// Calls `f` with mutable access to the contained value
+    fn with_mut<R>(&self, f: impl FnOnce(&mut Self::Inner) -> R) -> R;
+}
+
+impl<T> InteriorMut for std::cell::RefCell<T> {
+    type Inner = T;
+
+    fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+impl<T> InteriorMut for std::sync::Mutex<T> {
+    type Inner = T;
+
+    fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
 
-    /// Returns `BinrootsField::N`, often the name of the field if generated by [`binroots::binroots_struct`][`crate::binroots_struct`]
-    pub const fn name() -> &'static str {
-        N
+field_impl! {
+    impl<N, C: InteriorMut> BinrootsField<N, C> {
+        /// Mutates the value inside a `RefCell` or `Mutex` field through a shared reference. The borrow ends before returning,
+        /// so the field can be saved right after.
+        ///
+        /// ## Example
+        ///
+        /// ```
+        /// # #[cfg(not(feature = "stable"))] {
+        /// use std::cell::RefCell;
+        ///
+        /// use binroots::field::BinrootsField;
+        /// use binroots::save::RootType;
+        ///
+        /// let tags = BinrootsField::<"tags", RefCell<Vec<String>>>::new(RefCell::new(vec!["a".into()]));
+        ///
+        /// tags.update(|tags| tags.push("b".into()));
+        /// tags.save("field-update", RootType::InMemory).unwrap();
+        /// # }
+        /// ```
+        pub fn update<R>(&self, f: impl FnOnce(&mut C::Inner) -> R) -> R {
+            self.mark_dirty();
+            self.value.with_mut(f)
+        }
     }
 }
@@ -1,8 +1,18 @@
 //! ## `binroots::fileserializer`
-//! Contains [`SerializerError`][`crate::fileserializer::SerializerError`] and several internal definitions for serializing data into a file structure.
+//! Contains [`FileSerializer`][`crate::fileserializer::FileSerializer`], the [`PlannedWrite`][`crate::fileserializer::PlannedWrite`]s it produces, its [`SerializeOptions`][`crate::fileserializer::SerializeOptions`],
+//! and [`SerializerError`][`crate::fileserializer::SerializerError`] for serializing data into a file structure.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use serde::Serialize;
 
+use crate::naming::NamingPolicy;
+use crate::trace::{event, span};
+
 type SerializerResult<T> = std::result::Result<T, SerializerError>;
 
 /// Errors during binroots' serialization process.
@@ -10,6 +20,61 @@ type SerializerResult<T> = std::result::Result<T, SerializerError>;
 pub enum SerializerError {
     /// A message from the serializer
     Message(String),
+    /// Returned in [strict mode][`SerializeOptions::strict`] when a value can only be saved using one of binroots' conventions
+    Unrepresentable {
+        /// Where the value would have been saved, relative to the save root
+        path: String,
+        /// The convention binroots would have used to save the value
+        convention: Convention,
+    },
+    /// Returned by [`SerializeOptions::check_map_keys`] when a map's keys can't be used as file names
+    InvalidMapKey {
+        /// The Rust type of the key
+        type_name: String,
+    },
+    /// Returned when a map key serializes to bytes that aren't valid UTF-8
+    NonUtf8Key {
+        /// Where the key's entry would have been saved, relative to the save root
+        path: String,
+    },
+    /// Returned when a map below one of [`SerializeOptions::redacted_fields`] would save its keys as file names, which
+    /// can't be redacted
+    RedactedMapKey {
+        /// The folder the map would have been saved to, relative to the save root
+        path: String,
+    },
+    /// Returned by [`FileSerializer::serialize_value`] when serializing the value panicked, such as in a custom
+    /// [`Serialize`][`serde::Serialize`] implementation
+    Panicked {
+        /// The panic's message, if it had one
+        message: String,
+    },
+}
+
+/// Conventions binroots uses to save values that have no natural file representation.
+///
+/// Rejected in [strict mode][`SerializeOptions::strict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convention {
+    /// `None` is saved by deleting the file
+    NoneDeletesFile,
+    /// A map key of `None` is saved as [`Markers::none_key`]
+    NoneKey,
+    /// A map key of `()` is saved as [`Markers::unit_key`]
+    UnitKey,
+    /// The data of an enum variant is saved in a `.value` sibling of the file containing the variant's name
+    VariantValue,
+}
+
+impl std::fmt::Display for Convention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NoneDeletesFile => "`None` would delete the file",
+            Self::NoneKey => "a `None` map key would be saved as a marker filename",
+            Self::UnitKey => "a `()` map key would be saved as a marker filename",
+            Self::VariantValue => "the variant's data would be saved in a `.value` sibling",
+        })
+    }
 }
 
 impl serde::ser::Error for SerializerError {
@@ -22,33 +87,683 @@ impl std::fmt::Display for SerializerError {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             SerializerError::Message(msg) => formatter.write_str(msg),
+            SerializerError::Unrepresentable { path, convention } => write!(
+                formatter,
+                "Can't represent the value at {path:?} in strict mode: {convention}"
+            ),
+            SerializerError::InvalidMapKey { type_name } => write!(
+                formatter,
+                "Can't serialize a map with the key of {type_name}. Must be one of {VALID_MAP_KEYS:?} or an Option containing one of them."
+            ),
+            SerializerError::NonUtf8Key { path } => {
+                write!(formatter, "The map key at {path:?} isn't valid UTF-8")
+            }
+            SerializerError::RedactedMapKey { path } => write!(
+                formatter,
+                "Can't redact the map at {path:?}, since its keys would be saved as file names"
+            ),
+            SerializerError::Panicked { message } => {
+                write!(formatter, "Serializing panicked: {message}")
+            }
         }
     }
 }
 
 impl std::error::Error for SerializerError {}
 
-#[derive(Default, Debug, PartialEq)]
-pub(crate) enum FileOperationHint {
+/// The key types accepted by [`SerializeOptions::check_map_keys`]
+const VALID_MAP_KEYS: [&str; 14] = [
+    "String", "&str", "str", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64",
+    "()",
+];
+
+/// Builds the graph of [`FileSerializer::to_dot`]
+struct Dot<'a> {
+    root: &'a Path,
+    nodes: Vec<PathBuf>,
+    lines: Vec<String>,
+}
+
+impl Dot<'_> {
+    /// Adds the node at `path` with its label extended by `detail`, along with any missing parent folders. Nodes added
+    /// twice keep their first edge and merge their attributes.
+    fn node(&mut self, path: &Path, detail: &str, attrs: &str) {
+        let label = if path == self.root {
+            path.to_string_lossy()
+        } else {
+            path.file_name().unwrap_or_default().to_string_lossy()
+        };
+        let is_new = !self.nodes.iter().any(|node| node == path);
+        if !is_new && detail.is_empty() {
+            return;
+        }
+
+        if is_new {
+            self.nodes.push(path.into());
+        }
+        if is_new && path != self.root {
+            // Folders that are only implied, such as the `.value` folder of a struct variant
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => self.root.to_path_buf(),
+            };
+            if !self.nodes.contains(&parent) {
+                self.node(&parent, "", ", shape=folder");
+            }
+            self.edge(&parent, path, "");
+        }
+
+        self.lines.push(format!(
+            "    {} [label=\"{}{detail}\"{attrs}];",
+            quote(&path.to_string_lossy()),
+            escape(&label)
+        ));
+    }
+
+    fn edge(&mut self, from: &Path, to: &Path, attrs: &str) {
+        self.lines.push(format!(
+            "    {} -> {}{attrs};",
+            quote(&from.to_string_lossy()),
+            quote(&to.to_string_lossy())
+        ));
+    }
+}
+
+/// Quotes `id` as a DOT identifier
+fn quote(id: &str) -> String {
+    format!("\"{}\"", escape(id))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// An operation [`save_root`][`crate::save::save_root`] performs in addition to writing a [`PlannedWrite`]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub enum FileOperationHint {
+    /// No additional operation
     #[default]
     None,
+    /// The file at [`PlannedWrite::location`] is deleted instead of written. Produced by `None`.
     Delete,
-    DeleteValue,
+    /// The `.value` sibling left behind by a previous enum variant ([`PlannedWrite::stale_value`]) is deleted before writing.
+    /// Produced by enum variants, and contains the extension of the sibling ([`Markers::value`]).
+    DeleteValue(String),
+    /// The elements of the sequence folder at [`PlannedWrite::location`] from this index on are deleted, removing the
+    /// trailing elements of a sequence that got shorter. Produced by sequences such as `Vec`.
+    Truncate(usize),
+}
+
+/// A single file or folder planned by [`FileSerializer`], describing binroots' on-disk layout.
+///
+/// Paths are relative to the save root; resolve them against an absolute root with [`PlannedWrite::target`] and friends,
+/// exactly like [`save_root`][`crate::save::save_root`] does.
+///
+/// ## Example
+///
+/// ```
+/// use std::path::Path;
+///
+/// use binroots::fileserializer::{FileOperationHint, FileSerializer, SerializeOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// enum Activity {
+///     Playing(String),
+/// }
+///
+/// let mut serializer = FileSerializer::with_options("/activity", SerializeOptions::default());
+/// Activity::Playing("hideo kame".into()).serialize(&mut serializer).unwrap();
+///
+/// let root = Path::new("/tmp/binroots/status");
+/// let [payload, tag] = serializer.output() else { unreachable!() };
+///
+/// assert_eq!(payload.target(root), root.join("activity.value"));
+/// assert_eq!(payload.contents, b"hideo kame");
+/// assert_eq!(payload.hint, FileOperationHint::DeleteValue("value".into())); // The previous variant's payload is removed first
+/// assert_eq!(tag.target(root), root.join("activity"));
+/// assert_eq!(tag.contents, b"Playing");
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    /// The folder containing the entry, relative to the save root and separated by `/`
+    pub path: String,
+    /// The name of the entry inside [`PlannedWrite::path`], if it isn't that folder itself
+    pub name: Option<String>,
+    /// An extension appended to the entry's name, such as `value` for an enum variant's payload
+    pub variant: Option<String>,
+    /// The serialized contents of the file. Always empty for folders.
+    pub contents: Vec<u8>,
+    /// Whether this entry is a folder rather than a file
+    pub is_dir: bool,
+    /// The additional operation performed when saving this entry
+    pub hint: FileOperationHint,
+    /// An extension appended to the save root itself, such as `value` for fields of a struct variant
+    pub folder_variant: Option<String>,
+    /// The temporary file holding the contents after [`SerializeOptions::memory_budget`] moved them out of memory. The
+    /// contents are empty until [`PlannedWrite::unspill`] reads them back. Clones share the file, which is removed once
+    /// the last of them is dropped or unspilled.
+    pub spilled: Option<Arc<SpillFile>>,
+    /// Whether the file was already saved while serializing with
+    /// [`SaveOptions::streaming`][`crate::save::SaveOptions::streaming`], including its deletes, leaving its contents
+    /// empty. Saving skips it, apart from truncating a sequence folder that was streamed before its length was known.
+    pub streamed: bool,
+}
+
+/// A temporary file holding contents moved out of memory by [`SerializeOptions::memory_budget`], see
+/// [`PlannedWrite::spilled`]. The file is removed when this is dropped, along with the folder holding it once it's empty.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    /// The location of the temporary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // Holding the lock keeps `spill_file` from creating a file in the folder while it's removed
+        let mut dir = SPILL_DIR.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = std::fs::remove_file(&self.path);
+
+        let parent = self.path.parent();
+        if dir.as_deref() == parent
+            && parent.map_or(false, |parent| std::fs::remove_dir(parent).is_ok())
+        {
+            *dir = None;
+        }
+    }
+}
+
+impl PlannedWrite {
+    /// Reads contents moved out of memory by [`SerializeOptions::memory_budget`] back into [`PlannedWrite::contents`],
+    /// removing the temporary file. Does nothing if they weren't moved.
+    pub fn unspill(&mut self) -> std::io::Result<()> {
+        if let Some(spilled) = &self.spilled {
+            self.contents = std::fs::read(spilled.path())?;
+            self.spilled = None;
+        }
+
+        Ok(())
+    }
+
+    /// The save root after applying [`PlannedWrite::folder_variant`]
+    pub fn base(&self, root: &Path) -> PathBuf {
+        PathBuf::from(format!(
+            "{}{}",
+            root.to_string_lossy().trim_end_matches('/'),
+            if let Some(folder_variant) = &self.folder_variant {
+                format!(".{folder_variant}")
+            } else {
+                String::new()
+            }
+        ))
+    }
+
+    /// The entry's path below `root`, without [`PlannedWrite::variant`]
+    pub fn location(&self, root: &Path) -> PathBuf {
+        let base = self.base(root);
+
+        if let Some(name) = &self.name {
+            base.join(PathBuf::from(
+                format!("{}/{}", self.path.trim_matches('/'), name).trim_start_matches('/'),
+            ))
+        } else {
+            base.join(PathBuf::from(self.path.trim_matches('/')))
+        }
+    }
+
+    /// The path that is written (or created, for folders) below `root`
+    pub fn target(&self, root: &Path) -> PathBuf {
+        PathBuf::from(format!(
+            "{}{}",
+            self.location(root).to_string_lossy().trim_end_matches('/'),
+            if let Some(ext) = &self.variant {
+                format!(".{ext}")
+            } else {
+                String::new()
+            }
+        ))
+    }
+
+    /// The folder that's created before writing a file below `root`
+    pub fn parent_dir(&self, root: &Path) -> PathBuf {
+        let base = self.base(root);
+
+        if self.location(root) != base {
+            base
+        } else {
+            base.parent().map(Path::to_path_buf).unwrap_or(base)
+        }
+    }
+
+    /// The stale `.value` sibling removed below `root` when the hint is [`FileOperationHint::DeleteValue`]
+    pub fn stale_value(&self, root: &Path) -> Option<PathBuf> {
+        if let FileOperationHint::DeleteValue(value) = &self.hint {
+            Some(self.location(root).with_extension(value))
+        } else {
+            None
+        }
+    }
+}
+
+/// Options controlling how [`FileSerializer`] lays out data, resolved ahead of serialization
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct SerializeOptions {
+    /// Fails with [`SerializerError::InvalidMapKey`] when a map's key isn't a string, number, unit, or an `Option`
+    /// containing one of them.
+    ///
+    /// Defaults to `true` in debug builds and `false` in release builds.
+    pub check_map_keys: bool,
+    /// The marker strings written for values that have no natural file representation
+    pub markers: Markers,
+    /// Returns [`SerializerError::Unrepresentable`] instead of saving a value using one of binroots' [`Convention`]s,
+    /// keeping the on-disk format within a plain subset of files, folders and variant names. Defaults to `false`
+    pub strict: bool,
+    /// How enum variants are laid out on disk. Defaults to [`EnumRepresentation::Files`]
+    pub enum_representation: EnumRepresentation,
+    /// Human-oriented templates for top-level fields, keyed by field name. `{value}` in a template is replaced by the
+    /// field's contents, and the raw contents are saved in a `.raw` sibling. Only applies to fields saved as a single file
+    pub templates: BTreeMap<String, String>,
+    /// How scalar leaves are encoded. Defaults to [`LeafEncoding::Text`]
+    pub leaf_encoding: LeafEncoding,
+    /// Overrides [`SerializeOptions::leaf_encoding`] for the leaves below top-level fields, keyed by field name
+    pub field_encodings: BTreeMap<String, LeafEncoding>,
+    /// Top-level fields saved as a single file in a [`FieldFormat`] instead of a tree of files and folders, keyed by field
+    /// name. Suits fields such as deeply nested configs that are read and edited as a whole
+    pub field_formats: BTreeMap<String, FieldFormat>,
+    /// How the names of struct fields and enum variants are saved. [`SerializeOptions::templates`],
+    /// [`SerializeOptions::field_encodings`] and [`SerializeOptions::field_formats`] stay keyed by the Rust name.
+    /// Defaults to [`NamingPolicy::AsIs`]
+    pub naming: NamingPolicy,
+    /// Deletes the file of scalar struct fields holding their type's default value (`0`, `0.0`, `false`, `'\0'` or an
+    /// empty string) instead of writing it, keeping the tree sparse for consumers that treat a missing file as the
+    /// default. `Some(0)` is saved like `None`. Sequence elements, map values and enum payloads are always written.
+    /// Defaults to `false`
+    pub elide_defaults: bool,
+    /// Applies [`SerializeOptions::elide_defaults`] to the leaves below these top-level fields only, keyed by field name
+    pub elided_fields: BTreeSet<String>,
+    /// Ends text-encoded scalar leaves with a `\n`, like most Unix tools expect. Byte leaves are saved verbatim, and
+    /// [`load`][`crate::load`] strips the newline again. Defaults to `false`
+    pub trailing_newline: bool,
+    /// Top-level fields whose leaves are saved as `***` instead of their contents, keyed by field name. Enum variant names
+    /// below them stay visible. [`load`][`crate::load`] reads redacted leaves back as their default value. Saving a map
+    /// below them fails with [`SerializerError::RedactedMapKey`], unless the field is saved as a single file through
+    /// [`SerializeOptions::field_formats`]
+    pub redacted_fields: BTreeSet<String>,
+    /// Pads the names of sequence elements with zeros to at least this many digits, e.g. `001` for a width of `3`, so they
+    /// sort in order in directory listings. Defaults to `0`, leaving them unpadded
+    pub index_width: usize,
+    /// Caps the bytes of file contents [`FileSerializer`] keeps in memory. Once exceeded, the contents of leaves that were
+    /// already serialized are moved to temporary files in [`std::env::temp_dir`] (see [`PlannedWrite::spilled`]), which
+    /// saving reads back one at a time, so saving an unexpectedly huge value can't exhaust memory. Defaults to `None`
+    pub memory_budget: Option<usize>,
+    /// Saves arrays, maps and structs nested this many levels below the root (top-level fields being level `1`) as a
+    /// single pretty-printed `.json` file instead of further folders, so large recursive values don't explode into
+    /// thousands of tiny files. Fields of enum variants are always saved as files, and values JSON can't represent, such
+    /// as maps with optional keys, as folders. Defaults to `None`, saving every level as folders
+    pub max_depth: Option<usize>,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        Self {
+            check_map_keys: cfg!(debug_assertions),
+            markers: Markers::default(),
+            strict: false,
+            enum_representation: EnumRepresentation::default(),
+            templates: BTreeMap::new(),
+            leaf_encoding: LeafEncoding::default(),
+            field_encodings: BTreeMap::new(),
+            field_formats: BTreeMap::new(),
+            naming: NamingPolicy::default(),
+            elide_defaults: false,
+            elided_fields: BTreeSet::new(),
+            trailing_newline: false,
+            redacted_fields: BTreeSet::new(),
+            index_width: 0,
+            memory_budget: None,
+            max_depth: None,
+        }
+    }
+}
+
+impl SerializeOptions {
+    /// Sets [`SerializeOptions::markers`]
+    pub fn markers(mut self, markers: Markers) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    /// Sets [`SerializeOptions::strict`]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets [`SerializeOptions::enum_representation`]
+    pub fn enum_representation(mut self, enum_representation: EnumRepresentation) -> Self {
+        self.enum_representation = enum_representation;
+        self
+    }
+
+    /// Adds a template for the top-level field `field` to [`SerializeOptions::templates`]
+    pub fn template<F: Into<String>, T: Into<String>>(mut self, field: F, template: T) -> Self {
+        self.templates.insert(field.into(), template.into());
+        self
+    }
+
+    /// Sets [`SerializeOptions::leaf_encoding`]
+    pub fn leaf_encoding(mut self, leaf_encoding: LeafEncoding) -> Self {
+        self.leaf_encoding = leaf_encoding;
+        self
+    }
+
+    /// Sets the encoding of the top-level field `field` in [`SerializeOptions::field_encodings`]
+    pub fn field_encoding<F: Into<String>>(mut self, field: F, encoding: LeafEncoding) -> Self {
+        self.field_encodings.insert(field.into(), encoding);
+        self
+    }
+
+    /// Sets the format of the top-level field `field` in [`SerializeOptions::field_formats`]
+    pub fn field_format<F: Into<String>>(mut self, field: F, format: FieldFormat) -> Self {
+        self.field_formats.insert(field.into(), format);
+        self
+    }
+
+    /// Sets [`SerializeOptions::naming`]
+    pub fn naming(mut self, naming: NamingPolicy) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets [`SerializeOptions::elide_defaults`]
+    pub fn elide_defaults(mut self, elide_defaults: bool) -> Self {
+        self.elide_defaults = elide_defaults;
+        self
+    }
+
+    /// Adds the top-level field `field` to [`SerializeOptions::elided_fields`]
+    pub fn elide_field<F: Into<String>>(mut self, field: F) -> Self {
+        self.elided_fields.insert(field.into());
+        self
+    }
+
+    /// Sets [`SerializeOptions::trailing_newline`]
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Adds the top-level field `field` to [`SerializeOptions::redacted_fields`]
+    pub fn redact_field<F: Into<String>>(mut self, field: F) -> Self {
+        self.redacted_fields.insert(field.into());
+        self
+    }
+
+    /// Sets [`SerializeOptions::index_width`]
+    pub fn index_width(mut self, index_width: usize) -> Self {
+        self.index_width = index_width;
+        self
+    }
+
+    /// Sets [`SerializeOptions::memory_budget`]
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Sets [`SerializeOptions::max_depth`]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// The name of the sequence element at `index`, see [`SerializeOptions::index_width`]
+    pub fn index_name(&self, index: usize) -> String {
+        format!("{index:0width$}", width = self.index_width)
+    }
+
+    /// Whether default leaves below `path`, relative to the save root, are elided
+    pub(crate) fn elides(&self, path: &str) -> bool {
+        let field = path
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or_default();
+
+        self.elide_defaults
+            || self
+                .elided_fields
+                .iter()
+                .any(|elided| self.naming.apply(elided) == field)
+    }
+
+    /// Whether leaves below `path`, relative to the save root, are redacted
+    pub(crate) fn redacts(&self, path: &str) -> bool {
+        let field = path
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or_default();
+
+        self.redacted_fields
+            .iter()
+            .any(|redacted| self.naming.apply(redacted) == field)
+    }
+
+    /// The format of the top-level field saved as `name`, see [`SerializeOptions::field_formats`]
+    pub(crate) fn format_of(&self, name: &str) -> Option<FieldFormat> {
+        self.field_formats
+            .iter()
+            .find(|(field, _)| self.naming.apply(field) == name)
+            .map(|(_, format)| *format)
+    }
+
+    /// The template of the top-level field saved as `name`, see [`SerializeOptions::templates`]
+    pub(crate) fn template_of(&self, name: &str) -> Option<&String> {
+        self.templates
+            .iter()
+            .find(|(field, _)| self.naming.apply(field) == name)
+            .map(|(_, template)| template)
+    }
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub(crate) struct File {
-    pub(crate) path: String,
-    pub(crate) name: Option<String>,
-    pub(crate) variant: Option<String>,
-    pub(crate) output: Vec<u8>,
-    pub(crate) is_path: bool,
-    pub(crate) hint: FileOperationHint,
-    pub(crate) folder_variant: Option<String>,
+/// How [`FileSerializer`] encodes scalar leaves such as numbers, strings and booleans. Map keys and enum variant names are
+/// always saved as text.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "cbor")]
+/// # {
+/// use binroots::fileserializer::{FileSerializer, LeafEncoding, SerializeOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Reading {
+///     label: &'static str,
+///     samples: u32,
+/// }
+///
+/// let options = SerializeOptions::default().field_encoding("samples", LeafEncoding::Cbor);
+/// let mut serializer = FileSerializer::with_options("", options);
+/// Reading { label: "cpu", samples: 500 }.serialize(&mut serializer).unwrap();
+///
+/// let [_, label, samples] = serializer.output() else { unreachable!() };
+///
+/// assert_eq!(label.contents, b"cpu");
+/// assert_eq!(samples.variant.as_deref(), Some("cbor"));
+/// assert_eq!(samples.contents, [0x19, 0x01, 0xf4]);
+/// # }
+/// ```
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeafEncoding {
+    /// Human-readable text, e.g. `500` or `true`
+    #[default]
+    Text,
+    /// Compact [CBOR](https://cbor.io) in a file with a `.cbor` extension, for trees consumed by other programs rather
+    /// than people. Requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    Cbor,
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub(crate) struct FileSerializer {
+/// How a top-level field listed in [`SerializeOptions::field_formats`] is saved as a single file
+///
+/// ## Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use binroots::fileserializer::{FieldFormat, FileSerializer, SerializeOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Settings {
+///     theme: &'static str,
+///     keybinds: BTreeMap<&'static str, &'static str>,
+/// }
+///
+/// let options = SerializeOptions::default().field_format("keybinds", FieldFormat::Json);
+/// let mut serializer = FileSerializer::with_options("", options);
+/// let keybinds = BTreeMap::from([("quit", "q")]);
+/// Settings { theme: "dark", keybinds }.serialize(&mut serializer).unwrap();
+///
+/// let [_, theme, keybinds] = serializer.output() else { unreachable!() };
+///
+/// assert_eq!(theme.contents, b"dark");
+/// assert_eq!(keybinds.variant.as_deref(), Some("json"));
+/// assert_eq!(keybinds.contents, b"{\n  \"quit\": \"q\"\n}");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldFormat {
+    /// Pretty-printed JSON in a file with a `.json` extension
+    Json,
+}
+
+impl FieldFormat {
+    /// The extension of the field's file
+    pub fn extension(self) -> &'static str {
+        match self {
+            FieldFormat::Json => "json",
+        }
+    }
+
+    /// Encodes `value` in this format
+    pub fn encode<T: ?Sized + Serialize>(self, value: &T) -> SerializerResult<Vec<u8>> {
+        match self {
+            FieldFormat::Json => serde_json::to_vec_pretty(value)
+                .map_err(|e| SerializerError::Message(e.to_string())),
+        }
+    }
+}
+
+/// How [`FileSerializer`] lays out enum variants
+///
+/// ## Example
+///
+/// ```
+/// use binroots::fileserializer::{EnumRepresentation, FileSerializer, SerializeOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// enum Activity {
+///     Playing { game: String },
+/// }
+///
+/// let options = SerializeOptions::default().enum_representation(EnumRepresentation::JsonPayload);
+/// let mut serializer = FileSerializer::with_options("/activity", options);
+/// Activity::Playing { game: "hideo kame".into() }.serialize(&mut serializer).unwrap();
+///
+/// let [payload, tag] = serializer.output() else { unreachable!() };
+///
+/// assert_eq!(payload.variant.as_deref(), Some("json"));
+/// assert_eq!(payload.contents, br#"{"Playing":{"game":"hideo kame"}}"#);
+/// assert_eq!(tag.contents, b"playing");
+/// ```
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumRepresentation {
+    /// The variant's name is saved in `<field>`, and its data is saved in files and folders below `<field>.value`
+    #[default]
+    Files,
+    /// The variant's lowercase name is saved in `<field>`, and the whole enum is saved as externally tagged JSON in
+    /// `<field>.json`, so consumers can parse a single structured file per enum
+    JsonPayload,
+}
+
+/// Marker strings used by [`FileSerializer`] to represent values that have no natural file representation.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::fileserializer::Markers;
+///
+/// let markers = Markers {
+///     none_value: Some("null".into()), // Writes "null" instead of deleting the file
+///     variant: "variant:{variant}".into(),
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(markers.variant_contents("Playing"), "variant:Playing");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Markers {
+    /// The filename of a map entry whose key is `None`. Defaults to `__NONE__`
+    pub none_key: String,
+    /// The filename of a map entry whose key is `()`. Defaults to `__UNIT__`
+    pub unit_key: String,
+    /// The contents written for a `None` value. Defaults to `None`, which deletes the file instead
+    pub none_value: Option<String>,
+    /// The extension of an enum variant's payload, e.g. `activity.value`. Defaults to `value`
+    pub value: String,
+    /// The contents written for an enum variant, where `{variant}` is replaced by the variant's name. Defaults to `{variant}`
+    pub variant: String,
+}
+
+impl Default for Markers {
+    fn default() -> Self {
+        Self {
+            none_key: "__NONE__".into(),
+            unit_key: "__UNIT__".into(),
+            none_value: None,
+            value: "value".into(),
+            variant: "{variant}".into(),
+        }
+    }
+}
+
+impl Markers {
+    /// The contents written for the variant named `variant`
+    pub fn variant_contents(&self, variant: &str) -> String {
+        self.variant.replace("{variant}", variant)
+    }
+}
+
+/// A [`serde::Serializer`] that lays out data as a list of [`PlannedWrite`]s, ready to be saved by [`save_root`][`crate::save::save_root`].
+///
+/// ## Example
+///
+/// ```
+/// use binroots::fileserializer::{FileSerializer, SerializeOptions};
+/// use binroots::save::{save_root, RootType};
+/// use binroots::Serialize;
+///
+/// let mut serializer = FileSerializer::with_options("", SerializeOptions::default());
+/// (9, 8, 5).serialize(&mut serializer).unwrap();
+///
+/// assert_eq!(serializer.output()[1].name.as_deref(), Some("0"));
+/// assert_eq!(serializer.output()[1].contents, b"9");
+///
+/// save_root(serializer, "manual-serializer".into(), RootType::InMemory).unwrap();
+/// ```
+#[derive(Default, Debug)]
+pub struct FileSerializer {
     name: Option<String>,
     pub(crate) root: String,
     file: usize,
@@ -57,39 +772,441 @@ pub(crate) struct FileSerializer {
     is_key: bool,
     future_name: Option<String>,
     variant: Option<String>,
-    pub(crate) output: Vec<File>,
+    pub(crate) output: Vec<PlannedWrite>,
     folder_variant: Option<String>,
+    pub(crate) options: SerializeOptions,
+    json_variant: Option<JsonVariant>,
+    /// The output index of each open sequence's folder and the number of elements serialized into it
+    seq_dirs: Vec<(usize, usize)>,
+    /// The bytes of contents kept in memory, see [`SerializeOptions::memory_budget`]
+    buffered: usize,
+    /// Writes finished leaves while serializing, see [`SaveOptions::streaming`][`crate::save::SaveOptions::streaming`]
+    pub(crate) stream: Option<Stream>,
+    /// The paths below the root selected by a partial save such as [`save_fields_with`][`crate::save::save_fields_with`],
+    /// which [`SaveOptions::mirror`][`crate::save::SaveOptions::mirror`] is limited to. `None` selects everything below
+    /// `root`
+    pub(crate) scope: Option<Vec<String>>,
+    /// Whether the value being serialized is a struct field rather than a sequence element or map value, see
+    /// [`FileSerializer::elide`]
+    in_field: bool,
+}
+
+// Streaming writes entries as soon as they're finished, but doesn't change which files are planned
+impl PartialEq for FileSerializer {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            name,
+            root,
+            file,
+            seq_level,
+            seq,
+            is_key,
+            future_name,
+            variant,
+            output,
+            folder_variant,
+            options,
+            json_variant,
+            seq_dirs,
+            buffered,
+            stream: _,
+            scope,
+            in_field,
+        } = self;
+
+        *name == other.name
+            && *root == other.root
+            && *file == other.file
+            && *seq_level == other.seq_level
+            && *seq == other.seq
+            && *is_key == other.is_key
+            && *future_name == other.future_name
+            && *variant == other.variant
+            && *output == other.output
+            && *folder_variant == other.folder_variant
+            && *options == other.options
+            && *json_variant == other.json_variant
+            && *seq_dirs == other.seq_dirs
+            && *buffered == other.buffered
+            && *scope == other.scope
+            && *in_field == other.in_field
+    }
+}
+
+/// The error that stopped a [`Stream`], kept as it was returned so the save can report it
+pub(crate) type StreamError = Box<dyn std::error::Error + Send + Sync>;
+
+type StreamWrite = Box<dyn FnMut(&mut PlannedWrite) -> Result<(), StreamError> + Send>;
+
+/// Saves the entries of a [`FileSerializer`] as soon as they're finished, taking their contents
+pub(crate) struct Stream {
+    write: StreamWrite,
+    /// The number of entries of the output already handled
+    done: usize,
+    /// The error that stopped the stream
+    error: Option<StreamError>,
+}
+
+impl Stream {
+    pub(crate) fn new<F>(write: F) -> Self
+    where
+        F: FnMut(&mut PlannedWrite) -> Result<(), StreamError> + Send + 'static,
+    {
+        Self {
+            write: Box::new(write),
+            done: 0,
+            error: None,
+        }
+    }
+
+    /// Saves the entries among `output[..end]` that weren't handled yet, in order so folders and deletes come before the
+    /// leaves written into them. Returns the bytes of contents they freed.
+    fn write(&mut self, output: &mut [PlannedWrite], end: usize) -> SerializerResult<usize> {
+        let mut freed = 0;
+
+        for file in output.iter_mut().take(end).skip(self.done) {
+            self.done += 1;
+            freed += file.contents.len();
+            if let Err(e) = (self.write)(file) {
+                let message = e.to_string();
+                self.error = Some(e);
+                return Err(SerializerError::Message(message));
+            }
+            file.contents = Vec::new();
+            file.streamed = true;
+        }
+
+        Ok(freed)
+    }
+}
+
+impl std::fmt::Debug for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stream")
+            .field("done", &self.done)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The folder private to this process holding [`SpillFile`]s, created by the first of them and removed with the last
+static SPILL_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Creates a file for spilled contents with a random name, readable only by the current user, inside a folder private to
+/// this process, so other users can neither read it nor plant a file or symlink in its place
+fn spill_file() -> std::io::Result<(SpillFile, std::fs::File)> {
+    let mut dir = SPILL_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        let current = match &*dir {
+            Some(current) => current.clone(),
+            None => {
+                let created =
+                    std::env::temp_dir().join(format!("binroots-spill-{:016x}", random()));
+                let mut builder = std::fs::DirBuilder::new();
+                #[cfg(target_family = "unix")]
+                std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+                builder.create(&created)?;
+                dir.insert(created).clone()
+            }
+        };
+
+        let path = current.join(format!("{:016x}.spill", random()));
+        let mut open = std::fs::OpenOptions::new();
+        open.write(true).create_new(true);
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut open, 0o600);
+
+        match open.open(&path) {
+            // The folder was removed, such as by a cleaner of the temporary directory
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => *dir = None,
+            result => return result.map(|file| (SpillFile { path }, file)),
+        }
+    }
+}
+
+/// A random number from the OS-seeded keys of [`RandomState`][`std::collections::hash_map::RandomState`]
+fn random() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(NEXT.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// Serializes a byte slice as bytes rather than a sequence of integers
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// The data of a tuple or struct variant collected while serializing with [`EnumRepresentation::JsonPayload`]
+#[derive(Debug, PartialEq)]
+enum JsonVariant {
+    Tuple(&'static str, Vec<serde_json::Value>),
+    Struct(&'static str, serde_json::Map<String, serde_json::Value>),
 }
 
 impl FileSerializer {
-    pub fn advance(&mut self) {
+    /// Constructs a serializer writing below `root` (relative to the save root, usually empty) using pre-resolved `options`
+    pub fn with_options<R: Into<String>>(root: R, options: SerializeOptions) -> Self {
+        Self {
+            root: root.into(),
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Clears the output and serialization state, keeping the root, the options and the allocated buffers, so the
+    /// serializer can serialize another value without allocating them again
+    pub fn reset(&mut self) {
+        self.name = None;
+        self.file = 0;
+        self.seq_level = 0;
+        self.seq.clear();
+        self.is_key = false;
+        self.future_name = None;
+        self.variant = None;
+        // Dropping the entries removes their spill files
+        self.output.clear();
+        self.folder_variant = None;
+        self.json_variant = None;
+        self.seq_dirs.clear();
+        self.buffered = 0;
+        self.stream = None;
+        self.scope = None;
+        self.in_field = false;
+    }
+
+    /// Serializes `value` into this serializer, returning [`SerializerError::Panicked`] instead of unwinding if
+    /// serializing panics, so a bad value can't take down the program during a routine save. The serializer's output is
+    /// incomplete after an error and shouldn't be saved.
+    pub fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> SerializerResult<()> {
+        let _span = span!("serialize", root = %self.root);
+        let serialize = || match self.write_formatted(value) {
+            Ok(false) => value.serialize(&mut *self),
+            written => written.map(|_| ()),
+        };
+
+        let result = match std::panic::catch_unwind(AssertUnwindSafe(serialize)) {
+            Ok(result) => result,
+            Err(payload) => Err(SerializerError::Panicked {
+                message: payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default(),
+            }),
+        };
+        event!(debug, files = self.output.len(), "serialized");
+
+        match (result, &mut self.stream) {
+            (Ok(()), Some(stream)) => {
+                let end = self.output.len();
+                let freed = stream.write(&mut self.output, end)?;
+                self.buffered = self.buffered.saturating_sub(freed);
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Takes the error that stopped writing leaves with [`SaveOptions::streaming`][`crate::save::SaveOptions::streaming`]
+    pub(crate) fn take_stream_error(&mut self) -> Option<StreamError> {
+        self.stream.as_mut().and_then(|stream| stream.error.take())
+    }
+
+    /// The options this serializer was constructed with
+    pub fn options(&self) -> &SerializeOptions {
+        &self.options
+    }
+
+    /// The files serialized so far, in the order [`save_root`][`crate::save::save_root`] saves them
+    pub fn output(&self) -> &[PlannedWrite] {
+        &self.output
+    }
+
+    /// Consumes the serializer, returning the serialized files
+    pub fn into_output(self) -> Vec<PlannedWrite> {
+        self.output
+    }
+
+    /// Renders the files serialized so far as a Graphviz DOT graph, for debugging which paths a save creates, writes and
+    /// deletes. Folders are drawn as folders, leaves as notes labelled with their size in bytes, and deletions in dashed
+    /// red, including the stale `.value` siblings removed by [`FileOperationHint::DeleteValue`].
+    ///
+    /// Render it with e.g. `dot -Tsvg plan.dot -o plan.svg`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use binroots::fileserializer::FileSerializer;
+    /// use binroots::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Activity {
+    ///     Playing(String),
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Status {
+    ///     activity: Activity,
+    ///     nickname: Option<String>,
+    /// }
+    ///
+    /// let mut serializer = FileSerializer::default();
+    /// Status { activity: Activity::Playing("chess".into()), nickname: None }
+    ///     .serialize(&mut serializer)
+    ///     .unwrap();
+    ///
+    /// let dot = serializer.to_dot();
+    /// assert!(dot.starts_with("digraph plan {"));
+    /// assert!(dot.contains(r#""<root>/activity.value" [label="activity.value\n5 B\nreplaces stale", shape=note];"#));
+    /// assert!(dot.contains(r#""<root>/nickname" [label="nickname\ndelete", shape=note, style=dashed, color=red];"#));
+    /// assert!(dot.contains(r#""<root>" -> "<root>/activity";"#));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let root = Path::new("<root>");
+        let mut dot = Dot {
+            root,
+            nodes: Vec::new(),
+            lines: vec![
+                "digraph plan {".into(),
+                "    rankdir=LR;".into(),
+                "    node [fontname=\"monospace\"];".into(),
+            ],
+        };
+        dot.node(root, "", ", shape=folder");
+
+        for file in &self.output {
+            let target = file.target(root);
+
+            let stale_value = file.stale_value(root);
+            let (mut detail, attrs) = match (&file.hint, file.is_dir) {
+                (FileOperationHint::Delete, _) => {
+                    ("\\ndelete".into(), ", shape=note, style=dashed, color=red")
+                }
+                (FileOperationHint::Truncate(len), true) => {
+                    (format!("\\nkeeps {len}"), ", shape=folder")
+                }
+                (_, true) => (String::new(), ", shape=folder"),
+                (_, false) if file.spilled.is_some() => ("\\nspilled".into(), ", shape=note"),
+                (_, false) => (format!("\\n{} B", file.contents.len()), ", shape=note"),
+            };
+            // A payload replacing the previous variant's payload deletes its own path first
+            if stale_value.as_ref() == Some(&target) {
+                detail += "\\nreplaces stale";
+            }
+            dot.node(&target, &detail, attrs);
+
+            if let Some(stale_value) = stale_value.filter(|stale_value| stale_value != &target) {
+                dot.node(
+                    &stale_value,
+                    "\\ndelete stale",
+                    ", shape=note, style=dashed, color=red",
+                );
+                dot.edge(&target, &stale_value, " [style=dashed, color=red]");
+            }
+        }
+
+        dot.lines.push("}".into());
+        dot.lines.join("\n") + "\n"
+    }
+
+    /// Applies [`SerializeOptions::templates`] to the serialized files, adding a `.raw` sibling for each templated field
+    pub(crate) fn apply_templates(&mut self) {
+        let mut raw = Vec::new();
+
+        for file in self.output.iter_mut() {
+            let Some(template) = self.options.template_of(file.path.trim_matches('/')) else {
+                continue;
+            };
+            if file.is_dir
+                || file.name.is_some()
+                || file.variant.is_some()
+                || file.folder_variant.is_some()
+            {
+                continue;
+            }
+            // Templated fields are rendered in memory
+            let _ = file.unspill();
+
+            let mut raw_file = file.clone();
+            raw_file.variant = Some("raw".into());
+            raw.push(raw_file);
+            file.contents = template
+                .replace("{value}", &String::from_utf8_lossy(&file.contents))
+                .into_bytes();
+        }
+
+        self.output.extend(raw);
+    }
+
+    /// Merges entries that resolve to the same physical file or folder so each is written once, keeping the position and
+    /// contents of the last one
+    pub(crate) fn coalesce(&mut self) {
+        let root = Path::new("/");
+        let mut seen = std::collections::HashSet::new();
+        let mut output = std::mem::take(&mut self.output);
+
+        output.reverse();
+        output.retain(|file| seen.insert((file.target(root), file.is_dir)));
+        output.reverse();
+
+        self.output = output;
+    }
+
+    pub(crate) fn advance(&mut self) {
         self.name = None;
         self.file += 1;
     }
 
-    pub fn inc_seq(&mut self, by: usize) -> usize {
+    pub(crate) fn inc_seq(&mut self, by: usize) -> usize {
         self.seq[self.seq_level - 1] += by;
         self.seq[self.seq_level - 1]
     }
 
-    pub fn construct_seq(&mut self) {
+    pub(crate) fn construct_seq(&mut self) {
         self.seq_level += 1;
     }
 
-    pub fn destruct_seq(&mut self) {
-        self.seq.pop().unwrap();
+    pub(crate) fn destruct_seq(&mut self) {
+        // Empty sequences never pushed an index of their own
+        if self.seq.len() == self.seq_level {
+            self.seq.pop();
+        }
         self.seq_level -= 1;
     }
 
-    pub fn seq_to_path(&self) -> String {
-        let mut seqstr = self.seq.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    pub(crate) fn seq_to_path(&self) -> String {
+        let mut seqstr = self
+            .seq
+            .iter()
+            .map(|i| self.options.index_name(*i))
+            .collect::<Vec<_>>();
         seqstr.pop();
         seqstr.join("/")
     }
 
-    pub fn write<A: Into<Vec<u8>> + std::fmt::Debug>(&mut self, data: A) {
+    pub(crate) fn write<A: Into<Vec<u8>> + std::fmt::Debug>(
+        &mut self,
+        data: A,
+    ) -> SerializerResult<()> {
         if self.is_key {
-            self.future_name = Some(std::str::from_utf8(&data.into()).unwrap().into());
+            // Keys name files, so redacting their values alone would still publish them
+            if self.options.redacts(&self.root) {
+                return Err(SerializerError::RedactedMapKey {
+                    path: self.root.clone(),
+                });
+            }
+            let key = String::from_utf8(data.into()).map_err(|_| SerializerError::NonUtf8Key {
+                path: self.root.clone(),
+            })?;
+            self.future_name = Some(crate::naming::escape(&key));
             self.file -= 1;
         } else {
             if self.seq_level > 0 {
@@ -101,7 +1218,8 @@ impl FileSerializer {
             }
 
             let name = if self.seq_level > 0 {
-                Some((self.inc_seq(0)).to_string())
+                let index = self.inc_seq(0);
+                Some(self.options.index_name(index))
             } else {
                 self.name.clone()
             };
@@ -117,21 +1235,244 @@ impl FileSerializer {
                 }
             };
 
-            let file = File {
+            let file = PlannedWrite {
                 name,
                 path,
-                output: data.into(),
+                contents: data.into(),
                 variant: self.variant.clone(),
-                is_path: false,
+                is_dir: false,
                 hint: FileOperationHint::None,
                 folder_variant: self.folder_variant.clone(),
+                spilled: None,
+                streamed: false,
             };
 
+            // The last leaf may still be changed, such as by `FileSerializer::terminate`
+            if let Some(stream) = &mut self.stream {
+                let end = self.output.len().saturating_sub(1);
+                let freed = stream.write(&mut self.output, end)?;
+                self.buffered = self.buffered.saturating_sub(freed);
+            }
+
+            self.buffered += file.contents.len();
+            if self
+                .options
+                .memory_budget
+                .map_or(false, |budget| self.buffered > budget)
+            {
+                self.spill();
+            }
             self.output.push(file);
         }
+
+        Ok(())
     }
 
-    pub fn prev(&mut self) -> &mut File {
+    /// Moves the contents of finished leaves to temporary files, see [`SerializeOptions::memory_budget`]. The last leaf
+    /// may still be changed, such as by [`FileSerializer::terminate`], so it's kept. Contents that can't be moved stay in
+    /// memory.
+    fn spill(&mut self) {
+        use std::io::Write;
+
+        let kept = self.output.len().saturating_sub(1);
+        for file in &mut self.output[..kept] {
+            if file.is_dir || file.spilled.is_some() || file.contents.is_empty() {
+                continue;
+            }
+
+            let Ok((spilled, mut temp)) = spill_file() else {
+                return;
+            };
+            // Dropping the spill file when it couldn't be written removes it again
+            if temp.write_all(&file.contents).is_ok() {
+                self.buffered = self.buffered.saturating_sub(file.contents.len());
+                file.contents = Vec::new();
+                file.spilled = Some(Arc::new(spilled));
+            }
+        }
+    }
+
+    /// Writes a scalar leaf, either as `text` or encoded from `value` according to the [`LeafEncoding`] of the field being
+    /// serialized
+    pub(crate) fn write_leaf<A, T>(&mut self, text: A, value: &T) -> SerializerResult<()>
+    where
+        A: Into<Vec<u8>> + std::fmt::Debug,
+        T: ?Sized + Serialize,
+    {
+        match self.leaf_encoding() {
+            LeafEncoding::Text => self.write(text)?,
+            #[cfg(feature = "cbor")]
+            LeafEncoding::Cbor => {
+                let mut contents = Vec::new();
+                ciborium::into_writer(value, &mut contents)
+                    .map_err(|e| SerializerError::Message(e.to_string()))?;
+
+                self.write(contents)?;
+                let leaf = self.prev();
+                leaf.variant = Some(match &leaf.variant {
+                    Some(variant) => format!("{variant}.cbor"),
+                    None => "cbor".into(),
+                });
+            }
+        }
+
+        #[cfg(not(feature = "cbor"))]
+        let _ = value;
+
+        if !self.is_key && self.options.redacts(&self.root) {
+            self.prev().contents = b"***".to_vec();
+        }
+
+        Ok(())
+    }
+
+    /// Writes `value` as a single file if the current path is a top-level field listed in
+    /// [`SerializeOptions::field_formats`], or a nested value at [`SerializeOptions::max_depth`], returning whether it was
+    /// written
+    fn write_formatted<T: ?Sized + Serialize>(&mut self, value: &T) -> SerializerResult<bool> {
+        if self.is_key {
+            return Ok(false);
+        }
+
+        let name = self.root.trim_start_matches('/');
+        let depth = name.split('/').filter(|s| !s.is_empty()).count() + self.seq_level;
+        let format = match self.options.format_of(name) {
+            Some(format) if depth == 1 => format,
+            _ if self
+                .options
+                .max_depth
+                .map_or(false, |max_depth| depth >= max_depth.max(1)) =>
+            {
+                // Values JSON can't represent, such as maps with optional keys, are saved as folders instead
+                match serde_json::to_value(value) {
+                    Ok(json) if json.is_array() || json.is_object() => FieldFormat::Json,
+                    _ => return Ok(false),
+                }
+            }
+            _ => return Ok(false),
+        };
+
+        self.advance();
+        self.write(format.encode(value)?)?;
+        let leaf = self.prev();
+        leaf.variant = Some(match &leaf.variant {
+            Some(variant) => format!("{variant}.{}", format.extension()),
+            None => format.extension().into(),
+        });
+
+        if self.options.redacts(&self.root) {
+            self.prev().contents = b"***".to_vec();
+        }
+
+        Ok(true)
+    }
+
+    /// The encoding of leaves below the current top-level field. Map keys are always text.
+    fn leaf_encoding(&self) -> LeafEncoding {
+        if self.is_key {
+            return LeafEncoding::Text;
+        }
+
+        let field = self.root.trim_start_matches('/').split('/').next();
+        field
+            .and_then(|name| {
+                self.options
+                    .field_encodings
+                    .iter()
+                    .find(|(field, _)| self.options.naming.apply(field) == name)
+            })
+            .map(|(_, encoding)| *encoding)
+            .unwrap_or(self.options.leaf_encoding)
+    }
+
+    pub(crate) fn reject(&self, convention: Convention) -> SerializerResult<()> {
+        if !self.options.strict {
+            return Ok(());
+        }
+
+        let mut path = self.root.trim_start_matches('/').to_string();
+        for index in &self.seq {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path += &self.options.index_name(*index);
+        }
+
+        Err(SerializerError::Unrepresentable { path, convention })
+    }
+
+    /// Appends a newline to the text leaf just written if [`SerializeOptions::trailing_newline`] is set
+    pub(crate) fn terminate(&mut self) {
+        if !self.options.trailing_newline
+            || self.is_key
+            || self.leaf_encoding() != LeafEncoding::Text
+        {
+            return;
+        }
+
+        self.prev().contents.push(b'\n');
+    }
+
+    /// Turns the leaf just written into a deletion if it's a struct field holding the `default` value and
+    /// [`SerializeOptions::elides`] applies to it. Sequence elements and map values are always written, since a missing
+    /// element ends the sequence and a missing entry removes the key on load
+    pub(crate) fn elide(&mut self, default: bool) {
+        // Deleting a redacted leaf would reveal that it holds the default
+        if !default
+            || self.is_key
+            || !self.in_field
+            || !self.options.elides(&self.root)
+            || self.options.redacts(&self.root)
+        {
+            return;
+        }
+
+        let leaf = self.prev();
+        // The payload of an enum variant (or a `.cbor` leaf) lives next to another file
+        if leaf.variant.is_none() {
+            leaf.contents.clear();
+            leaf.hint = FileOperationHint::Delete;
+        }
+    }
+
+    pub(crate) fn delete_value_hint(&self) -> FileOperationHint {
+        FileOperationHint::DeleteValue(self.options.markers.value.clone())
+    }
+
+    pub(crate) fn is_json_payload(&self) -> bool {
+        self.options.enum_representation == EnumRepresentation::JsonPayload
+    }
+
+    /// Writes `<field>.json` containing `payload` tagged with `variant`, followed by `<field>` containing the lowercase tag
+    pub(crate) fn write_json_variant(
+        &mut self,
+        variant: &str,
+        payload: Option<serde_json::Value>,
+    ) -> SerializerResult<()> {
+        let value = match payload {
+            Some(payload) => serde_json::json!({ variant: payload }),
+            None => serde_json::Value::String(variant.into()),
+        };
+        let contents =
+            serde_json::to_vec(&value).map_err(|e| SerializerError::Message(e.to_string()))?;
+
+        self.variant = Some("json".into());
+        self.advance();
+        self.write(contents)?;
+        self.variant = None;
+        self.advance();
+        self.write(
+            self.options
+                .markers
+                .variant_contents(&variant.to_lowercase()),
+        )?;
+        let name = self.output[self.file - 2].name.clone();
+        self.output[self.file - 1].name = name;
+
+        Ok(())
+    }
+
+    pub(crate) fn prev(&mut self) -> &mut PlannedWrite {
         let len = self.output.len();
         &mut self.output[len - 1]
     }
@@ -151,11 +1492,9 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
 
     fn serialize_bool(self, v: bool) -> SerializerResult<()> {
         self.advance();
-        self.write(if v {
-            b"true".to_vec()
-        } else {
-            b"false".to_vec()
-        });
+        self.write_leaf(if v { "true" } else { "false" }, &v)?;
+        self.terminate();
+        self.elide(!v);
         Ok(())
     }
 
@@ -173,7 +1512,9 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
 
     fn serialize_i64(self, v: i64) -> SerializerResult<()> {
         self.advance();
-        self.write(itoa::Buffer::new().format(v).as_bytes());
+        self.write_leaf(itoa::Buffer::new().format(v).as_bytes(), &v)?;
+        self.terminate();
+        self.elide(v == 0);
         Ok(())
     }
 
@@ -191,7 +1532,9 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
 
     fn serialize_u64(self, v: u64) -> SerializerResult<()> {
         self.advance();
-        self.write(itoa::Buffer::new().format(v).as_bytes());
+        self.write_leaf(itoa::Buffer::new().format(v).as_bytes(), &v)?;
+        self.terminate();
+        self.elide(v == 0);
         Ok(())
     }
 
@@ -201,36 +1544,49 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
 
     fn serialize_f64(self, v: f64) -> SerializerResult<()> {
         self.advance();
-        self.write(ryu::Buffer::new().format(v).as_bytes());
+        self.write_leaf(ryu::Buffer::new().format(v).as_bytes(), &v)?;
+        self.terminate();
+        self.elide(v == 0.0);
         Ok(())
     }
 
     fn serialize_char(self, v: char) -> SerializerResult<()> {
         self.advance();
         let mut buf = [0; 4];
-        self.write(v.encode_utf8(buf.as_mut_slice()).as_bytes());
+        self.write_leaf(v.encode_utf8(buf.as_mut_slice()).as_bytes(), &v)?;
+        self.terminate();
+        self.elide(v == '\0');
         Ok(())
     }
 
     fn serialize_str(self, v: &str) -> SerializerResult<()> {
         self.advance();
-        self.write(v.as_bytes());
+        self.write_leaf(v.as_bytes(), v)?;
+        self.terminate();
+        self.elide(v.is_empty());
         Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> SerializerResult<()> {
         self.advance();
-        self.write(v);
-        Ok(())
+        self.write_leaf(v, &Bytes(v))
     }
 
     fn serialize_none(self) -> SerializerResult<()> {
+        if self.is_key {
+            self.reject(Convention::NoneKey)?;
+        } else if self.options.markers.none_value.is_none() {
+            self.reject(Convention::NoneDeletesFile)?;
+        }
+
         self.advance();
 
         if self.is_key {
-            self.write(b"__NONE__".to_vec());
+            self.write(self.options.markers.none_key.clone())?;
+        } else if let Some(none_value) = self.options.markers.none_value.clone() {
+            self.write(none_value)?;
         } else {
-            self.write(Vec::new());
+            self.write(Vec::new())?;
             self.prev().hint = FileOperationHint::Delete;
         }
 
@@ -238,11 +1594,15 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
     }
 
     fn serialize_unit(self) -> SerializerResult<()> {
+        if self.is_key {
+            self.reject(Convention::UnitKey)?;
+        }
+
         self.advance();
         if self.is_key {
-            self.write(b"__UNIT__".to_vec());
+            self.write(self.options.markers.unit_key.clone())?;
         } else {
-            self.write(Vec::new());
+            self.write(Vec::new())?;
         }
         Ok(())
     }
@@ -261,9 +1621,17 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
         _variant_index: u32,
         variant: &'static str,
     ) -> SerializerResult<()> {
+        if self.is_json_payload() {
+            return self.write_json_variant(variant, None);
+        }
+
         self.advance();
-        self.write(variant.as_bytes());
-        self.prev().hint = FileOperationHint::DeleteValue;
+        self.write(
+            self.options
+                .markers
+                .variant_contents(&self.options.naming.apply(variant)),
+        )?;
+        self.prev().hint = self.delete_value_hint();
         Ok(())
     }
 
@@ -282,21 +1650,34 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
         variant: &'static str,
         value: &T,
     ) -> SerializerResult<()> {
-        self.variant = Some("value".into());
+        if self.is_json_payload() {
+            let payload =
+                serde_json::to_value(value).map_err(|e| SerializerError::Message(e.to_string()))?;
+            return self.write_json_variant(variant, Some(payload));
+        }
+
+        self.reject(Convention::VariantValue)?;
+        self.variant = Some(self.options.markers.value.clone());
         value.serialize(&mut *self)?;
-        self.prev().hint = FileOperationHint::DeleteValue;
+        self.prev().hint = self.delete_value_hint();
         self.variant = None;
         self.advance();
-        self.write(variant.as_bytes());
-        self.output[self.file - 1].name = self.output[self.file - 2].name.clone();
+        self.write(
+            self.options
+                .markers
+                .variant_contents(&self.options.naming.apply(variant)),
+        )?;
+        let name = self.output[self.file - 2].name.clone();
+        self.output[self.file - 1].name = name;
 
         Ok(())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> SerializerResult<Self::SerializeSeq> {
         self.advance();
-        self.write(Vec::new());
-        self.output[self.file - 1].is_path = true;
+        self.write(Vec::new())?;
+        self.output[self.file - 1].is_dir = true;
+        self.seq_dirs.push((self.file - 1, 0));
         self.construct_seq();
 
         Ok(self)
@@ -321,15 +1702,25 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
         variant: &'static str,
         _len: usize,
     ) -> SerializerResult<Self::SerializeTupleVariant> {
-        self.variant = Some("value".into());
+        if self.is_json_payload() {
+            self.json_variant = Some(JsonVariant::Tuple(variant, Vec::new()));
+            return Ok(self);
+        }
+
+        self.reject(Convention::VariantValue)?;
+        self.variant = Some(self.options.markers.value.clone());
         self.advance();
-        self.write(Vec::new());
-        self.prev().hint = FileOperationHint::DeleteValue;
-        self.output[self.file - 1].is_path = true;
+        self.write(Vec::new())?;
+        self.prev().hint = self.delete_value_hint();
+        self.output[self.file - 1].is_dir = true;
         self.variant = None;
 
         self.advance();
-        self.write(variant.as_bytes());
+        self.write(
+            self.options
+                .markers
+                .variant_contents(&self.options.naming.apply(variant)),
+        )?;
         self.construct_seq();
 
         Ok(self)
@@ -337,8 +1728,8 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
 
     fn serialize_map(self, _len: Option<usize>) -> SerializerResult<Self::SerializeMap> {
         self.advance();
-        self.write(Vec::new());
-        self.output[self.file - 1].is_path = true;
+        self.write(Vec::new())?;
+        self.output[self.file - 1].is_dir = true;
         Ok(self)
     }
 
@@ -357,15 +1748,25 @@ impl<'a> serde::Serializer for &'a mut FileSerializer {
         variant: &'static str,
         _len: usize,
     ) -> SerializerResult<Self::SerializeStructVariant> {
-        self.variant = Some("value".into());
+        if self.is_json_payload() {
+            self.json_variant = Some(JsonVariant::Struct(variant, serde_json::Map::new()));
+            return Ok(self);
+        }
+
+        self.reject(Convention::VariantValue)?;
+        self.variant = Some(self.options.markers.value.clone());
         self.advance();
-        self.write(Vec::new());
-        self.prev().hint = FileOperationHint::DeleteValue;
-        self.output[self.file - 1].is_path = true;
+        self.write(Vec::new())?;
+        self.prev().hint = self.delete_value_hint();
+        self.output[self.file - 1].is_dir = true;
         self.variant = None;
 
         self.advance();
-        self.write(variant.as_bytes());
+        self.write(
+            self.options
+                .markers
+                .variant_contents(&self.options.naming.apply(variant)),
+        )?;
 
         Ok(self)
     }
@@ -379,12 +1780,22 @@ impl<'a> serde::ser::SerializeSeq for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)?;
+        let in_field = std::mem::replace(&mut self.in_field, false);
+        if !self.write_formatted(value)? {
+            value.serialize(&mut **self)?;
+        }
+        self.in_field = in_field;
+        if let Some((_, len)) = self.seq_dirs.last_mut() {
+            *len += 1;
+        }
 
         Ok(())
     }
 
     fn end(self) -> SerializerResult<()> {
+        if let Some((file, len)) = self.seq_dirs.pop() {
+            self.output[file].hint = FileOperationHint::Truncate(len);
+        }
         self.destruct_seq();
         Ok(())
     }
@@ -398,12 +1809,18 @@ impl<'a> serde::ser::SerializeTuple for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)?;
+        let in_field = std::mem::replace(&mut self.in_field, false);
+        if !self.write_formatted(value)? {
+            value.serialize(&mut **self)?;
+        }
+        self.in_field = in_field;
 
         Ok(())
     }
 
     fn end(self) -> SerializerResult<()> {
+        // Tuples always have the same length, so there's nothing to truncate
+        self.seq_dirs.pop();
         self.destruct_seq();
         Ok(())
     }
@@ -417,10 +1834,17 @@ impl<'a> serde::ser::SerializeTupleStruct for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)
+        let in_field = std::mem::replace(&mut self.in_field, false);
+        if !self.write_formatted(value)? {
+            value.serialize(&mut **self)?;
+        }
+        self.in_field = in_field;
+
+        Ok(())
     }
 
     fn end(self) -> SerializerResult<()> {
+        self.seq_dirs.pop();
         self.destruct_seq();
         Ok(())
     }
@@ -434,7 +1858,14 @@ impl<'a> serde::ser::SerializeTupleVariant for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
-        self.folder_variant = Some("value".into());
+        if let Some(JsonVariant::Tuple(_, fields)) = &mut self.json_variant {
+            fields.push(
+                serde_json::to_value(value).map_err(|e| SerializerError::Message(e.to_string()))?,
+            );
+            return Ok(());
+        }
+
+        self.folder_variant = Some(self.options.markers.value.clone());
         value.serialize(&mut **self)?;
         self.folder_variant = None;
 
@@ -442,6 +1873,10 @@ impl<'a> serde::ser::SerializeTupleVariant for &'a mut FileSerializer {
     }
 
     fn end(self) -> SerializerResult<()> {
+        if let Some(JsonVariant::Tuple(variant, fields)) = self.json_variant.take() {
+            return self.write_json_variant(variant, Some(fields.into()));
+        }
+
         self.destruct_seq();
         Ok(())
     }
@@ -455,26 +1890,21 @@ impl<'a> serde::ser::SerializeMap for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
-        #[cfg(debug_assertions)]
-        {
-            let valid_keys = [
-                "String", "&str", "str", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64",
-                "f32", "f64", "()",
-            ];
+        if self.options.check_map_keys {
             let type_name = &std::any::type_name::<T>()
-                .trim_start_matches("&")
+                .trim_start_matches('&')
                 .trim_start_matches("std::")
-                .trim_start_matches("core::");
+                .trim_start_matches("core::")
+                .trim_start_matches("alloc::string::");
 
-            let is_valid_key = valid_keys.iter().fold(false, |acc, x| {
+            let is_valid_key = VALID_MAP_KEYS.iter().fold(false, |acc, x| {
                 acc || (type_name == x) || (type_name == &format!("option::Option<{x}>"))
             });
 
             if !is_valid_key {
-                panic!(
-                    "Can't serialize a HashMap with the key of {}. Must be one of {valid_keys:?} or an Option containing one of them.",
-                    type_name,
-                )
+                return Err(SerializerError::InvalidMapKey {
+                    type_name: type_name.to_string(),
+                });
             }
         }
 
@@ -490,8 +1920,12 @@ impl<'a> serde::ser::SerializeMap for &'a mut FileSerializer {
         if let Some(future_name) = &self.future_name {
             self.root += &format!("/{}", future_name);
         }
-        value.serialize(&mut **self)?;
-        let split = self.root.split("/");
+        let in_field = std::mem::replace(&mut self.in_field, false);
+        if !self.write_formatted(value)? {
+            value.serialize(&mut **self)?;
+        }
+        self.in_field = in_field;
+        let split = self.root.split('/');
         self.root = split
             .clone()
             .take(split.count() - 1)
@@ -515,13 +1949,17 @@ impl<'a> serde::ser::SerializeStruct for &'a mut FileSerializer {
         T: ?Sized + serde::Serialize,
     {
         self.is_key = true;
-        key.serialize(&mut **self)?;
+        self.options.naming.apply(key).serialize(&mut **self)?;
         self.is_key = false;
         if let Some(future_name) = &self.future_name {
             self.root += &format!("/{}", future_name);
         }
-        value.serialize(&mut **self)?;
-        let split = self.root.split("/");
+        let in_field = std::mem::replace(&mut self.in_field, true);
+        if !self.write_formatted(value)? {
+            value.serialize(&mut **self)?;
+        }
+        self.in_field = in_field;
+        let split = self.root.split('/');
         self.root = split
             .clone()
             .take(split.count() - 1)
@@ -544,17 +1982,25 @@ impl<'a> serde::ser::SerializeStructVariant for &'a mut FileSerializer {
     where
         T: ?Sized + serde::Serialize,
     {
+        if let Some(JsonVariant::Struct(_, fields)) = &mut self.json_variant {
+            fields.insert(
+                key.into(),
+                serde_json::to_value(value).map_err(|e| SerializerError::Message(e.to_string()))?,
+            );
+            return Ok(());
+        }
+
         self.is_key = true;
 
-        key.serialize(&mut **self)?;
+        self.options.naming.apply(key).serialize(&mut **self)?;
 
         self.is_key = false;
         if let Some(future_name) = &self.future_name {
             self.root += &format!("/{}", future_name);
         }
-        self.folder_variant = Some("value".into());
+        self.folder_variant = Some(self.options.markers.value.clone());
         value.serialize(&mut **self)?;
-        let split = self.root.split("/");
+        let split = self.root.split('/');
         self.root = split
             .clone()
             .take(split.count() - 1)
@@ -566,6 +2012,10 @@ impl<'a> serde::ser::SerializeStructVariant for &'a mut FileSerializer {
     }
 
     fn end(self) -> SerializerResult<()> {
+        if let Some(JsonVariant::Struct(variant, fields)) = self.json_variant.take() {
+            return self.write_json_variant(variant, Some(fields.into()));
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,206 @@
+//! ## `binroots::fmt`
+//! Contains adapter modules for `#[serde(with = "...")]` that save values in a human-readable form instead of raw integers:
+//! - [`duration`][`crate::fmt::duration`] saves a [`Duration`][`std::time::Duration`] as `5s` or `2m30s`
+//! - [`bytesize`][`crate::fmt::bytesize`] saves a byte count as `512B` or `1.5MiB`
+//!
+//! ## Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use binroots::save::{root_location, RootType, Save};
+//! use binroots::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Download {
+//!     #[serde(with = "binroots::fmt::duration")]
+//!     elapsed: Duration,
+//!     #[serde(with = "binroots::fmt::bytesize")]
+//!     size: u64,
+//! }
+//!
+//! Download { elapsed: Duration::from_secs(150), size: 1536 * 1024 }
+//!     .save("fmt-download", RootType::InMemory)
+//!     .unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("fmt-download");
+//! assert_eq!(std::fs::read_to_string(root.join("elapsed")).unwrap(), "2m30s");
+//! assert_eq!(std::fs::read_to_string(root.join("size")).unwrap(), "1.5MiB");
+//! ```
+
+/// Saves a [`Duration`][`std::time::Duration`] as a sequence of units, e.g. `1h5m`, `2m30s` or `1s500ms`.
+///
+/// The units are `d`, `h`, `m`, `s`, `ms`, `us` and `ns`, and a zero duration is saved as `0s`.
+pub mod duration {
+    use std::time::Duration;
+
+    const UNITS: [(&str, u128); 7] = [
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+
+    /// Renders `duration`, e.g. `2m30s`
+    pub fn format(duration: &Duration) -> String {
+        let mut nanos = duration.as_nanos();
+        if nanos == 0 {
+            return "0s".into();
+        }
+
+        let mut out = String::new();
+        for (unit, size) in UNITS {
+            if nanos >= size {
+                out += &format!("{}{unit}", nanos / size);
+                nanos %= size;
+            }
+        }
+
+        out
+    }
+
+    /// Parses a duration rendered by [`format`]
+    pub fn parse(s: &str) -> Option<Duration> {
+        let mut nanos = 0u128;
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return None;
+        }
+
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+            let value = rest[..digits].parse::<u128>().ok()?;
+            rest = &rest[digits..];
+
+            let unit_len = rest
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (_, size) = UNITS.iter().find(|(unit, _)| *unit == &rest[..unit_len])?;
+            rest = &rest[unit_len..];
+
+            nanos = nanos.checked_add(value.checked_mul(*size)?)?;
+        }
+
+        Some(Duration::new(
+            u64::try_from(nanos / 1_000_000_000).ok()?,
+            (nanos % 1_000_000_000) as u32,
+        ))
+    }
+
+    /// Serializes `duration` with [`format`]. Use with `#[serde(with = "binroots::fmt::duration")]`
+    pub fn serialize<S: serde::Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(duration))
+    }
+
+    /// Deserializes a duration with [`parse`]. Use with `#[serde(with = "binroots::fmt::duration")]`
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        parse(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid duration {s:?}")))
+    }
+}
+
+/// Saves a byte count using binary units, e.g. `512B`, `4KiB` or `1.5MiB`.
+///
+/// The count is saved in the largest unit showing it exactly with at most two decimals, so it's never rounded and loads
+/// back as the same count: 1537KiB is saved as `1537KiB` rather than `1.5MiB`.
+pub mod bytesize {
+    const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    /// Integer types [`bytesize`][`crate::fmt::bytesize`] can save
+    pub trait ByteCount: Copy {
+        /// Converts the count to a `u64`
+        fn to_u64(self) -> u64;
+        /// Converts a `u64` to the count, if it fits
+        fn from_u64(bytes: u64) -> Option<Self>;
+    }
+
+    macro_rules! impl_byte_count {
+        ($($ty: ty),*) => {
+            $(
+                impl ByteCount for $ty {
+                    fn to_u64(self) -> u64 {
+                        self as u64
+                    }
+
+                    fn from_u64(bytes: u64) -> Option<Self> {
+                        bytes.try_into().ok()
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_byte_count!(u8, u16, u32, u64, usize);
+
+    /// Renders `bytes`, e.g. `1.5MiB`
+    pub fn format(bytes: u64) -> String {
+        let bytes = u128::from(bytes);
+        let (unit, size) = (1..UNITS.len())
+            .rev()
+            .map(|unit| (unit, 1024u128.pow(unit as u32)))
+            .find(|&(_, size)| bytes >= size && bytes * 100 % size == 0)
+            .unwrap_or((0, 1));
+
+        let hundredths = bytes * 100 / size % 100;
+        let decimals = match (hundredths, hundredths % 10) {
+            (0, _) => String::new(),
+            (_, 0) => format!(".{}", hundredths / 10),
+            _ => format!(".{hundredths:02}"),
+        };
+        format!("{}{decimals}{}", bytes / size, UNITS[unit])
+    }
+
+    /// Parses a byte count rendered by [`format`]. Counts written by hand with more decimals, such as `1.3KiB`, are
+    /// rounded to the nearest byte
+    pub fn parse(s: &str) -> Option<u64> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let unit = UNITS.iter().position(|unit| *unit == &s[split..])?;
+        let (whole, fraction) = s[..split].split_once('.').unwrap_or((&s[..split], ""));
+        if whole.is_empty() || fraction.contains('.') || fraction.len() > 18 {
+            return None;
+        }
+
+        let scale = 10u128.pow(fraction.len() as u32);
+        let value = whole
+            .parse::<u128>()
+            .ok()?
+            .checked_mul(scale)?
+            .checked_add(if fraction.is_empty() {
+                0
+            } else {
+                fraction.parse().ok()?
+            })?;
+        let bytes = value
+            .checked_mul(1024u128.pow(unit as u32))?
+            .checked_add(scale / 2)?
+            / scale;
+        u64::try_from(bytes).ok()
+    }
+
+    /// Serializes `bytes` with [`format`]. Use with `#[serde(with = "binroots::fmt::bytesize")]`
+    pub fn serialize<T: ByteCount, S: serde::Serializer>(
+        bytes: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(bytes.to_u64()))
+    }
+
+    /// Deserializes a byte count with [`parse`]. Use with `#[serde(with = "binroots::fmt::bytesize")]`
+    pub fn deserialize<'de, T: ByteCount, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        parse(&s)
+            .and_then(T::from_u64)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid byte size {s:?}")))
+    }
+}
@@ -0,0 +1,117 @@
+//! ## `binroots::guard`
+//! Contains [`SaveGuard`][`crate::guard::SaveGuard`], which mutably borrows a value and saves it when dropped, so scopes
+//! changing several fields can't forget the final save. Structs generated by
+//! [`binroots_struct`][`crate::binroots_struct`] create one with `self.autosave()`.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::guard::SaveGuard;
+//! use binroots::save::{root_location, RootType};
+//!
+//! let mut status = (String::from("idle"), 0);
+//!
+//! {
+//!     let mut status = SaveGuard::new(&mut status, "guard-status", RootType::InMemory);
+//!     status.0 = "busy".into();
+//!     status.1 += 1;
+//! } // Saved here
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("guard-status");
+//! assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "busy");
+//! assert_eq!(std::fs::read_to_string(root.join("1")).unwrap(), "1");
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+
+use crate::save::{RootType, Save, SaveError, SaveOptions};
+
+type Saver<'a, T> = Box<dyn FnOnce(&T) -> Result<(), SaveError> + 'a>;
+
+/// Mutable access to a value that's saved when the guard is dropped. Errors are ignored on drop, use
+/// [`SaveGuard::finish`] to handle them.
+#[must_use = "the value is saved as soon as the guard is dropped"]
+pub struct SaveGuard<'a, T: ?Sized> {
+    value: &'a mut T,
+    save: Option<Saver<'a, T>>,
+}
+
+impl<'a, T: Save> SaveGuard<'a, T> {
+    /// Borrows `value`, saving it to "[`root_location`][`crate::save::root_location`]/\<root\>" on drop
+    pub fn new<P: Into<PathBuf>>(value: &'a mut T, root: P, root_type: RootType) -> Self {
+        Self::with_options(value, root, root_type, SaveOptions::default())
+    }
+
+    /// Like [`SaveGuard::new`], saving with `options`
+    pub fn with_options<P: Into<PathBuf>>(
+        value: &'a mut T,
+        root: P,
+        root_type: RootType,
+        options: SaveOptions,
+    ) -> Self {
+        let root = root.into();
+
+        Self::with_saver(value, move |value| {
+            value.save_with(root, root_type, &options)
+        })
+    }
+}
+
+impl<'a, T: ?Sized> SaveGuard<'a, T> {
+    /// Borrows `value`, passing it to `save` on drop. Used for values with their own save method, such as the `save` of
+    /// a [`binroots_struct`][`crate::binroots_struct`].
+    pub fn with_saver<F>(value: &'a mut T, save: F) -> Self
+    where
+        F: FnOnce(&T) -> Result<(), SaveError> + 'a,
+    {
+        Self {
+            value,
+            save: Some(Box::new(save)),
+        }
+    }
+
+    /// Saves the value now, returning the result instead of ignoring it
+    pub fn finish(mut self) -> Result<(), SaveError> {
+        match self.save.take() {
+            Some(save) => save(self.value),
+            None => Ok(()),
+        }
+    }
+
+    /// Releases the value without saving it
+    pub fn cancel(mut self) {
+        self.save = None;
+    }
+}
+
+impl<T: ?Sized> Deref for SaveGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for SaveGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for SaveGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaveGuard")
+            .field("value", &self.value)
+            .field("pending", &self.save.is_some())
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Drop for SaveGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(save) = self.save.take() {
+            let _ = save(self.value);
+        }
+    }
+}
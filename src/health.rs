@@ -0,0 +1,173 @@
+//! ## `binroots::health`
+//! Contains [`health`][`crate::health::health`], which reports the state of a root's storage in one call so daemons can
+//! include it in their own health endpoints.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use binroots::health::health;
+//! use binroots::save::{RootType, Save};
+//!
+//! (1, 2).save("health-example", RootType::InMemory).unwrap();
+//!
+//! let health = health("health-example", RootType::InMemory).unwrap();
+//! assert!(health.exists && health.writable && !health.locked);
+//! assert!(!health.is_stale(Duration::from_secs(60)));
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::metadata::{Metadata, METADATA_FILE};
+use crate::save::{root_location, RootLocationError, RootType, GENERATION_FILE};
+
+/// The state of a root's storage, returned by [`health`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootHealth {
+    /// The root's folder, "[`root_location`]/\<root\>"
+    pub path: PathBuf,
+    /// Whether the root's folder exists
+    pub exists: bool,
+    /// Whether the root's folder, or the nearest existing folder above it, can be written to
+    pub writable: bool,
+    /// The bytes available to the current user on the root's filesystem. [`None`] if unknown, such as on non-Unix
+    /// platforms.
+    pub free_space: Option<u64>,
+    /// Whether a save using [`SaveOptions::generation`][`crate::save::SaveOptions::generation`] currently holds the root's
+    /// lock. Always `false` on non-Unix platforms.
+    pub locked: bool,
+    /// When the root was last saved, taken from its [`Metadata`] if present, otherwise from the newest modification
+    /// time of anything inside it. [`None`] if the root is missing or empty.
+    pub last_saved: Option<SystemTime>,
+}
+
+impl RootHealth {
+    /// How long ago the root was last saved. [`None`] if it never was.
+    pub fn staleness(&self) -> Option<Duration> {
+        self.last_saved
+            .map(|last_saved| last_saved.elapsed().unwrap_or_default())
+    }
+
+    /// Whether the root was never saved, or was last saved more than `max_age` ago
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.staleness()
+            .map_or(true, |staleness| staleness > max_age)
+    }
+}
+
+/// Reports the [`RootHealth`] of "[`root_location`]/\<root\>". Nothing inside the root is created or modified.
+pub fn health<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+) -> Result<RootHealth, RootLocationError> {
+    let path = root_location(root_type)?.join(root.into());
+    let exists = path.is_dir();
+    let existing = nearest_existing(&path);
+
+    Ok(RootHealth {
+        exists,
+        writable: writable(existing),
+        free_space: free_space(existing),
+        locked: exists && locked(&path),
+        last_saved: exists.then(|| last_saved(&path)).flatten(),
+        path,
+    })
+}
+
+/// The root's folder, or the nearest existing folder above it
+pub(crate) fn nearest_existing(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(path)
+}
+
+pub(crate) fn writable(path: &Path) -> bool {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+
+        // SAFETY: `path` is a valid nul-terminated string
+        unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    std::fs::metadata(path).map_or(false, |metadata| !metadata.permissions().readonly())
+}
+
+fn free_space(path: &Path) -> Option<u64> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+        // SAFETY: `path` is a valid nul-terminated string and `stat` is only read after `statvfs` succeeds
+        if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        let stat = unsafe { stat.assume_init() };
+
+        #[allow(clippy::unnecessary_cast)]
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Tries to take the generation lock without blocking, releasing it immediately
+fn locked(path: &Path) -> bool {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let Ok(file) = std::fs::File::open(path.join(GENERATION_FILE)) else {
+            return false;
+        };
+
+        // SAFETY: `file` is open for the duration of the call. The lock is released when `file` is dropped.
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) != 0 }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+fn last_saved(path: &Path) -> Option<SystemTime> {
+    std::fs::read_to_string(path.join(METADATA_FILE))
+        .ok()
+        .and_then(|contents| Metadata::from_file_contents(&contents))
+        .map(|metadata| metadata.updated)
+        .or_else(|| newest_modification(path))
+}
+
+/// The newest modification time of the entries below `dir`
+fn newest_modification(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok();
+
+            if metadata.is_dir() {
+                modified.max(newest_modification(&entry.path()))
+            } else {
+                modified
+            }
+        })
+        .max()
+}
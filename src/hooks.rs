@@ -0,0 +1,263 @@
+//! ## `binroots::hooks`
+//! Contains process-wide save hooks. Pre-save hooks registered with [`register_pre_save`][`crate::hooks::register_pre_save`]
+//! can veto a save, enforcing application-level invariants centrally, while post-save hooks registered with
+//! [`register_post_save`][`crate::hooks::register_post_save`] observe every successful save. Full-state providers
+//! registered with [`register_full_state`][`crate::hooks::register_full_state`] rebuild a root whose folder went missing,
+//! see [`RootMissing::Resave`][`crate::save::RootMissing::Resave`].
+//!
+//! ## Example
+//!
+//! ```
+//! use std::ops::ControlFlow;
+//! use std::sync::atomic::{AtomicBool, Ordering};
+//!
+//! use binroots::hooks;
+//! use binroots::save::{RootType, Save, SaveError};
+//!
+//! static MAINTENANCE: AtomicBool = AtomicBool::new(false);
+//!
+//! let hook = hooks::register_pre_save(|context| {
+//!     if MAINTENANCE.load(Ordering::Relaxed) && context.root.ends_with("published") {
+//!         ControlFlow::Break("never publish while in maintenance mode".into())
+//!     } else {
+//!         ControlFlow::Continue(())
+//!     }
+//! });
+//!
+//! MAINTENANCE.store(true, Ordering::Relaxed);
+//! assert!(matches!(
+//!     "hello".save("published", RootType::InMemory),
+//!     Err(SaveError::Vetoed { .. })
+//! ));
+//!
+//! hooks::unregister(hook);
+//! ```
+
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::fileserializer::{FileSerializer, PlannedWrite, SerializerError};
+use crate::save::RootType;
+
+/// Describes the save a hook is called for
+#[derive(Debug)]
+pub struct SaveContext<'a> {
+    /// The absolute path being saved to
+    pub root: &'a Path,
+    /// The [`RootType`] the save resolved its root with
+    pub root_type: &'a RootType,
+    /// The files the save will write, each at most once. Empty for post-save hooks.
+    pub files: &'a [PlannedWrite],
+    /// The leaves the save added, changed or deleted. Only computed by saves using
+    /// [`SaveOptions::watcher_friendly`][`crate::save::SaveOptions::watcher_friendly`], which compare every file with its
+    /// previous contents; [`None`] otherwise and for pre-save hooks.
+    pub changes: Option<&'a LeafChanges>,
+}
+
+/// The leaves changed by a save, with paths relative to the root, in sorted order. See [`SaveContext::changes`].
+///
+/// ## Example
+///
+/// ```
+/// use std::path::PathBuf;
+/// use std::sync::{Arc, Mutex};
+///
+/// use binroots::hooks;
+/// use binroots::save::{RootType, Save, SaveOptions};
+///
+/// let options = SaveOptions::default().watcher_friendly(true);
+/// (1, 2).save_with("hooks-changes", RootType::InMemory, &options).unwrap();
+///
+/// let changed = Arc::new(Mutex::new(Vec::new()));
+/// let hook_changed = changed.clone();
+/// let hook = hooks::register_post_save(move |context| {
+///     if let (true, Some(changes)) = (context.root.ends_with("hooks-changes"), context.changes) {
+///         *hook_changed.lock().unwrap() = changes.changed.clone();
+///     }
+/// });
+///
+/// (1, 3).save_with("hooks-changes", RootType::InMemory, &options).unwrap();
+/// hooks::unregister(hook);
+///
+/// assert_eq!(*changed.lock().unwrap(), vec![PathBuf::from("1")]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeafChanges {
+    /// Leaves that didn't exist before the save
+    pub added: Vec<PathBuf>,
+    /// Leaves whose contents changed
+    pub changed: Vec<PathBuf>,
+    /// Leaves the save removed, such as by `None` or a change of enum variant
+    pub deleted: Vec<PathBuf>,
+}
+
+impl LeafChanges {
+    /// Whether the save didn't change any leaf
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Identifies a registered hook, used to [`unregister`] it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+type PreSaveHook = Box<dyn Fn(&SaveContext) -> ControlFlow<String> + Send + Sync>;
+type PostSaveHook = Box<dyn Fn(&SaveContext) + Send + Sync>;
+type FullStateProvider =
+    Arc<dyn Fn(&mut FileSerializer) -> Result<(), SerializerError> + Send + Sync>;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static PRE_SAVE: RwLock<Vec<(HookId, PreSaveHook)>> = RwLock::new(Vec::new());
+static POST_SAVE: RwLock<Vec<(HookId, PostSaveHook)>> = RwLock::new(Vec::new());
+static FULL_STATE: RwLock<Vec<(HookId, PathBuf, FullStateProvider)>> = RwLock::new(Vec::new());
+
+fn next_id() -> HookId {
+    HookId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Registers a hook called before every save writes anything. Returning [`ControlFlow::Break`] aborts the save with
+/// [`SaveError::Vetoed`][`crate::save::SaveError::Vetoed`] carrying the given reason.
+pub fn register_pre_save<F>(hook: F) -> HookId
+where
+    F: Fn(&SaveContext) -> ControlFlow<String> + Send + Sync + 'static,
+{
+    let id = next_id();
+    PRE_SAVE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, Box::new(hook)));
+    id
+}
+
+/// Registers a hook called after every successful save
+pub fn register_post_save<F>(hook: F) -> HookId
+where
+    F: Fn(&SaveContext) + Send + Sync + 'static,
+{
+    let id = next_id();
+    POST_SAVE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, Box::new(hook)));
+    id
+}
+
+/// Registers a provider serializing the full state saved to `root` (as passed to [`Save::save`][`crate::save::Save::save`]),
+/// used by [`RootMissing::Resave`][`crate::save::RootMissing::Resave`] to rebuild the root when a partial save finds its
+/// folder missing. The most recently registered provider for a root wins.
+///
+/// The provider runs inside the partial save, so it must not wait on a lock the saving thread already holds.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+///
+/// use binroots::hooks;
+/// use binroots::save::{root_location, RootMissing, RootType, Save, SaveOptions};
+/// use binroots::{binroots_struct, Serialize};
+///
+/// #[binroots_struct]
+/// struct Status {
+///     connections: usize,
+///     is_online: bool,
+/// }
+///
+/// let status = Arc::new(RwLock::new(Status::new(3, true)));
+/// let provider = status.clone();
+/// hooks::register_full_state("hooks-status", move |serializer| {
+///     provider.read().unwrap().serialize(serializer)
+/// });
+///
+/// // A user deletes the root while the program runs
+/// let root = root_location(RootType::InMemory).unwrap().join("hooks-status");
+/// let _ = std::fs::remove_dir_all(&root);
+///
+/// let options = SaveOptions::default().root_missing(RootMissing::Resave);
+/// status.read().unwrap().connections.save_with("hooks-status", RootType::InMemory, &options).unwrap();
+/// assert_eq!(std::fs::read_to_string(root.join("is_online")).unwrap(), "true");
+/// ```
+pub fn register_full_state<P, F>(root: P, provider: F) -> HookId
+where
+    P: Into<PathBuf>,
+    F: Fn(&mut FileSerializer) -> Result<(), SerializerError> + Send + Sync + 'static,
+{
+    let id = next_id();
+    FULL_STATE.write().unwrap_or_else(|e| e.into_inner()).push((
+        id,
+        root.into(),
+        Arc::new(provider),
+    ));
+    id
+}
+
+/// Removes a hook registered with [`register_pre_save`], [`register_post_save`] or [`register_full_state`]. Returns
+/// whether it was registered.
+pub fn unregister(id: HookId) -> bool {
+    let mut removed = false;
+
+    PRE_SAVE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(i, _)| {
+            removed |= *i == id;
+            *i != id
+        });
+    POST_SAVE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(i, _)| {
+            removed |= *i == id;
+            *i != id
+        });
+    FULL_STATE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(i, _, _)| {
+            removed |= *i == id;
+            *i != id
+        });
+
+    removed
+}
+
+/// Whether any pre-save hook is registered
+pub(crate) fn has_pre_save() -> bool {
+    !PRE_SAVE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_empty()
+}
+
+pub(crate) fn run_pre_save(context: &SaveContext) -> ControlFlow<String> {
+    for (_, hook) in PRE_SAVE.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        hook(context)?;
+    }
+
+    ControlFlow::Continue(())
+}
+
+pub(crate) fn run_post_save(context: &SaveContext) {
+    for (_, hook) in POST_SAVE.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        hook(context);
+    }
+}
+
+/// Runs the latest full-state provider registered for `root`, if any
+pub(crate) fn run_full_state(
+    root: &Path,
+    serializer: &mut FileSerializer,
+) -> Option<Result<(), SerializerError>> {
+    let provider = FULL_STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .rev()
+        .find(|(_, r, _)| r == root)
+        .map(|(_, _, provider)| provider.clone())?;
+
+    Some(provider(serializer))
+}
@@ -60,16 +60,124 @@
 //! }
 //! ```
 
-#![feature(adt_const_params)]
-#![feature(io_error_more)]
+#![cfg_attr(not(feature = "stable"), feature(adt_const_params))]
+#![cfg_attr(not(feature = "stable"), allow(incomplete_features))]
+#![cfg_attr(not(feature = "stable"), feature(io_error_more))]
 #![warn(missing_docs)]
 
+pub mod autosave;
+pub mod backend;
+pub mod clock;
+pub mod config;
+pub mod consume;
+pub mod dircache;
 pub mod field;
 pub mod fileserializer;
+pub mod fmt;
+pub mod guard;
+pub mod health;
+pub mod hooks;
+pub mod load;
+pub mod metadata;
+pub mod namespace;
+pub mod naming;
+pub mod plan;
+pub mod queue;
+pub mod quiet;
+pub mod read;
+pub mod recovery;
+pub mod report;
 pub mod save;
+#[cfg(feature = "async_save")]
+pub mod save_async;
+pub mod snapshot;
+pub mod stats;
+mod trace;
+#[cfg(feature = "notify")]
+pub mod watch;
 
 pub use binroots_proc_macros::*;
-pub use serde::Serialize;
+pub use plan::plan;
+pub use serde::{Deserialize, Serialize};
+
+use std::sync::OnceLock;
+
+static APP_NAME: OnceLock<String> = OnceLock::new();
+
+/// Errors while calling [`init`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitError {
+    /// Returned when the application name was already set to something else
+    AlreadyInitialized {
+        /// The application name in use
+        app_name: String,
+    },
+    /// Returned when the application name is empty or isn't a single path component, such as `my/app` or `..`
+    InvalidName {
+        /// The rejected application name
+        app_name: String,
+    },
+}
+
+impl std::fmt::Display for InitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::AlreadyInitialized { app_name } => format!(
+                    "binroots was already initialized with the application name {app_name:?}"
+                ),
+                Self::InvalidName { app_name } =>
+                    format!("{app_name:?} can't be used as an application name"),
+            }
+        )
+    }
+}
+
+impl std::error::Error for InitError {}
+
+/// Sets the application name that namespaces every root directory, the `<CARGO_PKG_NAME>` in
+/// [`root_location`][`crate::save::root_location`]'s paths. Call it once at startup, before saving anything.
+///
+/// Without it, roots are namespaced by the package name binroots was compiled as, which is `binroots` itself rather than
+/// the application using it. Setting the same name again succeeds, while changing it fails with
+/// [`InitError::AlreadyInitialized`].
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{root_location, RootType};
+///
+/// binroots::init("my-app").unwrap();
+///
+/// assert!(root_location(RootType::InMemory).unwrap().ends_with("my-app"));
+/// assert!(binroots::init("other-app").is_err());
+/// ```
+pub fn init(app_name: &str) -> Result<(), InitError> {
+    if app_name.is_empty() || app_name == "." || app_name == ".." || app_name.contains(['/', '\\'])
+    {
+        return Err(InitError::InvalidName {
+            app_name: app_name.into(),
+        });
+    }
+
+    let current = APP_NAME.get_or_init(|| app_name.into());
+    if current != app_name {
+        return Err(InitError::AlreadyInitialized {
+            app_name: current.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// The application name set with [`init`], or `binroots` if it wasn't called
+pub fn app_name() -> &'static str {
+    APP_NAME
+        .get()
+        .map_or(env!("CARGO_PKG_NAME"), String::as_str)
+}
 
 #[cfg(test)]
 mod tests {
@@ -93,6 +201,7 @@ mod tests {
     use crate::*;
 
     #[test]
+    #[cfg(not(feature = "stable"))]
     fn serialize_field() {
         let field = field::BinrootsField::<"fieldname", &str>::new("Hello");
 
@@ -433,4 +542,3681 @@ mod tests {
 
         assert_file!("test_save_enums_will_delete_values", "Unit");
     }
+
+    #[test]
+    fn save_json_mirror() {
+        #[derive(Serialize)]
+        struct Rgb {
+            r: u8,
+            g: Option<u8>,
+        }
+
+        consume::save_json_mirror(&Rgb { r: 40, g: None }, "test_save_json_mirror", InMemory)
+            .unwrap();
+        assert_file!(
+            "test_save_json_mirror.json",
+            "{\n  \"r\": 40,\n  \"g\": null\n}"
+        );
+    }
+
+    #[test]
+    fn schema_reader_stubs() {
+        use consume::{LeafType, Schema, SchemaField};
+
+        let schema = Schema {
+            name: "Status",
+            root_folder: "status",
+            naming: naming::NamingPolicy::AsIs,
+            fields: &[
+                SchemaField {
+                    name: "connections",
+                    ty: "usize",
+                },
+                SchemaField {
+                    name: "nickname",
+                    ty: "Option<&'a str>",
+                },
+                SchemaField {
+                    name: "history",
+                    ty: "std::vec::Vec<[f32;3]>",
+                },
+                SchemaField {
+                    name: "is-up",
+                    ty: "bool",
+                },
+                SchemaField {
+                    name: "class",
+                    ty: "String",
+                },
+            ],
+        };
+
+        assert_eq!(
+            schema.leaf_types()[2].1,
+            LeafType::List(Box::new(LeafType::List(Box::new(LeafType::Float))))
+        );
+
+        let python = schema.python_reader("/tmp/binroots/status");
+        assert!(python.contains("ROOT = \"/tmp/binroots/status\""));
+        assert!(python.contains("def nickname(self) -> Optional[str]:\n        return _optional(_text)(self.root, \"nickname\")"));
+
+        let typescript = schema.typescript_reader("/tmp/binroots/status");
+        assert!(typescript.contains("get history(): Array<Array<number>> {\n        return list(list(float))(this.root, \"history\");"));
+
+        // Renamed fields keep their filename, but are read through identifiers
+        assert!(
+            python.contains("def is_up(self) -> bool:\n        return _bool(self.root, \"is-up\")")
+        );
+        assert!(
+            python.contains("def class_(self) -> str:\n        return _text(self.root, \"class\")")
+        );
+        assert!(typescript
+            .contains("get is_up(): boolean {\n        return bool(this.root, \"is-up\");"));
+
+        let escaped = "/tmp/binroots/\u{1b}stätus\u{7f}\"\\";
+        assert!(schema
+            .python_reader(escaped)
+            .contains("ROOT = \"/tmp/binroots/\\u001bstätus\u{7f}\\\"\\\\\"\n"));
+        assert!(schema
+            .typescript_reader(escaped)
+            .contains("export const ROOT = \"/tmp/binroots/\\u001bstätus\u{7f}\\\"\\\\\";"));
+    }
+
+    #[test]
+    fn serialize_with_options() {
+        let mut map = HashMap::new();
+        map.insert(true, "roots");
+
+        let mut serializer = fileserializer::FileSerializer::with_options(
+            "",
+            fileserializer::SerializeOptions {
+                check_map_keys: false,
+                ..Default::default()
+            },
+        );
+        map.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.output()[1].path, "/true");
+
+        save::save_root(serializer, "test_serialize_with_options".into(), InMemory).unwrap();
+        assert_file!("test_serialize_with_options/true", "roots");
+    }
+
+    #[test]
+    fn save_with_markers() {
+        use crate::fileserializer::Markers;
+
+        #[derive(Serialize)]
+        enum E {
+            Bin(Option<u8>),
+        }
+
+        let mut map = HashMap::new();
+        map.insert(None, E::Bin(None));
+        map.insert(Some("unit"), E::Bin(Some(3)));
+
+        let options = save::SaveOptions::default().markers(Markers {
+            none_key: "null".into(),
+            none_value: Some("null".into()),
+            value: "payload".into(),
+            variant: "{variant}!".into(),
+            ..Default::default()
+        });
+
+        map.save_with("test_save_with_markers", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_with_markers/null", "Bin!");
+        assert_file!("test_save_with_markers/null.payload", "null");
+        assert_file!("test_save_with_markers/unit", "Bin!");
+        assert_file!("test_save_with_markers/unit.payload", "3");
+    }
+
+    #[test]
+    fn save_strict_rejects_conventions() {
+        use crate::fileserializer::{Convention, SerializerError};
+
+        #[derive(Serialize)]
+        enum E {
+            Unit,
+            Single(u8),
+        }
+
+        #[derive(Serialize)]
+        struct Strict {
+            plain: E,
+            nested: Option<E>,
+        }
+
+        let options = save::SaveOptions::default().strict(true);
+
+        Strict {
+            plain: E::Unit,
+            nested: Some(E::Unit),
+        }
+        .save_with("test_save_strict", InMemory, &options)
+        .unwrap();
+        assert_file!("test_save_strict/plain", "Unit");
+
+        let rejected = |value: Strict| match value.save_with("test_save_strict", InMemory, &options)
+        {
+            Err(save::SaveError::SerializeError(SerializerError::Unrepresentable {
+                path,
+                convention,
+            })) => (path, convention),
+            other => panic!("expected a strict mode error, got {other:?}"),
+        };
+
+        assert_eq!(
+            rejected(Strict {
+                plain: E::Single(1),
+                nested: None,
+            }),
+            ("plain".into(), Convention::VariantValue)
+        );
+        assert_eq!(
+            rejected(Strict {
+                plain: E::Unit,
+                nested: None,
+            }),
+            ("nested".into(), Convention::NoneDeletesFile)
+        );
+    }
+
+    #[test]
+    fn save_watcher_friendly() {
+        #[derive(Serialize)]
+        enum E {
+            Single(u8),
+            Tuple(u8, u8),
+        }
+
+        let options = save::SaveOptions::default().watcher_friendly(true);
+        let modified = |path: &str| {
+            std::fs::metadata(root_location(InMemory).unwrap().join(path))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+
+        E::Tuple(8, 4)
+            .save_with("test_save_watcher_friendly", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_watcher_friendly.value/1", "4");
+
+        E::Single(12)
+            .save_with("test_save_watcher_friendly", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_watcher_friendly", "Single");
+        assert_file!("test_save_watcher_friendly.value", "12");
+
+        let before = modified("test_save_watcher_friendly.value");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        E::Single(12)
+            .save_with("test_save_watcher_friendly", InMemory, &options)
+            .unwrap();
+        assert_eq!(before, modified("test_save_watcher_friendly.value"));
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        #[derive(Serialize)]
+        struct Rgb {
+            r: u8,
+            g: Vec<u8>,
+        }
+
+        Rgb {
+            r: 40,
+            g: vec![6, 0],
+        }
+        .save("test_snapshot_round_trip", InMemory)
+        .unwrap();
+
+        let blob = snapshot::to_blob("test_snapshot_round_trip", InMemory).unwrap();
+        let restored =
+            snapshot::from_blob(&blob, "test_snapshot_round_trip.copy", InMemory).unwrap();
+
+        assert_eq!(restored.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            restored
+                .entries
+                .iter()
+                .map(|e| e.path.as_str())
+                .collect::<Vec<_>>(),
+            ["g", "g/0", "g/1", "r"]
+        );
+        assert_file!("test_snapshot_round_trip.copy/g/1", "0");
+        assert_file!("test_snapshot_round_trip.copy/r", "40");
+
+        assert!(matches!(
+            snapshot::Snapshot::from_bytes(&blob[..blob.len() - 1]),
+            Err(snapshot::SnapshotError::Malformed(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn snapshot_skips_symlinks() {
+        let location = root_location(InMemory).unwrap();
+        let root = location.join("test_snapshot_symlinks");
+        let _ = std::fs::remove_dir_all(&root);
+        (1, 2).save("test_snapshot_symlinks", InMemory).unwrap();
+        std::fs::write(location.join("test_snapshot_symlinks.secret"), "foreign").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+        std::os::unix::fs::symlink(
+            location.join("test_snapshot_symlinks.secret"),
+            root.join("outside"),
+        )
+        .unwrap();
+
+        let snapshot = snapshot::Snapshot::read_dir(&root).unwrap();
+        assert_eq!(
+            snapshot
+                .entries
+                .iter()
+                .map(|e| e.path.as_str())
+                .collect::<Vec<_>>(),
+            ["0", "1"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_symlink_policy() {
+        use save::{SaveOptions, SymlinkPolicy};
+
+        let root = root_location(InMemory).unwrap();
+        let target = root.join("test_save_symlink_policy.target");
+        let link = root.join("test_save_symlink_policy/r");
+
+        let reset = || {
+            std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+            std::fs::write(&target, "before").unwrap();
+            let _ = std::fs::remove_file(&link);
+            std::os::unix::fs::symlink(&target, &link).unwrap();
+        };
+        let save = |symlinks| {
+            #[derive(Serialize)]
+            struct Rgb {
+                r: u8,
+            }
+
+            Rgb { r: 40 }.save_with(
+                "test_save_symlink_policy",
+                InMemory,
+                &SaveOptions::default().symlinks(symlinks),
+            )
+        };
+
+        reset();
+        save(SymlinkPolicy::Follow).unwrap();
+        assert!(link.is_symlink());
+        assert_file!("test_save_symlink_policy.target", "40");
+
+        reset();
+        save(SymlinkPolicy::Replace).unwrap();
+        assert!(!link.is_symlink());
+        assert_file!("test_save_symlink_policy/r", "40");
+        assert_file!("test_save_symlink_policy.target", "before");
+
+        reset();
+        assert!(matches!(
+            save(SymlinkPolicy::Error),
+            Err(save::SaveError::SymlinkError { .. })
+        ));
+        assert_file!("test_save_symlink_policy.target", "before");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_root_rejects_untrusted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use save::{verify_root, SaveError, UntrustedRootReason};
+
+        let root = root_location(InMemory).unwrap();
+        verify_root(&root).unwrap();
+
+        let shared = root.join("test_verify_root.shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o777)).unwrap();
+
+        let planted = root.join("test_verify_root.planted");
+        let _ = std::fs::remove_file(&planted);
+        std::os::unix::fs::symlink(&shared, &planted).unwrap();
+
+        for (path, expected) in [
+            (shared, UntrustedRootReason::WorldWritable),
+            (planted, UntrustedRootReason::Symlink),
+        ] {
+            assert!(matches!(
+                verify_root(&path),
+                Err(SaveError::UntrustedRoot { reason, .. }) if reason == expected
+            ));
+        }
+    }
+
+    #[test]
+    fn consume_polling_watcher() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        #[derive(Serialize)]
+        struct Rgb {
+            r: u8,
+            g: u8,
+        }
+
+        Rgb { r: 40, g: 60 }
+            .save("test_consume_polling_watcher", InMemory)
+            .unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let watcher = consume::spawn_polling_watcher(
+            "test_consume_polling_watcher",
+            InMemory,
+            &["g"],
+            Duration::from_millis(10),
+            move |field| tx.send(field.to_string()).unwrap(),
+        )
+        .unwrap();
+        assert!(!watcher.is_native());
+
+        std::thread::sleep(Duration::from_millis(50));
+        Rgb { r: 41, g: 61 }
+            .save("test_consume_polling_watcher", InMemory)
+            .unwrap();
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), "g");
+        watcher.stop();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn save_hooks_can_veto() {
+        use std::ops::ControlFlow;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let saved = Arc::new(AtomicUsize::new(0));
+        let saved_hook = saved.clone();
+
+        let veto = hooks::register_pre_save(|context| {
+            if context.root.ends_with("test_save_hooks_can_veto") && context.files.len() > 1 {
+                ControlFlow::Break("too many files".into())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        let observe = hooks::register_post_save(move |context| {
+            if context.root.ends_with("test_save_hooks_can_veto") {
+                saved_hook.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        "one".save("test_save_hooks_can_veto", InMemory).unwrap();
+        assert!(matches!(
+            (1, 2).save("test_save_hooks_can_veto", InMemory),
+            Err(save::SaveError::Vetoed { reason }) if reason == "too many files"
+        ));
+        assert_file!("test_save_hooks_can_veto", "one");
+        assert_eq!(saved.load(Ordering::Relaxed), 1);
+
+        assert!(hooks::unregister(veto));
+        assert!(hooks::unregister(observe));
+        assert!(!hooks::unregister(veto));
+    }
+
+    #[test]
+    fn save_etag() {
+        let options = save::SaveOptions::default().etag(true);
+        let read_etag = || {
+            std::fs::read_to_string(
+                root_location(InMemory)
+                    .unwrap()
+                    .join("test_save_etag/.etag"),
+            )
+            .unwrap()
+        };
+
+        (1, 2)
+            .save_with("test_save_etag", InMemory, &options)
+            .unwrap();
+        let first = read_etag();
+        assert_eq!(first, save::etag("test_save_etag", InMemory).unwrap());
+
+        (1, 2)
+            .save_with("test_save_etag", InMemory, &options)
+            .unwrap();
+        assert_eq!(first, read_etag());
+
+        (1, 3)
+            .save_with("test_save_etag", InMemory, &options)
+            .unwrap();
+        assert_ne!(first, read_etag());
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn save_mtime_sidecars() {
+        #[derive(Serialize)]
+        struct Activity {
+            status: field::BinrootsField<"status", String>,
+            count: field::BinrootsField<"count", u8>,
+        }
+
+        let activity = Activity {
+            status: field::BinrootsField::new("idle".into()),
+            count: field::BinrootsField::new(3),
+        };
+        assert!(activity.status.last_saved().is_none());
+
+        let options = save::SaveOptions::default().mtime_sidecars(true);
+        activity
+            .status
+            .save_with("test_save_mtime_sidecars", InMemory, &options)
+            .unwrap();
+
+        assert!(activity.status.last_saved().is_some());
+        assert!(activity.count.last_saved().is_none());
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_mtime_sidecars");
+        let secs = std::fs::read_to_string(root.join("status.mtime"))
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!(secs > 0);
+        assert!(!root.join("count.mtime").exists());
+    }
+
+    #[test]
+    fn save_enum_json_payload() {
+        #[derive(Serialize)]
+        enum Activity {
+            Idle,
+            Playing(String),
+            Listening(String, u8),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            first: Activity,
+            second: Activity,
+            third: Activity,
+        }
+
+        let options = save::SaveOptions::default()
+            .enum_representation(fileserializer::EnumRepresentation::JsonPayload);
+        Status {
+            first: Activity::Idle,
+            second: Activity::Playing("hideo kame".into()),
+            third: Activity::Listening("radio".into(), 7),
+        }
+        .save_with("test_save_enum_json_payload", InMemory, &options)
+        .unwrap();
+
+        assert_file!("test_save_enum_json_payload/first", "idle");
+        assert_file!("test_save_enum_json_payload/first.json", r#""Idle""#);
+        assert_file!("test_save_enum_json_payload/second", "playing");
+        assert_file!(
+            "test_save_enum_json_payload/second.json",
+            r#"{"Playing":"hideo kame"}"#
+        );
+        assert_file!("test_save_enum_json_payload/third", "listening");
+        assert_file!(
+            "test_save_enum_json_payload/third.json",
+            r#"{"Listening":["radio",7]}"#
+        );
+    }
+
+    #[test]
+    fn save_if_generation_conflicts() {
+        let root = "test_save_if_generation_conflicts";
+        let start = save::generation(root, InMemory).unwrap();
+
+        (1, 2)
+            .save_with(
+                root,
+                InMemory,
+                &save::SaveOptions::default().generation(true),
+            )
+            .unwrap();
+        assert_eq!(save::generation(root, InMemory).unwrap(), start + 1);
+
+        assert!(matches!(
+            (3, 4).save_if_generation(root, InMemory, start),
+            Err(save::SaveError::Conflict { expected, found }) if expected == start && found == start + 1
+        ));
+        assert_file!(format!("{root}/1"), "2");
+
+        (3, 4)
+            .save_if_generation(root, InMemory, start + 1)
+            .unwrap();
+        assert_eq!(save::generation(root, InMemory).unwrap(), start + 2);
+        assert_file!(format!("{root}/1"), "4");
+    }
+
+    #[test]
+    fn read_leaves() {
+        #[derive(Serialize)]
+        enum Activity {
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            connections: usize,
+            activity: Activity,
+            email: Option<String>,
+        }
+
+        Status {
+            connections: 12,
+            activity: Activity::Playing("hideo kame".into()),
+            email: None,
+        }
+        .save("test_read_leaves", InMemory)
+        .unwrap();
+
+        assert_eq!(
+            read::leaf::<usize, _, _>("test_read_leaves", "connections", InMemory).unwrap(),
+            12
+        );
+        assert_eq!(
+            read::leaf::<String, _, _>("test_read_leaves", "activity", InMemory).unwrap(),
+            "Playing"
+        );
+        assert_eq!(
+            read::read_bytes("test_read_leaves", "activity.value", InMemory).unwrap(),
+            b"hideo kame"
+        );
+        assert!(matches!(
+            read::leaf::<bool, _, _>("test_read_leaves", "connections", InMemory),
+            Err(read::ReadError::ParseError { .. })
+        ));
+        assert!(matches!(
+            read::read_bytes("test_read_leaves", "email", InMemory),
+            Err(read::ReadError::ReadFileError { kind, .. }) if kind == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn read_walk_classifies() {
+        use read::NodeKind::*;
+
+        #[derive(Serialize)]
+        enum Activity {
+            Listening(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            activity: Activity,
+            tags: HashMap<&'static str, bool>,
+        }
+
+        Status {
+            activity: Activity::Listening("radio".into()),
+            tags: HashMap::from([("online", true)]),
+        }
+        .save_with(
+            "test_read_walk_classifies",
+            InMemory,
+            &save::SaveOptions::default().etag(true),
+        )
+        .unwrap();
+
+        let nodes = read::walk("test_read_walk_classifies", InMemory)
+            .unwrap()
+            .map(|node| {
+                let node = node.unwrap();
+                (node.path.to_string_lossy().into_owned(), node.kind)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            nodes,
+            [
+                (".etag".into(), Metadata),
+                ("activity".into(), VariantTag),
+                ("activity.value".into(), VariantPayload),
+                ("tags".into(), Map),
+                ("tags/online".into(), Leaf),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_walk_skips_symlinks() {
+        let location = root_location(InMemory).unwrap();
+        let root = location.join("test_read_walk_symlinks");
+        let _ = std::fs::remove_dir_all(&root);
+        (1, 2).save("test_read_walk_symlinks", InMemory).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+        std::os::unix::fs::symlink(&location, root.join("outside")).unwrap();
+
+        let nodes = read::walk("test_read_walk_symlinks", InMemory)
+            .unwrap()
+            .map(|node| node.unwrap().path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(nodes, ["0", "1"]);
+    }
+
+    #[test]
+    fn save_metadata_keeps_created() {
+        let root = "test_save_metadata_keeps_created";
+        let options = save::SaveOptions::default().metadata(true);
+        let _ = std::fs::remove_dir_all(root_location(InMemory).unwrap().join(root));
+
+        (1, 2).save_with(root, InMemory, &options).unwrap();
+        let first = metadata::read(root, InMemory).unwrap().unwrap();
+        assert_eq!(first.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(first.app_version, None);
+
+        (1, 3)
+            .save_with(root, InMemory, &options.app_version("2.0.0"))
+            .unwrap();
+        let second = metadata::read(root, InMemory).unwrap().unwrap();
+        assert_eq!(second.created, first.created);
+        assert!(second.updated >= first.updated);
+        assert_eq!(second.app_version.as_deref(), Some("2.0.0"));
+    }
+
+    #[test]
+    fn save_coalesces_shared_files() {
+        use std::ops::ControlFlow;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let planned = Arc::new(AtomicUsize::new(0));
+        let planned_hook = planned.clone();
+        let hook = hooks::register_pre_save(move |context| {
+            if context.root.ends_with("test_save_coalesces_shared_files") {
+                planned_hook.store(context.files.len(), Ordering::SeqCst);
+            }
+            ControlFlow::Continue(())
+        });
+
+        // `None` is saved with the marker filename `__NONE__`, which is shared by the literal key
+        let map = std::collections::BTreeMap::from([(None, 1), (Some("__NONE__"), 2)]);
+        map.save("test_save_coalesces_shared_files", InMemory)
+            .unwrap();
+        hooks::unregister(hook);
+
+        assert_eq!(planned.load(Ordering::SeqCst), 2); // The map's folder and a single `__NONE__`
+        assert_file!("test_save_coalesces_shared_files/__NONE__", "2");
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn save_fault_injection() {
+        use std::sync::Arc;
+
+        let faults = backend::FaultInjector::new(backend::Filesystem)
+            .fail_path("test_save_fault_injection/b")
+            .error_kind(std::io::ErrorKind::StorageFull);
+        let options = save::SaveOptions::default().backend(Arc::new(faults));
+
+        #[derive(Serialize)]
+        struct Pair {
+            a: u8,
+            b: u8,
+        }
+
+        assert!(matches!(
+            Pair { a: 1, b: 2 }.save_with("test_save_fault_injection", InMemory, &options),
+            Err(save::SaveError::WriteFileError { source, field, .. })
+                if source.kind() == std::io::ErrorKind::StorageFull
+                    && field.as_deref() == Some("test_save_fault_injection/b")
+        ));
+        assert_file!("test_save_fault_injection/a", "1");
+    }
+
+    #[test]
+    fn save_trash_keeps_removed() {
+        #[derive(Serialize)]
+        enum Activity {
+            Idle,
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            activity: Activity,
+            email: Option<&'static str>,
+        }
+
+        let root_name = "test_save_trash_keeps_removed";
+        let root = root_location(InMemory).unwrap().join(root_name);
+        let _ = std::fs::remove_dir_all(&root);
+        let options = save::SaveOptions::default().trash(true);
+
+        Status {
+            activity: Activity::Playing("hideo kame".into()),
+            email: Some("alice@example.com"),
+        }
+        .save_with(root_name, InMemory, &options)
+        .unwrap();
+        Status {
+            activity: Activity::Idle,
+            email: None,
+        }
+        .save_with(root_name, InMemory, &options)
+        .unwrap();
+
+        assert!(!root.join("email").exists());
+        assert!(!root.join("activity.value").exists());
+
+        let trashed = std::fs::read_dir(root.join(save::TRASH_FOLDER))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(trashed[0].join("email")).unwrap(),
+            "alice@example.com"
+        );
+        assert_eq!(
+            std::fs::read_to_string(trashed[0].join("activity.value")).unwrap(),
+            "hideo kame"
+        );
+    }
+
+    #[test]
+    fn queue_runs_critical_first() {
+        use queue::{Priority, SaveQueue};
+        use std::ops::ControlFlow;
+        use std::sync::{Arc, Condvar, Mutex};
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let order_hook = order.clone();
+        let gate = Arc::new((Mutex::new(false), Condvar::new()));
+        let gate_hook = gate.clone();
+
+        // Holds the worker in the blocker's save until every other save is queued
+        let pre = hooks::register_pre_save(move |context| {
+            if context
+                .root
+                .ends_with("test_queue_runs_critical_first_blocker")
+            {
+                let (open, changed) = &*gate_hook;
+                let _open = changed
+                    .wait_while(open.lock().unwrap(), |open| !*open)
+                    .unwrap();
+            }
+            ControlFlow::Continue(())
+        });
+        let post = hooks::register_post_save(move |context| {
+            let name = context.root.file_name().unwrap().to_string_lossy();
+            if let Some(name) = name.strip_prefix("test_queue_runs_critical_first_") {
+                order_hook.lock().unwrap().push(name.to_string());
+            }
+        });
+
+        let queue = SaveQueue::new(2);
+        let push = |value: u8, name: &str, priority| {
+            let root = format!("test_queue_runs_critical_first_{name}");
+            queue.push(
+                &(value,),
+                root,
+                InMemory,
+                priority,
+                save::SaveOptions::default(),
+            )
+        };
+
+        let blocker = push(0, "blocker", Priority::Bulk).unwrap();
+        while queue.pending() > 0 {
+            std::thread::yield_now();
+        }
+
+        let bulk = [
+            push(1, "bulk1", Priority::Bulk),
+            push(2, "bulk2", Priority::Bulk),
+        ];
+        let normal = push(3, "normal", Priority::Normal);
+        let critical = push(4, "critical", Priority::Critical).unwrap();
+
+        assert!(matches!(
+            normal,
+            Err(save::SaveError::QueueFull { pending: 2 })
+        ));
+        assert_eq!(queue.pending_with(Priority::Bulk), 2);
+
+        *gate.0.lock().unwrap() = true;
+        gate.1.notify_all();
+        queue.flush();
+        hooks::unregister(pre);
+        hooks::unregister(post);
+
+        blocker.wait().unwrap();
+        critical.wait().unwrap();
+        for ticket in bulk {
+            ticket.unwrap().wait().unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["blocker", "critical", "bulk1", "bulk2"]
+        );
+    }
+
+    #[test]
+    fn save_queue_reports_aborted_saves() {
+        use std::path::Path;
+        use std::sync::Arc;
+
+        use crate::backend::Backend;
+        use crate::queue::{Priority, SaveQueue};
+
+        /// Panics instead of writing, like a buggy backend would
+        #[derive(Debug)]
+        struct Panics;
+
+        impl Backend for Panics {
+            fn write(&self, _: &Path, _: &[u8]) -> std::io::Result<()> {
+                panic!("kaboom")
+            }
+            fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+                std::fs::create_dir_all(path)
+            }
+            fn remove(&self, path: &Path) -> std::io::Result<()> {
+                std::fs::remove_file(path)
+            }
+            fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+                std::fs::remove_dir(path)
+            }
+            fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+                std::fs::rename(from, to)
+            }
+        }
+
+        let queue = SaveQueue::new(4);
+        let ticket = queue
+            .push(
+                &(1u8,),
+                "test_save_queue_aborted",
+                InMemory,
+                Priority::Normal,
+                save::SaveOptions::default().backend(Arc::new(Panics)),
+            )
+            .unwrap();
+
+        assert!(matches!(ticket.wait(), Err(save::SaveError::SaveAborted)));
+    }
+
+    #[test]
+    fn backend_write_atomic_crosses_devices() {
+        use backend::{Backend, Filesystem};
+        use std::path::{Path, PathBuf};
+        use std::sync::Mutex;
+
+        /// Fails renames out of the temp folder like a rename across filesystems would, recording every file written
+        #[derive(Debug)]
+        struct OtherDevice<'a>(&'a Path, Mutex<Vec<PathBuf>>);
+
+        impl Backend for OtherDevice<'_> {
+            fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+                self.1.lock().unwrap().push(path.into());
+                Filesystem.write(path, contents)
+            }
+            fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.mkdir(path)
+            }
+            fn remove(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove(path)
+            }
+            fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove_dir(path)
+            }
+            fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+                if from.starts_with(self.0) {
+                    Err(std::io::ErrorKind::CrossesDevices.into())
+                } else {
+                    Filesystem.rename(from, to)
+                }
+            }
+        }
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_backend_write_atomic_crosses_devices");
+        let _ = std::fs::remove_dir_all(&root);
+        let temp = root.join("temp");
+        std::fs::create_dir_all(&temp).unwrap();
+
+        let target = root.join("value");
+        let backend = OtherDevice(&temp, Mutex::default());
+        backend
+            .write_atomic(
+                &target,
+                b"moved",
+                Some(&temp),
+                &clock::SequentialIds::new(),
+                None,
+            )
+            .unwrap();
+
+        // Both temporary files are named by the given ids
+        assert_eq!(
+            *backend.1.lock().unwrap(),
+            vec![
+                temp.join(".value.0.binroots-tmp"),
+                root.join(".value.1.binroots-tmp")
+            ]
+        );
+        assert_eq!(std::fs::read(&target).unwrap(), b"moved");
+        assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 2);
+
+        // Temporary files that can't be renamed over their target are removed
+        let folder = root.join("folder");
+        std::fs::create_dir_all(folder.join("nested")).unwrap();
+        assert!(backend
+            .write_atomic(
+                &folder,
+                b"blocked",
+                Some(&temp),
+                &clock::SequentialIds::new(),
+                None,
+            )
+            .is_err());
+        assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn save_shared_and_cell_values() {
+        use std::borrow::Cow;
+        use std::cell::{Cell, RefCell};
+        use std::rc::Rc;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Serialize)]
+        struct Shared {
+            cow: Cow<'static, str>,
+            rc: Rc<Vec<u8>>,
+            arc: Arc<str>,
+            cell: Cell<u8>,
+            refcell: RefCell<Vec<&'static str>>,
+            mutex: Mutex<bool>,
+        }
+
+        let shared = Shared {
+            cow: Cow::Borrowed("borrowed"),
+            rc: Rc::new(vec![1, 2]),
+            arc: Arc::from("shared"),
+            cell: Cell::new(5),
+            refcell: RefCell::new(vec!["a"]),
+            mutex: Mutex::new(true),
+        };
+        shared.refcell.borrow_mut().push("b");
+        shared
+            .save("test_save_shared_and_cell_values", InMemory)
+            .unwrap();
+
+        assert_file!("test_save_shared_and_cell_values/cow", "borrowed");
+        assert_file!("test_save_shared_and_cell_values/rc/1", "2");
+        assert_file!("test_save_shared_and_cell_values/arc", "shared");
+        assert_file!("test_save_shared_and_cell_values/cell", "5");
+        assert_file!("test_save_shared_and_cell_values/refcell/1", "b");
+        assert_file!("test_save_shared_and_cell_values/mutex", "true");
+
+        let mut arc = field::BinrootsField::<"arc", Arc<str>>::new(Arc::from("old"));
+        let old = arc.replace(Arc::from("new"));
+        assert_eq!(&*old, "old");
+
+        let counter = field::BinrootsField::<"counter", Mutex<u8>>::new(Mutex::new(1));
+        counter.update(|c| *c += 1);
+        counter
+            .save("test_save_shared_and_cell_values", InMemory)
+            .unwrap();
+        assert_file!("test_save_shared_and_cell_values/counter", "2");
+    }
+
+    #[test]
+    fn fmt_round_trips() {
+        use std::time::Duration;
+
+        for (duration, rendered) in [
+            (Duration::ZERO, "0s"),
+            (Duration::from_secs(5), "5s"),
+            (Duration::from_secs(150), "2m30s"),
+            (Duration::from_millis(1500), "1s500ms"),
+            (Duration::from_secs(90_061), "1d1h1m1s"),
+        ] {
+            assert_eq!(fmt::duration::format(&duration), rendered);
+            assert_eq!(fmt::duration::parse(rendered), Some(duration));
+        }
+        assert_eq!(fmt::duration::parse("5 parsecs"), None);
+
+        for (bytes, rendered) in [
+            (0, "0B"),
+            (512, "512B"),
+            (4096, "4KiB"),
+            (1536 * 1024, "1.5MiB"),
+            (3 * 1024 * 1024 * 1024, "3GiB"),
+            (1280, "1.25KiB"),
+            (1537 * 1024, "1537KiB"),
+            (1000, "1000B"),
+            (u64::MAX, "18446744073709551615B"),
+            (u64::MAX - 1023, "18014398509481983KiB"),
+        ] {
+            assert_eq!(fmt::bytesize::format(bytes), rendered);
+            assert_eq!(fmt::bytesize::parse(rendered), Some(bytes));
+        }
+        assert_eq!(fmt::bytesize::parse("1.5MB"), None);
+        assert_eq!(fmt::bytesize::parse("1.3KiB"), Some(1331));
+        assert_eq!(fmt::bytesize::parse("16EiB"), None);
+    }
+
+    #[test]
+    fn health_reports_root_state() {
+        use std::sync::{Arc, Mutex};
+
+        let missing = health::health("test_health_missing", InMemory).unwrap();
+        assert!(!missing.exists && missing.writable);
+        assert!(missing.free_space.is_some());
+        assert_eq!(missing.last_saved, None);
+        assert!(missing.is_stale(std::time::Duration::from_secs(60)));
+
+        let seen = Arc::new(Mutex::new(None));
+        let seen_hook = seen.clone();
+        let post = hooks::register_post_save(move |context| {
+            if context.root.ends_with("test_health_reports_root_state") {
+                *seen_hook.lock().unwrap() =
+                    health::health("test_health_reports_root_state", InMemory)
+                        .ok()
+                        .map(|health| health.locked);
+            }
+        });
+
+        let options = save::SaveOptions::default().generation(true);
+        (1, 2)
+            .save_with("test_health_reports_root_state", InMemory, &options)
+            .unwrap();
+        hooks::unregister(post);
+
+        // The generation lock is held until the post-save hooks have run
+        assert_eq!(*seen.lock().unwrap(), Some(true));
+
+        let health = health::health("test_health_reports_root_state", InMemory).unwrap();
+        assert!(health.exists && health.writable && !health.locked);
+        assert!(!health.is_stale(std::time::Duration::from_secs(60)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn save_root_missing() {
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_root_missing");
+        let field = field::BinrootsField::<"count", u8>::new(4);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let options = save::SaveOptions::default().root_missing(save::RootMissing::Error);
+        assert!(matches!(
+            field.save_with("test_save_root_missing", InMemory, &options),
+            Err(save::SaveError::RootMissing { .. })
+        ));
+        assert!(!root.exists());
+
+        let options = options.root_missing(save::RootMissing::Resave);
+        assert!(matches!(
+            field.save_with("test_save_root_missing", InMemory, &options),
+            Err(save::SaveError::RootMissing { .. })
+        ));
+
+        let provider = hooks::register_full_state("test_save_root_missing", |serializer| {
+            let mut map = HashMap::new();
+            map.insert("count", 4);
+            map.insert("total", 9);
+            map.serialize(serializer)
+        });
+        field
+            .save_with("test_save_root_missing", InMemory, &options)
+            .unwrap();
+        hooks::unregister(provider);
+
+        assert_file!("test_save_root_missing/count", "4");
+        assert_file!("test_save_root_missing/total", "9");
+    }
+
+    #[test]
+    fn post_save_changes() {
+        use std::path::PathBuf;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Serialize)]
+        struct Status {
+            online: bool,
+            name: Option<&'static str>,
+            count: u8,
+        }
+
+        let options = save::SaveOptions::default().watcher_friendly(true);
+        let status = Status {
+            online: true,
+            name: Some("a"),
+            count: 1,
+        };
+        let _ = std::fs::remove_dir_all(
+            root_location(InMemory)
+                .unwrap()
+                .join("test_post_save_changes"),
+        );
+        status
+            .save_with("test_post_save_changes", InMemory, &options)
+            .unwrap();
+
+        let seen = Arc::new(Mutex::new(None));
+        let seen_hook = seen.clone();
+        let post = hooks::register_post_save(move |context| {
+            if context.root.ends_with("test_post_save_changes") {
+                *seen_hook.lock().unwrap() = context.changes.cloned();
+            }
+        });
+
+        let status = Status {
+            online: true,
+            name: None,
+            count: 2,
+        };
+        status
+            .save_with("test_post_save_changes", InMemory, &options)
+            .unwrap();
+        let changes = seen.lock().unwrap().take().unwrap();
+
+        status
+            .save_with(
+                "test_post_save_changes",
+                InMemory,
+                &save::SaveOptions::default(),
+            )
+            .unwrap();
+        hooks::unregister(post);
+
+        assert_eq!(changes.added, Vec::<PathBuf>::new());
+        assert_eq!(changes.changed, vec![PathBuf::from("count")]);
+        assert_eq!(changes.deleted, vec![PathBuf::from("name")]);
+        assert_eq!(*seen.lock().unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_save_changes_skip_symlinks() {
+        use std::path::PathBuf;
+        use std::sync::{Arc, Mutex};
+
+        let options = save::SaveOptions::default().watcher_friendly(true);
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_post_save_symlinks");
+        let _ = std::fs::remove_dir_all(&root);
+        (vec![1, 2], 0)
+            .save_with("test_post_save_symlinks", InMemory, &options)
+            .unwrap();
+        std::os::unix::fs::symlink(&root, root.join("0/2")).unwrap();
+
+        let seen = Arc::new(Mutex::new(None));
+        let seen_hook = seen.clone();
+        let post = hooks::register_post_save(move |context| {
+            if context.root.ends_with("test_post_save_symlinks") {
+                *seen_hook.lock().unwrap() = context.changes.cloned();
+            }
+        });
+        (vec![1], 0)
+            .save_with("test_post_save_symlinks", InMemory, &options)
+            .unwrap();
+        hooks::unregister(post);
+
+        let changes = seen.lock().unwrap().take().unwrap();
+        assert_eq!(
+            changes.deleted,
+            vec![PathBuf::from("0/1"), PathBuf::from("0/2")]
+        );
+        assert!(root.join("1").exists());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn save_cbor_leaves() {
+        #[derive(Serialize)]
+        struct Reading {
+            label: &'static str,
+            samples: Vec<u16>,
+            tags: HashMap<&'static str, bool>,
+        }
+
+        let mut tags = HashMap::new();
+        tags.insert("hot", true);
+        let reading = Reading {
+            label: "cpu",
+            samples: vec![500, 7],
+            tags,
+        };
+
+        let options = save::SaveOptions::default()
+            .field_encoding("samples", fileserializer::LeafEncoding::Cbor)
+            .field_encoding("tags", fileserializer::LeafEncoding::Cbor);
+        reading
+            .save_with("test_save_cbor_leaves", InMemory, &options)
+            .unwrap();
+
+        assert_file!("test_save_cbor_leaves/label", "cpu");
+        assert_eq!(
+            std::fs::read(
+                root_location(InMemory)
+                    .unwrap()
+                    .join("test_save_cbor_leaves/samples/0.cbor")
+            )
+            .unwrap(),
+            [0x19, 0x01, 0xf4]
+        );
+
+        let sample: u16 = read::cbor_leaf("test_save_cbor_leaves", "samples/1", InMemory).unwrap();
+        let hot: bool = read::cbor_leaf("test_save_cbor_leaves", "tags/hot", InMemory).unwrap();
+        assert_eq!((sample, hot), (7, true));
+    }
+
+    #[test]
+    fn quiet_file_buffers_saves() {
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_quiet_file_buffers_saves");
+        let options = save::SaveOptions::default().quiet_file(true);
+        (1, 2)
+            .save_with("test_quiet_file_buffers_saves", InMemory, &options)
+            .unwrap();
+
+        std::fs::write(root.join(quiet::QUIET_FILE), "").unwrap();
+        (3, 4)
+            .save_with("test_quiet_file_buffers_saves", InMemory, &options)
+            .unwrap();
+        (5, 6)
+            .save_with("test_quiet_file_buffers_saves", InMemory, &options)
+            .unwrap();
+
+        assert_file!("test_quiet_file_buffers_saves/0", "1");
+        assert!(quiet::is_quiet("test_quiet_file_buffers_saves", InMemory).unwrap());
+        assert_eq!(
+            quiet::pending("test_quiet_file_buffers_saves", InMemory).unwrap(),
+            2
+        );
+        assert_eq!(
+            quiet::flush("test_quiet_file_buffers_saves", InMemory).unwrap(),
+            0
+        );
+
+        std::fs::remove_file(root.join(quiet::QUIET_FILE)).unwrap();
+        assert_eq!(
+            quiet::flush("test_quiet_file_buffers_saves", InMemory).unwrap(),
+            2
+        );
+        assert_file!("test_quiet_file_buffers_saves/0", "5");
+        assert_eq!(
+            quiet::pending("test_quiet_file_buffers_saves", InMemory).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn load_round_trips() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Mode {
+            Idle,
+            Busy(u8),
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Status {
+            name: String,
+            ratio: f64,
+            nickname: Option<String>,
+            away: Option<bool>,
+            tags: Vec<String>,
+            counts: HashMap<u32, u32>,
+            mode: Mode,
+            idle: Mode,
+        }
+
+        let status = Status {
+            name: "alex".into(),
+            ratio: 0.5,
+            nickname: None,
+            away: Some(false),
+            tags: vec!["a".into(), "b".into()],
+            counts: HashMap::from([(10, 1), (20, 2)]),
+            mode: Mode::Busy(7),
+            idle: Mode::Idle,
+        };
+        status.save("test_load_round_trips", InMemory).unwrap();
+
+        let loaded: Status = load::load("test_load_round_trips", InMemory).unwrap();
+        assert_eq!(loaded, status);
+
+        assert!(matches!(
+            load::load::<Status, _>("test_load_round_trips_missing", InMemory),
+            Err(load::LoadError::ReadFileError {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn naming_policy_migrates() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Activity {
+            Nothing,
+            WatchingTv(String),
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Status {
+            is_online: bool,
+            current_activity: Activity,
+            play_counts: std::collections::BTreeMap<String, u32>,
+        }
+
+        let status = Status {
+            is_online: true,
+            current_activity: Activity::WatchingTv("news".into()),
+            play_counts: [("late_show".to_string(), 3)].into(),
+        };
+        let _ = std::fs::remove_dir_all(
+            root_location(InMemory)
+                .unwrap()
+                .join("test_naming_policy_migrates"),
+        );
+        status
+            .save("test_naming_policy_migrates", InMemory)
+            .unwrap();
+
+        let renamed = naming::migrate(
+            &status,
+            "test_naming_policy_migrates",
+            InMemory,
+            &save::SaveOptions::default(),
+            naming::NamingPolicy::Kebab,
+        )
+        .unwrap();
+        assert_eq!(renamed.len(), 4);
+        assert_file!("test_naming_policy_migrates/is-online", "true");
+        assert_file!("test_naming_policy_migrates/current-activity.value", "news");
+        assert_file!("test_naming_policy_migrates/play-counts/late_show", "3");
+
+        let options = save::SaveOptions::default().naming(naming::NamingPolicy::Kebab);
+        status
+            .save_with("test_naming_policy_migrates", InMemory, &options)
+            .unwrap();
+        assert_file!(
+            "test_naming_policy_migrates/current-activity",
+            "watching-tv"
+        );
+
+        let loaded: Status =
+            load::load_with("test_naming_policy_migrates", InMemory, &options).unwrap();
+        assert_eq!(loaded, status);
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn watch_reports_external_edits() {
+        use std::path::Path;
+        use std::time::Duration;
+
+        use crate::watch::{ChangeEvent, ChangeKind, Watcher};
+
+        #[derive(Serialize)]
+        enum Activity {
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            activity: Activity,
+        }
+
+        Status {
+            activity: Activity::Playing("chess".into()),
+        }
+        .save("test_watch_reports_external_edits", InMemory)
+        .unwrap();
+        let watcher = Watcher::watch_root("test_watch_reports_external_edits", InMemory).unwrap();
+
+        std::fs::write(watcher.root().join("activity.value"), "go").unwrap();
+        std::fs::write(watcher.root().join(".notes"), "ignored").unwrap();
+        std::fs::remove_file(watcher.root().join("activity")).unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = watcher.next_timeout(Duration::from_secs(2)) {
+            events.push(event);
+        }
+
+        let activity = |kind| ChangeEvent {
+            logical_path: "activity".into(),
+            kind,
+        };
+        assert!(events.contains(&activity(ChangeKind::Modified)));
+        assert!(events.contains(&activity(ChangeKind::Removed)));
+        assert!(events
+            .iter()
+            .all(|event| event.logical_path == Path::new("activity")));
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn watch_coalesces_identical_events() {
+        use std::time::{Duration, Instant};
+
+        use crate::watch::{ChangeKind, Watcher};
+
+        (1, 2)
+            .save("test_watch_coalesces_identical_events", InMemory)
+            .unwrap();
+        let coalesce = Duration::from_millis(300);
+        let watcher =
+            Watcher::watch_root_with("test_watch_coalesces_identical_events", InMemory, coalesce)
+                .unwrap();
+
+        // Writes closer together than the window keep it open until the last one
+        let mut written = Instant::now();
+        for contents in ["3", "4", "5"] {
+            std::fs::write(watcher.root().join("0"), contents).unwrap();
+            written = Instant::now();
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        let mut modified = Vec::new();
+        while let Some(event) = watcher.next_timeout(Duration::from_secs(2)) {
+            if event.kind == ChangeKind::Modified {
+                assert!(written.elapsed() >= coalesce);
+                modified.push(std::fs::read_to_string(watcher.root().join("0")).unwrap());
+            }
+        }
+
+        assert_eq!(modified, ["5"]);
+    }
+
+    #[cfg(feature = "async_save")]
+    #[test]
+    fn save_async_matches_save() {
+        use crate::save_async::SaveAsync;
+
+        #[derive(Serialize)]
+        enum Activity {
+            Nothing,
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            nickname: Option<String>,
+            activity: Activity,
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let save = |status: Status, options: save::SaveOptions| {
+            runtime.block_on(status.save_async_with(
+                "test_save_async_matches_save",
+                InMemory,
+                &options,
+            ))
+        };
+
+        let status = Status {
+            nickname: Some("alex".into()),
+            activity: Activity::Playing("chess".into()),
+        };
+        save(status, save::SaveOptions::default()).unwrap();
+        assert_file!("test_save_async_matches_save/nickname", "alex");
+        assert_file!("test_save_async_matches_save/activity", "Playing");
+        assert_file!("test_save_async_matches_save/activity.value", "chess");
+
+        let status = Status {
+            nickname: None,
+            activity: Activity::Nothing,
+        };
+        let options = save::SaveOptions::default().generation(true);
+        save(status, options).unwrap();
+        assert_file!("test_save_async_matches_save/activity", "Nothing");
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_async_matches_save");
+        assert!(!root.join("nickname").exists());
+        assert!(!root.join("activity.value").exists());
+        assert!(root.join(save::GENERATION_FILE).exists());
+    }
+
+    #[cfg(all(feature = "async_save", unix))]
+    #[test]
+    fn save_async_read_only_and_cached_roots() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        use crate::dircache::DirCache;
+        use crate::save::{ReadOnlyPolicy, SaveError, SaveOptions};
+        use crate::save_async::SaveAsync;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        // Folders created by async saves are remembered by the cache like those of blocking saves
+        let cache = Arc::new(DirCache::new());
+        let options = SaveOptions::default().dir_cache(cache.clone());
+        runtime
+            .block_on(([1u8],).save_async_with("test_save_async_cached", InMemory, &options))
+            .unwrap();
+        assert!(!cache.is_empty());
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_async_read_only");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // Permissions don't restrict the superuser, leaving nothing to test
+        if !crate::health::writable(&root) {
+            let save = |options: SaveOptions| {
+                runtime.block_on((1u8,).save_async_with(
+                    "test_save_async_read_only",
+                    InMemory,
+                    &options,
+                ))
+            };
+            assert!(matches!(
+                save(SaveOptions::default()),
+                Err(SaveError::ReadOnlyRoot { .. })
+            ));
+            save(SaveOptions::default().read_only(ReadOnlyPolicy::Skip)).unwrap();
+            assert!(!root.join("0").exists());
+        }
+        std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn stats_record_saves() {
+        use std::ops::ControlFlow;
+
+        let _ = std::fs::remove_dir_all(
+            root_location(InMemory)
+                .unwrap()
+                .join("test_stats_record_saves"),
+        );
+        let veto = hooks::register_pre_save(|context| {
+            if context.root.ends_with("test_stats_record_saves") && context.files.len() > 2 {
+                ControlFlow::Break("too many files".into())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        let options = save::SaveOptions::default().stats(true);
+        (1,).save_with("test_stats_record_saves", InMemory, &options)
+            .unwrap();
+        assert!((1, 2)
+            .save_with("test_stats_record_saves", InMemory, &options)
+            .is_err());
+        hooks::unregister(veto);
+
+        let stats = stats::read("test_stats_record_saves", InMemory)
+            .unwrap()
+            .unwrap();
+        assert_eq!((stats.saves, stats.failures), (2, 1));
+        assert_eq!(
+            stats.last_error.as_deref(),
+            Some("A pre-save hook vetoed the save: too many files")
+        );
+        assert!(stats.last_save.is_some());
+
+        (2,).save_with("test_stats_record_saves", InMemory, &options)
+            .unwrap();
+        let stats = stats::read("test_stats_record_saves", InMemory)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (stats.saves, stats.failures, stats.last_error),
+            (3, 1, None)
+        );
+    }
+
+    #[test]
+    fn ensure_root_keeps_existing() {
+        use crate::save::{ensure_root, root_location, RootType, SaveOptions};
+
+        #[derive(Default, Serialize)]
+        struct Prepared {
+            pid: u32,
+            peers: Vec<u32>,
+            name: Option<String>,
+        }
+
+        let root = root_location(RootType::InMemory)
+            .unwrap()
+            .join("ensure-root");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("pid"), "42").unwrap();
+
+        let created =
+            ensure_root::<Prepared, _>("ensure-root", RootType::InMemory, &SaveOptions::default())
+                .unwrap();
+        assert!(!created.contains(&root.join("pid")));
+        assert!(created.contains(&root.join("peers")));
+        assert_file!("ensure-root/pid", "42");
+        assert!(root.join("peers").is_dir());
+
+        // Nothing left to create
+        let created =
+            ensure_root::<Prepared, _>("ensure-root", RootType::InMemory, &SaveOptions::default())
+                .unwrap();
+        assert!(created.is_empty());
+    }
+
+    #[test]
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    fn xdg_root_locations() {
+        use std::env::VarError;
+        use std::path::PathBuf;
+
+        use crate::save::{locate, RootType};
+
+        let package = env!("CARGO_PKG_NAME");
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+                    .ok_or(VarError::NotPresent)
+            }
+        };
+        let sandbox = env(&[
+            ("HOME", "/home/alex"),
+            ("TMPDIR", "/run/flatpak/tmp/"),
+            ("XDG_CACHE_HOME", "/var/cache/nix"),
+            ("XDG_DATA_HOME", "/data"),
+            ("XDG_CONFIG_HOME", "relative/config"),
+        ]);
+        let bare = env(&[("HOME", "/home/alex"), ("TMPDIR", "")]);
+
+        let at = |path: &str| PathBuf::from(path).join(package);
+        assert_eq!(locate(InMemory, sandbox).unwrap(), at("/run/flatpak/tmp"));
+        assert_eq!(locate(Persistent, sandbox).unwrap(), at("/var/cache/nix"));
+        assert_eq!(locate(RootType::Data, sandbox).unwrap(), at("/data"));
+        // Relative values are ignored
+        assert_eq!(
+            locate(RootType::Config, sandbox).unwrap(),
+            at("/home/alex/.config")
+        );
+        assert_eq!(
+            locate(RootType::Runtime, sandbox).unwrap(),
+            at("/run/flatpak/tmp")
+        );
+
+        assert_eq!(locate(InMemory, bare).unwrap(), at("/tmp"));
+        assert_eq!(locate(Persistent, bare).unwrap(), at("/home/alex/.cache"));
+        assert_eq!(
+            locate(RootType::Data, bare).unwrap(),
+            at("/home/alex/.local/share")
+        );
+    }
+
+    #[test]
+    fn save_fields_saves_subset() {
+        use crate::save::{save_fields_with, SaveError, SaveOptions};
+
+        #[derive(Serialize)]
+        struct Partial {
+            count: u8,
+            tags: Vec<u8>,
+            label: &'static str,
+        }
+
+        let mut partial = Partial {
+            count: 1,
+            tags: vec![1],
+            label: "old",
+        };
+        partial.save("save-fields", InMemory).unwrap();
+
+        partial.count = 2;
+        partial.tags = vec![2, 3];
+        partial.label = "new";
+        let options = SaveOptions::default();
+        save_fields_with(
+            &partial,
+            &["count", "tags"],
+            "save-fields",
+            InMemory,
+            &options,
+        )
+        .unwrap();
+
+        assert_file!("save-fields/count", "2");
+        assert_file!("save-fields/tags/1", "3");
+        assert_file!("save-fields/label", "old");
+
+        assert!(matches!(
+            save_fields_with(&partial, &["count", "missing"], "save-fields", InMemory, &options),
+            Err(SaveError::UnknownField { name }) if name == "missing"
+        ));
+        assert_file!("save-fields/count", "2");
+    }
+
+    #[test]
+    fn drop_cache_above_writes_large_leaves() {
+        use crate::save::SaveOptions;
+
+        let large = "x".repeat(64 * 1024);
+        let options = SaveOptions::default().drop_cache_above(1024);
+        (large.as_str(), "small")
+            .save_with("drop-cache", InMemory, &options)
+            .unwrap();
+        (large.as_str(), "smaller")
+            .save_with("drop-cache", InMemory, &options)
+            .unwrap();
+
+        assert_file!("drop-cache/0", large);
+        assert_file!("drop-cache/1", "smaller");
+
+        /// Records which kind of write each leaf used
+        #[derive(Debug, Default)]
+        struct Writes(std::sync::Mutex<Vec<&'static str>>);
+
+        impl backend::Backend for Writes {
+            fn write(&self, _: &std::path::Path, _: &[u8]) -> std::io::Result<()> {
+                self.0.lock().unwrap().push("write");
+                Ok(())
+            }
+            fn write_uncached(&self, _: &std::path::Path, _: &[u8]) -> std::io::Result<()> {
+                self.0.lock().unwrap().push("uncached");
+                Ok(())
+            }
+            fn write_locked(&self, _: &std::path::Path, _: &[u8]) -> std::io::Result<()> {
+                self.0.lock().unwrap().push("locked");
+                Ok(())
+            }
+            fn mkdir(&self, _: &std::path::Path) -> std::io::Result<()> {
+                Ok(())
+            }
+            fn remove(&self, _: &std::path::Path) -> std::io::Result<()> {
+                Ok(())
+            }
+            fn remove_dir(&self, _: &std::path::Path) -> std::io::Result<()> {
+                Ok(())
+            }
+            fn rename(&self, _: &std::path::Path, _: &std::path::Path) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // Locking takes precedence, so readers never see a partial large leaf
+        let writes = std::sync::Arc::new(Writes::default());
+        let options = options.lock_leaves(true).backend(writes.clone());
+        (large.as_str(), "small")
+            .save_with("drop-cache-locked", InMemory, &options)
+            .unwrap();
+        assert_eq!(*writes.0.lock().unwrap(), ["locked", "locked"]);
+    }
+
+    #[test]
+    fn init_validates_app_name() {
+        // Tests share the default name, so only setting it to the same value is safe
+        assert_eq!(init(env!("CARGO_PKG_NAME")), Ok(()));
+        assert_eq!(app_name(), env!("CARGO_PKG_NAME"));
+
+        assert!(
+            matches!(init("other"), Err(InitError::AlreadyInitialized { app_name }) if app_name == "binroots")
+        );
+        for invalid in ["", "..", "my/app"] {
+            assert!(matches!(init(invalid), Err(InitError::InvalidName { .. })));
+        }
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn atomic_writes_leave_no_temp_files() {
+        use crate::save::SaveOptions;
+
+        let options = SaveOptions::default().atomic(true);
+        (1, "one").save_with("atomic", InMemory, &options).unwrap();
+
+        // Symlinked leaves keep their link, with the new value in the target
+        let root = root_location(InMemory).unwrap().join("atomic");
+        let target = root_location(InMemory).unwrap().join("atomic-target");
+        std::fs::write(&target, "old").unwrap();
+        let _ = std::fs::remove_file(root.join("1"));
+        std::os::unix::fs::symlink(&target, root.join("1")).unwrap();
+
+        (2, "two").save_with("atomic", InMemory, &options).unwrap();
+
+        assert_file!("atomic/0", "2");
+        assert_file!("atomic-target", "two");
+        assert!(std::fs::symlink_metadata(root.join("1"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(!std::fs::read_dir(&root)
+            .unwrap()
+            .flatten()
+            .any(|entry| entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(backend::TEMP_EXTENSION)));
+
+        // Values named like a temporary file aren't clobbered
+        let notes = HashMap::from([("a", "first"), ("a.tmp", "second")]);
+        notes.save_with("atomic-notes", InMemory, &options).unwrap();
+        assert_file!("atomic-notes/a", "first");
+        assert_file!("atomic-notes/a.tmp", "second");
+    }
+
+    #[test]
+    fn elide_defaults_deletes_default_leaves() {
+        use crate::load::load_with;
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Sparse {
+            count: u8,
+            label: String,
+            enabled: bool,
+            ratio: f32,
+        }
+
+        let options = SaveOptions::default().elide_defaults(true);
+        let full = Sparse {
+            count: 1,
+            label: "one".into(),
+            enabled: true,
+            ratio: 0.5,
+        };
+        full.save_with("elide-defaults", InMemory, &options)
+            .unwrap();
+        assert_file!("elide-defaults/count", "1");
+
+        let sparse = Sparse {
+            count: 0,
+            label: String::new(),
+            enabled: true,
+            ratio: 0.0,
+        };
+        sparse
+            .save_with("elide-defaults", InMemory, &options)
+            .unwrap();
+
+        let root = root_location(InMemory).unwrap().join("elide-defaults");
+        for elided in ["count", "label", "ratio"] {
+            assert!(!root.join(elided).exists(), "{elided} wasn't elided");
+        }
+        assert_file!("elide-defaults/enabled", "true");
+        assert_eq!(
+            load_with::<Sparse, _>("elide-defaults", InMemory, &options).unwrap(),
+            sparse
+        );
+
+        // Only the chosen fields are elided
+        let options = SaveOptions::default().elide_field("label");
+        sparse
+            .save_with("elide-defaults", InMemory, &options)
+            .unwrap();
+        assert_file!("elide-defaults/count", "0");
+        assert!(!root.join("label").exists());
+    }
+
+    #[test]
+    fn elide_defaults_keeps_elements_and_values() {
+        use crate::load::load_with;
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Counts {
+            list: Vec<u8>,
+            map: HashMap<String, u8>,
+        }
+
+        let options = SaveOptions::default().elide_defaults(true);
+        let counts = Counts {
+            list: vec![0, 5, 0],
+            map: HashMap::from([("a".into(), 0), ("b".into(), 2)]),
+        };
+        counts
+            .save_with("elide-elements", InMemory, &options)
+            .unwrap();
+
+        assert_file!("elide-elements/list/0", "0");
+        assert_file!("elide-elements/map/a", "0");
+        assert_eq!(
+            load_with::<Counts, _>("elide-elements", InMemory, &options).unwrap(),
+            counts
+        );
+    }
+
+    #[test]
+    fn save_transactional_swaps_root() {
+        use crate::save::SaveOptions;
+
+        let options = SaveOptions::default().generation(true);
+        (1, 2, 3)
+            .save_with("transactional-swap", InMemory, &options)
+            .unwrap();
+        (4, 5)
+            .save_transactional_with("transactional-swap", InMemory, &options)
+            .unwrap();
+
+        let location = root_location(InMemory).unwrap();
+        let root = location.join("transactional-swap");
+        assert_file!("transactional-swap/0", "4");
+        assert!(!root.join("2").exists());
+        assert!(!location.join("transactional-swap.staging").exists());
+        assert!(!location.join("transactional-swap.old").exists());
+        // Hidden files carry over, so the generation keeps counting
+        assert!(crate::save::generation("transactional-swap", InMemory).unwrap() >= 2);
+    }
+
+    #[test]
+    fn fsync_syncs_leaves_and_parents() {
+        use std::path::{Path, PathBuf};
+        use std::sync::{Arc, Mutex};
+
+        use crate::backend::{Backend, Filesystem};
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, Default)]
+        struct Syncs(Mutex<Vec<PathBuf>>);
+
+        impl Backend for Syncs {
+            fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+                Filesystem.write(path, contents)
+            }
+            fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.mkdir(path)
+            }
+            fn remove(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove(path)
+            }
+            fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove_dir(path)
+            }
+            fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+                Filesystem.rename(from, to)
+            }
+            fn sync(&self, path: &Path) -> std::io::Result<()> {
+                self.0.lock().unwrap().push(path.into());
+                Filesystem.sync(path)
+            }
+        }
+
+        let syncs = Arc::new(Syncs::default());
+        let options = SaveOptions::default().fsync(true).backend(syncs.clone());
+        (1, vec![2]).save_with("fsync", InMemory, &options).unwrap();
+
+        let root = root_location(InMemory).unwrap().join("fsync");
+        let synced = syncs.0.lock().unwrap();
+        for path in [
+            root.join("0"),
+            root.join("1/0"),
+            root.clone(),
+            root.join("1"),
+        ] {
+            assert!(synced.contains(&path), "{path:?} wasn't synced");
+        }
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn read_only_root_policy() {
+        use std::sync::Arc;
+
+        use crate::save::{ReadOnlyPolicy, SaveError, SaveOptions};
+
+        let faults = Arc::new(
+            backend::FaultInjector::new(backend::Filesystem)
+                .fail_path("read-only-root/0")
+                .error_kind(std::io::ErrorKind::ReadOnlyFilesystem),
+        );
+        let options = SaveOptions::default().backend(faults);
+
+        assert!(matches!(
+            (1, 2).save_with("read-only-root", InMemory, &options),
+            Err(SaveError::ReadOnlyRoot { .. })
+        ));
+        (1, 2)
+            .save_with(
+                "read-only-root",
+                InMemory,
+                &options.read_only(ReadOnlyPolicy::Skip),
+            )
+            .unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn locked_read_waits_for_writer() {
+        use std::os::unix::io::AsRawFd;
+
+        use crate::save::{root_location, RootType, SaveOptions};
+
+        let options = SaveOptions::default().lock_leaves(true);
+        (1, "written")
+            .save_with("test-locked-read", RootType::InMemory, &options)
+            .unwrap();
+        assert_file!("test-locked-read/1", "written");
+
+        let path = root_location(RootType::InMemory)
+            .unwrap()
+            .join("test-locked-read/1");
+        let writer = std::fs::File::open(&path).unwrap();
+        assert_eq!(unsafe { libc::flock(writer.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+        let reader = std::thread::spawn(move || crate::read::locked_read(path).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!reader.is_finished());
+
+        drop(writer);
+        assert_eq!(reader.join().unwrap(), b"written");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn permissions_apply_before_contents() {
+        use backend::{Backend, Filesystem};
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::Path;
+        use std::sync::{Arc, Mutex};
+
+        /// Records the mode every file had when its contents were written
+        #[derive(Debug, Default)]
+        struct Modes(Mutex<Vec<u32>>);
+
+        impl Backend for Modes {
+            fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+                let mode = std::fs::metadata(path).map_or(0, |m| m.permissions().mode() & 0o777);
+                self.0.lock().unwrap().push(mode);
+                Filesystem.write(path, contents)
+            }
+            fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.mkdir(path)
+            }
+            fn remove(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove(path)
+            }
+            fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+                Filesystem.remove_dir(path)
+            }
+            fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+                Filesystem.rename(from, to)
+            }
+            fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+                Filesystem.set_mode(path, mode)
+            }
+            fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+                Filesystem.create_with_mode(path, mode)
+            }
+        }
+
+        for atomic in [false, true] {
+            let _ = std::fs::remove_dir_all(
+                root_location(InMemory)
+                    .unwrap()
+                    .join("test_permissions_apply_before_contents"),
+            );
+            let modes = Arc::new(Modes::default());
+            let options = save::SaveOptions::default()
+                .atomic(atomic)
+                .permissions(0o600)
+                .backend(modes.clone());
+            ("secret", 1)
+                .save_with("test_permissions_apply_before_contents", InMemory, &options)
+                .unwrap();
+
+            let modes = modes.0.lock().unwrap();
+            assert_eq!(modes.len(), 2);
+            assert!(modes.iter().all(|&mode| mode == 0o600), "{modes:?}");
+        }
+    }
+
+    #[test]
+    fn trailing_newline_and_permissions() {
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Status {
+            name: String,
+            count: u32,
+            #[serde(with = "serde_bytes")]
+            raw: Vec<u8>,
+        }
+
+        let status = Status {
+            name: "binroots".into(),
+            count: 3,
+            raw: vec![1, 2],
+        };
+        let options = SaveOptions::default()
+            .trailing_newline(true)
+            .permissions(0o600);
+        status
+            .save_with("test-newlines", InMemory, &options)
+            .unwrap();
+
+        assert_file!("test-newlines/name", "binroots\n");
+        assert_file!("test-newlines/count", "3\n");
+        assert_eq!(
+            std::fs::read(root_location(InMemory).unwrap().join("test-newlines/raw")).unwrap(),
+            [1, 2]
+        );
+        assert_eq!(
+            crate::load::load_with::<Status, _>("test-newlines", InMemory, &options).unwrap(),
+            status
+        );
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = root_location(InMemory).unwrap().join("test-newlines/count");
+            assert_eq!(
+                std::fs::metadata(path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+    }
+
+    #[test]
+    fn redacted_fields_are_masked() {
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Account {
+            name: String,
+            password: String,
+            pin: u32,
+        }
+
+        let account = Account {
+            name: "carter".into(),
+            password: "hunter2".into(),
+            pin: 0,
+        };
+        let options = SaveOptions::default()
+            .redact_field("password")
+            .redact_field("pin")
+            .elide_defaults(true);
+        account
+            .save_with("test-redacted", InMemory, &options)
+            .unwrap();
+
+        assert_file!("test-redacted/name", "carter");
+        assert_file!("test-redacted/password", "***");
+        assert_file!("test-redacted/pin", "***");
+        assert_eq!(account.password, "hunter2");
+        assert_eq!(
+            crate::load::load_with::<Account, _>("test-redacted", InMemory, &options).unwrap(),
+            Account {
+                name: "carter".into(),
+                password: String::new(),
+                pin: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn redacted_maps_keep_their_keys_private() {
+        use std::collections::BTreeMap;
+
+        use crate::fileserializer::{FieldFormat, SerializerError};
+        use crate::save::{SaveError, SaveOptions};
+
+        #[derive(Serialize)]
+        struct Keys {
+            keys: BTreeMap<String, String>,
+        }
+
+        let keys = Keys {
+            keys: [("sk-live-abc".to_string(), "prod".to_string())].into(),
+        };
+        let _ =
+            std::fs::remove_dir_all(root_location(InMemory).unwrap().join("test-redacted-maps"));
+
+        let options = SaveOptions::default().redact_field("keys");
+        assert!(matches!(
+            keys.save_with("test-redacted-maps", InMemory, &options),
+            Err(SaveError::SerializeError(
+                SerializerError::RedactedMapKey { .. }
+            ))
+        ));
+
+        let options = options.field_format("keys", FieldFormat::Json);
+        keys.save_with("test-redacted-maps", InMemory, &options)
+            .unwrap();
+        assert_file!("test-redacted-maps/keys.json", "***");
+
+        let tree = crate::snapshot::Snapshot::read_dir(
+            root_location(InMemory).unwrap().join("test-redacted-maps"),
+        )
+        .unwrap();
+        assert!(tree
+            .entries
+            .iter()
+            .all(|entry| !entry.path.contains("sk-live")
+                && !String::from_utf8_lossy(&entry.contents).contains("sk-live")));
+    }
+
+    #[test]
+    fn mirror_removes_stale_entries() {
+        use crate::save::SaveOptions;
+
+        #[derive(Serialize)]
+        struct Peers {
+            peers: HashMap<u32, u32>,
+        }
+
+        let options = SaveOptions::default().mirror(true);
+        let mut peers = Peers {
+            peers: HashMap::from([(1, 10), (2, 20)]),
+        };
+        peers.save_with("test-mirror", InMemory, &options).unwrap();
+        assert_file!("test-mirror/peers/2", "20");
+
+        let root = root_location(InMemory).unwrap().join("test-mirror");
+        std::fs::write(root.join(".hidden"), "kept").unwrap();
+        std::fs::create_dir_all(root.join("removed/nested")).unwrap();
+
+        peers.peers.remove(&2);
+        peers.save_with("test-mirror", InMemory, &options).unwrap();
+
+        assert_file!("test-mirror/peers/1", "10");
+        assert!(!root.join("peers/2").exists());
+        assert!(!root.join("removed").exists());
+        assert_file!("test-mirror/.hidden", "kept");
+    }
+
+    #[test]
+    fn mirror_keeps_unselected_fields() {
+        use crate::save::{save_fields_with, save_projected_with, SaveOptions};
+
+        #[derive(Serialize)]
+        struct Fields {
+            a: HashMap<u32, u32>,
+            b: HashMap<u32, u32>,
+        }
+
+        let options = SaveOptions::default().mirror(true);
+        let mut fields = Fields {
+            a: HashMap::from([(1, 10), (2, 20)]),
+            b: HashMap::from([(3, 30), (4, 40)]),
+        };
+        fields
+            .save_with("test-mirror-partial", InMemory, &options)
+            .unwrap();
+
+        fields.a.remove(&2);
+        fields.b.remove(&4);
+        save_fields_with(&fields, &["a"], "test-mirror-partial", InMemory, &options).unwrap();
+
+        let root = root_location(InMemory).unwrap().join("test-mirror-partial");
+        assert!(!root.join("a/2").exists());
+        assert_file!("test-mirror-partial/b/3", "30");
+        assert_file!("test-mirror-partial/b/4", "40");
+
+        fields.a.remove(&1);
+        save_projected_with(&fields, "a", "test-mirror-partial", InMemory, &options).unwrap();
+        assert!(!root.join("a/1").exists());
+        assert_file!("test-mirror-partial/b/4", "40");
+    }
+
+    #[test]
+    fn shrinking_vec_removes_trailing_elements() {
+        use crate::save::SaveOptions;
+
+        let options = SaveOptions::default().len_files(true);
+        (vec![1, 2, 3], 0)
+            .save_with("test-shrink", InMemory, &options)
+            .unwrap();
+        assert_file!("test-shrink/0/2", "3");
+        assert_file!("test-shrink/0/.len", "3");
+
+        (vec![1], 0)
+            .save_with("test-shrink", InMemory, &options)
+            .unwrap();
+        let root = root_location(InMemory).unwrap().join("test-shrink");
+        assert_file!("test-shrink/0/0", "1");
+        assert!(!root.join("0/1").exists());
+        assert!(!root.join("0/2").exists());
+        assert_file!("test-shrink/0/.len", "1");
+
+        let watcher_friendly = options.watcher_friendly(true);
+        (Vec::<u32>::new(), 0)
+            .save_with("test-shrink", InMemory, &watcher_friendly)
+            .unwrap();
+        assert!(!root.join("0/0").exists());
+        assert_file!("test-shrink/0/.len", "0");
+    }
+
+    #[test]
+    fn padded_sequence_indices() {
+        use crate::save::SaveOptions;
+
+        let options = SaveOptions::default().index_width(3);
+        let values = ((0..12).collect::<Vec<u32>>(), vec![vec![1]]);
+        values.save_with("test-padded", InMemory, &options).unwrap();
+
+        assert_file!("test-padded/000/000", "0");
+        assert_file!("test-padded/000/011", "11");
+        assert_file!("test-padded/001/000/000", "1");
+        assert_eq!(
+            crate::load::load_with::<(Vec<u32>, Vec<Vec<u32>>), _>(
+                "test-padded",
+                InMemory,
+                &options
+            )
+            .unwrap(),
+            values
+        );
+    }
+
+    #[test]
+    fn map_keys_are_escaped() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Peers {
+            peers: HashMap<String, u32>,
+        }
+
+        let peers = Peers {
+            peers: HashMap::from([
+                ("eu/west".to_string(), 1),
+                ("..".to_string(), 2),
+                ("50%".to_string(), 3),
+                (".etag".to_string(), 4),
+                ("notes.raw".to_string(), 5),
+                ("a:b".to_string(), 6),
+                ("CON".to_string(), 7),
+                ("end.".to_string(), 8),
+            ]),
+        };
+        peers.save("test-escaped-keys", InMemory).unwrap();
+
+        assert_file!("test-escaped-keys/peers/eu%2Fwest", "1");
+        assert_file!("test-escaped-keys/peers/%2E%2E", "2");
+        assert_file!("test-escaped-keys/peers/50%25", "3");
+        assert_file!("test-escaped-keys/peers/%2Eetag", "4");
+        assert_file!("test-escaped-keys/peers/notes%2Eraw", "5");
+        assert_file!("test-escaped-keys/peers/a%3Ab", "6");
+        assert_file!("test-escaped-keys/peers/%43ON", "7");
+        assert_file!("test-escaped-keys/peers/end%2E", "8");
+        assert_eq!(
+            crate::load::load::<Peers, _>("test-escaped-keys", InMemory).unwrap(),
+            peers
+        );
+    }
+
+    #[test]
+    fn memory_budget_spills_contents() {
+        use crate::fileserializer::{FileSerializer, SerializeOptions};
+        use crate::save::SaveOptions;
+
+        let lines = (0..8).map(|i| format!("line {i}")).collect::<Vec<_>>();
+
+        let mut serializer =
+            FileSerializer::with_options("", SerializeOptions::default().memory_budget(16));
+        lines.serialize(&mut serializer).unwrap();
+        let spilled = |serializer: &FileSerializer| {
+            serializer
+                .output()
+                .iter()
+                .filter_map(|file| {
+                    file.spilled
+                        .as_ref()
+                        .map(|spilled| spilled.path().to_path_buf())
+                })
+                .collect::<Vec<_>>()
+        };
+        let files = spilled(&serializer);
+        assert!(!files.is_empty());
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&files[0]).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        for file in serializer
+            .output
+            .iter_mut()
+            .filter(|file| file.spilled.is_some())
+        {
+            assert!(file.contents.is_empty());
+            file.unspill().unwrap();
+            assert!(file.contents.starts_with(b"line "));
+        }
+        assert!(files.iter().all(|file| !file.exists()));
+
+        // Spill files of output that's never saved are removed too
+        serializer.reset();
+        lines.serialize(&mut serializer).unwrap();
+        let files = spilled(&serializer);
+        assert!(!files.is_empty());
+        serializer.reset();
+        assert!(files.iter().all(|file| !file.exists()));
+
+        // Clones share the spill file, which outlives the entry it was cloned from
+        lines.serialize(&mut serializer).unwrap();
+        let original = serializer.output()[1].clone();
+        let mut clone = original.clone();
+        serializer.reset();
+        drop(original);
+        let file = clone.spilled.as_ref().unwrap().path().to_path_buf();
+        assert!(file.exists());
+        clone.unspill().unwrap();
+        assert_eq!(clone.contents, b"line 0");
+        assert!(!file.exists());
+
+        let options = SaveOptions::default().memory_budget(16);
+        (lines.clone(), 0)
+            .save_with("test-memory-budget", InMemory, &options)
+            .unwrap();
+        assert_file!("test-memory-budget/0/0", "line 0");
+        assert_file!("test-memory-budget/0/7", "line 7");
+    }
+
+    #[test]
+    #[cfg(feature = "stable")]
+    fn save_field_named_by_marker() {
+        struct Count;
+
+        impl field::FieldName for Count {
+            const NAME: &'static str = "count";
+        }
+
+        let field = field::BinrootsField::<Count, u8>::new(4);
+        assert_eq!(field::BinrootsField::<Count, u8>::name(), "count");
+
+        field
+            .save("test_save_field_named_by_marker", InMemory)
+            .unwrap();
+        assert_file!("test_save_field_named_by_marker/count", "4");
+        assert!(field.last_saved().is_some());
+    }
+
+    #[test]
+    fn root_config_overrides_options() {
+        use crate::config::{RootConfig, CONFIG_FILE};
+        use crate::save::SaveOptions;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Activity {
+            Idle,
+            Playing(String),
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Status {
+            count: u32,
+            activity: Activity,
+        }
+
+        assert_eq!(
+            RootConfig::parse("# Comment\ntrailing_newline = yes\nvalue_extension = ../x\njson_mirror=false\ncolor = red"),
+            RootConfig {
+                trailing_newline: None,
+                value_extension: None,
+                json_mirror: Some(false),
+            }
+        );
+
+        let root = root_location(InMemory).unwrap().join("test-root-config");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join(CONFIG_FILE),
+            "trailing_newline = true\nvalue_extension = payload\n",
+        )
+        .unwrap();
+
+        let status = Status {
+            count: 3,
+            activity: Activity::Playing("chess".into()),
+        };
+        let options = SaveOptions::default().root_config(true);
+        status
+            .save_with("test-root-config", InMemory, &options)
+            .unwrap();
+
+        assert_file!("test-root-config/count", "3\n");
+        assert_file!("test-root-config/activity", "Playing");
+        assert_file!("test-root-config/activity.payload", "chess\n");
+        assert_eq!(
+            crate::load::load_with::<Status, _>("test-root-config", InMemory, &options).unwrap(),
+            status
+        );
+
+        // Ignored unless enabled
+        status.save("test-root-config", InMemory).unwrap();
+        assert_file!("test-root-config/count", "3");
+    }
+
+    #[test]
+    fn serializer_errors_instead_of_panicking() {
+        use crate::fileserializer::{FileSerializer, SerializeOptions, SerializerError};
+
+        struct Explodes;
+
+        impl Serialize for Explodes {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                panic!("kaboom")
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            healthy: bool,
+            broken: Explodes,
+        }
+
+        let result = Status {
+            healthy: true,
+            broken: Explodes,
+        }
+        .save("test-serializer-panics", InMemory);
+        assert!(matches!(
+            result,
+            Err(save::SaveError::SerializeError(SerializerError::Panicked { message })) if message == "kaboom"
+        ));
+
+        let mut serializer = FileSerializer::with_options(
+            "",
+            SerializeOptions {
+                check_map_keys: true,
+                ..Default::default()
+            },
+        );
+        let map = HashMap::from([(vec![1u8], 2u8)]);
+        assert!(matches!(
+            serializer.serialize_value(&map),
+            Err(SerializerError::InvalidMapKey { .. })
+        ));
+    }
+
+    #[test]
+    fn memory_backend_keeps_tree_in_memory() {
+        use std::path::PathBuf;
+        use std::sync::Arc;
+
+        use crate::backend::Memory;
+        use crate::save::SaveOptions;
+
+        #[derive(Serialize)]
+        enum Activity {
+            Idle,
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            nickname: Option<String>,
+            activity: Activity,
+        }
+
+        let memory = Arc::new(Memory::new());
+        let options = SaveOptions::default().backend(memory.clone());
+        let root = root_location(InMemory).unwrap().join("test-memory-backend");
+
+        Status {
+            nickname: Some("alex".into()),
+            activity: Activity::Playing("chess".into()),
+        }
+        .save_with("test-memory-backend", InMemory, &options)
+        .unwrap();
+
+        memory.assert_file(root.join("nickname"), "alex");
+        memory.assert_file(root.join("activity.value"), "chess");
+        assert!(!root.join("nickname").exists());
+
+        Status {
+            nickname: None,
+            activity: Activity::Idle,
+        }
+        .save_with("test-memory-backend", InMemory, &options)
+        .unwrap();
+
+        memory.assert_missing(root.join("nickname"));
+        memory.assert_missing(root.join("activity.value"));
+        assert_eq!(
+            memory.tree(&root).into_keys().collect::<Vec<_>>(),
+            [PathBuf::from("activity")]
+        );
+    }
+
+    #[test]
+    fn memory_backend_reads_its_own_tree() {
+        use std::path::PathBuf;
+        use std::sync::Arc;
+
+        use crate::backend::Memory;
+        use crate::save::{Backend, SaveOptions, ETAG_FILE};
+
+        let memory = Arc::new(Memory::new());
+        let root = root_location(InMemory).unwrap().join("test-memory-reads");
+        let keys = |memory: &Memory| memory.tree(&root).into_keys().collect::<Vec<_>>();
+
+        let options = SaveOptions::default()
+            .backend(memory.clone())
+            .len_files(true)
+            .etag(true);
+        (vec![1, 2, 3], 0)
+            .save_with("test-memory-reads", InMemory, &options)
+            .unwrap();
+        let etag = memory.read_to_string(root.join(ETAG_FILE)).unwrap();
+
+        (vec![1], 0)
+            .save_with("test-memory-reads", InMemory, &options)
+            .unwrap();
+        memory.assert_missing(root.join("0/1"));
+        memory.assert_missing(root.join("0/2"));
+        memory.assert_file(root.join("0/.len"), "1");
+        assert_ne!(memory.read_to_string(root.join(ETAG_FILE)).unwrap(), etag);
+
+        memory.write(&root.join("stale"), b"x").unwrap();
+        let mirrored = options.clone().mirror(true).watcher_friendly(true);
+        (Vec::<u8>::new(), 0)
+            .save_with("test-memory-reads", InMemory, &mirrored)
+            .unwrap();
+        assert_eq!(
+            keys(&memory),
+            [
+                PathBuf::from(".etag"),
+                PathBuf::from("0/.len"),
+                PathBuf::from("1")
+            ]
+        );
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn save_projected_parts() {
+        use crate::save::{save_at_with, save_projected_with, SaveError, SaveOptions};
+
+        #[derive(Serialize)]
+        struct Game {
+            scores: Vec<u32>,
+            title: String,
+        }
+
+        let mut game = Game {
+            scores: vec![1, 2, 3],
+            title: "chess".into(),
+        };
+        game.save("test-save-projected", InMemory).unwrap();
+
+        game.scores[1] = 20;
+        game.title = "go".into();
+        let options = SaveOptions::default();
+        save_projected_with(&game, "scores/1", "test-save-projected", InMemory, &options).unwrap();
+
+        assert_file!("test-save-projected/scores/1", "20");
+        assert_file!("test-save-projected/title", "chess");
+        assert!(matches!(
+            save_projected_with(&game, "scores/7", "test-save-projected", InMemory, &options),
+            Err(SaveError::UnknownField { name }) if name == "scores/7"
+        ));
+
+        save_at_with(
+            &game.title,
+            "title",
+            "test-save-projected",
+            InMemory,
+            &options,
+        )
+        .unwrap();
+        assert_file!("test-save-projected/title", "go");
+    }
+
+    #[test]
+    fn plan_lists_operations_without_saving() {
+        use std::path::PathBuf;
+
+        use crate::plan::{Op, PlannedOp};
+
+        #[derive(Serialize)]
+        enum Activity {
+            Playing(String),
+        }
+
+        #[derive(Serialize)]
+        struct Status {
+            activity: Activity,
+            scores: Vec<u8>,
+        }
+
+        let ops = crate::plan(
+            &Status {
+                activity: Activity::Playing("chess".into()),
+                scores: vec![7],
+            },
+            "test-plan",
+        )
+        .unwrap();
+        let op = |path: &str, contents: &[u8], op| PlannedOp {
+            path: PathBuf::from(path),
+            contents: contents.to_vec(),
+            op,
+        };
+
+        assert_eq!(
+            ops,
+            [
+                op("test-plan", b"", Op::CreateDir),
+                op("test-plan/activity.value", b"", Op::Delete),
+                op("test-plan/activity.value", b"chess", Op::Write),
+                op("test-plan/activity", b"Playing", Op::Write),
+                op("test-plan/scores", b"", Op::CreateDir),
+                op("test-plan/scores", b"", Op::Truncate(1)),
+                op("test-plan/scores/0", b"7", Op::Write),
+            ]
+        );
+        assert!(!root_location(InMemory).unwrap().join("test-plan").exists());
+    }
+
+    /// The id of a process that already exited, for files left behind by a crashed save
+    #[cfg(unix)]
+    fn exited_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recover_interrupted_saves() {
+        let location = root_location(InMemory).unwrap();
+        let live = location.join("recover-interrupted");
+        let staging = location.join("recover-interrupted.staging");
+        let old = location.join("recover-interrupted.old");
+        let _ = std::fs::remove_dir_all(&live);
+        let pid = exited_pid();
+        let temp = format!("nested/.{pid}.1.binroots-tmp");
+        std::fs::create_dir_all(staging.join("nested")).unwrap();
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::write(staging.join("0"), "new").unwrap();
+        std::fs::write(staging.join(&temp), "half").unwrap();
+        std::fs::write(old.join("0"), "old").unwrap();
+        std::fs::write(staging.join(recovery::SWAP_MARKER), pid.to_string()).unwrap();
+        std::fs::write(old.join(recovery::SWAP_MARKER), pid.to_string()).unwrap();
+
+        let recovery = recovery::recover("recover-interrupted", InMemory).unwrap();
+
+        assert_eq!(recovery.restored, Some(staging.clone()));
+        assert_eq!(recovery.removed, vec![old.clone(), live.join(&temp)]);
+        assert_file!("recover-interrupted/0", "new");
+        assert!(!live.join(recovery::SWAP_MARKER).exists());
+        assert!(!staging.exists() && !old.exists());
+        assert!(!live.join(&temp).exists());
+        assert!(recovery::recover("recover-interrupted", InMemory)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recover_keeps_unmarked_files() {
+        let location = root_location(InMemory).unwrap();
+        let live = location.join("recover-unmarked");
+        let other = location.join("recover-unmarked.old");
+        let _ = std::fs::remove_dir_all(&live);
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(other.join("0"), "another root").unwrap();
+
+        HashMap::from([("notes.tmp", "kept")])
+            .save_with(
+                "recover-unmarked",
+                InMemory,
+                &save::SaveOptions::default().atomic(true),
+            )
+            .unwrap();
+        // An atomic write in progress in another process, here pid 1
+        let in_flight = live.join(".notes.1.0.binroots-tmp");
+        std::fs::write(&in_flight, "half").unwrap();
+
+        assert!(recovery::recover("recover-unmarked", InMemory)
+            .unwrap()
+            .is_empty());
+        assert_file!("recover-unmarked/notes.tmp", "kept");
+        assert_file!("recover-unmarked.old/0", "another root");
+        assert!(in_flight.exists());
+
+        // Neither are names without a process id, or of this process, which may still be saving on another thread
+        let kept = [
+            live.join(".notes.0.binroots-tmp"),
+            live.join(".notes.0.0.binroots-tmp"),
+            live.join(format!(".notes.{}.0.binroots-tmp", std::process::id())),
+        ];
+        let staging = location.join("recover-unmarked.staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join(recovery::SWAP_MARKER), "not a pid").unwrap();
+        recovery::mark(&other).unwrap();
+        for kept in &kept {
+            std::fs::write(kept, "half").unwrap();
+        }
+        assert!(recovery::recover("recover-unmarked", InMemory)
+            .unwrap()
+            .is_empty());
+        assert!(kept.iter().all(|kept| kept.exists()));
+        assert!(staging.exists());
+        for kept in &kept {
+            std::fs::remove_file(kept).unwrap();
+        }
+        std::fs::remove_dir_all(&staging).unwrap();
+        recovery::unmark(&other).unwrap();
+
+        let abandoned = live.join(format!(".notes.{}.0.binroots-tmp", exited_pid()));
+        std::fs::write(&abandoned, "half").unwrap();
+        assert_eq!(
+            recovery::recover("recover-unmarked", InMemory)
+                .unwrap()
+                .removed,
+            vec![abandoned]
+        );
+
+        // Roots saved without atomic writes aren't recovered automatically
+        let plain = location.join("recover-unmarked-plain");
+        std::fs::create_dir_all(&plain).unwrap();
+        std::fs::write(plain.join(".0.binroots-tmp"), "half").unwrap();
+        (1u8,).save("recover-unmarked-plain", InMemory).unwrap();
+        assert!(plain.join(".0.binroots-tmp").exists());
+    }
+
+    #[test]
+    fn deterministic_clock_and_ids() {
+        use clock::{FixedClock, SequentialIds};
+        use save::SaveOptions;
+        use std::sync::Arc;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        #[derive(Serialize)]
+        struct Status {
+            online: bool,
+            users: Vec<String>,
+        }
+
+        let clock = FixedClock(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let temp = root_location(InMemory).unwrap().join("deterministic-temp");
+        std::fs::create_dir_all(&temp).unwrap();
+        let status = Status {
+            online: true,
+            users: vec!["carter".into()],
+        };
+
+        let blobs = ["deterministic-a", "deterministic-b"].map(|root| {
+            let _ = std::fs::remove_dir_all(root_location(InMemory).unwrap().join(root));
+            let options = SaveOptions::default()
+                .clock(Arc::new(clock))
+                .ids(Arc::new(SequentialIds::new()))
+                .metadata(true)
+                .stats(true)
+                .mtime_sidecars(true)
+                .atomic(true)
+                .temp_dir(&temp);
+            status.save_with(root, InMemory, &options).unwrap();
+            snapshot::to_blob_with(root, InMemory, &clock).unwrap()
+        });
+
+        assert_eq!(blobs[0], blobs[1]);
+        assert_file!("deterministic-a/online.mtime", "1700000000");
+        assert!(std::fs::read_to_string(
+            root_location(InMemory)
+                .unwrap()
+                .join("deterministic-a/.stats")
+        )
+        .unwrap()
+        .contains("last_save=1700000000"));
+        assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn render_saved_and_unsaved_trees() {
+        #[derive(Serialize)]
+        struct Player {
+            name: String,
+            scores: Vec<u32>,
+        }
+
+        #[derive(Serialize)]
+        struct Game {
+            player: Player,
+            paused: bool,
+            title: Option<String>,
+        }
+
+        let game = Game {
+            player: Player {
+                name: "carter".into(),
+                scores: (0..11).collect(),
+            },
+            paused: false,
+            title: None,
+        };
+
+        let expected = "render-game
+├── paused
+└── player
+    ├── name
+    └── scores
+        ├── 0
+        ├── 1
+        ├── 2
+        ├── 3
+        ├── 4
+        ├── 5
+        ├── 6
+        ├── 7
+        ├── 8
+        ├── 9
+        └── 10
+";
+        assert_eq!(
+            save::render_value_tree(&game, "render-game").unwrap(),
+            expected
+        );
+
+        let _ = std::fs::remove_dir_all(root_location(InMemory).unwrap().join("render-game"));
+        game.save_with(
+            "render-game",
+            InMemory,
+            &save::SaveOptions::default().etag(true),
+        )
+        .unwrap();
+        assert_eq!(
+            save::render_tree("render-game", InMemory).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn save_and_load_field_formats() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Theme {
+            name: String,
+            colors: HashMap<String, String>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Settings {
+            volume: u8,
+            theme: Theme,
+        }
+
+        let settings = Settings {
+            volume: 7,
+            theme: Theme {
+                name: "dusk".into(),
+                colors: HashMap::from([("accent".into(), "#ff8800".into())]),
+            },
+        };
+        let options =
+            save::SaveOptions::default().field_format("theme", fileserializer::FieldFormat::Json);
+
+        let _ = std::fs::remove_dir_all(root_location(InMemory).unwrap().join("field-formats"));
+        settings
+            .save_with("field-formats", InMemory, &options)
+            .unwrap();
+
+        assert_file!("field-formats/volume", "7");
+        assert!(!root_location(InMemory)
+            .unwrap()
+            .join("field-formats/theme")
+            .exists());
+        assert_file!(
+            "field-formats/theme.json",
+            "{\n  \"name\": \"dusk\",\n  \"colors\": {\n    \"accent\": \"#ff8800\"\n  }\n}"
+        );
+        assert_eq!(
+            load::load_with::<Settings, _>("field-formats", InMemory, &options).unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn save_and_load_max_depth() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Node {
+            name: String,
+            children: Vec<Node>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Document {
+            title: String,
+            tree: Node,
+            tags: HashMap<String, Vec<u8>>,
+        }
+
+        let leaf = |name: &str| Node {
+            name: name.into(),
+            children: Vec::new(),
+        };
+        let document = Document {
+            title: "notes".into(),
+            tree: Node {
+                name: "root".into(),
+                children: vec![Node {
+                    name: "a".into(),
+                    children: vec![leaf("b")],
+                }],
+            },
+            tags: HashMap::from([("todo".into(), vec![1, 2])]),
+        };
+        let options = save::SaveOptions::default().max_depth(2);
+
+        let _ = std::fs::remove_dir_all(root_location(InMemory).unwrap().join("max-depth"));
+        document.save_with("max-depth", InMemory, &options).unwrap();
+
+        assert_file!("max-depth/title", "notes");
+        assert_file!("max-depth/tree/name", "root");
+        assert_file!(
+            "max-depth/tree/children.json",
+            serde_json::to_string_pretty(&document.tree.children).unwrap()
+        );
+        assert_file!("max-depth/tags/todo.json", "[\n  1,\n  2\n]");
+        assert!(!root_location(InMemory)
+            .unwrap()
+            .join("max-depth/tree/children")
+            .exists());
+        assert_eq!(
+            load::load_with::<Document, _>("max-depth", InMemory, &options).unwrap(),
+            document
+        );
+    }
+
+    #[test]
+    fn max_depth_only_collapses_at_the_limit() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Optional {
+            keys: HashMap<Option<u8>, u8>,
+        }
+
+        let options = save::SaveOptions::default().max_depth(2);
+        let names = HashMap::from([("x.json".to_string(), 1), ("y".to_string(), 2)]);
+        names
+            .save_with("max-depth-names", InMemory, &options)
+            .unwrap();
+        assert_eq!(
+            load::load_with::<HashMap<String, u8>, _>("max-depth-names", InMemory, &options)
+                .unwrap(),
+            names
+        );
+
+        // JSON has no optional keys, so the map is saved as folders
+        let optional = Optional {
+            keys: HashMap::from([(Some(7), 1)]),
+        };
+        optional
+            .save_with("max-depth-optional", InMemory, &options.max_depth(1))
+            .unwrap();
+        assert_file!("max-depth-optional/keys/7", "1");
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn field_standard_traits() {
+        use std::collections::HashSet;
+
+        let field: field::BinrootsField<"count", u8> = 4.into();
+        field.mark_saved();
+        let clone = field.clone();
+
+        assert_eq!(clone, field);
+        assert_eq!(clone.last_saved(), field.last_saved());
+        assert!(field::BinrootsField::<"count", u8>::new(3) < field);
+        assert_eq!(
+            HashSet::from([field, clone, field::BinrootsField::new(5)]).len(),
+            2
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn field_dirty_tracking() {
+        use std::cell::RefCell;
+
+        let mut count = field::BinrootsField::<"count", u8>::new(1);
+        assert!(count.is_dirty());
+
+        count.save("test_field_dirty_tracking", InMemory).unwrap();
+        assert!(!count.is_dirty());
+        assert!(!count.clone().is_dirty());
+
+        *count += 1;
+        assert!(count.is_dirty());
+
+        let tags = field::BinrootsField::<"tags", RefCell<Vec<u8>>>::new(RefCell::default());
+        tags.mark_saved();
+        tags.update(|tags| tags.push(1));
+        assert!(tags.is_dirty());
+
+        // Changes made while a save is running, here by serializing, keep the field dirty
+        struct ChangesMidSave;
+
+        impl Serialize for ChangesMidSave {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                RACING.mark_dirty();
+                serializer.serialize_u8(1)
+            }
+        }
+
+        static RACING: field::BinrootsField<"racing", ChangesMidSave> =
+            field::BinrootsField::new(ChangesMidSave);
+        RACING.save("test_field_dirty_tracking", InMemory).unwrap();
+        assert!(RACING.is_dirty() && RACING.last_saved().is_some());
+
+        // Failed saves keep it dirty too
+        let blocked = root_location(InMemory)
+            .unwrap()
+            .join("test_field_dirty_tracking_blocked");
+        let _ = std::fs::remove_dir_all(&blocked);
+        std::fs::write(&blocked, "").unwrap();
+        assert!(count
+            .save("test_field_dirty_tracking_blocked", InMemory)
+            .is_err());
+        assert!(count.is_dirty());
+    }
+
+    #[test]
+    fn save_guard_finish_and_cancel() {
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_guard_finish_and_cancel");
+        let _ = std::fs::remove_dir_all(&root);
+        let mut status = (1, 2);
+
+        let mut guard =
+            guard::SaveGuard::new(&mut status, "test_save_guard_finish_and_cancel", InMemory);
+        guard.0 = 3;
+        guard.cancel();
+        assert!(!root.exists());
+
+        let mut guard =
+            guard::SaveGuard::new(&mut status, "test_save_guard_finish_and_cancel", InMemory);
+        guard.1 = 4;
+        guard.finish().unwrap();
+        assert_file!("test_save_guard_finish_and_cancel/0", "3");
+        assert_file!("test_save_guard_finish_and_cancel/1", "4");
+    }
+
+    #[test]
+    fn autosave_coalesces_changes() {
+        let status = autosave::Autosave::new(
+            (0, 0),
+            "test_autosave_coalesces_changes",
+            InMemory,
+            std::time::Duration::from_secs(3600),
+        );
+
+        status.update(|status| status.0 = 1);
+        status.flush().unwrap();
+        assert_eq!(status.saves(), 1);
+
+        for i in 0..100 {
+            status.update(|status| status.1 = i);
+        }
+        // Still within the interval of the first save
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(status.saves(), 1);
+
+        drop(status);
+        assert_file!("test_autosave_coalesces_changes/0", "1");
+        assert_file!("test_autosave_coalesces_changes/1", "99");
+    }
+
+    #[test]
+    #[cfg(not(feature = "stable"))]
+    fn path_of_matches_saves() {
+        let field = field::BinrootsField::<"is_online", bool>::new(true);
+        field.save("test_path_of_matches_saves", InMemory).unwrap();
+
+        let path =
+            field::BinrootsField::<"is_online", bool>::path("test_path_of_matches_saves", InMemory)
+                .unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "true");
+
+        let options = save::SaveOptions::default().naming(naming::NamingPolicy::Kebab);
+        assert_eq!(
+            save::path_of_with(
+                "test_path_of_matches_saves",
+                InMemory,
+                &["is_online"],
+                &options
+            )
+            .unwrap(),
+            root_location(InMemory)
+                .unwrap()
+                .join("test_path_of_matches_saves/is-online")
+        );
+    }
+
+    #[test]
+    fn delete_to_trash() {
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_delete_to_trash");
+        let options = save::SaveOptions::default().trash(true);
+        (1, 2)
+            .save_with("test_delete_to_trash", InMemory, &options)
+            .unwrap();
+
+        save::delete_with("test_delete_to_trash", InMemory, &options).unwrap();
+        assert!(!root.join("0").exists());
+        assert!(root.join(save::TRASH_FOLDER).is_dir());
+
+        save::delete("test_delete_to_trash", InMemory).unwrap();
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn delete_rejects_escaping_roots() {
+        let location = root_location(InMemory).unwrap();
+        (1, 2).save("test_delete_rejects", InMemory).unwrap();
+
+        for root in ["", ".", "..", "test_delete_rejects/..", "/tmp"] {
+            assert!(
+                matches!(
+                    save::delete(root, InMemory),
+                    Err(save::SaveError::InvalidRoot { .. })
+                ),
+                "{root:?} was deleted"
+            );
+        }
+        assert!(location.join("test_delete_rejects/0").exists());
+    }
+
+    #[test]
+    fn save_reporting_lists_changes() {
+        let root = root_location(InMemory).unwrap().join("test_save_reporting");
+        vec![1, 22, 3]
+            .save("test_save_reporting", InMemory)
+            .unwrap();
+
+        let report = vec![4, 55]
+            .save_reporting("test_save_reporting", InMemory)
+            .unwrap();
+        assert!(report.written.contains(&root.join("0")));
+        assert!(report.written.contains(&root.join("1")));
+        assert_eq!(report.deleted, vec![root.join("2")]);
+
+        let leaves: u64 = report
+            .written
+            .iter()
+            .map(|path| std::fs::metadata(path).unwrap().len())
+            .sum();
+        assert_eq!(report.bytes_written, leaves);
+
+        let options = save::SaveOptions::default().trash(true);
+        let report = vec![4]
+            .save_reporting_with("test_save_reporting", InMemory, &options)
+            .unwrap();
+        assert_eq!(report.deleted, vec![root.join("1")]);
+        assert!(root.join(save::TRASH_FOLDER).is_dir());
+    }
+
+    #[test]
+    fn save_error_names_field() {
+        use std::error::Error;
+
+        #[derive(Serialize)]
+        struct Pair {
+            a: u8,
+            b: u8,
+        }
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_error_field");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("b/nested")).unwrap();
+
+        let e = Pair { a: 1, b: 2 }
+            .save("test_save_error_field", InMemory)
+            .unwrap_err();
+        assert!(matches!(e, save::SaveError::WriteFileError { .. }));
+        assert_eq!(e.field(), Some("test_save_error_field/b"));
+        assert!(e.io_error().unwrap().raw_os_error().is_some());
+        assert!(e.source().is_some());
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "Failed to write to {:?} (saving test_save_error_field/b) during save",
+                root.join("b")
+            )
+        );
+    }
+
+    #[test]
+    fn save_best_effort() {
+        #[derive(Serialize)]
+        struct Triple {
+            a: u8,
+            b: u8,
+            c: u8,
+        }
+
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_best_effort");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("b/nested")).unwrap();
+
+        let triple = Triple { a: 1, b: 2, c: 3 };
+        assert!(matches!(
+            triple.save("test_save_best_effort", InMemory),
+            Err(save::SaveError::WriteFileError { .. })
+        ));
+        assert!(!root.join("c").exists());
+
+        for watcher_friendly in [false, true] {
+            let options = save::SaveOptions::default()
+                .best_effort(true)
+                .watcher_friendly(watcher_friendly)
+                .etag(true)
+                .generation(true)
+                .mtime_sidecars(true);
+            match triple.save_with("test_save_best_effort", InMemory, &options) {
+                Err(save::SaveError::SaveErrors(errors)) => {
+                    assert_eq!(errors.len(), 1);
+                    assert_eq!(errors[0].field(), Some("test_save_best_effort/b"));
+                }
+                result => panic!("expected SaveErrors, got {result:?}"),
+            }
+            assert_file!("test_save_best_effort/a", "1");
+            assert_file!("test_save_best_effort/c", "3");
+
+            // The rest of the save still finished
+            assert!(root.join("a.mtime").exists());
+            assert!(root.join(save::ETAG_FILE).exists());
+            assert_eq!(
+                save::generation("test_save_best_effort", InMemory).unwrap(),
+                u64::from(watcher_friendly) + 1
+            );
+        }
+    }
+
+    #[test]
+    fn save_streaming() {
+        use crate::fileserializer::{FileSerializer, SerializeOptions, Stream};
+
+        #[derive(Serialize)]
+        struct Log {
+            name: String,
+            lines: Vec<String>,
+        }
+
+        let root = root_location(InMemory).unwrap().join("test_save_streaming");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let options = save::SaveOptions::default().streaming(true);
+        let log = Log {
+            name: "build".into(),
+            lines: (0..5).map(|i| format!("line {i}")).collect(),
+        };
+        let report = log
+            .save_reporting_with("test_save_streaming", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_streaming/name", "build");
+        assert_file!("test_save_streaming/lines/0", "line 0");
+        assert_file!("test_save_streaming/lines/4", "line 4");
+        // Streamed leaves aren't written a second time
+        assert_eq!(
+            report
+                .written
+                .iter()
+                .filter(|path| **path == root.join("lines/4"))
+                .count(),
+            1
+        );
+
+        let log = Log {
+            name: "test".into(),
+            lines: vec!["only".into()],
+        };
+        log.save_with("test_save_streaming", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_streaming/name", "test");
+        assert_file!("test_save_streaming/lines/0", "only");
+        assert!(!root.join("lines/1").exists());
+
+        let streamed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = streamed.clone();
+        let mut serializer = FileSerializer::with_options("", SerializeOptions::default());
+        serializer.stream = Some(Stream::new(move |file| {
+            if !file.is_dir {
+                sink.lock().unwrap().push(file.contents.clone());
+            }
+            Ok(())
+        }));
+        serializer.serialize_value(&vec!["a", "b", "c"]).unwrap();
+        assert_eq!(
+            *streamed.lock().unwrap(),
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+        );
+        assert!(serializer
+            .output()
+            .iter()
+            .all(|file| file.streamed && file.contents.is_empty()));
+
+        #[derive(Serialize)]
+        enum Activity {
+            Idle,
+            Playing(String),
+        }
+
+        let activity = root_location(InMemory)
+            .unwrap()
+            .join("test_save_streaming_enum");
+        let _ = std::fs::remove_dir_all(&activity);
+        let save = |value: Vec<Activity>| {
+            value
+                .save_with("test_save_streaming_enum", InMemory, &options)
+                .unwrap()
+        };
+
+        save(vec![Activity::Playing("x".into())]);
+        assert_file!("test_save_streaming_enum/0", "Playing");
+        assert_file!("test_save_streaming_enum/0.value", "x");
+
+        // The payload of a previous struct variant
+        std::fs::remove_file(activity.join("0.value")).unwrap();
+        std::fs::create_dir(activity.join("0.value")).unwrap();
+        std::fs::write(activity.join("0.value/a"), "1").unwrap();
+
+        save(vec![Activity::Playing("y".into())]);
+        assert_file!("test_save_streaming_enum/0", "Playing");
+        assert_file!("test_save_streaming_enum/0.value", "y");
+
+        save(vec![Activity::Idle]);
+        assert_file!("test_save_streaming_enum/0", "Idle");
+        assert!(!activity.join("0.value").exists());
+    }
+
+    #[test]
+    fn save_caches_folders() {
+        use crate::backend::Memory;
+        use crate::dircache::DirCache;
+        use std::sync::Arc;
+
+        #[derive(Debug, Default)]
+        struct Mkdirs(Memory, std::sync::Mutex<usize>);
+
+        impl save::Backend for Mkdirs {
+            fn write(&self, path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+                self.0.write(path, contents)
+            }
+
+            fn mkdir(&self, path: &std::path::Path) -> std::io::Result<()> {
+                *self.1.lock().unwrap() += 1;
+                self.0.mkdir(path)
+            }
+
+            fn remove(&self, path: &std::path::Path) -> std::io::Result<()> {
+                self.0.remove(path)
+            }
+
+            fn remove_dir(&self, path: &std::path::Path) -> std::io::Result<()> {
+                self.0.remove_dir(path)
+            }
+
+            fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+                self.0.rename(from, to)
+            }
+        }
+
+        let grid = vec![vec![0u8; 50]; 2];
+        let mkdirs = Arc::new(Mkdirs::default());
+        let options = save::SaveOptions::default().backend(mkdirs.clone());
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        let created = *mkdirs.1.lock().unwrap();
+        assert!(created < 10, "created {created} folders");
+
+        let cache = Arc::new(DirCache::new());
+        let options = options.dir_cache(cache.clone());
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        let before = *mkdirs.1.lock().unwrap();
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        assert_eq!(*mkdirs.1.lock().unwrap(), before);
+
+        // Folders removed behind the cache's back are recreated
+        let root = root_location(InMemory)
+            .unwrap()
+            .join("test_save_caches_folders");
+        let _ = std::fs::remove_dir_all(&root);
+        let options = save::SaveOptions::default().dir_cache(cache.clone());
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        std::fs::remove_dir_all(root.join("1")).unwrap();
+        grid.save_with("test_save_caches_folders", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_caches_folders/1/49", "0");
+    }
+
+    #[test]
+    fn save_into_reuses_serializer() {
+        use crate::fileserializer::FileSerializer;
+
+        let options = save::SaveOptions::default();
+        let mut serializer = FileSerializer::default();
+
+        vec![1, 2, 3]
+            .save_into(&mut serializer, "test_save_into", InMemory, &options)
+            .unwrap();
+        assert!(serializer.output().is_empty());
+        let capacity = serializer.output.capacity();
+        assert!(capacity >= 4);
+
+        vec![4, 5]
+            .save_into(&mut serializer, "test_save_into", InMemory, &options)
+            .unwrap();
+        assert_eq!(serializer.output.capacity(), capacity);
+        assert_file!("test_save_into/0", "4");
+        assert_file!("test_save_into/1", "5");
+        assert!(!root_location(InMemory)
+            .unwrap()
+            .join("test_save_into/2")
+            .exists());
+
+        ("a", "b")
+            .save_into(&mut serializer, "test_save_into_tuple", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_into_tuple/0", "a");
+        assert_file!("test_save_into_tuple/1", "b");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn save_emits_tracing_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        struct Messages(Arc<Mutex<Vec<String>>>);
+
+        struct Message(String);
+
+        impl Visit for Message {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{value:?}");
+                }
+            }
+        }
+
+        impl Subscriber for Messages {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut message = Message(String::new());
+                event.record(&mut message);
+                self.0.lock().unwrap().push(message.0);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Messages(messages.clone()), || {
+            (1, 2).save("test_save_tracing", InMemory).unwrap();
+        });
+
+        let messages = messages.lock().unwrap();
+        assert!(messages.iter().any(|message| message == "serialized"));
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|message| *message == "wrote file")
+                .count(),
+            2
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn save_parallel() {
+        let root = root_location(InMemory).unwrap().join("test_save_parallel");
+        let options = save::SaveOptions::default().parallel(true);
+
+        let values = (0..500).collect::<Vec<u32>>();
+        values
+            .save_with("test_save_parallel", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_parallel/0", "0");
+        assert_file!("test_save_parallel/499", "499");
+
+        let values = (0..10).rev().collect::<Vec<u32>>();
+        values
+            .save_with("test_save_parallel", InMemory, &options)
+            .unwrap();
+        assert_file!("test_save_parallel/0", "9");
+        assert!(!root.join("10").exists());
+    }
 }
@@ -0,0 +1,813 @@
+//! ## `binroots::load`
+//! Contains [`load`][`crate::load::load`], reading a saved root back into a value, and the
+//! [`FileDeserializer`][`crate::load::FileDeserializer`] it's built on. It understands everything
+//! [`FileSerializer`][`crate::fileserializer::FileSerializer`] writes: missing files are `None`, enum variants are read from
+//! their tag and `.value` payload (or `.json` with [`EnumRepresentation::JsonPayload`]), templated fields are read from their
+//! `.raw` sibling, [`LeafEncoding::Cbor`] leaves from `.cbor` files and fields listed in [`SerializeOptions::field_formats`]
+//! from their single file.
+//!
+//! [`EnumRepresentation::JsonPayload`]: crate::fileserializer::EnumRepresentation::JsonPayload
+//! [`LeafEncoding::Cbor`]: crate::fileserializer::LeafEncoding
+//! [`SerializeOptions::field_formats`]: crate::fileserializer::SerializeOptions::field_formats
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::load;
+//! use binroots::save::{RootType, Save};
+//! use binroots::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! enum Activity {
+//!     Nothing,
+//!     Playing(String),
+//! }
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Status {
+//!     connections: usize,
+//!     nickname: Option<String>,
+//!     activity: Activity,
+//!     scores: Vec<u8>,
+//! }
+//!
+//! let status = Status {
+//!     connections: 3,
+//!     nickname: None,
+//!     activity: Activity::Playing("hideo kame".into()),
+//!     scores: vec![9, 8],
+//! };
+//! status.save("load-status", RootType::InMemory).unwrap();
+//!
+//! assert_eq!(load::load::<Status, _>("load-status", RootType::InMemory).unwrap(), status);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+
+use crate::config::RootConfig;
+use crate::fileserializer::{FieldFormat, SerializeOptions};
+use crate::naming::{escape, unescape};
+use crate::save::{root_location, RootLocationError, RootType, SaveOptions};
+
+/// Errors while loading a saved root
+#[derive(Debug)]
+pub enum LoadError {
+    /// Returned when reading a file or folder fails, including when a required one doesn't exist
+    ReadFileError {
+        /// The path that was read
+        path: PathBuf,
+        /// The resulting IO error kind.
+        ///
+        /// See [`std::io::ErrorKind`]
+        kind: std::io::ErrorKind,
+    },
+    /// Returned when a file can't be parsed as the type it's loaded into
+    ParseError {
+        /// The path that was read
+        path: PathBuf,
+        /// The error reported while parsing
+        message: String,
+    },
+    /// A message from the type being deserialized, such as a missing field
+    Message(String),
+    /// An error caught while retrieving the project's root directory.
+    ///
+    /// See [`RootLocationError`]
+    RootLocationError(RootLocationError),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadFileError { path, kind } => {
+                write!(f, "Failed to read {path:?} during load; {kind}")
+            }
+            Self::ParseError { path, message } => {
+                write!(f, "Failed to parse {path:?} during load; {message}")
+            }
+            Self::Message(message) => write!(f, "Failed to load: {message}"),
+            Self::RootLocationError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl de::Error for LoadError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+type LoadResult<T> = Result<T, LoadError>;
+
+/// Loads the value saved to "[`root_location`]/\<root\>" by [`Save::save`][`crate::save::Save::save`]
+pub fn load<T: DeserializeOwned, P: Into<PathBuf>>(root: P, root_type: RootType) -> LoadResult<T> {
+    load_with(root, root_type, &SaveOptions::default())
+}
+
+/// Like [`load`], for values saved with [`Save::save_with`][`crate::save::Save::save_with`] using `options`.
+/// Only [`SaveOptions::serializer`] is used, adjusted by the root's [`RootConfig`] with [`SaveOptions::root_config`].
+pub fn load_with<T: DeserializeOwned, P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> LoadResult<T> {
+    let path = root_location(root_type)
+        .map_err(LoadError::RootLocationError)?
+        .join(root.into());
+
+    if !path.exists() {
+        return Err(LoadError::ReadFileError {
+            path,
+            kind: std::io::ErrorKind::NotFound,
+        });
+    }
+
+    let configured = options.root_config.then(|| {
+        let mut options = options.clone();
+        RootConfig::read(&path).apply(&mut options);
+        options
+    });
+    let options = configured.as_ref().unwrap_or(options);
+
+    T::deserialize(FileDeserializer::new(path, &options.serializer))
+}
+
+/// A [`serde::Deserializer`] reading a value from the files and folders below a path, laid out the way
+/// [`FileSerializer`][`crate::fileserializer::FileSerializer`] saves it with the same `options`
+#[derive(Debug, Clone)]
+pub struct FileDeserializer<'a> {
+    path: PathBuf,
+    /// The path relative to the save root, used to find top-level fields
+    relative: PathBuf,
+    options: &'a SerializeOptions,
+}
+
+impl<'a> FileDeserializer<'a> {
+    /// Constructs a deserializer reading the value saved at `path` (usually a save root) using `options`
+    pub fn new<P: Into<PathBuf>>(path: P, options: &'a SerializeOptions) -> Self {
+        Self {
+            path: path.into(),
+            relative: PathBuf::new(),
+            options,
+        }
+    }
+
+    fn child(&self, name: &str) -> Self {
+        Self {
+            path: self.path.join(name),
+            relative: self.relative.join(name),
+            options: self.options,
+        }
+    }
+
+    /// The deserializer of an enum variant's payload, e.g. `activity.value`
+    fn payload(&self) -> Self {
+        Self {
+            path: sibling(&self.path, &self.options.markers.value),
+            relative: self.relative.clone(),
+            options: self.options,
+        }
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+            || sibling(&self.path, "cbor").is_file()
+            || sibling(&self.path, "json").is_file()
+    }
+
+    /// The format of a value saved as a single file: a top-level field listed in [`SerializeOptions::field_formats`], or
+    /// a value collapsed at [`SerializeOptions::max_depth`]
+    fn format(&self) -> Option<FieldFormat> {
+        let mut components = self.relative.components();
+        if let (Some(field), None) = (components.next(), components.next()) {
+            if let Some(format) = self.options.format_of(field.as_os_str().to_str()?) {
+                return Some(format);
+            }
+        }
+
+        let depth = self.relative.components().count();
+        let collapsed = self
+            .options
+            .max_depth
+            .map_or(false, |max_depth| depth >= max_depth.max(1))
+            && !self.path.exists()
+            && sibling(&self.path, FieldFormat::Json.extension()).is_file();
+
+        collapsed.then_some(FieldFormat::Json)
+    }
+
+    /// Deserializes a top-level field saved as a single file in `format`
+    fn deserialize_formatted<'de, S: de::DeserializeSeed<'de>>(
+        &self,
+        format: FieldFormat,
+        seed: S,
+    ) -> LoadResult<S::Value> {
+        let path = sibling(&self.path, format.extension());
+        let file = std::fs::File::open(&path).map_err(|e| LoadError::ReadFileError {
+            path: path.clone(),
+            kind: e.kind(),
+        })?;
+
+        match format {
+            FieldFormat::Json => seed
+                .deserialize(&mut serde_json::Deserializer::from_reader(
+                    std::io::BufReader::new(file),
+                ))
+                .map_err(|e| LoadError::ParseError {
+                    path,
+                    message: e.to_string(),
+                }),
+        }
+    }
+
+    /// Whether the leaf is missing because it held its default value, see [`SerializeOptions::elides`]
+    fn elided(&self) -> bool {
+        !self.exists()
+            && self
+                .relative
+                .to_str()
+                .map_or(false, |path| self.options.elides(path))
+    }
+
+    /// Whether the leaf was saved as `***`, see [`SerializeOptions::redacted_fields`]
+    fn redacted(&self) -> bool {
+        self.relative
+            .to_str()
+            .map_or(false, |path| self.options.redacts(path))
+    }
+
+    fn not_found(&self) -> LoadError {
+        LoadError::ReadFileError {
+            path: self.path.clone(),
+            kind: std::io::ErrorKind::NotFound,
+        }
+    }
+
+    /// Reads the contents of a leaf, preferring the `.raw` sibling of templated fields and decoding `.cbor` leaves
+    fn read(&self) -> LoadResult<Vec<u8>> {
+        let templated = self
+            .relative
+            .to_str()
+            .map_or(false, |field| self.options.template_of(field).is_some());
+        let raw = sibling(&self.path, "raw");
+        let path = if templated && raw.is_file() {
+            raw
+        } else {
+            self.path.clone()
+        };
+
+        match std::fs::read(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => self.read_cbor(),
+            result => result.map_err(|e| LoadError::ReadFileError {
+                path,
+                kind: e.kind(),
+            }),
+        }
+    }
+
+    #[cfg(feature = "cbor")]
+    fn read_cbor(&self) -> LoadResult<Vec<u8>> {
+        use ciborium::value::Value;
+
+        let path = sibling(&self.path, "cbor");
+        let contents = std::fs::read(&path).map_err(|_| self.not_found())?;
+        let parse_error = |message: String| LoadError::ParseError {
+            path: path.clone(),
+            message,
+        };
+
+        Ok(
+            match ciborium::from_reader::<Value, _>(contents.as_slice())
+                .map_err(|e| parse_error(e.to_string()))?
+            {
+                Value::Integer(i) => i128::from(i).to_string().into_bytes(),
+                Value::Float(f) => ryu::Buffer::new().format(f).as_bytes().to_vec(),
+                Value::Bool(b) => b.to_string().into_bytes(),
+                Value::Text(s) => s.into_bytes(),
+                Value::Bytes(b) => b,
+                Value::Null => Vec::new(),
+                _ => return Err(parse_error("expected a scalar leaf".into())),
+            },
+        )
+    }
+
+    #[cfg(not(feature = "cbor"))]
+    fn read_cbor(&self) -> LoadResult<Vec<u8>> {
+        Err(self.not_found())
+    }
+
+    fn read_text(&self) -> LoadResult<String> {
+        let mut contents = self.read()?;
+        if self.options.trailing_newline && contents.last() == Some(&b'\n') {
+            contents.pop();
+        }
+
+        String::from_utf8(contents).map_err(|e| LoadError::ParseError {
+            path: self.path.clone(),
+            message: e.to_string(),
+        })
+    }
+
+    fn parse<T>(&self) -> LoadResult<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.read_text()?
+            .parse()
+            .map_err(|e: T::Err| LoadError::ParseError {
+                path: self.path.clone(),
+                message: e.to_string(),
+            })
+    }
+
+    /// The consecutive indices saved in a sequence folder
+    fn indices(&self) -> LoadResult<Vec<String>> {
+        if !self.path.is_dir() {
+            return Err(self.not_found());
+        }
+
+        Ok((0..)
+            .map(|i: usize| self.options.index_name(i))
+            .take_while(|i| self.child(i).exists())
+            .collect())
+    }
+
+    /// The keys saved in a map folder, skipping hidden files at the top of the root and the siblings binroots writes next
+    /// to entries, such as `.value` payloads and `.mtime` sidecars
+    fn keys(&self) -> LoadResult<Vec<String>> {
+        let entries = std::fs::read_dir(&self.path).map_err(|e| LoadError::ReadFileError {
+            path: self.path.clone(),
+            kind: e.kind(),
+        })?;
+        let names = entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        let is_root = self.relative.as_os_str().is_empty();
+        // Values saved as a single file are read back under the name without the extension, but only where the
+        // serializer collapses them: at `max_depth` and below, or for the top-level fields of `field_formats`
+        let depth = self.relative.components().count() + 1;
+        let collapses = |stem: &str| {
+            self.options
+                .max_depth
+                .map_or(false, |max_depth| depth >= max_depth.max(1))
+                || (is_root && self.options.format_of(stem).is_some())
+        };
+        let siblings = [
+            self.options.markers.value.as_str(),
+            "raw",
+            "json",
+            "mtime",
+            "cbor",
+        ];
+
+        let mut keys = names
+            .iter()
+            .filter(|name| !(is_root && name.starts_with('.')))
+            .filter_map(|name| match name.rsplit_once('.') {
+                Some((stem, "cbor")) => Some(stem.to_string()),
+                Some((stem, "json")) if collapses(stem) && !names.iter().any(|n| n == stem) => {
+                    Some(stem.to_string())
+                }
+                Some((stem, ext)) if siblings.contains(&ext) && names.iter().any(|n| n == stem) => {
+                    None
+                }
+                _ => Some(name.clone()),
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+
+        Ok(keys)
+    }
+
+    /// The name of the variant saved in an enum's tag file, mapped back to one of `variants` through
+    /// [`SerializeOptions::naming`]
+    fn variant(&self, variants: &[&str]) -> LoadResult<String> {
+        let contents = self.read_text()?;
+        let (prefix, suffix) = self
+            .options
+            .markers
+            .variant
+            .split_once("{variant}")
+            .unwrap_or(("", ""));
+
+        contents
+            .strip_prefix(prefix)
+            .and_then(|contents| contents.strip_suffix(suffix))
+            .map(|saved| {
+                variants
+                    .iter()
+                    .find(|variant| self.options.naming.apply(variant) == saved)
+                    .map_or_else(|| saved.to_string(), |variant| variant.to_string())
+            })
+            .ok_or_else(|| LoadError::ParseError {
+                path: self.path.clone(),
+                message: format!("{contents:?} isn't an enum variant"),
+            })
+    }
+}
+
+/// `path` with `.<ext>` appended
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(".");
+    path.push(ext);
+    path.into()
+}
+
+macro_rules! deserialize_parsed {
+    ($($method: ident => $visit: ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+                if self.elided() || self.redacted() {
+                    return visitor.$visit(Default::default());
+                }
+
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FileDeserializer<'a> {
+    type Error = LoadError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        if self.path.is_dir() {
+            let keys = self.keys()?;
+            if !keys.is_empty() && keys.iter().all(|key| key.parse::<usize>().is_ok()) {
+                self.deserialize_seq(visitor)
+            } else {
+                self.deserialize_map(visitor)
+            }
+        } else if self.exists() {
+            self.deserialize_string(visitor)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        if self.elided() || self.redacted() {
+            return visitor.visit_string(String::new());
+        }
+
+        visitor.visit_string(self.read_text()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        if self.redacted() {
+            return visitor.visit_byte_buf(Vec::new());
+        }
+
+        visitor.visit_byte_buf(self.read()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        if !self.exists() {
+            return visitor.visit_none();
+        }
+
+        if let Some(none_value) = &self.options.markers.none_value {
+            if self.path.is_file() && self.read()? == none_value.as_bytes() {
+                return visitor.visit_none();
+            }
+        }
+
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        let names = self.indices()?;
+        visitor.visit_seq(Entries::new(
+            self,
+            names.into_iter().map(|name| (name.clone(), name)),
+        ))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> LoadResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        let names = self.keys()?;
+        visitor.visit_map(Entries::new(
+            self,
+            names.into_iter().map(|name| (unescape(&name), name)),
+        ))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        if !self.path.is_dir() {
+            return Err(self.not_found());
+        }
+
+        let names = fields
+            .iter()
+            .map(|field| (field.to_string(), escape(&self.options.naming.apply(field))))
+            .filter(|(_, name)| {
+                let child = self.child(name);
+                child.exists() || child.elided()
+            })
+            .collect::<Vec<_>>();
+        visitor.visit_map(Entries::new(self, names))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        let json = sibling(&self.path, "json");
+        if json.is_file() {
+            let file = std::fs::File::open(&json).map_err(|e| LoadError::ReadFileError {
+                path: json.clone(),
+                kind: e.kind(),
+            })?;
+
+            return serde_json::Deserializer::from_reader(std::io::BufReader::new(file))
+                .deserialize_enum(name, variants, visitor)
+                .map_err(|e| LoadError::ParseError {
+                    path: json,
+                    message: e.to_string(),
+                });
+        }
+
+        let variant = self.variant(variants)?;
+        visitor.visit_enum(Variant {
+            variant,
+            payload: self.payload(),
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// The entries of a sequence, map or struct folder as `(key, name)` pairs, where `key` is the index, map key or field
+/// name and `name` is the entry's name on disk
+struct Entries<'a> {
+    parent: FileDeserializer<'a>,
+    names: std::vec::IntoIter<(String, String)>,
+    current: Option<String>,
+}
+
+impl<'a> Entries<'a> {
+    fn new<I: IntoIterator<Item = (String, String)>>(
+        parent: FileDeserializer<'a>,
+        names: I,
+    ) -> Self {
+        Self {
+            parent,
+            names: names.into_iter().collect::<Vec<_>>().into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for Entries<'a> {
+    type Error = LoadError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> LoadResult<Option<T::Value>> {
+        let Some((_, name)) = self.names.next() else {
+            return Ok(None);
+        };
+
+        let child = self.parent.child(&name);
+        match child.format() {
+            Some(format) => child.deserialize_formatted(format, seed).map(Some),
+            None => seed.deserialize(child).map(Some),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.names.len())
+    }
+}
+
+impl<'de, 'a> de::MapAccess<'de> for Entries<'a> {
+    type Error = LoadError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> LoadResult<Option<K::Value>> {
+        let Some((key, name)) = self.names.next() else {
+            return Ok(None);
+        };
+
+        let key = seed.deserialize(Key {
+            key: &key,
+            options: self.parent.options,
+        })?;
+        self.current = Some(name);
+
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> LoadResult<V::Value> {
+        let name = self
+            .current
+            .take()
+            .ok_or_else(|| LoadError::Message("a map value was requested before its key".into()))?;
+
+        let child = self.parent.child(&name);
+        match child.format() {
+            Some(format) => child.deserialize_formatted(format, seed),
+            None => seed.deserialize(child),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.names.len())
+    }
+}
+
+/// An enum variant read from its tag file, with its payload in the `.value` sibling
+struct Variant<'a> {
+    variant: String,
+    payload: FileDeserializer<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = LoadError;
+    type Variant = FileDeserializer<'a>;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> LoadResult<(S::Value, Self::Variant)> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.payload))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for FileDeserializer<'a> {
+    type Error = LoadError;
+
+    fn unit_variant(self) -> LoadResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> LoadResult<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> LoadResult<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> LoadResult<V::Value> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// A map key read from a file name
+struct Key<'a> {
+    key: &'a str,
+    options: &'a SerializeOptions,
+}
+
+impl<'a> Key<'a> {
+    /// Keys are file names, which are never elided
+    fn elided(&self) -> bool {
+        false
+    }
+
+    /// Keys are file names, which are never redacted
+    fn redacted(&self) -> bool {
+        false
+    }
+
+    fn parse<T>(&self) -> LoadResult<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.key
+            .parse()
+            .map_err(|e: T::Err| LoadError::Message(format!("invalid map key {:?}: {e}", self.key)))
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Key<'a> {
+    type Error = LoadError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        visitor.visit_string(self.key.into())
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        if self.key == self.options.markers.none_key {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> LoadResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
@@ -0,0 +1,162 @@
+//! ## `binroots::metadata`
+//! Contains [`Metadata`][`crate::metadata::Metadata`], describing who wrote a root and with which on-disk layout.
+//! Written to `<root>/.binroots` by saves using [`SaveOptions::metadata`][`crate::save::SaveOptions::metadata`].
+//!
+//! ## Format
+//! One `key=value` pair per line, so it's easy to read from shell scripts:
+//! ```text
+//! crate_version=0.2.1
+//! app_version=1.4.0
+//! layout_version=1
+//! created=1700000000
+//! updated=1700000060
+//! ```
+//! Timestamps are seconds since the Unix epoch. `app_version` is omitted when unknown, and unknown keys are ignored.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backend::Backend;
+use crate::read::ReadError;
+use crate::save::{root_location, write_to, RootType, SaveError};
+
+/// The name of the metadata file inside a root
+pub const METADATA_FILE: &str = ".binroots";
+
+/// The version of the on-disk layout written by this version of binroots
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// The contents of a root's `.binroots` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// The version of binroots that last saved the root
+    pub crate_version: String,
+    /// The version of the application that last saved the root, see [`SaveOptions::app_version`][`crate::save::SaveOptions::app_version`]
+    pub app_version: Option<String>,
+    /// The [`LAYOUT_VERSION`] the root was last saved with
+    pub layout_version: u32,
+    /// When the metadata was first written, truncated to seconds
+    pub created: SystemTime,
+    /// When the root was last saved, truncated to seconds
+    pub updated: SystemTime,
+}
+
+impl Metadata {
+    /// Renders the metadata in the `.binroots` format
+    pub fn to_file_contents(&self) -> String {
+        let secs = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+
+        let mut out = format!("crate_version={}\n", self.crate_version);
+        if let Some(app_version) = &self.app_version {
+            out += &format!("app_version={app_version}\n");
+        }
+        out += &format!("layout_version={}\n", self.layout_version);
+        out += &format!("created={}\n", secs(self.created));
+        out += &format!("updated={}\n", secs(self.updated));
+
+        out
+    }
+
+    /// Parses the `.binroots` format. Returns [`None`] if a required key is missing or invalid.
+    pub fn from_file_contents(contents: &str) -> Option<Self> {
+        let mut crate_version = None;
+        let mut app_version = None;
+        let mut layout_version = None;
+        let mut created = None;
+        let mut updated = None;
+
+        let time = |value: &str| {
+            value
+                .parse()
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        };
+
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("crate_version", value)) => crate_version = Some(value.to_string()),
+                Some(("app_version", value)) => app_version = Some(value.to_string()),
+                Some(("layout_version", value)) => layout_version = value.parse().ok(),
+                Some(("created", value)) => created = time(value),
+                Some(("updated", value)) => updated = time(value),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            crate_version: crate_version?,
+            app_version,
+            layout_version: layout_version?,
+            created: created?,
+            updated: updated?,
+        })
+    }
+}
+
+/// Reads the metadata of "[`root_location`]/\<root\>". Returns [`None`] if the root has no `.binroots` file.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::metadata::{read, LAYOUT_VERSION};
+/// use binroots::save::{RootType, Save, SaveOptions};
+///
+/// let options = SaveOptions::default().metadata(true).app_version("1.4.0");
+/// (1, 2).save_with("metadata-example", RootType::InMemory, &options).unwrap();
+///
+/// let metadata = read("metadata-example", RootType::InMemory).unwrap().unwrap();
+/// assert_eq!(metadata.app_version.as_deref(), Some("1.4.0"));
+/// assert_eq!(metadata.layout_version, LAYOUT_VERSION);
+/// ```
+pub fn read<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<Option<Metadata>, ReadError> {
+    let path = root_location(root_type)
+        .map_err(ReadError::RootLocationError)?
+        .join(root.into())
+        .join(METADATA_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            Metadata::from_file_contents(&contents)
+                .map(Some)
+                .ok_or(ReadError::ParseError {
+                    path,
+                    message: "missing or invalid metadata keys".into(),
+                })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ReadError::ReadFileError {
+            path,
+            kind: e.kind(),
+        }),
+    }
+}
+
+/// Writes `<path>/.binroots`, keeping the `created` timestamp of an existing file
+pub(crate) fn write(
+    backend: &dyn Backend,
+    path: &Path,
+    app_version: Option<&str>,
+    now: SystemTime,
+) -> Result<(), SaveError> {
+    let file = path.join(METADATA_FILE);
+
+    let created = backend
+        .read(&file)
+        .ok()
+        .and_then(|contents| Metadata::from_file_contents(&String::from_utf8_lossy(&contents)))
+        .map_or(now, |previous| previous.created);
+
+    let metadata = Metadata {
+        crate_version: env!("CARGO_PKG_VERSION").into(),
+        app_version: app_version.map(Into::into),
+        layout_version: LAYOUT_VERSION,
+        created,
+        updated: now,
+    };
+
+    write_to(backend, file, metadata.to_file_contents().into_bytes())
+}
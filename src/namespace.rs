@@ -0,0 +1,67 @@
+//! ## `binroots::namespace`
+//! Contains [`reserve`][`crate::namespace::reserve`], which claims a root folder for a single type so that two libraries
+//! linked into the same program can't publish to the same folder unnoticed.
+//! [`binroots_struct`][`crate::binroots_struct`] reserves its `ROOT_FOLDER` before every save, and generates
+//! `reserve_root` for reserving it at startup.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::namespace;
+//! use binroots::save::{RootType, SaveError};
+//!
+//! namespace::reserve("namespace-status", RootType::InMemory, "network::Status").unwrap();
+//! namespace::reserve("namespace-status", RootType::InMemory, "network::Status").unwrap(); // Reserving again is fine
+//!
+//! assert!(matches!(
+//!     namespace::reserve("namespace-status", RootType::InMemory, "audio::Status"),
+//!     Err(SaveError::RootCollision { .. })
+//! ));
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::save::{root_location, RootType, SaveError};
+
+static RESERVED: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
+
+/// Reserves "[`root_location`]/\<root\>" for the type named `owner`, such as `my_crate::Status`. Fails with
+/// [`SaveError::RootCollision`] if another type already reserved the same folder in this process.
+pub fn reserve<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    owner: &str,
+) -> Result<(), SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into());
+
+    let mut reserved = RESERVED.lock().unwrap_or_else(|e| e.into_inner());
+    let reserved = reserved.get_or_insert_with(HashMap::new);
+    match reserved.get(&path) {
+        Some(first) if first != owner => Err(SaveError::RootCollision {
+            path,
+            first: first.clone(),
+            second: owner.into(),
+        }),
+        Some(_) => Ok(()),
+        None => {
+            reserved.insert(path, owner.into());
+            Ok(())
+        }
+    }
+}
+
+/// The type that reserved "[`root_location`]/\<root\>" with [`reserve`], if any
+pub fn owner<P: Into<PathBuf>>(root: P, root_type: RootType) -> Option<String> {
+    let path = root_location(root_type).ok()?.join(root.into());
+
+    RESERVED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()?
+        .get(&path)
+        .cloned()
+}
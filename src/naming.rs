@@ -0,0 +1,274 @@
+//! ## `binroots::naming`
+//! Contains [`NamingPolicy`][`crate::naming::NamingPolicy`], which maps the names of struct fields and enum variants to
+//! the names saved on disk, [`migrate`][`crate::naming::migrate`] for moving a tree saved under one policy to another, and
+//! [`escape`][`crate::naming::escape`], which turns map keys and field names into safe file names.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::naming::NamingPolicy;
+//! use binroots::save::{root_location, RootType, Save, SaveOptions};
+//! use binroots::Serialize;
+//!
+//! #[derive(Serialize)]
+//! enum Activity {
+//!     Nothing,
+//!     WatchingTv,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Status {
+//!     is_online: bool,
+//!     current_activity: Activity,
+//! }
+//!
+//! let options = SaveOptions::default().naming(NamingPolicy::Kebab);
+//! Status { is_online: true, current_activity: Activity::WatchingTv }
+//!     .save_with("naming-status", RootType::InMemory, &options)
+//!     .unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("naming-status");
+//! assert_eq!(std::fs::read_to_string(root.join("is-online")).unwrap(), "true");
+//! assert_eq!(std::fs::read_to_string(root.join("current-activity")).unwrap(), "watching-tv");
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::fileserializer::{FileSerializer, SerializeOptions};
+use crate::save::{root_location, RootType, SaveError, SaveOptions};
+
+/// How the names of struct fields and enum variants are saved. Map keys are always saved as-is, apart from [`escape`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NamingPolicy {
+    /// `is_online` and `WatchingTv` are saved as `is-online` and `watching-tv`
+    Kebab,
+    /// `is_online` and `WatchingTv` are saved as `is_online` and `watching_tv`
+    Snake,
+    /// Names are saved exactly as they're written in Rust
+    #[default]
+    AsIs,
+}
+
+impl NamingPolicy {
+    /// Returns `name` as it's saved under this policy
+    ///
+    /// ```
+    /// use binroots::naming::NamingPolicy;
+    ///
+    /// assert_eq!(NamingPolicy::Kebab.apply("HTTPServer"), "http-server");
+    /// assert_eq!(NamingPolicy::Snake.apply("is-online"), "is_online");
+    /// assert_eq!(NamingPolicy::AsIs.apply("WatchingTv"), "WatchingTv");
+    /// ```
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            Self::Kebab => words(name).join("-"),
+            Self::Snake => words(name).join("_"),
+            Self::AsIs => name.into(),
+        }
+    }
+}
+
+/// Splits `name` into lowercase words on `_`, `-`, spaces and case changes
+fn words(name: &str) -> Vec<String> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if c.is_uppercase() && !word.is_empty() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map_or(false, |next| next.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        word.extend(c.to_lowercase());
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Renames the struct fields of `value`, saved at "[`root_location`]/\<root\>" with `options`, to follow `policy`.
+/// Returns the renamed paths as `(from, to)` pairs, parents before their children.
+///
+/// Only the names `options.serializer.naming` produces for `value` are renamed, so pass the value as it's saved, such as
+/// loaded with [`load_with`][`crate::load::load_with`]. The root's own folder, map keys, hidden files such as
+/// [`METADATA_FILE`][`crate::metadata::METADATA_FILE`] and entries of `value` that aren't on disk are left alone, and the
+/// extension of siblings like `activity.value` is kept. Enum variant names saved as file contents are left unchanged
+/// too, so save the value again with the new policy afterwards.
+pub fn migrate<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+    policy: NamingPolicy,
+) -> Result<Vec<(PathBuf, PathBuf)>, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into());
+
+    let plan = |naming| {
+        let mut serializer = FileSerializer::with_options(
+            "",
+            SerializeOptions {
+                naming,
+                ..options.serializer.clone()
+            },
+        );
+        serializer
+            .serialize_value(value)
+            .map_err(SaveError::SerializeError)?;
+        serializer.apply_templates();
+
+        Ok::<_, SaveError>(
+            serializer
+                .output()
+                .iter()
+                .map(|file| file.target(&path))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let from = plan(options.serializer.naming)?;
+    let to = plan(policy)?;
+
+    let mut renamed = Vec::new();
+    for (from, to) in from.iter().zip(&to) {
+        // Folders come before their contents and were already renamed, so the entry is in its new folder
+        let (Some(parent), Some(name)) = (to.parent(), from.file_name()) else {
+            continue;
+        };
+        rename(&parent.join(name), to, &mut renamed)?;
+    }
+
+    Ok(renamed)
+}
+
+/// Renames the entry at `path` to `target`, unless it's already there or doesn't exist
+fn rename(
+    path: &Path,
+    target: &Path,
+    renamed: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), SaveError> {
+    if target == path || std::fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    if std::fs::symlink_metadata(target).is_ok() {
+        return Err(SaveError::RenameError {
+            from: path.into(),
+            to: target.into(),
+            source: std::io::ErrorKind::AlreadyExists.into(),
+            field: None,
+        });
+    }
+
+    std::fs::rename(path, target).map_err(|e| SaveError::RenameError {
+        from: path.into(),
+        to: target.into(),
+        source: e,
+        field: None,
+    })?;
+    renamed.push((path.into(), target.into()));
+
+    Ok(())
+}
+
+/// Names Windows reserves for devices, even with an extension such as `CON.txt`
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The extensions binroots appends to the siblings of a field
+pub(crate) const SIBLINGS: [&str; 5] = ["value", "raw", "cbor", "json", "mtime"];
+
+/// Percent-encodes the parts of a map key or field name that can't be saved as a single file name inside the root, the
+/// same way on every platform so a tree can be moved between them: `%`, `/`, `\`, `<>:"|?*`, control characters, a
+/// leading `.` (which would collide with `.`, `..` and metadata files such as `.etag`), the first letter of reserved
+/// Windows device names like `CON`, trailing dots and spaces, and the dot before an extension binroots gives the siblings
+/// of a field, such as `.raw` or `.mtime`. Reversed by [`unescape`].
+///
+/// ## Example
+///
+/// ```
+/// use binroots::naming::{escape, unescape};
+///
+/// assert_eq!(escape("eu/west"), "eu%2Fwest");
+/// assert_eq!(escape(".."), "%2E%2E");
+/// assert_eq!(escape(".etag"), "%2Eetag");
+/// assert_eq!(escape("notes.raw"), "notes%2Eraw");
+/// assert_eq!(escape("plain"), "plain");
+/// assert_eq!(unescape(&escape("50% off")), "50% off");
+/// ```
+pub fn escape(name: &str) -> String {
+    let stem = name.split('.').next().unwrap_or_default();
+    let reserved = RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem));
+    let sibling = name
+        .rsplit_once('.')
+        .filter(|(_, extension)| SIBLINGS.contains(extension))
+        .map(|(stem, _)| stem.chars().count());
+    let last = name.chars().count().saturating_sub(1);
+
+    let mut escaped = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        let encode = matches!(
+            c,
+            '%' | '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*'
+        ) || c.is_control()
+            || (i == 0 && (reserved || c == '.'))
+            || Some(i) == sibling
+            || (i == last && matches!(c, '.' | ' '));
+
+        if encode {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped += &format!("%{byte:02X}");
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+/// Decodes the names encoded by [`escape`]. Invalid escapes are kept as they are.
+pub fn unescape(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
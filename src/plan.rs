@@ -0,0 +1,113 @@
+//! ## `binroots::plan`
+//! Contains [`plan`][`crate::plan::plan`], a dry run of [`Save::save`][`crate::save::Save::save`] listing the operations a
+//! save would perform as [`PlannedOp`][`crate::plan::PlannedOp`]s without touching the disk, so tools can preview or diff
+//! a save before making it.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::plan::{Op, PlannedOp};
+//! use binroots::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Status {
+//!     nickname: Option<String>,
+//!     connections: usize,
+//! }
+//!
+//! let ops = binroots::plan(&Status { nickname: None, connections: 3 }, "status").unwrap();
+//!
+//! assert!(ops.contains(&PlannedOp { path: "status/nickname".into(), contents: Vec::new(), op: Op::Delete }));
+//! assert!(ops.contains(&PlannedOp { path: "status/connections".into(), contents: b"3".to_vec(), op: Op::Write }));
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::fileserializer::{FileOperationHint, FileSerializer, PlannedWrite, SerializerError};
+use crate::save::SaveOptions;
+
+/// What a save does at a [`PlannedOp::path`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Creates the folder along with its missing parents
+    CreateDir,
+    /// Writes [`PlannedOp::contents`] to the file
+    Write,
+    /// Removes the file or folder if it exists
+    Delete,
+    /// Removes the elements of the sequence folder from this index on
+    Truncate(usize),
+}
+
+/// A single operation of a save, listed by [`plan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedOp {
+    /// The path operated on, relative to the [`root_location`][`crate::save::root_location`]
+    pub path: PathBuf,
+    /// The contents written by [`Op::Write`]. Empty for other operations.
+    pub contents: Vec<u8>,
+    /// The operation
+    pub op: Op,
+}
+
+/// Lists the operations saving `value` to `root` would perform, in order, without touching the disk. Paths start with
+/// `root`; join them onto [`root_location`][`crate::save::root_location`] for the absolute paths.
+pub fn plan<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    root: P,
+) -> Result<Vec<PlannedOp>, SerializerError> {
+    plan_with(value, root, &SaveOptions::default())
+}
+
+/// Like [`plan`], for saves using `options`. Only [`SaveOptions::serializer`] is used.
+pub fn plan_with<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    root: P,
+    options: &SaveOptions,
+) -> Result<Vec<PlannedOp>, SerializerError> {
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer.serialize_value(value)?;
+    serializer.apply_templates();
+    serializer.coalesce();
+
+    let root = root.into();
+    let mut ops = Vec::new();
+    for mut file in serializer.into_output() {
+        file.unspill()
+            .map_err(|e| SerializerError::Message(e.to_string()))?;
+        push_ops(&mut ops, file, &root);
+    }
+
+    Ok(ops)
+}
+
+/// Appends the operations saving `file` below `root` performs
+fn push_ops(ops: &mut Vec<PlannedOp>, mut file: PlannedWrite, root: &Path) {
+    let op = |path: PathBuf, op: Op| PlannedOp {
+        path,
+        contents: Vec::new(),
+        op,
+    };
+
+    if let Some(stale_value) = file.stale_value(root) {
+        ops.push(op(stale_value, Op::Delete));
+    }
+
+    if file.is_dir {
+        let target = file.target(root);
+        ops.push(op(target.clone(), Op::CreateDir));
+        if let FileOperationHint::Truncate(len) = file.hint {
+            ops.push(op(target, Op::Truncate(len)));
+        }
+    } else if file.hint == FileOperationHint::Delete {
+        ops.push(op(file.location(root), Op::Delete));
+    } else {
+        ops.push(PlannedOp {
+            path: file.target(root),
+            contents: std::mem::take(&mut file.contents),
+            op: Op::Write,
+        });
+    }
+}
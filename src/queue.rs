@@ -0,0 +1,255 @@
+//! ## `binroots::queue`
+//! Contains [`SaveQueue`][`crate::queue::SaveQueue`], a background saver for apps saving several roots of differing
+//! importance. Queued saves run one at a time, highest [`Priority`][`crate::queue::Priority`] first, so critical state
+//! isn't stuck behind large caches.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::queue::{Priority, SaveQueue};
+//! use binroots::save::{RootType, SaveOptions};
+//!
+//! let queue = SaveQueue::new(16);
+//!
+//! let cache = queue
+//!     .push(&vec![0u8; 64], "queue-cache", RootType::InMemory, Priority::Bulk, SaveOptions::default())
+//!     .unwrap();
+//! let crash = queue
+//!     .push(&("panicked", 101), "queue-crash", RootType::InMemory, Priority::Critical, SaveOptions::default())
+//!     .unwrap();
+//!
+//! crash.wait().unwrap();
+//! cache.wait().unwrap();
+//! ```
+
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use serde::Serialize;
+
+use crate::config;
+use crate::fileserializer::FileSerializer;
+use crate::save::{save_root_with, RootType, SaveError, SaveOptions};
+
+/// How urgently a queued save should run. Higher priorities run first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Large, easily rebuilt data such as caches
+    Bulk,
+    /// Regular application state
+    Normal,
+    /// State that must reach the disk as soon as possible, such as crash reports. Never rejected when the queue is full.
+    Critical,
+}
+
+/// Waits for the result of a save queued with [`SaveQueue::push`]
+#[derive(Debug)]
+pub struct SaveTicket(Receiver<Result<(), SaveError>>);
+
+impl SaveTicket {
+    /// Blocks until the save has run, returning its result. Fails with [`SaveError::SaveAborted`] if it stopped without
+    /// one, such as when the save panicked.
+    pub fn wait(self) -> Result<(), SaveError> {
+        self.0.recv().unwrap_or(Err(SaveError::SaveAborted))
+    }
+
+    /// Returns the result of the save if it has run, like [`SaveTicket::wait`]
+    pub fn try_result(&self) -> Option<Result<(), SaveError>> {
+        match self.0.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(SaveError::SaveAborted)),
+        }
+    }
+}
+
+struct Job {
+    priority: Priority,
+    sequence: u64,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    /// Higher priorities first, then oldest first
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    jobs: BinaryHeap<Job>,
+    sequence: u64,
+    running: bool,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// A background saver running queued saves one at a time in order of [`Priority`]. Pending saves are finished when it's
+/// dropped.
+pub struct SaveQueue {
+    shared: Arc<Shared>,
+    capacity: usize,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for SaveQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaveQueue")
+            .field("capacity", &self.capacity)
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+impl SaveQueue {
+    /// Starts a queue holding at most `capacity` pending saves below [`Priority::Critical`]
+    pub fn new(capacity: usize) -> Self {
+        let shared = Arc::new(Shared::default());
+        let worker_shared = shared.clone();
+
+        Self {
+            shared,
+            capacity,
+            worker: Some(std::thread::spawn(move || work(&worker_shared))),
+        }
+    }
+
+    /// Serializes `value` immediately and queues saving it to "[`root_location`][`crate::save::root_location`]/\<root\>".
+    ///
+    /// Returns [`SaveError::QueueFull`] as back-pressure if the queue already holds `capacity` pending saves, unless
+    /// `priority` is [`Priority::Critical`].
+    pub fn push<T: Serialize + ?Sized, P: Into<PathBuf>>(
+        &self,
+        value: &T,
+        root: P,
+        root_type: RootType,
+        priority: Priority,
+        options: SaveOptions,
+    ) -> Result<SaveTicket, SaveError> {
+        let root = root.into();
+        let options = config::configure(&options, &root, &root_type)?.into_owned();
+
+        let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+        serializer
+            .serialize_value(value)
+            .map_err(SaveError::SerializeError)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        let mut state = self.lock();
+        if priority != Priority::Critical && state.jobs.len() >= self.capacity {
+            return Err(SaveError::QueueFull {
+                pending: state.jobs.len(),
+            });
+        }
+
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.jobs.push(Job {
+            priority,
+            sequence,
+            run: Box::new(move || {
+                let _ = tx.send(save_root_with(serializer, root, root_type, &options));
+            }),
+        });
+        drop(state);
+
+        self.shared.changed.notify_all();
+        Ok(SaveTicket(rx))
+    }
+
+    /// The number of saves waiting to run
+    pub fn pending(&self) -> usize {
+        self.lock().jobs.len()
+    }
+
+    /// The number of saves of `priority` waiting to run
+    pub fn pending_with(&self, priority: Priority) -> usize {
+        self.lock()
+            .jobs
+            .iter()
+            .filter(|job| job.priority == priority)
+            .count()
+    }
+
+    /// Blocks until every queued save has run
+    pub fn flush(&self) {
+        let mut state = self.lock();
+        while !state.jobs.is_empty() || state.running {
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SaveQueue {
+    fn drop(&mut self) {
+        self.lock().stopped = true;
+        self.shared.changed.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        let job = loop {
+            if let Some(job) = state.jobs.pop() {
+                break job;
+            }
+            if state.stopped {
+                return;
+            }
+            state = shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        };
+        state.running = true;
+        drop(state);
+
+        (job.run)();
+
+        shared
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .running = false;
+        shared.changed.notify_all();
+    }
+}
@@ -0,0 +1,215 @@
+//! ## `binroots::quiet`
+//! Contains do-not-disturb windows, letting readers freeze a root during their critical sections. Saves to a quiet root
+//! are buffered instead of written, and flushed in order once the window ends.
+//!
+//! A window is opened either in-process with [`hold`][`crate::quiet::hold`], or by another process creating
+//! `<root>/.quiet` ([`QUIET_FILE`][`crate::quiet::QUIET_FILE`]), which is honored by saves using
+//! [`SaveOptions::quiet_file`][`crate::save::SaveOptions::quiet_file`]. Buffered saves are flushed when the last
+//! [`QuietWindow`][`crate::quiet::QuietWindow`] ends, by the next save to the root, or by
+//! [`flush`][`crate::quiet::flush`].
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::quiet;
+//! use binroots::save::{root_location, RootType, Save};
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("quiet-status");
+//! (1, 2).save("quiet-status", RootType::InMemory).unwrap();
+//!
+//! let window = quiet::hold("quiet-status", RootType::InMemory).unwrap();
+//! (3, 4).save("quiet-status", RootType::InMemory).unwrap();
+//! assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "1"); // Buffered
+//!
+//! assert_eq!(window.end().unwrap(), 1);
+//! assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "3");
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::fileserializer::FileSerializer;
+use crate::save::{
+    root_location, save_root_with, RootLocationError, RootType, SaveError, SaveOptions,
+};
+
+/// The name of the file marking a root as quiet, see [`SaveOptions::quiet_file`]
+pub const QUIET_FILE: &str = ".quiet";
+
+/// A save buffered while its root was quiet
+struct Pending {
+    serializer: FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: SaveOptions,
+}
+
+#[derive(Default)]
+struct Quiet {
+    windows: usize,
+    pending: Vec<Pending>,
+}
+
+/// The quiet state of every root with an open window or buffered saves, keyed by absolute path
+static QUIET: Mutex<BTreeMap<PathBuf, Quiet>> = Mutex::new(BTreeMap::new());
+
+fn lock() -> MutexGuard<'static, BTreeMap<PathBuf, Quiet>> {
+    QUIET.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An open do-not-disturb window created by [`hold`]. Saves to its root are buffered until it ends, either with
+/// [`QuietWindow::end`] or when it's dropped.
+#[derive(Debug)]
+#[must_use = "the window ends as soon as it's dropped"]
+pub struct QuietWindow {
+    path: PathBuf,
+    ended: bool,
+}
+
+impl QuietWindow {
+    /// Ends the window, flushing the buffered saves if no other window is open. Returns how many were flushed.
+    pub fn end(mut self) -> Result<usize, SaveError> {
+        self.ended = true;
+        release(&self.path)
+    }
+}
+
+impl Drop for QuietWindow {
+    fn drop(&mut self) {
+        if !self.ended {
+            let _ = release(&self.path);
+        }
+    }
+}
+
+fn release(path: &Path) -> Result<usize, SaveError> {
+    if let Some(quiet) = lock().get_mut(path) {
+        quiet.windows = quiet.windows.saturating_sub(1);
+    }
+
+    flush_at(path)
+}
+
+/// Opens a do-not-disturb window on "[`root_location`]/\<root\>". Windows nest: saves stay buffered until every open
+/// window has ended.
+pub fn hold<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+) -> Result<QuietWindow, RootLocationError> {
+    let path = root_location(root_type)?.join(root.into());
+    lock().entry(path.clone()).or_default().windows += 1;
+
+    Ok(QuietWindow { path, ended: false })
+}
+
+/// Whether saves to "[`root_location`]/\<root\>" are currently buffered by an open [`QuietWindow`] or a [`QUIET_FILE`]
+pub fn is_quiet<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<bool, RootLocationError> {
+    let path = root_location(root_type)?.join(root.into());
+
+    Ok(windows(&path) > 0 || path.join(QUIET_FILE).exists())
+}
+
+/// The number of saves to "[`root_location`]/\<root\>" buffered while it was quiet
+pub fn pending<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<usize, RootLocationError> {
+    let path = root_location(root_type)?.join(root.into());
+
+    Ok(lock().get(&path).map_or(0, |quiet| quiet.pending.len()))
+}
+
+/// Flushes the saves to "[`root_location`]/\<root\>" buffered while it was quiet, unless it still is. Returns how many
+/// were flushed. Call it after a reader removes the root's [`QUIET_FILE`] to publish without waiting for the next save.
+pub fn flush<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<usize, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into());
+
+    flush_at(&path)
+}
+
+/// Whether saves to the root at `path` were buffered while it was quiet
+#[cfg(feature = "async_save")]
+pub(crate) fn has_pending(path: &Path) -> bool {
+    lock()
+        .get(path)
+        .map_or(false, |quiet| !quiet.pending.is_empty())
+}
+
+/// Whether a save to the root at `path` would be buffered, or has to wait for buffered saves to be flushed first
+pub(crate) fn is_held(path: &Path, options: &SaveOptions) -> bool {
+    let quiet = lock();
+    let held = quiet.get(path).map_or(false, |quiet| {
+        quiet.windows > 0 || !quiet.pending.is_empty()
+    });
+
+    held || (options.quiet_file && path.join(QUIET_FILE).exists())
+}
+
+fn windows(path: &Path) -> usize {
+    lock().get(path).map_or(0, |quiet| quiet.windows)
+}
+
+/// Buffers the save of `serializer` to the root at `path` if the root is quiet, taking it out of `serializer`. Returns
+/// whether the save was buffered.
+pub(crate) fn defer(
+    path: &Path,
+    serializer: &mut FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> bool {
+    let mut quiet = lock();
+    let windows = quiet.get(path).map_or(0, |quiet| quiet.windows);
+
+    if windows == 0 && !(options.quiet_file && path.join(QUIET_FILE).exists()) {
+        return false;
+    }
+
+    quiet
+        .entry(path.to_path_buf())
+        .or_default()
+        .pending
+        .push(Pending {
+            serializer: std::mem::take(serializer),
+            root,
+            root_type,
+            options: options.clone(),
+        });
+
+    true
+}
+
+/// Saves everything buffered for the root at `path` in order, unless it's still quiet. Saves that weren't flushed because
+/// of an error stay buffered.
+pub(crate) fn flush_at(path: &Path) -> Result<usize, SaveError> {
+    let pending = {
+        let mut quiet = lock();
+        let Some(state) = quiet.get_mut(path) else {
+            return Ok(0);
+        };
+
+        let honors_file = state.pending.iter().any(|save| save.options.quiet_file);
+        if state.windows > 0 || (honors_file && path.join(QUIET_FILE).exists()) {
+            return Ok(0);
+        }
+
+        quiet
+            .remove(path)
+            .map(|state| state.pending)
+            .unwrap_or_default()
+    };
+
+    let mut pending = pending.into_iter();
+    let mut count = 0;
+    while let Some(save) = pending.next() {
+        if let Err(e) = save_root_with(save.serializer, save.root, save.root_type, &save.options) {
+            let mut quiet = lock();
+            let state = quiet.entry(path.to_path_buf()).or_default();
+            state.pending.splice(0..0, pending);
+            return Err(e);
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,385 @@
+//! ## `binroots::read`
+//! Contains [`leaf`][`crate::read::leaf`] and [`read_bytes`][`crate::read::read_bytes`] for reading individual values
+//! published by another process, resolving the same paths as [`save`][`crate::save`], [`locked_read`][`crate::read::locked_read`]
+//! for reading leaves written under a lock, and [`walk`][`crate::read::walk`] for traversing a saved root.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::read;
+//! use binroots::save::{RootType, Save};
+//! use binroots::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Status {
+//!     connections: usize,
+//!     is_online: bool,
+//! }
+//!
+//! Status { connections: 3, is_online: true }
+//!     .save("read-status", RootType::InMemory)
+//!     .unwrap();
+//!
+//! let connections: usize = read::leaf("read-status", "connections", RootType::InMemory).unwrap();
+//! assert_eq!(connections, 3);
+//! assert_eq!(read::read_bytes("read-status", "is_online", RootType::InMemory).unwrap(), b"true");
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::save::{root_location, RootLocationError, RootType};
+
+/// Errors while reading a saved value
+#[derive(Debug)]
+pub enum ReadError {
+    /// Returned when reading the file fails, including when it doesn't exist (such as a saved `None`)
+    ReadFileError {
+        /// The path that was read
+        path: PathBuf,
+        /// The resulting IO error kind.
+        ///
+        /// See [`std::io::ErrorKind`]
+        kind: std::io::ErrorKind,
+    },
+    /// Returned when the file isn't valid UTF-8 or can't be parsed as the requested type
+    ParseError {
+        /// The path that was read
+        path: PathBuf,
+        /// The error reported while parsing
+        message: String,
+    },
+    /// An error caught while retrieving the project's root directory.
+    ///
+    /// See [`RootLocationError`]
+    RootLocationError(RootLocationError),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadFileError { path, kind } => write!(f, "Failed to read {path:?}; {kind}"),
+            Self::ParseError { path, message } => write!(f, "Failed to parse {path:?}; {message}"),
+            Self::RootLocationError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// Resolves `path` below "[`root_location`]/\<root\>"
+fn resolve<R: Into<PathBuf>, P: AsRef<Path>>(
+    root: R,
+    path: P,
+    root_type: RootType,
+) -> Result<PathBuf, ReadError> {
+    Ok(root_location(root_type)
+        .map_err(ReadError::RootLocationError)?
+        .join(root.into())
+        .join(path))
+}
+
+/// Reads the raw contents of the file at `path` below "[`root_location`]/\<root\>", such as `activity` or `activity.value`
+pub fn read_bytes<R: Into<PathBuf>, P: AsRef<Path>>(
+    root: R,
+    path: P,
+    root_type: RootType,
+) -> Result<Vec<u8>, ReadError> {
+    let path = resolve(root, path, root_type)?;
+
+    std::fs::read(&path).map_err(|e| ReadError::ReadFileError {
+        path,
+        kind: e.kind(),
+    })
+}
+
+/// Reads the raw contents of the file at the absolute `path` while holding a shared lock on it, waiting for a save using
+/// [`SaveOptions::lock_leaves`][`crate::save::SaveOptions::lock_leaves`] to finish writing it first. Only locks on
+/// Unix; elsewhere, the file is read normally.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::read::locked_read;
+/// use binroots::save::{root_location, RootType, Save, SaveOptions};
+///
+/// let options = SaveOptions::default().lock_leaves(true);
+/// (1, "large value").save_with("locked-read", RootType::InMemory, &options).unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("locked-read");
+/// assert_eq!(locked_read(root.join("1")).unwrap(), b"large value");
+/// ```
+pub fn locked_read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, ReadError> {
+    use std::io::Read;
+
+    let path = path.as_ref();
+    let error = |e: std::io::Error| ReadError::ReadFileError {
+        path: path.into(),
+        kind: e.kind(),
+    };
+    let mut file = std::fs::File::open(path).map_err(error)?;
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY: `file` is open for the duration of the call. The lock is released when `file` is dropped.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH) } != 0 {
+            return Err(error(std::io::Error::last_os_error()));
+        }
+    }
+
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).map_err(error)?;
+
+    Ok(contents)
+}
+
+/// Reads the file at `path` below "[`root_location`]/\<root\>" and parses it with [`FromStr`]
+pub fn leaf<T, R, P>(root: R, path: P, root_type: RootType) -> Result<T, ReadError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    R: Into<PathBuf>,
+    P: AsRef<Path>,
+{
+    let path = resolve(root, path, root_type)?;
+    let contents = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData => ReadError::ParseError {
+            path: path.clone(),
+            message: e.to_string(),
+        },
+        kind => ReadError::ReadFileError {
+            path: path.clone(),
+            kind,
+        },
+    })?;
+
+    contents.parse().map_err(|e: T::Err| ReadError::ParseError {
+        path,
+        message: e.to_string(),
+    })
+}
+
+/// Reads `<path>.cbor` below "[`root_location`]/\<root\>", saved with
+/// [`LeafEncoding::Cbor`][`crate::fileserializer::LeafEncoding::Cbor`], and decodes it. Requires the `cbor` feature.
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "cbor")]
+/// # {
+/// use binroots::fileserializer::LeafEncoding;
+/// use binroots::read;
+/// use binroots::save::{RootType, Save, SaveOptions};
+///
+/// let options = SaveOptions::default().leaf_encoding(LeafEncoding::Cbor);
+/// (500u32, "cpu").save_with("read-cbor", RootType::InMemory, &options).unwrap();
+///
+/// let samples: u32 = read::cbor_leaf("read-cbor", "0", RootType::InMemory).unwrap();
+/// assert_eq!(samples, 500);
+/// # }
+/// ```
+#[cfg(feature = "cbor")]
+pub fn cbor_leaf<T, R, P>(root: R, path: P, root_type: RootType) -> Result<T, ReadError>
+where
+    T: serde::de::DeserializeOwned,
+    R: Into<PathBuf>,
+    P: AsRef<Path>,
+{
+    let mut path = resolve(root, path, root_type)?.into_os_string();
+    path.push(".cbor");
+    let path = PathBuf::from(path);
+
+    let contents = std::fs::read(&path).map_err(|e| ReadError::ReadFileError {
+        path: path.clone(),
+        kind: e.kind(),
+    })?;
+
+    ciborium::from_reader(contents.as_slice()).map_err(|e| ReadError::ParseError {
+        path,
+        message: e.to_string(),
+    })
+}
+
+/// What a [`Node`] represents, according to binroots' layout conventions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A file containing a single value
+    Leaf,
+    /// A folder whose entries are named by their index
+    Sequence,
+    /// A folder whose entries are named by their key or field name, including empty folders
+    Map,
+    /// A file containing the name of an enum variant, next to its [`NodeKind::VariantPayload`]
+    VariantTag,
+    /// The data of an enum variant, saved next to its [`NodeKind::VariantTag`] with an extension such as `.value` or `.json`
+    VariantPayload,
+    /// A file written by binroots itself rather than serialized, such as `.etag` at the root or a `<field>.mtime` sidecar
+    Metadata,
+}
+
+/// An entry yielded by [`walk`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The path of the entry relative to the root
+    pub path: PathBuf,
+    /// What the entry represents
+    pub kind: NodeKind,
+    /// Whether the entry is a folder
+    pub is_dir: bool,
+    /// How many folders deep the entry is, starting at `0` for entries directly inside the root
+    pub depth: usize,
+}
+
+/// An iterator over the entries of a saved root, created by [`walk`]. Folders are yielded before their contents.
+#[derive(Debug)]
+pub struct Walk {
+    base: PathBuf,
+    stack: Vec<Node>,
+}
+
+/// Walks everything saved below "[`root_location`]/\<root\>", classifying each entry by binroots' layout conventions.
+///
+/// Entries are yielded depth-first in sorted order. Symlinks are skipped, so links looping back into the root or pointing
+/// outside of it are never followed.
+///
+/// ## Example
+///
+/// ```
+/// use std::path::Path;
+///
+/// use binroots::read::{walk, NodeKind};
+/// use binroots::save::{RootType, Save};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// enum Activity {
+///     Playing(String),
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     activity: Activity,
+///     scores: Vec<u8>,
+/// }
+///
+/// Status { activity: Activity::Playing("hideo kame".into()), scores: vec![9, 8] }
+///     .save("walk-status", RootType::InMemory)
+///     .unwrap();
+///
+/// let nodes = walk("walk-status", RootType::InMemory)
+///     .unwrap()
+///     .map(|node| node.map(|node| (node.path, node.kind)))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(nodes[0], (Path::new("activity").into(), NodeKind::VariantTag));
+/// assert_eq!(nodes[1], (Path::new("activity.value").into(), NodeKind::VariantPayload));
+/// assert_eq!(nodes[2], (Path::new("scores").into(), NodeKind::Sequence));
+/// assert_eq!(nodes[3], (Path::new("scores/0").into(), NodeKind::Leaf));
+/// ```
+pub fn walk<R: Into<PathBuf>>(root: R, root_type: RootType) -> Result<Walk, ReadError> {
+    let mut walk = Walk {
+        base: resolve(root, "", root_type)?,
+        stack: Vec::new(),
+    };
+    walk.push_children(Path::new(""), 0)?;
+
+    Ok(walk)
+}
+
+impl Walk {
+    /// Classifies the entries of the folder at `dir` (relative to the root) and queues them in sorted order
+    fn push_children(&mut self, dir: &Path, depth: usize) -> Result<(), ReadError> {
+        let entries = list(&self.base.join(dir))?;
+        let names = entries
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        for (name, is_dir) in entries.into_iter().rev() {
+            let path = self.base.join(dir).join(&name);
+            let sibling_of = |ext: &str| {
+                name.strip_suffix(ext)
+                    .and_then(|stem| stem.strip_suffix('.'))
+                    .filter(|stem| names.iter().any(|n| n == stem))
+                    .is_some()
+            };
+
+            let kind = if (depth == 0 && name.starts_with('.')) || sibling_of("mtime") {
+                NodeKind::Metadata
+            } else if Path::new(&name)
+                .extension()
+                .map_or(false, |ext| sibling_of(&ext.to_string_lossy()))
+            {
+                NodeKind::VariantPayload
+            } else if is_dir {
+                let children = list(&path)?;
+                if !children.is_empty() && children.iter().all(|(n, _)| n.parse::<usize>().is_ok())
+                {
+                    NodeKind::Sequence
+                } else {
+                    NodeKind::Map
+                }
+            } else if names.iter().any(|n| {
+                n.strip_prefix(name.as_str())
+                    .and_then(|ext| ext.strip_prefix('.'))
+                    .map_or(false, |ext| !ext.contains('.') && ext != "mtime")
+            }) {
+                NodeKind::VariantTag
+            } else {
+                NodeKind::Leaf
+            };
+
+            self.stack.push(Node {
+                path: dir.join(&name),
+                kind,
+                is_dir,
+                depth,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<Node, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+
+        if node.is_dir {
+            if let Err(e) = self.push_children(&node.path.clone(), node.depth + 1) {
+                return Some(Err(e));
+            }
+        }
+
+        Some(Ok(node))
+    }
+}
+
+/// The sorted names of the entries inside `dir` and whether they're folders. Symlinks are skipped, so links looping back
+/// into the root or pointing outside of it are never followed.
+fn list(dir: &Path) -> Result<Vec<(String, bool)>, ReadError> {
+    let io_error = |e: std::io::Error| ReadError::ReadFileError {
+        path: dir.to_path_buf(),
+        kind: e.kind(),
+    };
+
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let file_type = entry.file_type().map_err(io_error)?;
+        if !file_type.is_symlink() {
+            entries.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                file_type.is_dir(),
+            ));
+        }
+    }
+    entries.sort();
+
+    Ok(entries)
+}
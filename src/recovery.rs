@@ -0,0 +1,242 @@
+//! ## `binroots::recovery`
+//! Contains [`recover`][`crate::recovery::recover`], which cleans up after saves interrupted by a crash or power loss:
+//! - `<root>.staging` and `<root>.old` folders left by [`Save::save_transactional`][`crate::save::Save::save_transactional`],
+//!   which marks them with a [`SWAP_MARKER`][`crate::recovery::SWAP_MARKER`] file. If the root itself went missing
+//!   mid-swap, the finished staging folder (or else the previous tree) takes its place.
+//! - Temporary files left inside the root by [`SaveOptions::atomic`][`crate::save::SaveOptions::atomic`] writes. Hidden
+//!   names ending in [`TEMP_EXTENSION`][`crate::backend::TEMP_EXTENSION`] are reserved for them.
+//!
+//! Folders and files that aren't marked as binroots' own are never touched, and neither are those of processes that are
+//! still running, so other writers can keep saving to the root. Only Unix can tell whether a process exited, so
+//! elsewhere nothing is cleaned up. Transactional saves and saves using
+//! [`SaveOptions::atomic`][`crate::save::SaveOptions::atomic`] run it once per root and process before writing, so
+//! their roots heal themselves on the next start. Call it directly to see what was cleaned up.
+//!
+//! ## Example
+//!
+//! ```
+//! # #[cfg(unix)] {
+//! use binroots::recovery::recover;
+//! use binroots::save::{root_location, RootType, Save};
+//!
+//! let mut crashed = std::process::Command::new("true").spawn().unwrap();
+//! crashed.wait().unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("recovery-status");
+//! (1, 2).save("recovery-status", RootType::InMemory).unwrap();
+//! let temp = root.join(format!(".0.{}.0.binroots-tmp", crashed.id()));
+//! std::fs::write(&temp, "half-writ").unwrap(); // Left behind by a crash
+//!
+//! let recovery = recover("recovery-status", RootType::InMemory).unwrap();
+//!
+//! assert_eq!(recovery.removed, vec![temp.clone()]);
+//! assert!(!temp.exists());
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::backend::TEMP_EXTENSION;
+use crate::save::{root_location, RootType, SaveError};
+
+static RECOVERED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// The file marking a `<root>.staging` or `<root>.old` folder as created by
+/// [`Save::save_transactional`][`crate::save::Save::save_transactional`], containing the id of the process that created
+/// it. [`recover`] leaves folders without it alone.
+pub const SWAP_MARKER: &str = ".binroots-swap";
+
+/// What [`recover`] cleaned up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recovery {
+    /// The `<root>.staging` or `<root>.old` folder moved back into place of a missing root
+    pub restored: Option<PathBuf>,
+    /// The leftover folders and temporary files that were removed
+    pub removed: Vec<PathBuf>,
+}
+
+impl Recovery {
+    /// Whether there was nothing to clean up
+    pub fn is_empty(&self) -> bool {
+        self.restored.is_none() && self.removed.is_empty()
+    }
+}
+
+/// Finishes or discards what interrupted saves left behind in "[`root_location`]/\<root\>". Staging folders and temporary
+/// files of processes that are still running are kept.
+pub fn recover<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<Recovery, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into());
+
+    recover_at(&path)
+}
+
+/// Runs [`recover`] on the root at `path` unless this process already did
+pub(crate) fn recover_once(path: &Path) -> Result<(), SaveError> {
+    let first = RECOVERED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashSet::new)
+        .insert(path.to_path_buf());
+
+    if first {
+        recover_at(path)?;
+    }
+
+    Ok(())
+}
+
+fn recover_at(live: &Path) -> Result<Recovery, SaveError> {
+    let staging = sibling(live, ".staging");
+    let old = sibling(live, ".old");
+    let mut recovery = Recovery::default();
+
+    // The live root is only moved away after the staging folder was saved completely
+    if !live.exists() && is_abandoned(&old) {
+        let finished = if is_abandoned(&staging) {
+            &staging
+        } else {
+            &old
+        };
+        std::fs::rename(finished, live).map_err(|e| SaveError::RenameError {
+            from: finished.clone(),
+            to: live.to_path_buf(),
+            source: e,
+            field: None,
+        })?;
+        recovery.restored = Some(finished.clone());
+    }
+
+    for leftover in [staging, old] {
+        if is_abandoned(&leftover) {
+            remove(&leftover, true)?;
+            recovery.removed.push(leftover);
+        }
+    }
+
+    // The new tree of a swap that was interrupted before it was unmarked
+    if is_abandoned(live) {
+        unmark(live)?;
+    }
+
+    remove_temp_files(live, &mut recovery.removed)?;
+
+    Ok(recovery)
+}
+
+/// Marks the folder `dir` as part of a swap by this process, see [`SWAP_MARKER`]
+pub(crate) fn mark(dir: &Path) -> Result<(), SaveError> {
+    let marker = dir.join(SWAP_MARKER);
+    std::fs::write(&marker, std::process::id().to_string()).map_err(|e| SaveError::WriteFileError {
+        path: marker,
+        contents: Vec::new(),
+        source: e,
+        field: None,
+    })
+}
+
+/// Removes the [`SWAP_MARKER`] of `dir`, if any
+pub(crate) fn unmark(dir: &Path) -> Result<(), SaveError> {
+    match std::fs::remove_file(dir.join(SWAP_MARKER)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(SaveError::DeleteFileError {
+            path: dir.join(SWAP_MARKER),
+            source: e,
+            field: None,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `dir` is marked with a [`SWAP_MARKER`] by a process that isn't running anymore
+fn is_abandoned(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join(SWAP_MARKER)).map_or(false, |pid| has_exited(pid.trim()))
+}
+
+/// Whether the process with the id `pid` is known to have exited. `false` for this process, for anything that isn't a
+/// process id, and on platforms other than Unix where binroots can't check, so recovery never touches the files of a
+/// save that might still be running.
+fn has_exited(pid: &str) -> bool {
+    let Ok(pid) = pid.parse::<u32>() else {
+        return false;
+    };
+    if pid == 0 || pid == std::process::id() {
+        return false;
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+
+        // SAFETY: signal `0` only checks whether `pid` exists and may be signalled, without sending anything
+        let result = unsafe { libc::kill(pid, 0) };
+        result != 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        false
+    }
+}
+
+/// Whether `name` is the temporary file of an atomic write by a process that isn't running anymore. The process is
+/// taken from ids made by [`ProcessIds`][`crate::clock::ProcessIds`], `<pid>.<counter>`, so temporary files named by
+/// other [`IdGen`][`crate::clock::IdGen`]s are kept.
+fn is_abandoned_temp(name: &str) -> bool {
+    let Some(stem) = name
+        .strip_prefix('.')
+        .and_then(|name| name.strip_suffix(TEMP_EXTENSION))
+        .and_then(|name| name.strip_suffix('.'))
+    else {
+        return false;
+    };
+
+    let mut parts = stem.rsplit('.');
+    let _counter = parts.next();
+    parts.next().map_or(false, has_exited)
+}
+
+/// `path` with `suffix` appended to its last component
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_os_string();
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+/// Removes the temporary files of atomic writes below `dir`, recording them in `removed`
+fn remove_temp_files(dir: &Path, removed: &mut Vec<PathBuf>) -> Result<(), SaveError> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    let mut entries = entries.flatten().collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type().map_or(false, |t| t.is_dir()) {
+            remove_temp_files(&path, removed)?;
+        } else if is_abandoned_temp(&entry.file_name().to_string_lossy()) {
+            remove(&path, false)?;
+            removed.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn remove(path: &Path, dir: bool) -> Result<(), SaveError> {
+    let result = if dir {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+
+    result.map_err(|e| SaveError::DeleteFileError {
+        path: path.to_path_buf(),
+        source: e,
+        field: None,
+    })
+}
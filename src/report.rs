@@ -0,0 +1,145 @@
+//! ## `binroots::report`
+//! Contains [`SaveReport`][`crate::report::SaveReport`], returned by
+//! [`Save::save_reporting`][`crate::save::Save::save_reporting`] to describe what a save did, so callers can log it or
+//! expose it as metrics.
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::save::{root_location, RootType, Save};
+//!
+//! let report = ("idle", 0).save_reporting("report-status", RootType::InMemory).unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("report-status");
+//! assert!(report.written.contains(&root.join("0")));
+//! assert!(report.bytes_written >= 5);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::clock::IdGen;
+
+/// What a save did. Paths are absolute and listed in the order they were touched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveReport {
+    /// The files written, including bookkeeping files such as
+    /// [`METADATA_FILE`][`crate::metadata::METADATA_FILE`]
+    pub written: Vec<PathBuf>,
+    /// The files and folders removed or moved to the trash. A removed folder is listed once, without its contents
+    pub deleted: Vec<PathBuf>,
+    /// The total size of the files written
+    pub bytes_written: u64,
+    /// How long the save took, measured with [`SaveOptions::clock`][`crate::save::SaveOptions::clock`]
+    pub duration: Duration,
+}
+
+/// A [`Backend`] recording the operations it forwards to `inner` into a [`SaveReport`]
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    inner: Arc<dyn Backend>,
+    report: Mutex<SaveReport>,
+}
+
+impl Recorder {
+    pub(crate) fn new(inner: Arc<dyn Backend>) -> Self {
+        Self {
+            inner,
+            report: Mutex::new(SaveReport::default()),
+        }
+    }
+
+    /// Takes the report recorded so far
+    pub(crate) fn take(&self) -> SaveReport {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SaveReport> {
+        self.report.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn written(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        result: std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        if result.is_ok() {
+            let mut report = self.lock();
+            report.written.push(path.to_path_buf());
+            report.bytes_written += contents.len() as u64;
+        }
+        result
+    }
+
+    fn deleted(&self, path: &Path, result: std::io::Result<()>) -> std::io::Result<()> {
+        if result.is_ok() {
+            self.lock().deleted.push(path.to_path_buf());
+        }
+        result
+    }
+}
+
+impl Backend for Recorder {
+    fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.written(path, contents, self.inner.write(path, contents))
+    }
+
+    fn mkdir(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.mkdir(path)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.deleted(path, self.inner.remove(path))
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        self.deleted(path, self.inner.remove_dir(path))
+    }
+
+    // Saves only rename to move entries into the trash
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.deleted(from, self.inner.rename(from, to))
+    }
+
+    fn write_uncached(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.written(path, contents, self.inner.write_uncached(path, contents))
+    }
+
+    fn write_locked(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        self.written(path, contents, self.inner.write_locked(path, contents))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.inner.set_mode(path, mode)
+    }
+
+    fn create_with_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        self.inner.create_with_mode(path, mode)
+    }
+
+    fn sync(&self, path: &Path) -> std::io::Result<()> {
+        self.inner.sync(path)
+    }
+
+    fn is_filesystem(&self) -> bool {
+        self.inner.is_filesystem()
+    }
+
+    fn write_atomic(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        temp_dir: Option<&Path>,
+        ids: &dyn IdGen,
+        mode: Option<u32>,
+    ) -> std::io::Result<()> {
+        self.written(
+            path,
+            contents,
+            self.inner.write_atomic(path, contents, temp_dir, ids, mode),
+        )
+    }
+}
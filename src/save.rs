@@ -1,28 +1,71 @@
 //! ## `binroots::save`
 //!
-//! Contains the [`SaveError`][`crate::save::SaveError`] struct and the [`Save`][`crate::save::Save`] trait, as well as
+//! Contains the [`SaveError`][`crate::save::SaveError`] struct, the [`Save`][`crate::save::Save`] trait and its [`SaveOptions`][`crate::save::SaveOptions`], as well as
 //! an implementation of `save` for [`BinrootsField`][`crate::field::BinrootsField`]'
+//!
+//! Re-exports [`Backend`][`crate::save::Backend`] and [`Filesystem`][`crate::save::Filesystem`] from
+//! [`binroots::backend`][`crate::backend`], for plugging alternative sinks into [`SaveOptions::backend`].
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::{ErrorKind, Write};
-use std::path::PathBuf;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::ControlFlow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
 use serde::Serialize;
 
-use crate::field::BinrootsField;
-use crate::fileserializer::{FileOperationHint, FileSerializer, SerializerError};
+use crate::backend::TEMP_EXTENSION;
+pub use crate::backend::{Backend, Filesystem};
+use crate::clock::{self, Clock, IdGen};
+use crate::config;
+use crate::dircache::{CachedDirs, DirCache};
+use crate::field::{field_impl, BinrootsField};
+use crate::fileserializer::{
+    EnumRepresentation, FieldFormat, FileOperationHint, FileSerializer, LeafEncoding, Markers,
+    PlannedWrite, SerializeOptions, SerializerError, Stream,
+};
+use crate::health;
+use crate::hooks::{self, LeafChanges, SaveContext};
+use crate::metadata;
+use crate::naming::NamingPolicy;
+use crate::plan::Op;
+use crate::quiet;
+use crate::recovery;
+use crate::report::Recorder;
+pub use crate::report::SaveReport;
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::stats;
+use crate::trace::{event, span};
 
 /// Passed to [`Save::save`] to decide which path to save files to
 #[derive(Debug, Clone)]
 pub enum RootType {
     /// Saves to an in-memory location:
-    /// - On Unix, `/tmp/<CARGO_PKG_NAME>/`
+    /// - On Unix, `$TMPDIR/<CARGO_PKG_NAME>/`, falling back to `/tmp/<CARGO_PKG_NAME>/`
     /// - On Windows, `%LOCALAPPDATA%\<CARGO_PKG_NAME>\.memcache\`
     InMemory,
     /// Saves to a persistent location:
-    /// - On Unix, `$HOME/.cache/<CARGO_PKG_NAME>/`
+    /// - On Unix, `$XDG_CACHE_HOME/<CARGO_PKG_NAME>/`, falling back to `$HOME/.cache/<CARGO_PKG_NAME>/`
     /// - On Windows, `%LOCALAPPDATA%\<CARGO_PKG_NAME>\.cache\`
     Persistent,
+    /// Saves settings the user expects to keep:
+    /// - On Unix, `$XDG_CONFIG_HOME/<CARGO_PKG_NAME>/`, falling back to `$HOME/.config/<CARGO_PKG_NAME>/`
+    /// - On macOS, `$HOME/Library/Application Support/<CARGO_PKG_NAME>/config/`
+    /// - On Windows, `%APPDATA%\<CARGO_PKG_NAME>\config\`
+    Config,
+    /// Saves data the user expects to keep:
+    /// - On Unix, `$XDG_DATA_HOME/<CARGO_PKG_NAME>/`, falling back to `$HOME/.local/share/<CARGO_PKG_NAME>/`
+    /// - On macOS, `$HOME/Library/Application Support/<CARGO_PKG_NAME>/data/`
+    /// - On Windows, `%APPDATA%\<CARGO_PKG_NAME>\data\`
+    Data,
+    /// Saves state that only lives as long as the user's session, such as sockets or a daemon's status:
+    /// - On Unix, `$XDG_RUNTIME_DIR/<CARGO_PKG_NAME>/`, falling back to the [`RootType::InMemory`] location
+    /// - On macOS, `$TMPDIR/<CARGO_PKG_NAME>/`
+    /// - On Windows, `%TEMP%\<CARGO_PKG_NAME>\`
+    Runtime,
 }
 
 /// Errors during the save process.
@@ -32,28 +75,41 @@ pub enum SaveError {
     CreateDirectoryError {
         /// The path where `save` attempted to create folders
         path: PathBuf,
-        /// The resulting IO error kind.
-        ///
-        /// See [`std::io::ErrorKind`]
-        kind: std::io::ErrorKind,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
     },
     /// Returned when `save` fails to call [`std::fs::File::create`]
     CreateFileError {
         /// The path where `save` attempted to create a file
         path: PathBuf,
-        /// The resulting IO error kind.
-        ///
-        /// See [`std::io::ErrorKind`]
-        kind: std::io::ErrorKind,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
     },
     /// Returned when `save` fails to call [`std::fs::remove_file`]
     DeleteFileError {
         /// The path where `save` attempted to delete a file
         path: PathBuf,
-        /// The resulting IO error kind.
-        ///
-        /// See [`std::io::ErrorKind`]
-        kind: std::io::ErrorKind,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
+    },
+    /// Returned when save fails to read back a file or folder below the root
+    ReadFileError {
+        /// The path where `save` attempted to read
+        path: PathBuf,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
     },
     /// Returned when save fails to write to a file that's already been `create`d
     /// - `contents` will only appear when reporting the error with `Debug`
@@ -62,10 +118,11 @@ pub enum SaveError {
         path: PathBuf,
         /// The contents that `save` attempted to write into the file. Won't be reported when [`Display`][`std::fmt::Display`]ing the error.
         contents: Vec<u8>,
-        /// The resulting IO error kind.
-        ///
-        /// See [`std::io::ErrorKind`]
-        kind: std::io::ErrorKind,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
     },
     /// An error caught during binroots's serialization process.
     ///
@@ -75,6 +132,97 @@ pub enum SaveError {
     ///
     /// See [`RootLocationError`]
     RootLocationError(RootLocationError),
+    /// Returned when the program's root directory fails [`verify_root`]. Disable the check with [`SaveOptions::verify_root`].
+    UntrustedRoot {
+        /// The program's root directory
+        path: PathBuf,
+        /// Why the directory isn't trusted
+        reason: UntrustedRootReason,
+    },
+    /// Returned when a pre-save hook registered with [`hooks::register_pre_save`] vetoes the save
+    Vetoed {
+        /// The reason given by the hook
+        reason: String,
+    },
+    /// Returned when `save` would write through a symlink while using [`SymlinkPolicy::Error`]
+    SymlinkError {
+        /// The path of the symlink
+        path: PathBuf,
+    },
+    /// Returned when the root's generation counter advanced past [`SaveOptions::expected_generation`], meaning another
+    /// writer saved since the caller last read it
+    Conflict {
+        /// The generation the caller expected
+        expected: u64,
+        /// The generation found on disk
+        found: u64,
+    },
+    /// Returned by [`SaveQueue::push`][`crate::queue::SaveQueue::push`] as back-pressure when the queue is full
+    QueueFull {
+        /// The number of saves waiting to run
+        pending: usize,
+    },
+    /// Returned when saving part of a root whose folder doesn't exist, such as after a user deleted it mid-run, while using
+    /// [`RootMissing::Error`] or [`RootMissing::Resave`] without a registered full-state provider
+    RootMissing {
+        /// The missing root folder
+        path: PathBuf,
+    },
+    /// Returned when [`naming::migrate`][`crate::naming::migrate`] fails to rename a file or folder, including when the
+    /// new name is already taken
+    RenameError {
+        /// The path being renamed
+        from: PathBuf,
+        /// The path it was renamed to
+        to: PathBuf,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
+    },
+    /// Returned when [`SaveOptions::fsync`] fails to flush a file or folder to durable storage
+    SyncError {
+        /// The path being flushed
+        path: PathBuf,
+        /// The resulting IO error
+        source: std::io::Error,
+        /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`, or
+        /// `None` if the operation didn't belong to a single value
+        field: Option<String>,
+    },
+    /// Returned when the root can't be written to, such as on a read-only filesystem, while using
+    /// [`ReadOnlyPolicy::Error`]
+    ReadOnlyRoot {
+        /// The root's folder, or the folder above it that can't be written to
+        path: PathBuf,
+    },
+    /// Returned by [`save_fields_with`] when asked to save a field the value doesn't have
+    UnknownField {
+        /// The requested field
+        name: String,
+    },
+    /// Returned by [`namespace::reserve`][`crate::namespace::reserve`] when two types save to the same root folder
+    RootCollision {
+        /// The root's folder
+        path: PathBuf,
+        /// The type that reserved the folder first
+        first: String,
+        /// The type that tried to reserve it again
+        second: String,
+    },
+    /// Returned by saves using [`SaveOptions::best_effort`] when one or more files failed, in the order they were
+    /// attempted
+    SaveErrors(Vec<SaveError>),
+    /// Returned by [`delete`] when the root is empty, absolute or contains `.` or `..`, so removing it would remove the
+    /// whole root location or a folder outside of it
+    InvalidRoot {
+        /// The root as passed to [`delete`]
+        root: PathBuf,
+    },
+    /// Returned by [`SaveTicket::wait`][`crate::queue::SaveTicket::wait`] when a queued save stopped without a result,
+    /// such as when it panicked
+    SaveAborted,
 }
 
 impl std::fmt::Display for SaveError {
@@ -83,22 +231,132 @@ impl std::fmt::Display for SaveError {
             f,
             "{}",
             match self {
-                Self::CreateDirectoryError { path, kind } =>
-                    format!("Failed to create directory at {path:?} during save; {kind}"),
-                Self::CreateFileError { path, kind } =>
-                    format!("Failed to create (open) file at {path:?} during save; {kind}"),
-                Self::DeleteFileError { path, kind } =>
-                    format!("Failed to delete a file at {path:?} during save; {kind}"),
-                Self::WriteFileError { path, kind, .. } =>
-                    format!("Faile to write to {path:?} during save; {kind}"),
+                Self::CreateDirectoryError { path, field, .. } =>
+                    format!("Failed to create directory at {path:?}{} during save", in_field(field)),
+                Self::CreateFileError { path, field, .. } => format!(
+                    "Failed to create (open) file at {path:?}{} during save",
+                    in_field(field)
+                ),
+                Self::DeleteFileError { path, field, .. } =>
+                    format!("Failed to delete a file at {path:?}{} during save", in_field(field)),
+                Self::ReadFileError { path, field, .. } =>
+                    format!("Failed to read {path:?}{} during save", in_field(field)),
+                Self::WriteFileError { path, field, .. } =>
+                    format!("Failed to write to {path:?}{} during save", in_field(field)),
                 Self::SerializeError(e) => format!("Failed to serialize during save: {e}"),
                 Self::RootLocationError(e) => format!("{e}"),
+                Self::UntrustedRoot { path, reason } =>
+                    format!("Refusing to save into the untrusted root directory {path:?}; {reason}"),
+                Self::Vetoed { reason } => format!("A pre-save hook vetoed the save: {reason}"),
+                Self::SymlinkError { path } =>
+                    format!("Refusing to write through the symlink at {path:?} during save"),
+                Self::Conflict { expected, found } => format!(
+                    "Expected generation {expected} during save, but another writer advanced it to {found}"
+                ),
+                Self::QueueFull { pending } =>
+                    format!("The save queue is full with {pending} pending saves"),
+                Self::RootMissing { path } =>
+                    format!("Refusing to save part of the missing root {path:?}"),
+                Self::RenameError { from, to, field, .. } =>
+                    format!("Failed to rename {from:?} to {to:?}{}", in_field(field)),
+                Self::SyncError { path, field, .. } =>
+                    format!("Failed to flush {path:?}{} to disk during save", in_field(field)),
+                Self::ReadOnlyRoot { path } =>
+                    format!("Can't save to {path:?}, which is read-only"),
+                Self::UnknownField { name } =>
+                    format!("Failed to save the field {name:?}, which doesn't exist"),
+                Self::RootCollision { path, first, second } =>
+                    format!("Both {first} and {second} save to {path:?}"),
+                Self::SaveErrors(errors) => format!(
+                    "{} files failed to save: {}",
+                    errors.len(),
+                    errors
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+                Self::InvalidRoot { root } => format!(
+                    "Refusing to delete {root:?}, which isn't a relative path below the root location"
+                ),
+                Self::SaveAborted => "The queued save stopped without finishing".into(),
             }
         )
     }
 }
 
-impl std::error::Error for SaveError {}
+/// Describes the logical path of a failed operation inside [`SaveError`]'s messages
+fn in_field(field: &Option<String>) -> String {
+    field
+        .as_ref()
+        .map_or_else(String::new, |field| format!(" (saving {field})"))
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SerializeError(e) => Some(e),
+            Self::RootLocationError(e) => Some(e),
+            Self::SaveErrors(errors) => errors.first().map(|e| e as _),
+            _ => self.io_error().map(|e| e as _),
+        }
+    }
+}
+
+impl SaveError {
+    /// The IO error kind that caused the error, if it was caused by a filesystem operation
+    pub fn kind(&self) -> Option<std::io::ErrorKind> {
+        match self {
+            Self::RootLocationError(RootLocationError::CreateDirectoryError { kind, .. }) => {
+                Some(*kind)
+            }
+            _ => self.io_error().map(std::io::Error::kind),
+        }
+    }
+
+    /// The IO error that caused the error, including its OS error code, if it was caused by a filesystem operation
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            Self::CreateDirectoryError { source, .. }
+            | Self::CreateFileError { source, .. }
+            | Self::DeleteFileError { source, .. }
+            | Self::ReadFileError { source, .. }
+            | Self::WriteFileError { source, .. }
+            | Self::RenameError { source, .. }
+            | Self::SyncError { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// The logical path of the value being saved when the error occurred, such as `status/activity.value/0`
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Self::CreateDirectoryError { field, .. }
+            | Self::CreateFileError { field, .. }
+            | Self::DeleteFileError { field, .. }
+            | Self::ReadFileError { field, .. }
+            | Self::WriteFileError { field, .. }
+            | Self::RenameError { field, .. }
+            | Self::SyncError { field, .. } => field.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Records `value` as the logical path of the value being saved, unless the error already has one
+    pub(crate) fn in_field(mut self, value: &Path) -> Self {
+        if let Self::CreateDirectoryError { field, .. }
+        | Self::CreateFileError { field, .. }
+        | Self::DeleteFileError { field, .. }
+        | Self::ReadFileError { field, .. }
+        | Self::WriteFileError { field, .. }
+        | Self::RenameError { field, .. }
+        | Self::SyncError { field, .. } = &mut self
+        {
+            field.get_or_insert_with(|| value.to_string_lossy().into_owned());
+        }
+        self
+    }
+}
 
 /// Provides data with the ability to save to the disk.
 ///
@@ -145,105 +403,210 @@ pub trait Save {
     /// [`Serialize`][`serde::Serialize`]s and saves data to "[BINROOTS_DIR][`crate::BINROOTS_DIR`]/\<root\>"
     ///
     /// See [`Save`][`crate::save::Save`] for an example of how to use it.
-    fn save<P: Into<PathBuf>>(&self, root: P, root_type: RootType) -> Result<(), SaveError>;
-}
-
-impl<T: Serialize> Save for T {
     fn save<P: Into<PathBuf>>(&self, root: P, root_type: RootType) -> Result<(), SaveError> {
-        let mut serializer = FileSerializer::default();
-        self.serialize(&mut serializer)
-            .map_err(|e| SaveError::SerializeError(e))?;
+        self.save_with(root, root_type, &SaveOptions::default())
+    }
 
-        save_root(serializer, root.into(), root_type)
+    /// Like [`Save::save`], tuned by `options`
+    fn save_with<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<(), SaveError> {
+        self.save_into(&mut FileSerializer::default(), root, root_type, options)
     }
-}
 
-impl<const N: &'static str, T: Serialize> BinrootsField<N, T> {
-    /// [`Serialize`][`serde::Serialize`]s and saves data to "[BINROOTS_DIR][`crate::BINROOTS_DIR`]/\<root\>"
+    /// Like [`Save::save_with`], but serializes into `serializer` after [resetting][`FileSerializer::reset`] it, so
+    /// programs saving many times per second can reuse its buffers across saves
+    ///
+    /// ## Example
     ///
-    /// Modifies the root save path by appending `BinrootsField::N` (generated as the field name by [`binroots::binroots_struct`][`crate::binroots_struct`])
-    pub fn save<P: Into<PathBuf>>(&self, root: P, root_type: RootType) -> Result<(), SaveError> {
-        let mut serializer = FileSerializer::default();
-        serializer.root = format!("/{N}");
-        self.value
-            .serialize(&mut serializer)
-            .map_err(|e| SaveError::SerializeError(e))?;
+    /// ```
+    /// use binroots::fileserializer::FileSerializer;
+    /// use binroots::save::{RootType, Save, SaveOptions};
+    ///
+    /// let options = SaveOptions::default();
+    /// let mut serializer = FileSerializer::default();
+    ///
+    /// for frame in 0..60 {
+    ///     (frame, "rendering").save_into(&mut serializer, "frames", RootType::InMemory, &options).unwrap();
+    /// }
+    /// ```
+    fn save_into<P: Into<PathBuf>>(
+        &self,
+        serializer: &mut FileSerializer,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<(), SaveError>;
 
-        save_root(serializer, root.into(), root_type)
+    /// Like [`Save::save`], but returns a [`SaveReport`] listing the files written and deleted, the bytes written and
+    /// how long the save took
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use binroots::save::{RootType, Save};
+    ///
+    /// vec![1, 2, 3].save("reporting", RootType::InMemory).unwrap();
+    /// let report = vec![4, 5].save_reporting("reporting", RootType::InMemory).unwrap();
+    ///
+    /// assert!(report.deleted.iter().any(|path| path.ends_with("reporting/2")));
+    /// println!("wrote {} bytes in {:?}", report.bytes_written, report.duration);
+    /// ```
+    fn save_reporting<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+    ) -> Result<SaveReport, SaveError> {
+        self.save_reporting_with(root, root_type, &SaveOptions::default())
     }
-}
 
-pub(crate) fn save_root(
-    serializer: FileSerializer,
-    root: PathBuf,
-    root_type: RootType,
-) -> Result<(), SaveError> {
-    let path = root_location(root_type.clone())
-        .map_err(|e| SaveError::RootLocationError(e))?
-        .join(root);
-
-    for file in serializer.output {
-        let path = &PathBuf::from(format!(
-            "{}{}",
-            path.to_string_lossy().trim_end_matches('/'),
-            if let Some(folder_variant) = file.folder_variant.clone() {
-                format!(".{folder_variant}")
-            } else {
-                format!("")
-            }
-        ));
+    /// Like [`Save::save_reporting`], tuned by `options`. Operations performed outside [`SaveOptions::backend`], such as
+    /// the folder swap of [`Save::save_transactional`], aren't reported
+    fn save_reporting_with<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<SaveReport, SaveError> {
+        let recorder = Arc::new(Recorder::new(options.backend.clone()));
+        let reporting = options.clone().backend(recorder.clone());
 
-        let file_path = if let Some(name) = &file.name {
-            path.join(PathBuf::from(
-                format!("{}/{}", &file.path.trim_matches('/'), name).trim_start_matches("/"),
-            ))
-        } else {
-            path.join(PathBuf::from(&file.path.trim_matches('/')))
-        };
+        let started = options.clock.now();
+        self.save_with(root, root_type, &reporting)?;
+
+        Ok(SaveReport {
+            duration: options
+                .clock
+                .now()
+                .duration_since(started)
+                .unwrap_or_default(),
+            ..recorder.take()
+        })
+    }
+
+    /// Like [`Save::save`], but fails with [`SaveError::Conflict`] unless the root's [`generation`] is still `expected`.
+    /// On success, the generation becomes `expected + 1`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use binroots::save::{generation, RootType, Save, SaveError};
+    ///
+    /// let current = generation("counter", RootType::InMemory).unwrap();
+    /// (1, 2).save_if_generation("counter", RootType::InMemory, current).unwrap();
+    ///
+    /// // Another writer that read `current` before the save above lost the race
+    /// assert!(matches!(
+    ///     (3, 4).save_if_generation("counter", RootType::InMemory, current),
+    ///     Err(SaveError::Conflict { .. })
+    /// ));
+    /// ```
+    fn save_if_generation<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        expected: u64,
+    ) -> Result<(), SaveError> {
+        self.save_with(
+            root,
+            root_type,
+            &SaveOptions::default().expected_generation(expected),
+        )
+    }
+
+    /// Like [`Save::save`], but saves into a fresh `<root>.staging` folder next to the root and then swaps it with the
+    /// live root, so a crash mid-save can never leave a tree mixing old and new files. Hidden files of the live root,
+    /// such as [`METADATA_FILE`][`crate::metadata::METADATA_FILE`], are carried over.
+    ///
+    /// On Linux, both folders are exchanged atomically with `renameat2(RENAME_EXCHANGE)`. Elsewhere, the live root is
+    /// briefly moved to `<root>.old` before the staging folder takes its place.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use binroots::save::{root_location, RootType, Save};
+    ///
+    /// (1, 2, 3).save("transactional", RootType::InMemory).unwrap();
+    /// (4, 5).save_transactional("transactional", RootType::InMemory).unwrap();
+    ///
+    /// let root = root_location(RootType::InMemory).unwrap().join("transactional");
+    /// assert_eq!(std::fs::read_to_string(root.join("0")).unwrap(), "4");
+    /// assert!(!root.join("2").exists()); // Nothing is left over from the previous save
+    /// ```
+    fn save_transactional<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+    ) -> Result<(), SaveError> {
+        self.save_transactional_with(root, root_type, &SaveOptions::default())
+    }
 
-        if file.hint == FileOperationHint::DeleteValue {
-            rmdir(file_path.with_extension("value"))?;
-            rm(file_path.with_extension("value"))?;
+    /// Like [`Save::save_transactional`], tuned by `options`. Hooks and options such as [`SaveOptions::stats`] apply to
+    /// the staging folder. Always operates on the real filesystem, regardless of [`SaveOptions::backend`]
+    fn save_transactional_with<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<(), SaveError> {
+        let root = root.into();
+        let mut staging_root = root.clone().into_os_string();
+        staging_root.push(".staging");
+        let staging_root = PathBuf::from(staging_root);
+
+        let location = root_location(root_type.clone()).map_err(SaveError::RootLocationError)?;
+        let live = location.join(&root);
+        let staging = location.join(&staging_root);
+
+        recovery::recover_once(&live)?;
+        prepare_staging(&live, &staging)?;
+        self.save_with(staging_root, root_type, options)?;
+        swap_staging(&staging, &live)?;
+
+        if options.fsync {
+            sync(&*options.backend, live.parent().unwrap_or(&location))?;
         }
 
-        if !file.is_path {
-            std::fs::create_dir_all(if &file_path != path {
-                &path
-            } else {
-                path.parent().unwrap()
+        Ok(())
+    }
+}
+
+/// Empties and marks `staging`, and copies the hidden files of `live` into it
+fn prepare_staging(live: &Path, staging: &Path) -> Result<(), SaveError> {
+    match std::fs::remove_dir_all(staging) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(SaveError::DeleteFileError {
+                path: staging.into(),
+                source: e,
+                field: None,
             })
-            .map_err(|e| SaveError::CreateDirectoryError {
-                path: path.clone(),
-                kind: e.kind(),
-            })?;
+        }
+        _ => {}
+    }
+    std::fs::create_dir_all(staging).map_err(|e| SaveError::CreateDirectoryError {
+        path: staging.into(),
+        source: e,
+        field: None,
+    })?;
+    recovery::mark(staging)?;
 
-            if file.hint == FileOperationHint::Delete {
-                rm(file_path)?;
-            } else {
-                let filename = format!(
-                    "{}{}",
-                    file_path.to_string_lossy().trim_end_matches('/'),
-                    if let Some(ext) = &file.variant {
-                        format!(".{ext}")
-                    } else {
-                        format!("")
-                    }
-                );
-                save_to(filename.into(), file.output)?;
-            }
-        } else {
-            std::fs::create_dir_all(format!(
-                "{}{}",
-                file_path.to_string_lossy().trim_end_matches('/'),
-                if let Some(ext) = &file.variant {
-                    format!(".{ext}")
-                } else {
-                    format!("")
-                }
-            ))
-            .map_err(|e| SaveError::CreateDirectoryError {
-                path: file_path,
-                kind: e.kind(),
+    let Ok(entries) = std::fs::read_dir(live) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let hidden = name.starts_with('.')
+            && name != recovery::SWAP_MARKER
+            && !name.ends_with(TEMP_EXTENSION);
+        if hidden && entry.file_type().map_or(false, |t| t.is_file()) {
+            let target = staging.join(entry.file_name());
+            std::fs::copy(entry.path(), &target).map_err(|e| SaveError::CreateFileError {
+                path: target,
+                source: e,
+                field: None,
             })?;
         }
     }
@@ -251,120 +614,2497 @@ pub(crate) fn save_root(
     Ok(())
 }
 
-fn rmdir(path: PathBuf) -> Result<(), SaveError> {
-    std::fs::remove_dir_all(path.clone()).map_or_else(
-        |e| {
-            let kind = e.kind();
+/// Replaces `live` with `staging`, removing the previous tree. Both are marked for [`recovery`] until the swap finishes.
+fn swap_staging(staging: &Path, live: &Path) -> Result<(), SaveError> {
+    let rename_error = |from: &Path, to: &Path, source| SaveError::RenameError {
+        from: from.into(),
+        to: to.into(),
+        source,
+        field: None,
+    };
+    let remove = |path: &Path| {
+        std::fs::remove_dir_all(path).map_err(|e| SaveError::DeleteFileError {
+            path: path.into(),
+            source: e,
+            field: None,
+        })
+    };
 
-            match kind {
-                ErrorKind::NotFound => Ok(()),
-                ErrorKind::NotADirectory => Ok(()),
-                _ => Err(SaveError::DeleteFileError { path, kind }),
-            }
-        },
-        |_| Ok(()),
-    )
-}
+    if !live.exists() {
+        std::fs::rename(staging, live).map_err(|e| rename_error(staging, live, e))?;
+        return recovery::unmark(live);
+    }
+    recovery::mark(live)?;
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
 
-fn rm(path: PathBuf) -> Result<(), SaveError> {
-    std::fs::remove_file(path.to_string_lossy().trim_end_matches('/')).map_or_else(
-        |e| {
-            let kind = e.kind();
+        if let (Ok(from), Ok(to)) = (
+            CString::new(staging.as_os_str().as_bytes()),
+            CString::new(live.as_os_str().as_bytes()),
+        ) {
+            // SAFETY: `from` and `to` are `CString`s, so both pointers are nul-terminated, and they're owned by this
+            // block, so the pointers stay valid until `renameat2` returns. `AT_FDCWD` is always a valid directory fd
+            let exchanged = unsafe {
+                libc::renameat2(
+                    libc::AT_FDCWD,
+                    from.as_ptr(),
+                    libc::AT_FDCWD,
+                    to.as_ptr(),
+                    libc::RENAME_EXCHANGE,
+                )
+            } == 0;
 
-            match kind {
-                ErrorKind::NotFound => Ok(()),
-                _ => Err(SaveError::DeleteFileError { path, kind }),
+            // The staging folder now holds the previous tree. Filesystems without support fall back to renaming.
+            if exchanged {
+                recovery::unmark(live)?;
+                return remove(staging);
             }
-        },
-        |_| Ok(()),
-    )
-}
+        }
+    }
 
-fn save_to(path: PathBuf, contents: Vec<u8>) -> Result<(), SaveError> {
-    let mut file_tgt = File::create(&path).map_err(|e| SaveError::CreateFileError {
-        path: path.clone(),
-        kind: e.kind(),
-    })?;
+    let mut old = live.as_os_str().to_os_string();
+    old.push(".old");
+    let old = PathBuf::from(old);
 
-    file_tgt
-        .write(&contents)
-        .map_err(|e| SaveError::WriteFileError {
-            path,
-            contents,
-            kind: e.kind(),
-        })?;
+    if old.exists() {
+        remove(&old)?;
+    }
+    if let Err(e) = std::fs::rename(live, &old) {
+        let _ = recovery::unmark(live);
+        return Err(rename_error(live, &old, e));
+    }
+    if let Err(e) = std::fs::rename(staging, live) {
+        if std::fs::rename(&old, live).is_ok() {
+            let _ = recovery::unmark(live);
+        }
+        return Err(rename_error(staging, live, e));
+    }
 
-    Ok(())
+    recovery::unmark(live)?;
+    remove(&old)
 }
 
-/// Errors during [`root_location`]
-#[derive(Debug)]
-pub enum RootLocationError {
-    /// Returned when [`root_location`] fails to resolve a path
-    PathBufError(std::convert::Infallible),
-    /// Returned when [`root_location`] fails to retrieve a runtime environment variable
-    GetVarError(std::env::VarError),
-    /// Returned when [`root_location`] encounters an error during the recursive creation of a folder structure
-    CreateDirectoryError {
-        /// The path where [`root_location`] attempted to create folders
-        path: PathBuf,
-        /// The resulting IO error kind.
-        ///
-        /// See [`std::io::ErrorKind`]
-        kind: std::io::ErrorKind,
-    },
+/// Options passed to [`Save::save_with`] and [`BinrootsField::save_with`] to tune a single save.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::fileserializer::Markers;
+/// use binroots::save::{RootType, Save, SaveOptions};
+///
+/// let options = SaveOptions::default().markers(Markers {
+///     none_value: Some("null".into()),
+///     ..Default::default()
+/// });
+///
+/// None::<u8>.save_with("nothing", RootType::InMemory, &options).unwrap(); // Writes "null" instead of deleting the file
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SaveOptions {
+    /// Options forwarded to the [`FileSerializer`]
+    pub serializer: SerializeOptions,
+    /// Orders operations to minimize the events seen by file watchers such as inotify: deletes are batched before writes,
+    /// stale enum payloads that are about to be rewritten aren't deleted first, and files whose contents didn't change aren't
+    /// written at all. Costs a read of every file. Defaults to `false`
+    pub watcher_friendly: bool,
+    /// How symlinks found in place of a file are handled. Defaults to [`SymlinkPolicy::Follow`]
+    pub symlinks: SymlinkPolicy,
+    /// Checks the program's root directory with [`verify_root`] before writing, guarding shared locations like `/tmp`
+    /// against directories planted by other users. Disable in controlled environments. Defaults to `true`
+    pub verify_root: bool,
+    /// After saving, writes a combined hash of every file in the root to `<root>/.etag` (see [`etag`]), so consumers can
+    /// skip re-reading the tree when it didn't change. Defaults to `false`
+    pub etag: bool,
+    /// After saving, writes the time of the save in seconds since the Unix epoch to a `<field>.mtime` sidecar next to
+    /// each top-level field that was saved. Defaults to `false`
+    pub mtime_sidecars: bool,
+    /// Increments the root's generation counter in `<root>/.generation` (see [`generation`]) after each save.
+    /// Defaults to `false`
+    pub generation: bool,
+    /// Fails the save with [`SaveError::Conflict`] unless the root's generation counter equals this value, then increments
+    /// it. The counter is locked for the duration of the save. Defaults to `None`
+    pub expected_generation: Option<u64>,
+    /// After saving, writes the root's [`Metadata`][`crate::metadata::Metadata`] to `<root>/.binroots`. Defaults to `false`
+    pub metadata: bool,
+    /// The application version recorded in the root's metadata. Defaults to `None`
+    pub app_version: Option<String>,
+    /// The [`Backend`] performing the save's filesystem operations. Defaults to [`Filesystem`]
+    pub backend: Arc<dyn Backend>,
+    /// Moves files and folders removed by the save (by `None` or a change of enum variant) to
+    /// `<root>/.trash/<timestamp>/` instead of deleting them, giving users a way to undo. Defaults to `false`
+    pub trash: bool,
+    /// Where atomic writes create their temporary files, see [`Backend::write_atomic`]. Should be on the same filesystem as
+    /// the root; otherwise writes fall back to a temporary file next to the target. Defaults to `None`, the target's folder
+    pub temp_dir: Option<PathBuf>,
+    /// How saving part of a root, such as a single [`BinrootsField`], handles the root's folder not existing.
+    /// Defaults to [`RootMissing::Recreate`]
+    pub root_missing: RootMissing,
+    /// Buffers the save while the root contains a [`QUIET_FILE`][`crate::quiet::QUIET_FILE`], honoring do-not-disturb
+    /// windows opened by readers in other processes. Windows opened with [`quiet::hold`][`crate::quiet::hold`] are always
+    /// honored. Defaults to `false`
+    pub quiet_file: bool,
+    /// After each save, successful or not, updates the root's [`SaveStats`][`crate::stats::SaveStats`] in
+    /// `<root>/.stats`, so consumers can monitor the publisher without a separate channel. Defaults to `false`
+    pub stats: bool,
+    /// Writes leaves of at least this many bytes with [`Backend::write_uncached`], keeping saves of very large fields from
+    /// evicting the rest of the page cache. Each such leaf is flushed to disk before its save returns. Ignored by
+    /// [`SaveOptions::atomic`] writes, and by [`SaveOptions::lock_leaves`], since readers seeing whole values matters more
+    /// than the page cache. Defaults to `None`
+    pub drop_cache_above: Option<usize>,
+    /// Writes each leaf to a temporary file and renames it over the target with [`Backend::write_atomic`], so readers
+    /// never see a half-written value. Takes precedence over [`SaveOptions::drop_cache_above`]. The first such save to a
+    /// root in each process removes temporary files left by crashes, see [`recovery`]. Defaults to `false`
+    pub atomic: bool,
+    /// Flushes every written leaf and its parent folder to durable storage with [`Backend::sync`] before the save
+    /// returns, so saved state survives power loss. [`Save::save_transactional`] also flushes the root's parent after
+    /// swapping. Defaults to `false`
+    pub fsync: bool,
+    /// How a root that can't be written to is handled. Defaults to [`ReadOnlyPolicy::Error`]
+    pub read_only: ReadOnlyPolicy,
+    /// Holds an exclusive lock on each leaf while writing it with [`Backend::write_locked`], so readers using
+    /// [`read::locked_read`][`crate::read::locked_read`] never see a partial value. Ignored by [`SaveOptions::atomic`]
+    /// writes, which readers never see partially anyway. Takes precedence over [`SaveOptions::drop_cache_above`]. Only
+    /// effective on Unix. Defaults to `false`
+    pub lock_leaves: bool,
+    /// The Unix permission bits, such as `0o600`, set on every written leaf with [`Backend::set_mode`]. Folders keep the
+    /// permissions they were created with. Defaults to `None`, leaving them to the process' umask
+    pub permissions: Option<u32>,
+    /// After saving, removes the files and folders below the saved fields that the save didn't produce, such as the
+    /// entries of removed map keys. Hidden entries like `.etag` and `.mtime` sidecars of saved fields are kept. Removed
+    /// entries go to the trash with [`SaveOptions::trash`]. Defaults to `false`
+    pub mirror: bool,
+    /// Writes the number of elements of each sequence to a [`LEN_FILE`] inside its folder, so shell scripts know how many
+    /// entries exist without listing them. Defaults to `false`
+    pub len_files: bool,
+    /// Reads the root's [`CONFIG_FILE`][`crate::config::CONFIG_FILE`] before serializing, letting end users adjust
+    /// options such as [`SerializeOptions::trailing_newline`] with a [`RootConfig`][`crate::config::RootConfig`].
+    /// Defaults to `false`
+    pub root_config: bool,
+    /// Attempts every file even after some of them failed, then fails with [`SaveError::SaveErrors`] listing every
+    /// failure, instead of stopping at the first failed file and leaving the rest of the tree outdated. The rest of the
+    /// save, such as [`SaveOptions::mirror`], [`SaveOptions::etag`] and post-save hooks, still runs first. Defaults to
+    /// `false`
+    pub best_effort: bool,
+    /// Writes each leaf of [`Save::save_with`] as soon as it's serialized, rather than after serializing the whole value,
+    /// so large values aren't held in memory twice. Leaves are written like any other save, including
+    /// [`SaveOptions::atomic`]. Saves that need every file before writing anything, such as with
+    /// [`SaveOptions::watcher_friendly`], templates, pre-save hooks, generation checks or a quiet root, aren't streamed.
+    ///
+    /// Since leaves are written before the whole value is serialized, a streamed save that fails part way, whether a
+    /// write fails or serialization does, leaves the tree half-written: the leaves before the failure are new and the
+    /// rest are outdated. [`SaveOptions::atomic`] only protects each leaf. Defaults to `false`
+    pub streaming: bool,
+    /// The folders known to exist, shared by every save with these options so folders created by a previous save aren't
+    /// created again. Each save caches the folders it creates either way. Defaults to `None`
+    pub dir_cache: Option<Arc<DirCache>>,
+    /// Writes the files of a save concurrently on rayon's thread pool after performing every delete, speeding up values
+    /// that produce thousands of files. Ignored with [`SaveOptions::watcher_friendly`]. Defaults to `false`
+    #[cfg(feature = "parallel")]
+    pub parallel: bool,
+    /// The [`Clock`] that timestamps such as `.mtime` sidecars, metadata, stats and trash folders are taken from.
+    /// Defaults to [`SystemClock`]
+    pub clock: Arc<dyn Clock>,
+    /// The [`IdGen`] naming temporary files in [`SaveOptions::temp_dir`]. Defaults to [`ProcessIds`]
+    pub ids: Arc<dyn IdGen>,
 }
 
-impl std::fmt::Display for RootLocationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::PathBufError(inf) =>
-                    format!("Failed while retrieving the program's root directory: {inf}"),
-                Self::GetVarError(ve) =>
-                    format!("Failed to get an environment variable while retrieving the program's root directory: {ve}"),
-                Self::CreateDirectoryError { path, kind } =>
-                    format!("Failed to create (open) file at {path:?} while attempting to initialize the program's root directory; {kind}"),
-            }
-        )
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            serializer: SerializeOptions::default(),
+            watcher_friendly: false,
+            symlinks: SymlinkPolicy::default(),
+            verify_root: true,
+            etag: false,
+            mtime_sidecars: false,
+            generation: false,
+            expected_generation: None,
+            metadata: false,
+            app_version: None,
+            backend: Arc::new(Filesystem),
+            trash: false,
+            temp_dir: None,
+            root_missing: RootMissing::default(),
+            quiet_file: false,
+            stats: false,
+            drop_cache_above: None,
+            atomic: false,
+            fsync: false,
+            read_only: ReadOnlyPolicy::default(),
+            lock_leaves: false,
+            permissions: None,
+            mirror: false,
+            len_files: false,
+            root_config: false,
+            best_effort: false,
+            streaming: false,
+            dir_cache: None,
+            #[cfg(feature = "parallel")]
+            parallel: false,
+            clock: clock::default_clock(),
+            ids: clock::default_ids(),
+        }
     }
 }
 
-/// Initializes and returns the active program root directory, the folder where files are stored when calling [`Save::save`][`crate::save::Save::save`]
-/// - On Windows, the path will always be `%LOCALAPPDATA%\<CARGO_PKG_NAME>\cache` regardless of `location`, since in-memory folders on Windows are inpossible with safe rust.
-/// - On Unix with [`RootType::InMemory`], `/tmp/<CARGO_PKG_NAME>/`
-/// - On Unix with [`RootType::Persistent`], `$HOME/.cache/<CARGO_PKG_NAME>/`
-///
-/// CARGO_PKG_NAME is generated during compile-time using the [`env`] macro.
-pub fn root_location(location: RootType) -> Result<PathBuf, RootLocationError> {
-    use std::str::FromStr;
+/// How [`save_root_with`] handles a symlink found where it's about to write a file, such as a leaf that a user
+/// redirected elsewhere. Set with [`SaveOptions::symlinks`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Writes to the symlink's target, keeping the symlink in place
+    #[default]
+    Follow,
+    /// Removes the symlink and writes a regular file in its place, leaving the target untouched
+    Replace,
+    /// Fails with [`SaveError::SymlinkError`]
+    Error,
+}
 
-    #[cfg(target_family = "unix")]
-    let path = match location {
-        RootType::InMemory => PathBuf::from_str(&format!("/tmp/{}", env!("CARGO_PKG_NAME")))
-            .map_err(|e| RootLocationError::PathBufError(e)),
-        RootType::Persistent => PathBuf::from_str(&format!(
-            "{}/.cache/{}",
-            std::env::var("HOME").map_err(|e| RootLocationError::GetVarError(e))?,
-            env!("CARGO_PKG_NAME")
-        ))
-        .map_err(|e| RootLocationError::PathBufError(e)),
-    }?;
+/// How [`save_root_with`] handles saving part of a root, such as a single [`BinrootsField`], when the root's folder doesn't
+/// exist, e.g. because a user deleted it while the program was running. Set with [`SaveOptions::root_missing`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RootMissing {
+    /// Recreates the folder, leaving only the saved part in the root
+    #[default]
+    Recreate,
+    /// Fails with [`SaveError::RootMissing`]
+    Error,
+    /// Saves the full state returned by the provider registered for the root with
+    /// [`hooks::register_full_state`] instead, failing with [`SaveError::RootMissing`] if there is none
+    Resave,
+}
 
-    #[cfg(target_family = "windows")]
-    let path = PathBuf::from_str(&format!(
-        "{}\\{}\\.cache",
-        std::env::var("LOCALAPPDATA").map_err(|e| RootLocationError::GetVarError(e))?,
-        env!("CARGO_PKG_NAME")
-    ))
-    .map_err(|e| RootLocationError::PathBufError(e))?;
+/// How [`save_root_with`] handles a root that can't be written to, such as one on a read-only filesystem or owned by
+/// another user. Set with [`SaveOptions::read_only`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadOnlyPolicy {
+    /// Fails with [`SaveError::ReadOnlyRoot`]
+    #[default]
+    Error,
+    /// Skips the save without an error, letting the program keep running without publishing its state
+    Skip,
+}
 
-    std::fs::create_dir_all(path.clone()).map_err(|e| RootLocationError::CreateDirectoryError {
-        path: path.clone(),
-        kind: e.kind(),
-    })?;
+impl SaveOptions {
+    /// Sets [`SerializeOptions::markers`], the marker strings used for `None`, `()` and enum variants
+    pub fn markers(mut self, markers: Markers) -> Self {
+        self.serializer.markers = markers;
+        self
+    }
+
+    /// Sets [`SerializeOptions::strict`], rejecting values that can only be saved using one of binroots' conventions
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.serializer.strict = strict;
+        self
+    }
+
+    /// Adds a template for the top-level field `field` to [`SerializeOptions::templates`]
+    pub fn template<F: Into<String>, T: Into<String>>(mut self, field: F, template: T) -> Self {
+        self.serializer = self.serializer.template(field, template);
+        self
+    }
+
+    /// Sets [`SerializeOptions::enum_representation`], how enum variants are laid out on disk
+    pub fn enum_representation(mut self, enum_representation: EnumRepresentation) -> Self {
+        self.serializer.enum_representation = enum_representation;
+        self
+    }
+
+    /// Sets [`SerializeOptions::leaf_encoding`], how scalar leaves are encoded
+    pub fn leaf_encoding(mut self, leaf_encoding: LeafEncoding) -> Self {
+        self.serializer.leaf_encoding = leaf_encoding;
+        self
+    }
+
+    /// Sets the encoding of the top-level field `field` in [`SerializeOptions::field_encodings`]
+    pub fn field_encoding<F: Into<String>>(mut self, field: F, encoding: LeafEncoding) -> Self {
+        self.serializer = self.serializer.field_encoding(field, encoding);
+        self
+    }
+
+    /// Sets [`SerializeOptions::max_depth`], saving values nested deeper as single JSON files
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.serializer = self.serializer.max_depth(max_depth);
+        self
+    }
+
+    /// Sets the format of the top-level field `field` in [`SerializeOptions::field_formats`], saving it as a single file
+    pub fn field_format<F: Into<String>>(mut self, field: F, format: FieldFormat) -> Self {
+        self.serializer = self.serializer.field_format(field, format);
+        self
+    }
+
+    /// Sets [`SerializeOptions::elide_defaults`], deleting leaves that hold their type's default value
+    pub fn elide_defaults(mut self, elide_defaults: bool) -> Self {
+        self.serializer.elide_defaults = elide_defaults;
+        self
+    }
+
+    /// Adds the top-level field `field` to [`SerializeOptions::elided_fields`]
+    pub fn elide_field<F: Into<String>>(mut self, field: F) -> Self {
+        self.serializer = self.serializer.elide_field(field);
+        self
+    }
+
+    /// Adds the top-level field `field` to [`SerializeOptions::redacted_fields`], saving its leaves as `***`
+    pub fn redact_field<F: Into<String>>(mut self, field: F) -> Self {
+        self.serializer = self.serializer.redact_field(field);
+        self
+    }
+
+    /// Sets [`SerializeOptions::memory_budget`], moving serialized contents to temporary files past `bytes`
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.serializer.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Sets [`SerializeOptions::index_width`], zero-padding the names of sequence elements
+    pub fn index_width(mut self, index_width: usize) -> Self {
+        self.serializer.index_width = index_width;
+        self
+    }
+
+    /// Sets [`SerializeOptions::trailing_newline`], ending text leaves with a newline
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.serializer.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Sets [`SerializeOptions::naming`]
+    pub fn naming(mut self, naming: NamingPolicy) -> Self {
+        self.serializer.naming = naming;
+        self
+    }
+
+    /// Sets [`SaveOptions::watcher_friendly`]
+    pub fn watcher_friendly(mut self, watcher_friendly: bool) -> Self {
+        self.watcher_friendly = watcher_friendly;
+        self
+    }
+
+    /// Sets [`SaveOptions::symlinks`]
+    pub fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets [`SaveOptions::verify_root`]
+    pub fn verify_root(mut self, verify_root: bool) -> Self {
+        self.verify_root = verify_root;
+        self
+    }
+
+    /// Sets [`SaveOptions::etag`]
+    pub fn etag(mut self, etag: bool) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    /// Sets [`SaveOptions::mtime_sidecars`]
+    pub fn mtime_sidecars(mut self, mtime_sidecars: bool) -> Self {
+        self.mtime_sidecars = mtime_sidecars;
+        self
+    }
+
+    /// Sets [`SaveOptions::generation`]
+    pub fn generation(mut self, generation: bool) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Sets [`SaveOptions::expected_generation`]
+    pub fn expected_generation(mut self, expected: u64) -> Self {
+        self.expected_generation = Some(expected);
+        self
+    }
+
+    /// Sets [`SaveOptions::metadata`]
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets [`SaveOptions::app_version`]
+    pub fn app_version<V: Into<String>>(mut self, app_version: V) -> Self {
+        self.app_version = Some(app_version.into());
+        self
+    }
+
+    /// Sets [`SaveOptions::backend`]
+    pub fn backend(mut self, backend: Arc<dyn Backend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets [`SaveOptions::clock`]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets [`SaveOptions::ids`]
+    pub fn ids(mut self, ids: Arc<dyn IdGen>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Sets [`SaveOptions::trash`]
+    pub fn trash(mut self, trash: bool) -> Self {
+        self.trash = trash;
+        self
+    }
+
+    /// Sets [`SaveOptions::temp_dir`]
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, temp_dir: P) -> Self {
+        self.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    /// Sets [`SaveOptions::root_missing`]
+    pub fn root_missing(mut self, root_missing: RootMissing) -> Self {
+        self.root_missing = root_missing;
+        self
+    }
+
+    /// Sets [`SaveOptions::quiet_file`]
+    pub fn quiet_file(mut self, quiet_file: bool) -> Self {
+        self.quiet_file = quiet_file;
+        self
+    }
+
+    /// Sets [`SaveOptions::stats`]
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Sets [`SaveOptions::drop_cache_above`]
+    pub fn drop_cache_above(mut self, bytes: usize) -> Self {
+        self.drop_cache_above = Some(bytes);
+        self
+    }
+
+    /// Sets [`SaveOptions::atomic`]
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Sets [`SaveOptions::fsync`]
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Sets [`SaveOptions::read_only`]
+    pub fn read_only(mut self, read_only: ReadOnlyPolicy) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets [`SaveOptions::lock_leaves`]
+    pub fn lock_leaves(mut self, lock_leaves: bool) -> Self {
+        self.lock_leaves = lock_leaves;
+        self
+    }
+
+    /// Sets [`SaveOptions::permissions`]
+    pub fn permissions(mut self, mode: u32) -> Self {
+        self.permissions = Some(mode);
+        self
+    }
+
+    /// Sets [`SaveOptions::mirror`]
+    pub fn mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Sets [`SaveOptions::len_files`]
+    pub fn len_files(mut self, len_files: bool) -> Self {
+        self.len_files = len_files;
+        self
+    }
+
+    /// Sets [`SaveOptions::root_config`]
+    pub fn root_config(mut self, root_config: bool) -> Self {
+        self.root_config = root_config;
+        self
+    }
+
+    /// Sets [`SaveOptions::best_effort`]
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.best_effort = best_effort;
+        self
+    }
+
+    /// Sets [`SaveOptions::streaming`]
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Sets [`SaveOptions::dir_cache`]
+    pub fn dir_cache(mut self, dir_cache: Arc<DirCache>) -> Self {
+        self.dir_cache = Some(dir_cache);
+        self
+    }
+
+    /// Sets [`SaveOptions::parallel`]
+    #[cfg(feature = "parallel")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+}
+
+impl<T: Serialize> Save for T {
+    fn save_into<P: Into<PathBuf>>(
+        &self,
+        serializer: &mut FileSerializer,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> Result<(), SaveError> {
+        let root = root.into();
+        let options = config::configure(options, &root, &root_type)?;
+
+        serializer.reset();
+        serializer.root.clear();
+        if serializer.options != options.serializer {
+            serializer.options = options.serializer.clone();
+        }
+        serializer.stream = stream(&root, &root_type, &options)?;
+        let serialized = serializer.serialize_value(self);
+        if let Some(e) = serializer.take_stream_error() {
+            return Err(match e.downcast::<SaveError>() {
+                Ok(e) => *e,
+                Err(e) => SaveError::SerializeError(SerializerError::Message(e.to_string())),
+            });
+        }
+        serialized.map_err(SaveError::SerializeError)?;
+
+        save_serialized(serializer, root, root_type, &options)
+    }
+}
+
+/// A [`Stream`] writing leaves below "[`root_location`]/\<root\>" while serializing, or `None` if the save shouldn't be
+/// streamed, see [`SaveOptions::streaming`]
+fn stream(
+    root: &Path,
+    root_type: &RootType,
+    options: &SaveOptions,
+) -> Result<Option<Stream>, SaveError> {
+    if !options.streaming
+        || options.watcher_friendly
+        || !options.serializer.templates.is_empty()
+        || options.generation
+        || options.expected_generation.is_some()
+        || hooks::has_pre_save()
+    {
+        return Ok(None);
+    }
+
+    // Problems with the root are reported by `save_root_with` once it checks them again
+    let Ok(location) = root_location(root_type.clone()) else {
+        return Ok(None);
+    };
+    if options.verify_root && verify_root(&location).is_err() {
+        return Ok(None);
+    }
+    let path = location.join(root);
+    if quiet::is_held(&path, options) {
+        return Ok(None);
+    }
+    if options.backend.is_filesystem() {
+        if !health::writable(health::nearest_existing(&path)) {
+            return Ok(None);
+        }
+        if options.atomic {
+            recovery::recover_once(&path)?;
+        }
+    }
+
+    let root = root.to_path_buf();
+    let options = caching_dirs(options);
+    Ok(Some(Stream::new(move |file: &mut PlannedWrite| {
+        let field = file.target(&root);
+        let contents = std::mem::take(&mut file.contents);
+        let spilled = file.spilled.take();
+        let mut leaf = file.clone();
+        leaf.contents = contents;
+        leaf.spilled = spilled;
+
+        // The entry's deletes run right before it's written, since running them once the save finishes would remove it
+        save_planned(leaf, &path, &options, &Deleter::new(&path, &options))
+            .map_err(|e| Box::new(e.in_field(&field)).into())
+    })))
+}
+
+field_impl! {
+    impl<N, T: Serialize> BinrootsField<N, T> {
+        /// [`Serialize`][`serde::Serialize`]s and saves data to "[BINROOTS_DIR][`crate::BINROOTS_DIR`]/\<root\>"
+        ///
+        /// Modifies the root save path by appending `BinrootsField::N` (generated as the field name by [`binroots::binroots_struct`][`crate::binroots_struct`])
+        pub fn save<P: Into<PathBuf>>(&self, root: P, root_type: RootType) -> Result<(), SaveError> {
+            self.save_with(root, root_type, &SaveOptions::default())
+        }
+
+        /// Like [`BinrootsField::save`], tuned by `options`
+        pub fn save_with<P: Into<PathBuf>>(
+            &self,
+            root: P,
+            root_type: RootType,
+            options: &SaveOptions,
+        ) -> Result<(), SaveError> {
+            let root = root.into();
+            let options = config::configure(options, &root, &root_type)?;
+
+            let mut serializer = FileSerializer::with_options(
+                format!("/{}", Self::name()),
+                options.serializer.clone(),
+            );
+            let was_dirty = self.begin_save();
+            let result = serializer
+                .serialize_value(&self.value)
+                .map_err(SaveError::SerializeError)
+                .and_then(|()| save_root_with(serializer, root, root_type, &options));
+            self.end_save(was_dirty, result.is_ok());
+
+            result
+        }
+    }
+}
+
+field_impl! {
+    impl<N, T> BinrootsField<N, T> {
+        /// The absolute path [`BinrootsField::save`] saves to: "[`root_location`]/\<root\>/`BinrootsField::N`"
+        pub fn path<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<PathBuf, RootLocationError> {
+            path_of(root, root_type, &[Self::name()])
+        }
+    }
+}
+
+/// The absolute path of the value at `fields` inside "[`root_location`]/\<root\>", such as `&["activity"]` for a
+/// top-level field or `&["counts", "apples"]` for a map entry below it. The value doesn't have to be saved yet.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{path_of, root_location, RootType};
+///
+/// let root = root_location(RootType::InMemory).unwrap();
+///
+/// assert_eq!(
+///     path_of("status", RootType::InMemory, &["activity"]).unwrap(),
+///     root.join("status/activity")
+/// );
+/// ```
+pub fn path_of<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    fields: &[&str],
+) -> Result<PathBuf, RootLocationError> {
+    path_of_with(root, root_type, fields, &SaveOptions::default())
+}
+
+/// Like [`path_of`], naming each of `fields` with the [`SerializeOptions::naming`] of `options`, as saves with `options`
+/// name struct fields. Map keys aren't renamed when saving, so resolve those with [`path_of`].
+pub fn path_of_with<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    fields: &[&str],
+    options: &SaveOptions,
+) -> Result<PathBuf, RootLocationError> {
+    let mut path = root_location(root_type)?.join(root.into());
+    for field in fields {
+        path.push(options.serializer.naming.apply(field));
+    }
+
+    Ok(path)
+}
+
+/// Removes the root at "[`root_location`]/\<root\>" along with everything saved in it and its
+/// [JSON mirror][`crate::consume::save_json_mirror`], such as for a "reset state" command or on shutdown. Does nothing if
+/// the root doesn't exist.
+///
+/// Fails with [`SaveError::InvalidRoot`] before removing anything if `root` is empty, absolute or contains `.` or `..`.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{delete, root_location, RootType, Save};
+///
+/// (1, 2).save("delete-status", RootType::InMemory).unwrap();
+/// delete("delete-status", RootType::InMemory).unwrap();
+///
+/// assert!(!root_location(RootType::InMemory).unwrap().join("delete-status").exists());
+/// ```
+pub fn delete<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<(), SaveError> {
+    delete_with(root, root_type, &SaveOptions::default())
+}
+
+/// Like [`delete`], removing through [`SaveOptions::backend`]. With [`SaveOptions::trash`], the root's contents are moved
+/// to its trash instead, keeping the root folder and the trash.
+pub fn delete_with<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let root = root.into();
+    let relative = root.components().next().is_some()
+        && root
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !relative {
+        return Err(SaveError::InvalidRoot { root });
+    }
+
+    let path = root_location(root_type.clone())
+        .map_err(SaveError::RootLocationError)?
+        .join(&root);
+
+    rm(
+        crate::consume::json_mirror_path(root, root_type)?,
+        &*options.backend,
+    )?;
+
+    if !options.trash {
+        return rmdir(path, &*options.backend);
+    }
+
+    let deleter = Deleter::new(&path, options);
+    let Ok(entries) = options.backend.read_dir(&path) else {
+        return Ok(());
+    };
+    for entry in entries {
+        if entry.path.file_name() != Some(TRASH_FOLDER.as_ref()) {
+            deleter.move_to_trash(entry.path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves only the top-level `fields` of `value` to "[`root_location`]/\<root\>", in one pass sharing the directory
+/// creation, hooks and locks of a full save. Fields are named as in Rust, before [`SaveOptions::naming`] is applied.
+///
+/// Fails with [`SaveError::UnknownField`] before writing anything if `value` has no field named like one of `fields`.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{root_location, save_fields_with, RootType, Save, SaveOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     is_online: bool,
+///     connections: usize,
+///     nickname: String,
+/// }
+///
+/// let mut status = Status { is_online: false, connections: 0, nickname: "alex".into() };
+/// status.save("fields-status", RootType::InMemory).unwrap();
+///
+/// status.is_online = true;
+/// status.connections = 3;
+/// status.nickname = "sam".into();
+/// save_fields_with(&status, &["is_online", "connections"], "fields-status", RootType::InMemory, &SaveOptions::default())
+///     .unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("fields-status");
+/// assert_eq!(std::fs::read_to_string(root.join("connections")).unwrap(), "3");
+/// assert_eq!(std::fs::read_to_string(root.join("nickname")).unwrap(), "alex"); // Not saved
+/// ```
+pub fn save_fields_with<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    fields: &[&str],
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let root = root.into();
+    let options = config::configure(options, &root, &root_type)?;
+
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer
+        .serialize_value(value)
+        .map_err(SaveError::SerializeError)?;
+
+    let names = fields
+        .iter()
+        .map(|field| options.serializer.naming.apply(field))
+        .collect::<Vec<_>>();
+    let field_of = |file: &PlannedWrite| {
+        let path = file.path.trim_start_matches('/');
+        match path.split_once('/') {
+            Some((field, _)) => Some(field.to_string()),
+            None if !path.is_empty() => Some(path.to_string()),
+            None => file.name.clone(),
+        }
+    };
+
+    if let Some(missing) = fields.iter().zip(&names).find_map(|(field, name)| {
+        (!serializer
+            .output()
+            .iter()
+            .any(|file| field_of(file).as_ref() == Some(name)))
+        .then_some(field)
+    }) {
+        return Err(SaveError::UnknownField {
+            name: missing.to_string(),
+        });
+    }
+
+    // The root's own folder is kept so it's still created
+    serializer
+        .output
+        .retain(|file| field_of(file).map_or(true, |field| names.contains(&field)));
+    serializer.scope = Some(names);
+
+    save_root_with(serializer, root, root_type, &options)
+}
+
+/// Saves `value` as the entry at `field_path` below "[`root_location`]/\<root\>", such as `"activity"` or
+/// `"settings/theme"`, exactly where it would be saved as part of the value containing it. Only `value` is serialized, so
+/// a borrowed part of a larger value (like `&status.activity`) can be saved without cloning the rest. Segments of
+/// `field_path` are named as in Rust, before [`SaveOptions::naming`] is applied.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{root_location, save_at_with, RootType, SaveOptions};
+///
+/// let scores = vec![3, 5];
+/// save_at_with(&scores, "game/scores", "projection-at", RootType::InMemory, &SaveOptions::default()).unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("projection-at");
+/// assert_eq!(std::fs::read_to_string(root.join("game/scores/1")).unwrap(), "5");
+/// ```
+pub fn save_at_with<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    field_path: &str,
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let root = root.into();
+    let options = config::configure(options, &root, &root_type)?;
+
+    let mut serializer = FileSerializer::with_options(
+        format!("/{}", projection(field_path, &options).join("/")),
+        options.serializer.clone(),
+    );
+    serializer
+        .serialize_value(value)
+        .map_err(SaveError::SerializeError)?;
+
+    save_root_with(serializer, root, root_type, &options)
+}
+
+/// Saves only the part of `value` at `field_path` to "[`root_location`]/\<root\>", like [`save_fields_with`] but reaching
+/// into nested fields, map entries and sequence elements, such as `"settings/theme"` or `"scores/0"`. `value` is
+/// serialized by reference, so nothing is cloned. Segments of `field_path` are named as in Rust, before
+/// [`SaveOptions::naming`] is applied.
+///
+/// Fails with [`SaveError::UnknownField`] before writing anything if `value` has nothing at `field_path`.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{root_location, save_projected_with, RootType, Save, SaveOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Settings {
+///     theme: String,
+///     volume: u8,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     settings: Settings,
+///     nickname: String,
+/// }
+///
+/// let mut status = Status { settings: Settings { theme: "light".into(), volume: 3 }, nickname: "alex".into() };
+/// status.save("projection-status", RootType::InMemory).unwrap();
+///
+/// status.settings.theme = "dark".into();
+/// status.settings.volume = 9;
+/// save_projected_with(&status, "settings/theme", "projection-status", RootType::InMemory, &SaveOptions::default())
+///     .unwrap();
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("projection-status");
+/// assert_eq!(std::fs::read_to_string(root.join("settings/theme")).unwrap(), "dark");
+/// assert_eq!(std::fs::read_to_string(root.join("settings/volume")).unwrap(), "3"); // Not saved
+/// ```
+pub fn save_projected_with<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    field_path: &str,
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let root = root.into();
+    let options = config::configure(options, &root, &root_type)?;
+
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer
+        .serialize_value(value)
+        .map_err(SaveError::SerializeError)?;
+
+    let projection = projection(field_path, &options).join("/");
+    let path_of = |file: &PlannedWrite| {
+        let path = file.path.trim_matches('/');
+        match (&file.name, path.is_empty()) {
+            (Some(name), true) => name.clone(),
+            (Some(name), false) => format!("{path}/{name}"),
+            (None, _) => path.to_string(),
+        }
+    };
+    let below = |path: &str, ancestor: &str| {
+        path.strip_prefix(ancestor)
+            .map_or(false, |rest| rest.starts_with('/'))
+    };
+
+    if !serializer.output().iter().any(|file| {
+        let path = path_of(file);
+        path == projection || below(&path, &projection)
+    }) {
+        return Err(SaveError::UnknownField {
+            name: field_path.to_string(),
+        });
+    }
+
+    // Folders containing the projection are kept so they're still created
+    serializer.output.retain(|file| {
+        let path = path_of(file);
+        path == projection
+            || below(&path, &projection)
+            || (file.is_dir && (path.is_empty() || below(&projection, &path)))
+    });
+    serializer.scope = Some(vec![projection]);
+
+    save_root_with(serializer, root, root_type, &options)
+}
+
+/// The segments of `field_path` as they're named on disk
+fn projection(field_path: &str, options: &SaveOptions) -> Vec<String> {
+    field_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| crate::naming::escape(&options.serializer.naming.apply(segment)))
+        .collect()
+}
+
+/// Saves the files produced by `serializer` to "[`root_location`]/\<root\>"
+///
+/// Used by [`Save::save`] and [`BinrootsField::save`]. Call it directly after driving a [`FileSerializer`] yourself,
+/// e.g. one constructed with [`FileSerializer::with_options`].
+pub fn save_root(
+    serializer: FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+) -> Result<(), SaveError> {
+    save_root_with(serializer, root, root_type, &SaveOptions::default())
+}
+
+/// Like [`save_root`], tuned by `options`. [`SaveOptions::serializer`] is ignored, since `serializer` has already run.
+///
+/// Entries of `serializer` that resolve to the same file, such as several map keys sharing a marker filename, are merged
+/// beforehand: every file is written at most once per save, with the contents of the last entry.
+pub fn save_root_with(
+    mut serializer: FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    save_serialized(&mut serializer, root, root_type, options)
+}
+
+/// [`save_root_with`] taking the serializer by reference, leaving its buffers empty but allocated for the next save
+fn save_serialized(
+    serializer: &mut FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let Some(path) = prepare_root(&root, &root_type, options)? else {
+        return Ok(());
+    };
+
+    if quiet::defer(&path, serializer, root.clone(), root_type.clone(), options) {
+        return Ok(());
+    }
+    quiet::flush_at(&path)?;
+
+    let _span = span!("save", root = %path.display());
+    let started = options.clock.now();
+    let result = match write_root(serializer, root, &path, root_type, &caching_dirs(options)) {
+        // The filesystem was remounted read-only, or the backend isn't checked ahead of time
+        Err(e) if e.kind() == Some(std::io::ErrorKind::ReadOnlyFilesystem) => {
+            return read_only(path, options);
+        }
+        result => result,
+    };
+    if options.stats && options.backend.is_dir(&path) {
+        let recorded = stats::record(
+            &*options.backend,
+            &path,
+            options.clock.now_secs(),
+            options
+                .clock
+                .now()
+                .duration_since(started)
+                .unwrap_or_default(),
+            result.as_ref().err(),
+        );
+        // The save's own error is more useful than a failure to record it
+        if result.is_ok() {
+            recorded?;
+        }
+    }
+
+    result
+}
+
+/// Resolves the path of the root `root` before saving to it, verifying the root location and recovering the root as
+/// `options` ask. Returns [`None`] if the root is read-only and [`SaveOptions::read_only`] skips the save.
+pub(crate) fn prepare_root(
+    root: &Path,
+    root_type: &RootType,
+    options: &SaveOptions,
+) -> Result<Option<PathBuf>, SaveError> {
+    let location = match root_location(root_type.clone()) {
+        Err(RootLocationError::CreateDirectoryError { path, kind }) if is_read_only(kind) => {
+            return read_only(path, options).map(|()| None);
+        }
+        location => location.map_err(SaveError::RootLocationError)?,
+    };
+    if options.verify_root {
+        verify_root(&location)?;
+    }
+    let path = location.join(root);
+
+    if options.backend.is_filesystem() {
+        let existing = health::nearest_existing(&path);
+        if !health::writable(existing) {
+            return read_only(existing.into(), options).map(|()| None);
+        }
+        if options.atomic {
+            recovery::recover_once(&path)?;
+        }
+    }
+
+    Ok(Some(path))
+}
+
+/// `options` with its backend skipping the folders in [`SaveOptions::dir_cache`], or in a cache for a single save
+fn caching_dirs(options: &SaveOptions) -> SaveOptions {
+    let cache = options.dir_cache.clone().unwrap_or_default();
+    options
+        .clone()
+        .backend(Arc::new(CachedDirs::new(options.backend.clone(), cache)))
+}
+
+fn is_read_only(kind: std::io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        std::io::ErrorKind::ReadOnlyFilesystem | std::io::ErrorKind::PermissionDenied
+    )
+}
+
+/// Handles a save into the read-only `path` according to [`SaveOptions::read_only`]
+pub(crate) fn read_only(path: PathBuf, options: &SaveOptions) -> Result<(), SaveError> {
+    match options.read_only {
+        ReadOnlyPolicy::Error => Err(SaveError::ReadOnlyRoot { path }),
+        ReadOnlyPolicy::Skip => Ok(()),
+    }
+}
+
+/// The part of [`save_root_with`] after buffered saves were flushed, writing `serializer` to the root at `path`
+fn write_root(
+    serializer: &mut FileSerializer,
+    root: PathBuf,
+    path: &Path,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    let path = path.to_path_buf();
+
+    if !serializer.root.is_empty() && !options.backend.exists(&path) {
+        match options.root_missing {
+            RootMissing::Recreate => {}
+            RootMissing::Error => return Err(SaveError::RootMissing { path }),
+            RootMissing::Resave => {
+                let mut full = FileSerializer::with_options("", options.serializer.clone());
+                match hooks::run_full_state(&root, &mut full) {
+                    Some(result) => result.map_err(SaveError::SerializeError)?,
+                    None => return Err(SaveError::RootMissing { path }),
+                }
+                *serializer = full;
+            }
+        }
+    }
+
+    serializer.apply_templates();
+    serializer.coalesce();
+
+    let context = SaveContext {
+        root: &path,
+        root_type: &root_type,
+        files: serializer.output(),
+        changes: None,
+    };
+    if let ControlFlow::Break(reason) = hooks::run_pre_save(&context) {
+        return Err(SaveError::Vetoed { reason });
+    }
+
+    let mut generation_lock = if options.generation || options.expected_generation.is_some() {
+        let mut lock = GenerationLock::acquire(&path)?;
+        let found = lock.read()?;
+        match options.expected_generation {
+            Some(expected) if expected != found => {
+                return Err(SaveError::Conflict { expected, found })
+            }
+            _ => Some((lock, found)),
+        }
+    } else {
+        None
+    };
+
+    let saved_at = options.clock.now();
+    let mut fields = serializer
+        .output()
+        .iter()
+        .filter_map(|file| {
+            file.location(&path)
+                .strip_prefix(&path)
+                .ok()?
+                .components()
+                .next()
+                .map(|c| c.as_os_str().to_os_string())
+        })
+        .collect::<Vec<_>>();
+    fields.sort();
+    fields.dedup();
+
+    let produced = options.mirror.then(|| produced(serializer.output(), &path));
+    let scope = match serializer.scope.take() {
+        Some(scope) => Some(scope),
+        None if !serializer.root.trim_matches('/').is_empty() => {
+            Some(vec![serializer.root.trim_matches('/').to_string()])
+        }
+        None => None,
+    };
+
+    // With `best_effort`, the files that failed are reported after the rest of the save's bookkeeping
+    let mut failures = Failures::new(options);
+    let changes = if options.watcher_friendly {
+        Some(save_batched(
+            &mut serializer.output,
+            &root,
+            &path,
+            options,
+            &mut failures,
+        )?)
+    } else {
+        save_sequential(&mut serializer.output, &root, &path, options, &mut failures)?;
+        None
+    };
+
+    if let Some(produced) = produced {
+        let deleter = Deleter::new(&path, options);
+        // Partial saves only mirror the subtrees they wrote, keeping the rest of the root
+        if let Some(scope) = &scope {
+            for selected in scope {
+                let selected = path.join(selected);
+                if options.backend.is_dir(&selected) {
+                    mirror(&selected, &produced, &deleter)?;
+                }
+            }
+        } else {
+            mirror(&path, &produced, &deleter)?;
+        }
+    }
+
+    if options.mtime_sidecars {
+        let secs = saved_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        for field in fields {
+            let mut sidecar = field;
+            sidecar.push(".mtime");
+            write_to(
+                &*options.backend,
+                path.join(sidecar),
+                secs.to_string().into_bytes(),
+            )?;
+        }
+    }
+
+    if options.metadata {
+        metadata::write(
+            &*options.backend,
+            &path,
+            options.app_version.as_deref(),
+            options.clock.now_secs(),
+        )?;
+    }
+
+    if let Some((lock, found)) = &mut generation_lock {
+        lock.write(*found + 1)?;
+    }
+
+    if options.etag {
+        let etag = etag_of(&*options.backend, &path)?;
+        write_to(&*options.backend, path.join(ETAG_FILE), etag.into_bytes())?;
+    }
+
+    hooks::run_post_save(&SaveContext {
+        root: &path,
+        root_type: &root_type,
+        files: &[],
+        changes: changes.as_ref(),
+    });
+
+    failures.finish()
+}
+
+/// Saves and drains `files` below `path` in order, recording the errors of failed files in `failures`. `root` is the root
+/// as passed to the save, naming the value each error belongs to.
+fn save_sequential(
+    files: &mut Vec<PlannedWrite>,
+    root: &Path,
+    path: &Path,
+    options: &SaveOptions,
+    failures: &mut Failures,
+) -> Result<(), SaveError> {
+    #[cfg(feature = "parallel")]
+    if options.parallel {
+        return save_parallel(files, root, path, options, failures);
+    }
+
+    let deleter = Deleter::new(path, options);
+
+    for file in files.drain(..) {
+        let field = file.target(root);
+        failures
+            .check(save_planned(file, path, options, &deleter).map_err(|e| e.in_field(&field)))?;
+    }
+
+    Ok(())
+}
+
+/// The errors of the files that failed to save with [`SaveOptions::best_effort`]
+struct Failures {
+    best_effort: bool,
+    errors: Vec<SaveError>,
+}
+
+impl Failures {
+    fn new(options: &SaveOptions) -> Self {
+        Self {
+            best_effort: options.best_effort,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Records the error of `result` with [`SaveOptions::best_effort`], or returns it to stop the save
+    fn check(&mut self, result: Result<(), SaveError>) -> Result<(), SaveError> {
+        match result {
+            Err(e) if self.best_effort => {
+                self.errors.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn finish(self) -> Result<(), SaveError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SaveError::SaveErrors(self.errors))
+        }
+    }
+}
+
+/// Saves `files` below `path` like [`save_sequential`], but performs every delete and creates every folder first, then
+/// writes the leaves concurrently on rayon's thread pool
+#[cfg(feature = "parallel")]
+fn save_parallel(
+    files: &mut Vec<PlannedWrite>,
+    root: &Path,
+    path: &Path,
+    options: &SaveOptions,
+    failures: &mut Failures,
+) -> Result<(), SaveError> {
+    use rayon::prelude::*;
+
+    let deleter = Deleter::new(path, options);
+
+    for file in files.iter() {
+        failures.check(
+            prepare_planned(file, path, options, &deleter)
+                .map_err(|e| e.in_field(&file.target(root))),
+        )?;
+    }
+
+    let write = |file: PlannedWrite| {
+        let field = file.target(root);
+        write_planned(file, path, options).map_err(|e| e.in_field(&field))
+    };
+    if options.best_effort {
+        let written: Vec<_> = files.par_drain(..).map(write).collect();
+        for result in written {
+            failures.check(result)?;
+        }
+    } else {
+        files.par_drain(..).try_for_each(write)?;
+    }
+
+    Ok(())
+}
+
+fn save_planned(
+    file: PlannedWrite,
+    path: &Path,
+    options: &SaveOptions,
+    deleter: &Deleter,
+) -> Result<(), SaveError> {
+    prepare_planned(&file, path, options, deleter)?;
+    write_planned(file, path, options)
+}
+
+/// Performs the deletes of `file` below `path` and creates it if it's a folder. Only the truncation of folders that
+/// were streamed is left to do, since their elements were already written.
+fn prepare_planned(
+    file: &PlannedWrite,
+    path: &Path,
+    options: &SaveOptions,
+    deleter: &Deleter,
+) -> Result<(), SaveError> {
+    let backend = &*options.backend;
+
+    if let Some(stale_value) = file.stale_value(path).filter(|_| !file.streamed) {
+        deleter.rmdir(stale_value.clone())?;
+        deleter.rm(stale_value)?;
+    }
+
+    if !file.is_dir {
+        if file.hint == FileOperationHint::Delete && !file.streamed {
+            mkdir(backend, &file.parent_dir(path))?;
+            deleter.rm(file.location(path))?;
+        }
+    } else {
+        let target = file.target(path);
+        mkdir(backend, &target)?;
+
+        if let FileOperationHint::Truncate(len) = file.hint {
+            truncate(&target, len, deleter)?;
+            if options.len_files {
+                write_to(backend, target.join(LEN_FILE), len.to_string().into_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `file` below `path` along with its folder, unless it's a folder itself, deleted or already streamed
+fn write_planned(
+    mut file: PlannedWrite,
+    path: &Path,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    if file.is_dir || file.hint == FileOperationHint::Delete || file.streamed {
+        return Ok(());
+    }
+
+    mkdir(&*options.backend, &file.parent_dir(path))?;
+    unspill(&mut file)?;
+    save_leaf(
+        file.target(path),
+        std::mem::take(&mut file.contents),
+        options,
+    )
+}
+
+/// Reads contents spilled by [`SerializeOptions::memory_budget`] back into memory
+fn unspill(file: &mut PlannedWrite) -> Result<(), SaveError> {
+    let spilled = file.spilled.clone();
+    file.unspill().map_err(|e| SaveError::ReadFileError {
+        path: spilled
+            .map(|spilled| spilled.path().to_path_buf())
+            .unwrap_or_default(),
+        source: e,
+        field: None,
+    })
+}
+
+/// Removes the elements of the sequence folder `dir` from index `len` on, see [`FileOperationHint::Truncate`]
+fn truncate(dir: &Path, len: usize, deleter: &Deleter) -> Result<(), SaveError> {
+    let Ok(entries) = deleter.backend.read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        if seq_index(&name).map_or(true, |index| index < len) {
+            continue;
+        }
+
+        if entry.is_dir {
+            deleter.rmdir(entry.path)?;
+        } else {
+            deleter.rm(entry.path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The index of the sequence element saved as `name`, which may carry an extension such as `.value`
+pub(crate) fn seq_index(name: &str) -> Option<usize> {
+    name.split('.').next()?.parse().ok()
+}
+
+/// Saves with [`SaveOptions::watcher_friendly`]: every delete happens before any write, stale entries that are about to be
+/// rewritten aren't deleted, and files whose contents didn't change aren't touched. Returns the leaves that changed.
+fn save_batched(
+    output: &mut Vec<PlannedWrite>,
+    root: &Path,
+    path: &Path,
+    options: &SaveOptions,
+    failures: &mut Failures,
+) -> Result<LeafChanges, SaveError> {
+    let mut deleter = Deleter::new(path, options);
+    deleter.deleted = Some(RefCell::new(Vec::new()));
+    let mut changes = LeafChanges::default();
+    let mut files = HashSet::new();
+    let mut dirs = HashSet::new();
+
+    for file in output.iter() {
+        if file.is_dir {
+            dirs.insert(file.target(path));
+        } else if file.hint != FileOperationHint::Delete {
+            files.insert(file.target(path));
+        }
+    }
+
+    for file in output.iter() {
+        failures.check(
+            clear_stale(file, path, &files, &dirs, &deleter)
+                .map_err(|e| e.in_field(&file.target(root))),
+        )?;
+    }
+
+    for file in output.drain(..) {
+        let field = file.target(root);
+        failures.check(
+            write_batched(file, path, options, &mut changes).map_err(|e| e.in_field(&field)),
+        )?;
+    }
+
+    // A leaf deleted and written again by the same save was changed rather than added
+    let deleted = deleter.deleted.map(RefCell::into_inner).unwrap_or_default();
+    for leaf in &deleted {
+        if let Some(i) = changes.added.iter().position(|added| added == leaf) {
+            changes.changed.push(changes.added.remove(i));
+        }
+    }
+    changes.deleted = deleted
+        .into_iter()
+        .filter(|leaf| !changes.changed.contains(leaf))
+        .collect();
+
+    changes.added.sort();
+    changes.changed.sort();
+    changes.deleted.sort();
+    changes.added.dedup();
+    changes.changed.dedup();
+    changes.deleted.dedup();
+
+    Ok(changes)
+}
+
+/// Creates the folder of `file` below `path` and writes it unless its contents didn't change, recording it in `changes`
+fn write_batched(
+    mut file: PlannedWrite,
+    path: &Path,
+    options: &SaveOptions,
+    changes: &mut LeafChanges,
+) -> Result<(), SaveError> {
+    let backend = &*options.backend;
+    let dir = if file.is_dir {
+        file.target(path)
+    } else if file.hint != FileOperationHint::Delete {
+        file.parent_dir(path)
+    } else {
+        return Ok(());
+    };
+
+    mkdir(backend, &dir)?;
+
+    if let (FileOperationHint::Truncate(len), true) = (&file.hint, options.len_files) {
+        let len_file = dir.join(LEN_FILE);
+        let len = len.to_string().into_bytes();
+        if backend
+            .read(&len_file)
+            .map_or(true, |existing| existing != len)
+        {
+            write_to(backend, len_file, len)?;
+        }
+    }
+
+    if !file.is_dir {
+        let target = file.target(path);
+        let relative = target.strip_prefix(path).unwrap_or(&target).to_path_buf();
+
+        unspill(&mut file)?;
+        match backend.read(&target) {
+            Ok(existing) if existing == file.contents => return Ok(()),
+            Ok(_) => changes.changed.push(relative),
+            Err(_) => changes.added.push(relative),
+        }
+        save_leaf(target, std::mem::take(&mut file.contents), options)?;
+    }
+
+    Ok(())
+}
+
+/// Performs the deletes of `file` below `path` ahead of the writes of [`save_batched`]
+fn clear_stale(
+    file: &PlannedWrite,
+    path: &Path,
+    files: &HashSet<PathBuf>,
+    dirs: &HashSet<PathBuf>,
+    deleter: &Deleter,
+) -> Result<(), SaveError> {
+    if let Some(stale_value) = file.stale_value(path) {
+        prune(stale_value, files, dirs, deleter)?;
+    }
+
+    if file.hint == FileOperationHint::Delete {
+        deleter.rm(file.location(path))
+    } else if let FileOperationHint::Truncate(len) = file.hint {
+        truncate(&file.target(path), len, deleter)
+    } else {
+        Ok(())
+    }
+}
+
+/// Removes `path` unless it's about to be rewritten as the same kind of entry. Folders that are kept are pruned recursively.
+fn prune(
+    path: PathBuf,
+    files: &HashSet<PathBuf>,
+    dirs: &HashSet<PathBuf>,
+    deleter: &Deleter,
+) -> Result<(), SaveError> {
+    if !deleter.backend.exists(&path) {
+        return Ok(());
+    }
+
+    let is_dir = deleter.backend.is_dir(&path);
+    if is_dir && dirs.contains(&path) {
+        let entries = deleter
+            .backend
+            .read_dir(&path)
+            .map_err(|e| SaveError::DeleteFileError {
+                path: path.clone(),
+                source: e,
+                field: None,
+            })?;
+
+        for entry in entries {
+            prune(entry.path, files, dirs, deleter)?;
+        }
+
+        Ok(())
+    } else if is_dir {
+        deleter.rmdir(path)
+    } else if files.contains(&path) {
+        Ok(())
+    } else {
+        deleter.rm(path)
+    }
+}
+
+/// The files and folders written by `files` below `root`, along with the folders containing them
+fn produced(files: &[PlannedWrite], root: &Path) -> HashSet<PathBuf> {
+    let mut produced = HashSet::new();
+
+    for file in files.iter().filter(|f| f.hint != FileOperationHint::Delete) {
+        let target = file.target(root);
+        for ancestor in target.ancestors() {
+            if ancestor == root || !produced.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    produced
+}
+
+/// Removes the entries below `dir` that aren't `produced`, see [`SaveOptions::mirror`]
+fn mirror(dir: &Path, produced: &HashSet<PathBuf>, deleter: &Deleter) -> Result<(), SaveError> {
+    let Ok(entries) = deleter.backend.read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let path = entry.path;
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let is_dir = entry.is_dir;
+
+        if name.starts_with('.') {
+            continue;
+        } else if produced.contains(&path) {
+            if is_dir {
+                mirror(&path, produced, deleter)?;
+            }
+        } else if name
+            .strip_suffix(".mtime")
+            .map_or(false, |field| produced.contains(&dir.join(field)))
+        {
+            continue;
+        } else if is_dir {
+            deleter.rmdir(path)?;
+        } else {
+            deleter.rm(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the entries a save replaces, moving them to the trash with [`SaveOptions::trash`]
+struct Deleter<'a> {
+    backend: &'a dyn Backend,
+    root: &'a Path,
+    trash: Option<PathBuf>,
+    /// Records the leaves removed, relative to `root`, when set
+    deleted: Option<RefCell<Vec<PathBuf>>>,
+}
+
+impl<'a> Deleter<'a> {
+    fn new(root: &'a Path, options: &'a SaveOptions) -> Self {
+        let trash = options.trash.then(|| {
+            let now = options
+                .clock
+                .now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            root.join(TRASH_FOLDER)
+                .join(format!("{}.{:09}", now.as_secs(), now.subsec_nanos()))
+        });
+
+        Self {
+            backend: &*options.backend,
+            root,
+            trash,
+            deleted: None,
+        }
+    }
+
+    /// Records the leaves at or below `path` in [`Deleter::deleted`] before they're removed. Only folders are descended
+    /// into when `dir` is set, matching what [`rm`] and [`rmdir`] remove.
+    fn record(&self, path: &Path, dir: bool) {
+        let Some(deleted) = &self.deleted else {
+            return;
+        };
+        let path = PathBuf::from(path.to_string_lossy().trim_end_matches('/'));
+
+        if self.backend.exists(&path) && self.backend.is_dir(&path) == dir {
+            for leaf in leaves(self.backend, &path) {
+                let relative = leaf.strip_prefix(self.root).unwrap_or(&leaf).to_path_buf();
+                deleted.borrow_mut().push(relative);
+            }
+        }
+    }
+
+    fn rm(&self, path: PathBuf) -> Result<(), SaveError> {
+        self.record(&path, false);
+        if self.backend.exists(&path) && !self.backend.is_dir(&path) {
+            self.move_to_trash(path)
+        } else {
+            rm(path, self.backend)
+        }
+    }
+
+    fn rmdir(&self, path: PathBuf) -> Result<(), SaveError> {
+        self.record(&path, true);
+        if self.backend.is_dir(&path) {
+            self.move_to_trash(path)
+        } else {
+            rmdir(path, self.backend)
+        }
+    }
+
+    /// Moves `path` below the trash folder of this save, or removes it if the trash is disabled
+    fn move_to_trash(&self, path: PathBuf) -> Result<(), SaveError> {
+        let (Some(trash), Ok(relative)) = (&self.trash, path.strip_prefix(self.root)) else {
+            return if self.backend.is_dir(&path) {
+                rmdir(path, self.backend)
+            } else {
+                rm(path, self.backend)
+            };
+        };
+
+        let target = trash.join(relative);
+        if let Some(parent) = target.parent() {
+            mkdir(self.backend, parent)?;
+        }
+
+        match self.backend.rename(&path, &target) {
+            Ok(()) => {
+                event!(trace, path = %path.display(), "moved to trash");
+                Ok(())
+            }
+            Err(e) => Err(SaveError::DeleteFileError {
+                path,
+                source: e,
+                field: None,
+            }),
+        }
+    }
+}
+
+/// The files at or below `path`. Symlinks are leaves themselves, so links to folders are never followed.
+fn leaves(backend: &dyn Backend, path: &Path) -> Vec<PathBuf> {
+    if !backend.is_dir(path) {
+        return vec![path.to_path_buf()];
+    }
+
+    match backend.read_dir(path) {
+        Ok(entries) => entries
+            .iter()
+            .flat_map(|entry| leaves(backend, &entry.path))
+            .collect(),
+        Err(_) => vec![path.to_path_buf()],
+    }
+}
+
+fn rmdir(path: PathBuf, backend: &dyn Backend) -> Result<(), SaveError> {
+    match backend.remove_dir(&path) {
+        Ok(()) => {
+            event!(trace, path = %path.display(), "deleted folder");
+            Ok(())
+        }
+        Err(e) => match e.kind() {
+            ErrorKind::NotFound => Ok(()),
+            ErrorKind::NotADirectory => Ok(()),
+            _ => Err(SaveError::DeleteFileError {
+                path,
+                source: e,
+                field: None,
+            }),
+        },
+    }
+}
+
+fn rm(path: PathBuf, backend: &dyn Backend) -> Result<(), SaveError> {
+    match backend.remove(Path::new(path.to_string_lossy().trim_end_matches('/'))) {
+        Ok(()) => {
+            event!(trace, path = %path.display(), "deleted file");
+            Ok(())
+        }
+        Err(e) => match e.kind() {
+            ErrorKind::NotFound => Ok(()),
+            _ => Err(SaveError::DeleteFileError {
+                path,
+                source: e,
+                field: None,
+            }),
+        },
+    }
+}
+
+/// Creates the folder at `path` along with its missing parents
+fn mkdir(backend: &dyn Backend, path: &Path) -> Result<(), SaveError> {
+    backend
+        .mkdir(path)
+        .map_err(|e| SaveError::CreateDirectoryError {
+            path: path.to_path_buf(),
+            source: e,
+            field: None,
+        })?;
+    event!(trace, path = %path.display(), "created folder");
+
+    Ok(())
+}
+
+/// Creates the file interface of `T` below "[`root_location`]/\<root\>" from its [`Default`] value, so installers can
+/// prepare the root (and adjust its permissions) before the program first saves. Files and folders that already exist are
+/// left untouched. Returns the paths that were created.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{ensure_root, root_location, RootType, SaveOptions};
+/// use binroots::Serialize;
+///
+/// #[derive(Default, Serialize)]
+/// struct Status {
+///     connections: usize,
+///     nickname: String,
+/// }
+///
+/// let root = root_location(RootType::InMemory).unwrap().join("ensure-status");
+/// # let _ = std::fs::remove_dir_all(&root);
+/// std::fs::create_dir_all(&root).unwrap();
+/// std::fs::write(root.join("nickname"), "alex").unwrap();
+///
+/// let created = ensure_root::<Status, _>("ensure-status", RootType::InMemory, &SaveOptions::default()).unwrap();
+///
+/// assert_eq!(created, vec![root.join("connections")]);
+/// assert_eq!(std::fs::read_to_string(root.join("nickname")).unwrap(), "alex"); // Untouched
+/// ```
+pub fn ensure_root<T: Serialize + Default, P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<Vec<PathBuf>, SaveError> {
+    let root = root.into();
+    let options = config::configure(options, &root, &root_type)?;
+
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer
+        .serialize_value(&T::default())
+        .map_err(SaveError::SerializeError)?;
+
+    ensure_root_with(serializer, root, root_type, &options)
+}
+
+/// Like [`ensure_root`], creating the files and folders produced by `serializer` that don't exist yet. Payloads such as
+/// `activity.value` are skipped when their field already exists, so an existing enum keeps its variant.
+pub fn ensure_root_with(
+    mut serializer: FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<Vec<PathBuf>, SaveError> {
+    let location = root_location(root_type).map_err(SaveError::RootLocationError)?;
+    if options.verify_root {
+        verify_root(&location)?;
+    }
+    let path = location.join(root);
+    let backend = &*options.backend;
+
+    serializer.apply_templates();
+    serializer.coalesce();
+
+    let mut created = Vec::new();
+    if !backend.exists(&path) {
+        created.push(path.clone());
+    }
+    mkdir(backend, &path)?;
+
+    for mut file in serializer.output {
+        let target = file.target(&path);
+        if file.hint == FileOperationHint::Delete
+            || backend.exists(&target)
+            || (file.variant.is_some() && backend.exists(&file.location(&path)))
+        {
+            continue;
+        }
+
+        let parent = if file.is_dir {
+            target.clone()
+        } else {
+            file.parent_dir(&path)
+        };
+        mkdir(backend, &parent)?;
+
+        if !file.is_dir {
+            unspill(&mut file)?;
+            write_to(backend, target.clone(), std::mem::take(&mut file.contents))?;
+        }
+        created.push(target);
+    }
+
+    Ok(created)
+}
+
+/// The name of the folder [`SaveOptions::trash`] moves removed entries to
+pub const TRASH_FOLDER: &str = ".trash";
+
+/// The name of the file [`SaveOptions::etag`] writes the root's etag to
+pub const ETAG_FILE: &str = ".etag";
+
+/// The name of the file [`SaveOptions::len_files`] writes inside each sequence folder
+pub const LEN_FILE: &str = ".len";
+
+/// Computes the etag of everything saved below "[`root_location`]/\<root\>": a 64-bit FNV-1a hash of every file's relative
+/// path and contents, in sorted order, as 16 hexadecimal digits.
+///
+/// Hidden entries at the top of the root (such as `.etag` itself) aren't included.
+pub fn etag<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<String, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into());
+
+    etag_of(&Filesystem, &path)
+}
+
+fn etag_of(backend: &dyn Backend, path: &Path) -> Result<String, SaveError> {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let snapshot = Snapshot::read_backend(backend, path).map_err(|e| match e {
+        SnapshotError::IoError { path, kind } => SaveError::ReadFileError {
+            path,
+            source: kind.into(),
+            field: None,
+        },
+        e => SaveError::SerializeError(SerializerError::Message(e.to_string())),
+    })?;
+
+    let mut hash = OFFSET;
+    for entry in snapshot.entries.iter().filter(|e| !e.path.starts_with('.')) {
+        for byte in entry
+            .path
+            .as_bytes()
+            .iter()
+            .chain([entry.is_dir as u8, 0].iter())
+            .chain(entry.contents.iter())
+            .chain([0].iter())
+        {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+
+    Ok(format!("{hash:016x}"))
+}
+
+/// Renders the files and folders saved below "[`root_location`]/\<root\>" like the `tree` command, so docs, logs and
+/// debugging sessions can show the layout a type maps to. Hidden entries such as `.etag` are left out, and entries are
+/// sorted by name, with sequence indices in numeric order.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{render_tree, RootType, Save};
+/// use binroots::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     online: bool,
+///     users: Vec<String>,
+/// }
+///
+/// let status = Status { online: true, users: vec!["carter".into(), "alex".into()] };
+/// status.save("render-status", RootType::InMemory).unwrap();
+///
+/// assert_eq!(
+///     render_tree("render-status", RootType::InMemory).unwrap(),
+///     "render-status\n├── online\n└── users\n    ├── 0\n    └── 1\n"
+/// );
+/// ```
+pub fn render_tree<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<String, SaveError> {
+    let root = root.into();
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(&root);
+
+    let snapshot = Snapshot::read_dir(path).map_err(|e| match e {
+        SnapshotError::IoError { path, kind } => SaveError::ReadFileError {
+            path,
+            source: kind.into(),
+            field: None,
+        },
+        e => SaveError::SerializeError(SerializerError::Message(e.to_string())),
+    })?;
+
+    let mut tree = Tree::default();
+    for entry in &snapshot.entries {
+        tree.insert(Path::new(&entry.path));
+    }
+
+    Ok(tree.render(&root.to_string_lossy()))
+}
+
+/// Like [`render_tree`], rendering the layout saving `value` to `root` would produce without touching the disk
+pub fn render_value_tree<T: Serialize + ?Sized, P: Into<PathBuf>>(
+    value: &T,
+    root: P,
+) -> Result<String, SerializerError> {
+    let root = root.into();
+
+    let mut tree = Tree::default();
+    for planned in crate::plan::plan(value, &root)? {
+        if matches!(planned.op, Op::CreateDir | Op::Write) {
+            if let Ok(relative) = planned.path.strip_prefix(&root) {
+                tree.insert(relative);
+            }
+        }
+    }
+
+    Ok(tree.render(&root.to_string_lossy()))
+}
+
+/// The entries of a folder rendered by [`render_tree`], by name
+#[derive(Default)]
+struct Tree(BTreeMap<String, Tree>);
+
+impl Tree {
+    /// Adds the entry at `path`, relative to this folder, along with its parents. Hidden entries are skipped.
+    fn insert(&mut self, path: &Path) {
+        let mut tree = self;
+        for component in path.components() {
+            let name = component.as_os_str().to_string_lossy();
+            if name.starts_with('.') {
+                return;
+            }
+            tree = tree.0.entry(name.into_owned()).or_default();
+        }
+    }
+
+    fn render(&self, name: &str) -> String {
+        let mut out = format!("{name}\n");
+        self.render_entries("", &mut out);
+        out
+    }
+
+    fn render_entries(&self, prefix: &str, out: &mut String) {
+        let mut entries = self.0.iter().collect::<Vec<_>>();
+        entries
+            .sort_by_key(|(name, _)| (name.parse::<u64>().map_or(u64::MAX, |index| index), *name));
+
+        for (i, (name, entry)) in entries.iter().enumerate() {
+            let last = i + 1 == entries.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            out.push_str(&format!("{prefix}{branch}{name}\n"));
+            entry.render_entries(&format!("{prefix}{indent}"), out);
+        }
+    }
+}
+
+/// The name of the file holding the root's generation counter, see [`SaveOptions::generation`]
+pub const GENERATION_FILE: &str = ".generation";
+
+/// Reads the generation counter of "[`root_location`]/\<root\>", incremented by saves using [`SaveOptions::generation`] or
+/// [`SaveOptions::expected_generation`]. Returns `0` if the root has never been saved with either.
+pub fn generation<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<u64, SaveError> {
+    let path = root_location(root_type)
+        .map_err(SaveError::RootLocationError)?
+        .join(root.into())
+        .join(GENERATION_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents.trim().parse().unwrap_or(0)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(SaveError::ReadFileError {
+            path,
+            source: e,
+            field: None,
+        }),
+    }
+}
+
+/// The open generation file of a root, exclusively locked on Unix until dropped
+struct GenerationLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl GenerationLock {
+    fn acquire(root: &Path) -> Result<Self, SaveError> {
+        std::fs::create_dir_all(root).map_err(|e| SaveError::CreateDirectoryError {
+            path: root.to_path_buf(),
+            source: e,
+            field: None,
+        })?;
+
+        let path = root.join(GENERATION_FILE);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| SaveError::CreateFileError {
+                path: path.clone(),
+                source: e,
+                field: None,
+            })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            // SAFETY: `file` is open for the duration of the call. The lock is released when `file` is dropped.
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(SaveError::CreateFileError {
+                    path,
+                    source: std::io::Error::last_os_error(),
+                    field: None,
+                });
+            }
+        }
+
+        Ok(Self { path, file })
+    }
+
+    fn read(&mut self) -> Result<u64, SaveError> {
+        let mut contents = String::new();
+        self.file
+            .read_to_string(&mut contents)
+            .map_err(|e| SaveError::ReadFileError {
+                path: self.path.clone(),
+                source: e,
+                field: None,
+            })?;
+
+        Ok(contents.trim().parse().unwrap_or(0))
+    }
+
+    fn write(&mut self, generation: u64) -> Result<(), SaveError> {
+        let contents = generation.to_string().into_bytes();
+
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.seek(SeekFrom::Start(0)))
+            .and_then(|_| self.file.write_all(&contents))
+            .map_err(|e| SaveError::WriteFileError {
+                path: self.path.clone(),
+                contents,
+                source: e,
+                field: None,
+            })
+    }
+}
+
+/// Why [`verify_root`] rejected a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntrustedRootReason {
+    /// The directory is a symlink, which another user could have planted to redirect writes
+    Symlink,
+    /// The directory is owned by another user, identified by their uid
+    OwnedByOtherUser(u32),
+    /// The directory can be written to by every user
+    WorldWritable,
+}
+
+impl std::fmt::Display for UntrustedRootReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Symlink => write!(f, "it's a symlink"),
+            Self::OwnedByOtherUser(uid) => write!(f, "it's owned by another user (uid {uid})"),
+            Self::WorldWritable => write!(f, "it's writable by every user"),
+        }
+    }
+}
+
+/// Verifies that `path` is a real directory owned by the current user and not writable by every user, failing with
+/// [`SaveError::UntrustedRoot`] otherwise. Guards against the classic `/tmp` attack where another user plants the
+/// program's root directory (or a symlink to somewhere else) ahead of time.
+///
+/// Called by [`save_root_with`] on the program's root directory unless [`SaveOptions::verify_root`] is disabled.
+/// Always succeeds on non-Unix platforms.
+pub fn verify_root(path: &Path) -> Result<(), SaveError> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let untrusted = |reason| SaveError::UntrustedRoot {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let metadata =
+            std::fs::symlink_metadata(path).map_err(|e| SaveError::CreateDirectoryError {
+                path: path.to_path_buf(),
+                source: e,
+                field: None,
+            })?;
+
+        if metadata.file_type().is_symlink() {
+            return Err(untrusted(UntrustedRootReason::Symlink));
+        }
+
+        // SAFETY: `getuid` has no preconditions and can't fail
+        let uid = unsafe { libc::getuid() };
+        if metadata.uid() != uid {
+            return Err(untrusted(UntrustedRootReason::OwnedByOtherUser(
+                metadata.uid(),
+            )));
+        }
+
+        if metadata.mode() & 0o002 != 0 {
+            return Err(untrusted(UntrustedRootReason::WorldWritable));
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Writes a single leaf, applying [`SaveOptions::symlinks`]
+fn save_leaf(path: PathBuf, contents: Vec<u8>, options: &SaveOptions) -> Result<(), SaveError> {
+    let is_symlink = std::fs::symlink_metadata(&path).map_or(false, |m| m.file_type().is_symlink());
+    let mut path = path;
+
+    if is_symlink {
+        match options.symlinks {
+            // Renaming over the symlink would replace it, so atomic writes replace its target instead
+            SymlinkPolicy::Follow if options.atomic => {
+                path = std::fs::canonicalize(&path).unwrap_or(path);
+            }
+            SymlinkPolicy::Follow => {}
+            SymlinkPolicy::Replace => rm(path.clone(), &*options.backend)?,
+            SymlinkPolicy::Error => return Err(SaveError::SymlinkError { path }),
+        }
+    }
+
+    let backend = &*options.backend;
+    // The mode is set before the contents are written, so they're never readable with other permissions
+    let created = match options.permissions {
+        Some(mode) if !options.atomic => backend.create_with_mode(&path, mode),
+        _ => Ok(()),
+    };
+    let written = created.and_then(|_| {
+        if options.atomic {
+            backend.write_atomic(
+                &path,
+                &contents,
+                options.temp_dir.as_deref(),
+                &*options.ids,
+                options.permissions,
+            )
+        } else if options.lock_leaves {
+            backend.write_locked(&path, &contents)
+        } else if options
+            .drop_cache_above
+            .map_or(false, |bytes| contents.len() >= bytes)
+        {
+            backend.write_uncached(&path, &contents)
+        } else {
+            backend.write(&path, &contents)
+        }
+    });
+    let written = written.and_then(|_| match options.permissions {
+        Some(mode) if !options.atomic => backend.set_mode(&path, mode),
+        _ => Ok(()),
+    });
+    if let Err(e) = written {
+        return Err(SaveError::WriteFileError {
+            path,
+            source: e,
+            field: None,
+            contents,
+        });
+    }
+    event!(trace, path = %path.display(), bytes = contents.len(), "wrote file");
+
+    if options.fsync {
+        sync(backend, &path)?;
+        if let Some(parent) = path.parent() {
+            sync(backend, parent)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn sync(backend: &dyn Backend, path: &Path) -> Result<(), SaveError> {
+    backend.sync(path).map_err(|e| SaveError::SyncError {
+        path: path.into(),
+        source: e,
+        field: None,
+    })
+}
+
+pub(crate) fn write_to(
+    backend: &dyn Backend,
+    path: PathBuf,
+    contents: Vec<u8>,
+) -> Result<(), SaveError> {
+    match backend.write(&path, &contents) {
+        Ok(()) => {
+            event!(trace, path = %path.display(), bytes = contents.len(), "wrote file");
+            Ok(())
+        }
+        Err(e) => Err(SaveError::WriteFileError {
+            path,
+            source: e,
+            field: None,
+            contents,
+        }),
+    }
+}
+
+pub(crate) fn save_to(path: PathBuf, contents: Vec<u8>) -> Result<(), SaveError> {
+    let mut file_tgt = File::create(&path).map_err(|e| SaveError::CreateFileError {
+        path: path.clone(),
+        source: e,
+        field: None,
+    })?;
+
+    file_tgt
+        .write(&contents)
+        .map_err(|e| SaveError::WriteFileError {
+            path,
+            contents,
+            source: e,
+            field: None,
+        })?;
+
+    Ok(())
+}
+
+/// Errors during [`root_location`]
+#[derive(Debug)]
+pub enum RootLocationError {
+    /// Returned when [`root_location`] fails to resolve a path
+    PathBufError(std::convert::Infallible),
+    /// Returned when [`root_location`] fails to retrieve a runtime environment variable
+    GetVarError(std::env::VarError),
+    /// Returned when [`root_location`] encounters an error during the recursive creation of a folder structure
+    CreateDirectoryError {
+        /// The path where [`root_location`] attempted to create folders
+        path: PathBuf,
+        /// The resulting IO error kind.
+        ///
+        /// See [`std::io::ErrorKind`]
+        kind: std::io::ErrorKind,
+    },
+}
+
+impl std::error::Error for RootLocationError {}
+
+impl std::fmt::Display for RootLocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::PathBufError(inf) =>
+                    format!("Failed while retrieving the program's root directory: {inf}"),
+                Self::GetVarError(ve) =>
+                    format!("Failed to get an environment variable while retrieving the program's root directory: {ve}"),
+                Self::CreateDirectoryError { path, kind } =>
+                    format!("Failed to create (open) file at {path:?} while attempting to initialize the program's root directory; {kind}"),
+            }
+        )
+    }
+}
+
+/// Initializes and returns the active program root directory, the folder where files are stored when calling [`Save::save`][`crate::save::Save::save`]
+/// - On Windows with [`RootType::InMemory`] or [`RootType::Persistent`], the path will always be `%LOCALAPPDATA%\<CARGO_PKG_NAME>\cache`, since in-memory folders on Windows are inpossible with safe rust.
+/// - On Unix with [`RootType::InMemory`], `$TMPDIR/<CARGO_PKG_NAME>/`, falling back to `/tmp/<CARGO_PKG_NAME>/`
+/// - On Unix with [`RootType::Persistent`], `$XDG_CACHE_HOME/<CARGO_PKG_NAME>/`, falling back to `$HOME/.cache/<CARGO_PKG_NAME>/`
+/// - See [`RootType::Config`], [`RootType::Data`] and [`RootType::Runtime`] for their locations on each platform
+///
+/// Unset, empty and relative environment variables are ignored in favor of the fallback, so sandboxes like Flatpak or Nix
+/// builds that redirect them work without changes. CARGO_PKG_NAME is the application name set with
+/// [`binroots::init`][`crate::init`], defaulting to the package name binroots was compiled as, `binroots`.
+pub fn root_location(location: RootType) -> Result<PathBuf, RootLocationError> {
+    let path = locate(location, |name| std::env::var(name))?;
+
+    std::fs::create_dir_all(path.clone()).map_err(|e| RootLocationError::CreateDirectoryError {
+        path: path.clone(),
+        kind: e.kind(),
+    })?;
+
+    Ok(path)
+}
+
+/// Resolves the root directory of `location` without creating it, reading environment variables with `var`
+pub(crate) fn locate(
+    location: RootType,
+    var: impl Fn(&str) -> Result<String, std::env::VarError>,
+) -> Result<PathBuf, RootLocationError> {
+    use std::str::FromStr;
+
+    let package = crate::app_name();
+    #[cfg(target_family = "unix")]
+    let home = || var("HOME").map_err(RootLocationError::GetVarError);
+    // A directory from the environment, ignored unless it's absolute
+    #[cfg(target_family = "unix")]
+    let dir = |name| {
+        var(name)
+            .ok()
+            .filter(|dir| dir.starts_with('/'))
+            .map(|dir| dir.trim_end_matches('/').to_string())
+    };
+    #[cfg(target_family = "unix")]
+    let tmp = || dir("TMPDIR").unwrap_or_else(|| "/tmp".into());
+    #[cfg(target_family = "unix")]
+    let cache = || dir("XDG_CACHE_HOME").map_or_else(|| home().map(|home| home + "/.cache"), Ok);
+
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    let path = match location {
+        RootType::InMemory => PathBuf::from_str(&format!("{}/{package}", tmp())),
+        RootType::Persistent => PathBuf::from_str(&format!("{}/{package}", cache()?)),
+        RootType::Config => PathBuf::from_str(&format!(
+            "{}/{package}",
+            dir("XDG_CONFIG_HOME").map_or_else(|| home().map(|home| home + "/.config"), Ok)?
+        )),
+        RootType::Data => PathBuf::from_str(&format!(
+            "{}/{package}",
+            dir("XDG_DATA_HOME").map_or_else(|| home().map(|home| home + "/.local/share"), Ok)?
+        )),
+        RootType::Runtime => PathBuf::from_str(&format!(
+            "{}/{package}",
+            dir("XDG_RUNTIME_DIR").unwrap_or_else(tmp)
+        )),
+    }
+    .map_err(RootLocationError::PathBufError)?;
+
+    #[cfg(target_os = "macos")]
+    let path = match location {
+        RootType::InMemory | RootType::Runtime => {
+            PathBuf::from_str(&format!("{}/{package}", tmp()))
+        }
+        RootType::Persistent => PathBuf::from_str(&format!("{}/{package}", cache()?)),
+        RootType::Config => PathBuf::from_str(&format!(
+            "{}/Library/Application Support/{package}/config",
+            home()?
+        )),
+        RootType::Data => PathBuf::from_str(&format!(
+            "{}/Library/Application Support/{package}/data",
+            home()?
+        )),
+    }
+    .map_err(RootLocationError::PathBufError)?;
+
+    #[cfg(target_family = "windows")]
+    let path = {
+        let var = |name| var(name).map_err(RootLocationError::GetVarError);
+
+        match location {
+            RootType::InMemory | RootType::Persistent => {
+                PathBuf::from_str(&format!("{}\\{package}\\.cache", var("LOCALAPPDATA")?))
+            }
+            RootType::Config => {
+                PathBuf::from_str(&format!("{}\\{package}\\config", var("APPDATA")?))
+            }
+            RootType::Data => PathBuf::from_str(&format!("{}\\{package}\\data", var("APPDATA")?)),
+            RootType::Runtime => PathBuf::from_str(&format!("{}\\{package}", var("TEMP")?)),
+        }
+        .map_err(RootLocationError::PathBufError)?
+    };
 
     Ok(path)
 }
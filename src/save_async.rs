@@ -0,0 +1,329 @@
+//! ## `binroots::save_async`
+//! Contains [`SaveAsync`][`crate::save_async::SaveAsync`], saving data from a tokio runtime without blocking it. Requires
+//! the `async_save` feature.
+//!
+//! Data is serialized right away, and the files are written with `tokio::fs`. Saves using options that need the
+//! [`Backend`][`crate::backend::Backend`] or hold locks, such as [`SaveOptions::generation`] or a custom backend, or
+//! that replace a default such as [`SaveOptions::clock`] or [`SaveOptions::dir_cache`], run [`save_root_with`] on
+//! tokio's blocking pool instead, so every option behaves exactly as it does with [`Save`][`crate::save::Save`].
+//! Read-only roots are detected on the blocking pool too, and handled according to [`SaveOptions::read_only`].
+//!
+//! ## Example
+//!
+//! ```
+//! use binroots::save::{root_location, RootType};
+//! use binroots::save_async::SaveAsync;
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! runtime
+//!     .block_on((1, 2).save_async("save-async", RootType::InMemory))
+//!     .unwrap();
+//!
+//! let root = root_location(RootType::InMemory).unwrap().join("save-async");
+//! assert_eq!(std::fs::read_to_string(root.join("1")).unwrap(), "2");
+//! ```
+
+use std::future::Future;
+use std::io::ErrorKind;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::clock;
+use crate::config;
+use crate::fileserializer::{FileOperationHint, FileSerializer, PlannedWrite};
+use crate::hooks::{self, SaveContext};
+use crate::quiet;
+use crate::save::{
+    prepare_root, read_only, save_root_with, seq_index, RootMissing, RootType, SaveError,
+    SaveOptions, SymlinkPolicy,
+};
+use crate::trace::event;
+
+/// Provides data with the ability to save to the disk from async code. The async counterpart of
+/// [`Save`][`crate::save::Save`].
+pub trait SaveAsync {
+    /// [`Serialize`][`serde::Serialize`]s and saves data to "[BINROOTS_DIR][`crate::BINROOTS_DIR`]/\<root\>"
+    fn save_async<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+    ) -> impl Future<Output = Result<(), SaveError>> + Send + 'static;
+
+    /// Like [`SaveAsync::save_async`], tuned by `options`
+    fn save_async_with<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> impl Future<Output = Result<(), SaveError>> + Send + 'static;
+}
+
+impl<T: Serialize> SaveAsync for T {
+    fn save_async<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+    ) -> impl Future<Output = Result<(), SaveError>> + Send + 'static {
+        let root = root.into();
+        save_serialized(
+            serialize(self, &root, &root_type, &SaveOptions::default()),
+            root,
+            root_type,
+        )
+    }
+
+    fn save_async_with<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        root_type: RootType,
+        options: &SaveOptions,
+    ) -> impl Future<Output = Result<(), SaveError>> + Send + 'static {
+        let root = root.into();
+        save_serialized(serialize(self, &root, &root_type, options), root, root_type)
+    }
+}
+
+/// Serializes `value` right away, so the returned future doesn't borrow it. Returns the options with the root's
+/// [`RootConfig`][`crate::config::RootConfig`] applied.
+fn serialize<T: Serialize>(
+    value: &T,
+    root: &Path,
+    root_type: &RootType,
+    options: &SaveOptions,
+) -> Result<(FileSerializer, SaveOptions), SaveError> {
+    let options = config::configure(options, root, root_type)?.into_owned();
+
+    let mut serializer = FileSerializer::with_options("", options.serializer.clone());
+    serializer
+        .serialize_value(value)
+        .map_err(SaveError::SerializeError)?;
+
+    Ok((serializer, options))
+}
+
+async fn save_serialized(
+    serialized: Result<(FileSerializer, SaveOptions), SaveError>,
+    root: PathBuf,
+    root_type: RootType,
+) -> Result<(), SaveError> {
+    let (serializer, options) = serialized?;
+    save_root_async(serializer, root, root_type, &options).await
+}
+
+/// The async counterpart of [`save_root_with`], saving the files produced by `serializer` to
+/// "[`root_location`]/\<root\>" with `tokio::fs`. Use it to save a [`BinrootsField`][`crate::field::BinrootsField`] by
+/// driving a [`FileSerializer`] rooted at the field's name.
+pub async fn save_root_async(
+    mut serializer: FileSerializer,
+    root: PathBuf,
+    root_type: RootType,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    if needs_blocking(options) {
+        let options = options.clone();
+        return blocking(move || save_root_with(serializer, root, root_type, &options)).await;
+    }
+
+    // Read-only roots are handled exactly like `save_root_with` does
+    let prepared = {
+        let (root, root_type, options) = (root.clone(), root_type.clone(), options.clone());
+        blocking(move || prepare_root(&root, &root_type, &options)).await?
+    };
+    let Some(path) = prepared else {
+        return Ok(());
+    };
+
+    if quiet::defer(
+        &path,
+        &mut serializer,
+        root.clone(),
+        root_type.clone(),
+        options,
+    ) {
+        return Ok(());
+    }
+    if quiet::has_pending(&path) {
+        let path = path.clone();
+        blocking(move || quiet::flush_at(&path)).await?;
+    }
+
+    serializer.apply_templates();
+    serializer.coalesce();
+
+    let context = SaveContext {
+        root: &path,
+        root_type: &root_type,
+        files: serializer.output(),
+        changes: None,
+    };
+    if let ControlFlow::Break(reason) = hooks::run_pre_save(&context) {
+        return Err(SaveError::Vetoed { reason });
+    }
+
+    match write_files(serializer.output, &root, &path).await {
+        // The filesystem was remounted read-only since the root was checked
+        Err(e) if e.kind() == Some(ErrorKind::ReadOnlyFilesystem) => {
+            return read_only(path, options)
+        }
+        result => result?,
+    }
+
+    hooks::run_post_save(&SaveContext {
+        root: &path,
+        root_type: &root_type,
+        files: &[],
+        changes: None,
+    });
+
+    Ok(())
+}
+
+/// Writes `files` below the root at `path`, the path of the root `root`
+async fn write_files(files: Vec<PlannedWrite>, root: &Path, path: &Path) -> Result<(), SaveError> {
+    for mut file in files {
+        let field = file.target(root);
+        let in_field = |e: SaveError| e.in_field(&field);
+
+        if let Some(stale_value) = file.stale_value(path) {
+            rmdir(stale_value.clone()).await.map_err(in_field)?;
+            rm(stale_value).await.map_err(in_field)?;
+        }
+
+        if !file.is_dir {
+            mkdir(file.parent_dir(path)).await.map_err(in_field)?;
+
+            if file.hint == FileOperationHint::Delete {
+                rm(file.location(path)).await.map_err(in_field)?;
+            } else {
+                let target = file.target(path);
+                if let Err(e) = tokio::fs::write(&target, &file.contents).await {
+                    return Err(SaveError::WriteFileError {
+                        path: target,
+                        source: e,
+                        field: Some(field.to_string_lossy().into_owned()),
+                        contents: std::mem::take(&mut file.contents),
+                    });
+                }
+                event!(trace, path = %target.display(), bytes = file.contents.len(), "wrote file");
+            }
+        } else {
+            let target = file.target(path);
+            mkdir(target.clone()).await.map_err(in_field)?;
+
+            if let FileOperationHint::Truncate(len) = file.hint {
+                truncate(&target, len).await.map_err(in_field)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the save uses options only [`save_root_with`] implements
+fn needs_blocking(options: &SaveOptions) -> bool {
+    #[cfg(feature = "parallel")]
+    if options.parallel {
+        return true;
+    }
+
+    !options.backend.is_filesystem()
+        || options.watcher_friendly
+        || options.symlinks != SymlinkPolicy::Follow
+        || options.etag
+        || options.mtime_sidecars
+        || options.generation
+        || options.expected_generation.is_some()
+        || options.metadata
+        || options.trash
+        || options.stats
+        || options.drop_cache_above.is_some()
+        || options.atomic
+        || options.fsync
+        || options.lock_leaves
+        || options.permissions.is_some()
+        || options.mirror
+        || options.len_files
+        || options.serializer.memory_budget.is_some()
+        || options.root_missing != RootMissing::Recreate
+        || options.best_effort
+        || options.root_config
+        || options.dir_cache.is_some()
+        || !clock::is_default_clock(&options.clock)
+        || !clock::is_default_ids(&options.ids)
+}
+
+/// Runs `f` on tokio's blocking pool, resuming its panic if it panics
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Removes the elements of the sequence folder `dir` from index `len` on
+async fn truncate(dir: &Path, len: usize) -> Result<(), SaveError> {
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return Ok(());
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if seq_index(&entry.file_name().to_string_lossy()).map_or(true, |index| index < len) {
+            continue;
+        }
+
+        if entry.file_type().await.map_or(false, |t| t.is_dir()) {
+            rmdir(entry.path()).await?;
+        } else {
+            rm(entry.path()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn mkdir(path: PathBuf) -> Result<(), SaveError> {
+    match tokio::fs::create_dir_all(&path).await {
+        Ok(()) => {
+            event!(trace, path = %path.display(), "created folder");
+            Ok(())
+        }
+        Err(e) => Err(SaveError::CreateDirectoryError {
+            path,
+            source: e,
+            field: None,
+        }),
+    }
+}
+
+async fn rmdir(path: PathBuf) -> Result<(), SaveError> {
+    match tokio::fs::remove_dir_all(&path).await {
+        Ok(()) => {
+            event!(trace, path = %path.display(), "deleted folder");
+            Ok(())
+        }
+        Err(e) if !matches!(e.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) => {
+            Err(SaveError::DeleteFileError {
+                path,
+                source: e,
+                field: None,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn rm(path: PathBuf) -> Result<(), SaveError> {
+    match tokio::fs::remove_file(Path::new(path.to_string_lossy().trim_end_matches('/'))).await {
+        Ok(()) => {
+            event!(trace, path = %path.display(), "deleted file");
+            Ok(())
+        }
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(SaveError::DeleteFileError {
+            path,
+            source: e,
+            field: None,
+        }),
+        _ => Ok(()),
+    }
+}
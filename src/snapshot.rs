@@ -0,0 +1,359 @@
+//! ## `binroots::snapshot`
+//! Contains [`to_blob`][`crate::snapshot::to_blob`] and [`from_blob`][`crate::snapshot::from_blob`] for packing a saved root
+//! into a single self-describing binary payload, so it can be shipped over a socket or stored in a database and later
+//! re-materialized as a file tree.
+//!
+//! ## Format
+//! All integers are little-endian.
+//! - The magic bytes `BRSNAP`, followed by a `u16` format version (currently `1`)
+//! - The version of binroots that wrote the snapshot, as a `u32` length followed by UTF-8
+//! - The creation time in seconds since the Unix epoch, as a `u64`
+//! - The number of entries as a `u32`, followed by each entry:
+//!   - A `u8` kind, `0` for a file and `1` for a folder
+//!   - The entry's path relative to the root, separated by `/`, as a `u32` length followed by UTF-8
+//!   - The contents of the entry as a `u64` length followed by the bytes. Always empty for folders.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backend::{Backend, Filesystem};
+use crate::clock::{Clock, SystemClock};
+use crate::save::{root_location, RootLocationError, RootType};
+
+const MAGIC: &[u8; 6] = b"BRSNAP";
+
+/// The snapshot format version written by [`Snapshot::to_bytes`]
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Errors while creating or restoring a [`Snapshot`]
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Returned when reading or writing the root fails
+    IoError {
+        /// The path that was being read or written
+        path: PathBuf,
+        /// The resulting IO error kind.
+        ///
+        /// See [`std::io::ErrorKind`]
+        kind: std::io::ErrorKind,
+    },
+    /// Returned when the blob isn't a snapshot or is truncated
+    Malformed(String),
+    /// Returned when the blob was written by an unsupported version of the snapshot format
+    UnsupportedVersion(u16),
+    /// An error caught while retrieving the project's root directory.
+    ///
+    /// See [`RootLocationError`]
+    RootLocationError(RootLocationError),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError { path, kind } => {
+                write!(f, "Failed to access {path:?} during a snapshot; {kind}")
+            }
+            Self::Malformed(reason) => write!(f, "Malformed snapshot: {reason}"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"
+            ),
+            Self::RootLocationError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// A single file or folder inside a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    /// The path of the entry relative to the root, separated by `/`
+    pub path: String,
+    /// Whether the entry is a folder
+    pub is_dir: bool,
+    /// The contents of the entry. Always empty for folders.
+    pub contents: Vec<u8>,
+}
+
+/// Every file and folder below a root, along with metadata describing the snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The version of binroots that created the snapshot
+    pub crate_version: String,
+    /// When the snapshot was created, truncated to seconds
+    pub created: SystemTime,
+    /// Every entry below the root, sorted by path. Folders come before their contents.
+    pub entries: Vec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// Reads every file and folder below `path`. Symlinks are skipped, so links looping back into the root or pointing
+    /// outside of it are never followed.
+    pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        Self::read_dir_with(path, &SystemClock)
+    }
+
+    /// Like [`Snapshot::read_dir`], taking the creation time from `clock`
+    pub fn read_dir_with<P: AsRef<Path>>(
+        path: P,
+        clock: &dyn Clock,
+    ) -> Result<Self, SnapshotError> {
+        Self::read_with(&Filesystem, path.as_ref(), clock)
+    }
+
+    /// Like [`Snapshot::read_dir`], reading through `backend`
+    pub(crate) fn read_backend(backend: &dyn Backend, path: &Path) -> Result<Self, SnapshotError> {
+        Self::read_with(backend, path, &SystemClock)
+    }
+
+    fn read_with(
+        backend: &dyn Backend,
+        path: &Path,
+        clock: &dyn Clock,
+    ) -> Result<Self, SnapshotError> {
+        let mut entries = Vec::new();
+        collect(backend, path, "", &mut entries)?;
+
+        Ok(Self {
+            crate_version: env!("CARGO_PKG_VERSION").into(),
+            created: clock.now_secs(),
+            entries,
+        })
+    }
+
+    /// Replaces everything below `path` with the snapshot's entries
+    pub fn write_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |e: std::io::Error| SnapshotError::IoError {
+                path,
+                kind: e.kind(),
+            }
+        };
+
+        if let Some(entry) = self.entries.iter().find(|entry| {
+            entry
+                .path
+                .split('/')
+                .any(|c| c.is_empty() || c == "." || c == "..")
+        }) {
+            return Err(SnapshotError::Malformed(format!(
+                "entry {:?} escapes the root",
+                entry.path
+            )));
+        }
+
+        match std::fs::remove_dir_all(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error(path)(e))?,
+            _ => {}
+        }
+        std::fs::create_dir_all(path).map_err(io_error(path))?;
+
+        for entry in &self.entries {
+            let target = path.join(&entry.path);
+            if entry.is_dir {
+                std::fs::create_dir_all(&target).map_err(io_error(&target))?;
+            } else {
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent).map_err(io_error(parent))?;
+                }
+                std::fs::write(&target, &entry.contents).map_err(io_error(&target))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encodes the snapshot into a blob. See the [module documentation][`crate::snapshot`] for the format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::from(&MAGIC[..]);
+        out.extend(SNAPSHOT_VERSION.to_le_bytes());
+        out.extend((self.crate_version.len() as u32).to_le_bytes());
+        out.extend(self.crate_version.as_bytes());
+        out.extend(
+            self.created
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .to_le_bytes(),
+        );
+        out.extend((self.entries.len() as u32).to_le_bytes());
+
+        for entry in &self.entries {
+            out.push(entry.is_dir as u8);
+            out.extend((entry.path.len() as u32).to_le_bytes());
+            out.extend(entry.path.as_bytes());
+            out.extend((entry.contents.len() as u64).to_le_bytes());
+            out.extend(&entry.contents);
+        }
+
+        out
+    }
+
+    /// Decodes a blob created by [`Snapshot::to_bytes`]
+    pub fn from_bytes(blob: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader(blob);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Malformed("missing magic bytes".into()));
+        }
+
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let crate_version = reader.string()?;
+        let created = UNIX_EPOCH + Duration::from_secs(u64::from_le_bytes(reader.array()?));
+        let count = u32::from_le_bytes(reader.array()?);
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let is_dir = match reader.take(1)?[0] {
+                0 => false,
+                1 => true,
+                kind => {
+                    return Err(SnapshotError::Malformed(format!(
+                        "unknown entry kind {kind}"
+                    )))
+                }
+            };
+            let path = reader.string()?;
+            let len = u64::from_le_bytes(reader.array()?) as usize;
+            let contents = reader.take(len)?.to_vec();
+
+            entries.push(SnapshotEntry {
+                path,
+                is_dir,
+                contents,
+            });
+        }
+
+        if !reader.0.is_empty() {
+            return Err(SnapshotError::Malformed("trailing bytes".into()));
+        }
+
+        Ok(Self {
+            crate_version,
+            created,
+            entries,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < len {
+            return Err(SnapshotError::Malformed("unexpected end of blob".into()));
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const L: usize>(&mut self) -> Result<[u8; L], SnapshotError> {
+        Ok(self.take(L)?.try_into().unwrap())
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        let len = u32::from_le_bytes(self.array()?) as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| SnapshotError::Malformed("path isn't valid UTF-8".into()))
+    }
+}
+
+fn collect(
+    backend: &dyn Backend,
+    dir: &Path,
+    prefix: &str,
+    entries: &mut Vec<SnapshotEntry>,
+) -> Result<(), SnapshotError> {
+    let mut children = backend.read_dir(dir).map_err(|e| SnapshotError::IoError {
+        path: dir.to_path_buf(),
+        kind: e.kind(),
+    })?;
+    children.sort();
+
+    for child in children {
+        let child_path = child.path;
+        let path = format!(
+            "{prefix}{}",
+            child_path.file_name().unwrap_or_default().to_string_lossy()
+        );
+
+        if child.is_symlink {
+            continue;
+        } else if child.is_dir {
+            entries.push(SnapshotEntry {
+                path: path.clone(),
+                is_dir: true,
+                contents: Vec::new(),
+            });
+            collect(backend, &child_path, &format!("{path}/"), entries)?;
+        } else {
+            let contents = backend
+                .read(&child_path)
+                .map_err(|e| SnapshotError::IoError {
+                    path: child_path,
+                    kind: e.kind(),
+                })?;
+            entries.push(SnapshotEntry {
+                path,
+                is_dir: false,
+                contents,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs everything saved below "[`root_location`]/\<root\>" into a single blob.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{RootType, Save};
+/// use binroots::snapshot::{from_blob, to_blob};
+///
+/// (9, 8, 5).save("snapshot-source", RootType::InMemory).unwrap();
+///
+/// let blob = to_blob("snapshot-source", RootType::InMemory).unwrap();
+/// from_blob(&blob, "snapshot-copy", RootType::InMemory).unwrap();
+/// ```
+pub fn to_blob<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<Vec<u8>, SnapshotError> {
+    to_blob_with(root, root_type, &SystemClock)
+}
+
+/// Like [`to_blob`], taking the creation time of the snapshot from `clock`, so blobs of the same tree are byte-identical
+pub fn to_blob_with<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+    clock: &dyn Clock,
+) -> Result<Vec<u8>, SnapshotError> {
+    let path = root_location(root_type)
+        .map_err(SnapshotError::RootLocationError)?
+        .join(root.into());
+
+    Ok(Snapshot::read_dir_with(path, clock)?.to_bytes())
+}
+
+/// Replaces everything below "[`root_location`]/\<root\>" with the contents of a blob created by [`to_blob`]
+pub fn from_blob<P: Into<PathBuf>>(
+    blob: &[u8],
+    root: P,
+    root_type: RootType,
+) -> Result<Snapshot, SnapshotError> {
+    let snapshot = Snapshot::from_bytes(blob)?;
+    let path = root_location(root_type)
+        .map_err(SnapshotError::RootLocationError)?
+        .join(root.into());
+
+    snapshot.write_dir(path)?;
+    Ok(snapshot)
+}
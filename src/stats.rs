@@ -0,0 +1,150 @@
+//! ## `binroots::stats`
+//! Contains [`SaveStats`][`crate::stats::SaveStats`], describing how the publisher of a root is behaving. Written to
+//! `<root>/.stats` after every save using [`SaveOptions::stats`][`crate::save::SaveOptions::stats`], including failed
+//! ones, so dashboards already watching the tree can alert on a publisher that stopped saving or keeps failing.
+//!
+//! ## Format
+//! One `key=value` pair per line, like [`metadata`][`crate::metadata`]:
+//! ```text
+//! saves=42
+//! failures=1
+//! last_save=1700000060
+//! last_duration_us=1830
+//! last_error=Failed to delete a file at "/tmp/app/status/activity.value" during save; permission denied
+//! ```
+//! `last_save` is in seconds since the Unix epoch. `last_error` is only present while the last save failed, and unknown
+//! keys are ignored.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backend::Backend;
+use crate::read::ReadError;
+use crate::save::{root_location, write_to, RootType, SaveError};
+
+/// The name of the statistics file inside a root
+pub const STATS_FILE: &str = ".stats";
+
+/// The contents of a root's `.stats` file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveStats {
+    /// The number of saves attempted, including failed ones
+    pub saves: u64,
+    /// The number of saves that failed
+    pub failures: u64,
+    /// When the last save finished, truncated to seconds
+    pub last_save: Option<SystemTime>,
+    /// How long the last save took, saved with microsecond precision
+    pub last_duration: Duration,
+    /// The error of the last save, if it failed
+    pub last_error: Option<String>,
+}
+
+impl SaveStats {
+    /// Renders the statistics in the `.stats` format
+    pub fn to_file_contents(&self) -> String {
+        let mut out = format!("saves={}\nfailures={}\n", self.saves, self.failures);
+        if let Some(last_save) = self.last_save {
+            let secs = last_save
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            out += &format!("last_save={secs}\n");
+        }
+        out += &format!("last_duration_us={}\n", self.last_duration.as_micros());
+        if let Some(last_error) = &self.last_error {
+            out += &format!("last_error={}\n", last_error.replace('\n', " "));
+        }
+
+        out
+    }
+
+    /// Parses the `.stats` format. Missing or invalid keys keep their default values.
+    pub fn from_file_contents(contents: &str) -> Self {
+        let mut stats = Self::default();
+
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("saves", value)) => stats.saves = value.parse().unwrap_or_default(),
+                Some(("failures", value)) => stats.failures = value.parse().unwrap_or_default(),
+                Some(("last_save", value)) => {
+                    stats.last_save = value
+                        .parse()
+                        .ok()
+                        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                }
+                Some(("last_duration_us", value)) => {
+                    stats.last_duration = Duration::from_micros(value.parse().unwrap_or_default())
+                }
+                Some(("last_error", value)) => stats.last_error = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        stats
+    }
+}
+
+/// Reads the statistics of "[`root_location`]/\<root\>". Returns [`None`] if the root has no `.stats` file.
+///
+/// ## Example
+///
+/// ```
+/// use binroots::save::{RootType, Save, SaveOptions};
+/// use binroots::stats::read;
+///
+/// let options = SaveOptions::default().stats(true);
+/// (1, 2).save_with("stats-example", RootType::InMemory, &options).unwrap();
+/// (3, 4).save_with("stats-example", RootType::InMemory, &options).unwrap();
+///
+/// let stats = read("stats-example", RootType::InMemory).unwrap().unwrap();
+/// assert!(stats.saves >= 2);
+/// assert_eq!(stats.last_error, None);
+/// ```
+pub fn read<P: Into<PathBuf>>(
+    root: P,
+    root_type: RootType,
+) -> Result<Option<SaveStats>, ReadError> {
+    let path = root_location(root_type)
+        .map_err(ReadError::RootLocationError)?
+        .join(root.into())
+        .join(STATS_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some(SaveStats::from_file_contents(&contents))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ReadError::ReadFileError {
+            path,
+            kind: e.kind(),
+        }),
+    }
+}
+
+/// Records a save finished at `now` that took `duration` in `<path>/.stats`
+pub(crate) fn record(
+    backend: &dyn Backend,
+    path: &Path,
+    now: SystemTime,
+    duration: Duration,
+    error: Option<&SaveError>,
+) -> Result<(), SaveError> {
+    let file = path.join(STATS_FILE);
+    let mut stats = backend
+        .read(&file)
+        .map(|contents| SaveStats::from_file_contents(&String::from_utf8_lossy(&contents)))
+        .unwrap_or_default();
+
+    stats.saves += 1;
+    if error.is_some() {
+        stats.failures += 1;
+    }
+    stats.last_save = Some(now);
+    stats.last_duration = duration;
+    // The message leaves out the IO error, which dashboards need without walking `source`
+    stats.last_error = error.map(|error| match error.io_error() {
+        Some(io) => format!("{error}; {io}"),
+        None => error.to_string(),
+    });
+
+    write_to(backend, file, stats.to_file_contents().into_bytes())
+}
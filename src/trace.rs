@@ -0,0 +1,28 @@
+//! Instrumentation with [`tracing`](https://docs.rs/tracing) behind the `tracing` feature. Without the feature, the
+//! macros below compile to nothing.
+
+/// Emits a `tracing` event, e.g. `event!(trace, path = %path.display(), "wrote file")`
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "binroots", $($arg)*);
+    };
+}
+
+/// Enters a `tracing` span at the debug level until the returned guard is dropped, e.g.
+/// `let _span = span!("save", root = %path.display());`
+macro_rules! span {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(target: "binroots", $($arg)*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::trace::Disabled;
+        span
+    }};
+}
+
+pub(crate) use {event, span};
+
+/// The span guard without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Disabled;
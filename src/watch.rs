@@ -0,0 +1,277 @@
+//! ## `binroots::watch`
+//! Contains [`Watcher`][`crate::watch::Watcher`], which reports changes made to a saved root from outside the program,
+//! such as a user editing one of its files by hand. Requires the `notify` feature.
+//!
+//! Events are reported by their logical path: the path of the changed field relative to the root, with the extensions
+//! of binroots' siblings such as `.value` and `.raw` removed and hidden files like
+//! [`METADATA_FILE`][`crate::metadata::METADATA_FILE`] ignored. Saves made by the program itself are reported too.
+//!
+//! The platform's native watcher is used: inotify on Linux, FSEvents on macOS, ReadDirectoryChangesW on Windows and
+//! kqueue on the BSDs. Each reports a single write differently, so identical events are coalesced into one, reported
+//! once none arrived for a short window, see [`DEFAULT_COALESCE`][`crate::watch::DEFAULT_COALESCE`]. By then the field
+//! holds the last of the writes.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! use binroots::save::{root_location, RootType, Save};
+//! use binroots::watch::Watcher;
+//!
+//! (1, 2).save("watch-example", RootType::InMemory).unwrap();
+//! let watcher = Watcher::watch_root("watch-example", RootType::InMemory).unwrap();
+//!
+//! // Somebody else edits the first element
+//! let root = root_location(RootType::InMemory).unwrap().join("watch-example");
+//! std::fs::write(root.join("0"), "5").unwrap();
+//!
+//! let event = watcher.next_timeout(Duration::from_secs(5)).unwrap();
+//! assert_eq!(event.logical_path, Path::new("0"));
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{RecursiveMode, Watcher as _};
+
+use crate::naming::SIBLINGS;
+use crate::save::{root_location, RootLocationError, RootType};
+
+/// How long [`Watcher::watch_root`] coalesces identical events for. FSEvents already batches the changes it reports, while
+/// ReadDirectoryChangesW reports a single write as several modifications, and inotify reports truncating and writing a
+/// file separately.
+pub const DEFAULT_COALESCE: Duration = if cfg!(target_os = "macos") {
+    Duration::from_millis(10)
+} else if cfg!(target_os = "windows") {
+    Duration::from_millis(100)
+} else {
+    Duration::from_millis(50)
+};
+
+/// Errors while setting up a [`Watcher`]
+#[derive(Debug)]
+pub enum WatchError {
+    /// Returned when the root's folder doesn't exist and can't be created
+    CreateDirectoryError {
+        /// The root's folder
+        path: PathBuf,
+        /// The resulting IO error kind.
+        ///
+        /// See [`std::io::ErrorKind`]
+        kind: std::io::ErrorKind,
+    },
+    /// Returned when the platform's file watcher can't be started, such as when inotify's watch limit is reached
+    NotifyError(notify::Error),
+    /// An error caught while retrieving the project's root directory.
+    ///
+    /// See [`RootLocationError`]
+    RootLocationError(RootLocationError),
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::CreateDirectoryError { path, kind } =>
+                    format!("Failed to create directory at {path:?} to watch; {kind}"),
+                Self::NotifyError(e) => format!("Failed to watch the root: {e}"),
+                Self::RootLocationError(e) => format!("{e}"),
+            }
+        )
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+/// Forwards the events of `raw` to `sender` once no identical event arrived for `coalesce`, in the order they first
+/// arrived. Returns when `raw` disconnects.
+fn debounce(raw: &Receiver<ChangeEvent>, sender: &Sender<ChangeEvent>, coalesce: Duration) {
+    // Events waiting for the window to go quiet, with when they last arrived
+    let mut pending: Vec<(ChangeEvent, Instant)> = Vec::new();
+
+    loop {
+        let received = match pending.iter().map(|(_, at)| *at + coalesce).min() {
+            Some(deadline) => raw.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => raw.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(event) => {
+                let now = Instant::now();
+                match pending.iter_mut().find(|(pending, _)| *pending == event) {
+                    Some((_, at)) => *at = now,
+                    None => pending.push((event, now)),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let now = Instant::now();
+        pending.retain(|(event, at)| {
+            let quiet = now.duration_since(*at) >= coalesce;
+            if quiet {
+                let _ = sender.send(event.clone());
+            }
+            !quiet
+        });
+    }
+}
+
+/// How a field changed, see [`ChangeEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The field's file or folder was created, or moved into place
+    Created,
+    /// The field's contents changed
+    Modified,
+    /// The field's file or folder was deleted, or moved away
+    Removed,
+}
+
+/// A change to a watched root, reported by [`Watcher`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChangeEvent {
+    /// The path of the changed field relative to the root, e.g. `activity` when `activity.value` changed
+    pub logical_path: PathBuf,
+    /// How the field changed
+    pub kind: ChangeKind,
+}
+
+/// Watches a saved root for changes, see the [module docs][`crate::watch`].
+///
+/// Iterating blocks until the next change, and ends when the watcher stops.
+#[derive(Debug)]
+pub struct Watcher {
+    root: PathBuf,
+    events: Receiver<ChangeEvent>,
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Starts watching "[`root_location`]/\<root\>" and everything below it, creating the root's folder if it doesn't
+    /// exist yet. Identical events are coalesced until none arrived for [`DEFAULT_COALESCE`]
+    pub fn watch_root<P: Into<PathBuf>>(root: P, root_type: RootType) -> Result<Self, WatchError> {
+        Self::watch_root_with(root, root_type, DEFAULT_COALESCE)
+    }
+
+    /// Like [`Watcher::watch_root`], reporting an event once no identical event arrived for `coalesce`, so a field
+    /// written several times in a row is reported once, after the last write. [`Duration::ZERO`] reports every event.
+    pub fn watch_root_with<P: Into<PathBuf>>(
+        root: P,
+        root_type: RootType,
+        coalesce: Duration,
+    ) -> Result<Self, WatchError> {
+        let path = root_location(root_type)
+            .map_err(WatchError::RootLocationError)?
+            .join(root.into());
+
+        std::fs::create_dir_all(&path).map_err(|e| WatchError::CreateDirectoryError {
+            path: path.clone(),
+            kind: e.kind(),
+        })?;
+        // Events report canonical paths on some platforms
+        let path = path.canonicalize().unwrap_or(path);
+
+        let (sender, events) = mpsc::channel();
+        let (raw_sender, raw) = mpsc::channel();
+        let root = path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let Some(kind) = change_kind(&event.kind) else {
+                    return;
+                };
+
+                for path in &event.paths {
+                    if let Some(logical_path) = logical_path(&root, path) {
+                        let _ = raw_sender.send(ChangeEvent { logical_path, kind });
+                    }
+                }
+            })
+            .map_err(WatchError::NotifyError)?;
+        // Stops once `watcher`, and with it `raw_sender`, is dropped
+        std::thread::spawn(move || debounce(&raw, &sender, coalesce));
+
+        watcher
+            .watch(&path, RecursiveMode::Recursive)
+            .map_err(WatchError::NotifyError)?;
+
+        Ok(Self {
+            root: path,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// The absolute path of the watched root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the next change if one already happened, without blocking
+    pub fn try_next(&self) -> Option<ChangeEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next change
+    pub fn next_timeout(&self, timeout: Duration) -> Option<ChangeEvent> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = ChangeEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.recv().ok()
+    }
+}
+
+fn change_kind(kind: &EventKind) -> Option<ChangeKind> {
+    match kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            Some(ChangeKind::Created)
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            Some(ChangeKind::Removed)
+        }
+        EventKind::Modify(_) | EventKind::Any | EventKind::Other => Some(ChangeKind::Modified),
+        EventKind::Access(_) => None,
+    }
+}
+
+/// The logical path of `path` below `root`, or [`None`] for the root itself and hidden files
+fn logical_path(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut logical = PathBuf::new();
+
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            return None;
+        }
+
+        logical.push(match name.rsplit_once('.') {
+            Some((stem, extension)) if SIBLINGS.contains(&extension) => stem,
+            _ => &name,
+        });
+    }
+
+    (!logical.as_os_str().is_empty()).then_some(logical)
+}